@@ -0,0 +1,108 @@
+//! The platform side of a channel client.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use rustyclaw_core::types::{ReplyTarget, ResponseData};
+
+use crate::files::download_file;
+
+/// What a platform client provides. Everything else (pairing, built-in
+/// commands, queueing, chunking) lives in the runtime.
+#[async_trait]
+pub trait ChannelAdapter: Send + Sync {
+    /// Channel name, used as the queue file prefix and pairing namespace.
+    fn name(&self) -> &'static str;
+
+    /// Longest message the platform accepts, measured after [`format`](Self::format).
+    fn max_message_len(&self) -> usize;
+
+    /// Render agent markdown into the platform's markup.
+    fn format(&self, markdown: &str) -> String;
+
+    /// Whether choices and pages can be shown as buttons. When false, choices
+    /// are listed in the text and long responses are always split.
+    fn interactive(&self) -> bool {
+        false
+    }
+
+    /// Deliver one response from the outgoing queue. Failures are retried
+    /// later unless the error is [`Undeliverable`]. A retry passes the whole
+    /// response again, so adapters that post it as several messages should
+    /// skip the ones an earlier attempt already sent.
+    async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()>;
+
+    /// Send a short markdown notice, such as a pairing prompt or command output.
+    async fn reply(&self, target: &ReplyTarget, text: &str) -> Result<()>;
+
+    /// Fetch a remote attachment. The default treats the reference as a
+    /// public URL; override for auth headers or platform file IDs.
+    async fn download(&self, reference: &str, dest: &Path) -> Result<()> {
+        download_file(reference, dest).await
+    }
+
+    /// Called once for each new pairing request, e.g. to notify admins.
+    async fn pairing_requested(&self, _sender: &str, _sender_id: &str, _code: &str) {}
+
+    /// Reply target for a response queued without one.
+    async fn fallback_target(&self, _message_id: &str) -> Option<ReplyTarget> {
+        None
+    }
+}
+
+/// A send failure that retrying cannot fix, such as a chat that no longer
+/// exists. The response is dropped instead of kept for another attempt.
+#[derive(Debug)]
+pub struct Undeliverable(pub String);
+
+impl std::fmt::Display for Undeliverable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Undeliverable {}
+
+/// A message received from the platform, before pairing and queueing.
+#[derive(Debug, Clone)]
+pub struct Incoming {
+    pub sender: String,
+    pub sender_id: String,
+    pub text: String,
+    pub attachments: Vec<Attachment>,
+    pub reply_to: ReplyTarget,
+    /// Agent or team for messages that don't start with `@id`
+    pub default_route: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    /// Original file name; prefixed with the channel and message ID on disk
+    pub name: String,
+    pub source: AttachmentSource,
+}
+
+#[derive(Debug, Clone)]
+pub enum AttachmentSource {
+    /// URL or platform file ID, fetched through [`ChannelAdapter::download`]
+    Remote(String),
+    /// Already in hand, e.g. an email part
+    Bytes(Vec<u8>),
+}
+
+/// A response ready to deliver.
+#[derive(Debug)]
+pub struct Outgoing<'a> {
+    pub response: &'a ResponseData,
+    pub target: &'a ReplyTarget,
+    /// Attachments that still exist on disk
+    pub files: Vec<PathBuf>,
+    /// Markdown pieces that each fit the platform limit once formatted
+    pub chunks: Vec<String>,
+    /// `[choices: ...]` labels; only set for interactive adapters
+    pub choices: Vec<String>,
+    /// Saved page set; send `chunks[0]` with page controls instead of every chunk
+    pub page_id: Option<String>,
+}
@@ -0,0 +1,126 @@
+//! Built-in commands answered by the channel itself instead of an agent.
+
+use rustyclaw_core::config::{get_agents, get_settings, get_teams, get_workspace_path, Paths};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuiltinCommand {
+    Agents,
+    Teams,
+    /// Agent IDs to reset, without the leading `@`
+    Reset(Vec<String>),
+}
+
+/// Agent IDs from command arguments: `@coder writer` -> `["coder", "writer"]`.
+pub fn parse_agent_args(args: &str) -> Vec<String> {
+    args.split_whitespace()
+        .map(|a| a.trim_start_matches('@').to_lowercase())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// Parse `/agents`, `/teams` and `/reset @id...`. A `!` prefix works too, for
+/// platforms whose clients swallow unknown slash commands.
+pub fn parse_builtin(text: &str) -> Option<BuiltinCommand> {
+    let text = text.trim();
+    let rest = text.strip_prefix('/').or_else(|| text.strip_prefix('!'))?;
+    let (command, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    match command.to_lowercase().as_str() {
+        "agent" | "agents" if args.trim().is_empty() => Some(BuiltinCommand::Agents),
+        "team" | "teams" if args.trim().is_empty() => Some(BuiltinCommand::Teams),
+        "reset" => Some(BuiltinCommand::Reset(parse_agent_args(args))),
+        _ => None,
+    }
+}
+
+/// Markdown reply for a built-in command.
+pub fn run_builtin(paths: &Paths, command: &BuiltinCommand) -> String {
+    match command {
+        BuiltinCommand::Agents => agent_list_text(paths),
+        BuiltinCommand::Teams => team_list_text(paths),
+        BuiltinCommand::Reset(ids) if ids.is_empty() => {
+            "Usage: `/reset @agent_id` (several IDs are allowed).".to_string()
+        }
+        BuiltinCommand::Reset(ids) => reset_agents(paths, ids),
+    }
+}
+
+pub fn agent_list_text(paths: &Paths) -> String {
+    let settings = match get_settings(&paths.settings_file) {
+        Ok(s) => s,
+        Err(_) => return "Could not load agent configuration.".to_string(),
+    };
+    let agents = get_agents(&settings);
+    if agents.len() <= 1 && agents.contains_key("default") {
+        return "No agents configured. Using default single-agent mode.\n\nConfigure agents in `.rustyclaw/settings.json` or run `rustyclaw agent add`.".to_string();
+    }
+    let mut text = "**Available Agents:**\n".to_string();
+    for (id, agent) in &agents {
+        text += &format!("\n**@{}** - {}", id, agent.name);
+        text += &format!("\n  Provider: {}/{}", agent.provider, agent.model);
+        text += &format!("\n  Directory: {}", agent.working_directory);
+    }
+    text += "\n\nUsage: Start your message with `@agent_id` to route to a specific agent.";
+    text
+}
+
+pub fn team_list_text(paths: &Paths) -> String {
+    let settings = match get_settings(&paths.settings_file) {
+        Ok(s) => s,
+        Err(_) => return "Could not load team configuration.".to_string(),
+    };
+    let teams = get_teams(&settings);
+    if teams.is_empty() {
+        return "No teams configured.\n\nCreate a team with `rustyclaw team add`.".to_string();
+    }
+    let mut text = "**Available Teams:**\n".to_string();
+    for (id, team) in &teams {
+        text += &format!("\n**@{}** - {}", id, team.name);
+        text += &format!("\n  Agents: {}", team.agents.join(", "));
+        text += &format!("\n  Leader: @{}", team.leader_agent);
+    }
+    text += "\n\nUsage: Start your message with `@team_id` to route to a team.";
+    text
+}
+
+/// Flag agents for a session reset on their next message.
+pub fn reset_agents(paths: &Paths, agent_args: &[String]) -> String {
+    let settings = match get_settings(&paths.settings_file) {
+        Ok(s) => s,
+        Err(_) => return "Could not process reset command. Check settings.".to_string(),
+    };
+    let agents = get_agents(&settings);
+    let workspace_path = get_workspace_path(&settings);
+    let mut results = Vec::new();
+    for agent_id in agent_args {
+        let agent_id = agent_id.trim_start_matches('@').to_lowercase();
+        if !agents.contains_key(&agent_id) {
+            results.push(format!("Agent '{}' not found.", agent_id));
+            continue;
+        }
+        let flag_dir = workspace_path.join(&agent_id);
+        let _ = std::fs::create_dir_all(&flag_dir);
+        let _ = std::fs::write(flag_dir.join("reset_flag"), "reset");
+        results.push(format!("Reset @{} ({}).", agent_id, agents[&agent_id].name));
+    }
+    results.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_builtin() {
+        assert_eq!(parse_builtin("/agents"), Some(BuiltinCommand::Agents));
+        assert_eq!(parse_builtin(" !team "), Some(BuiltinCommand::Teams));
+        assert_eq!(
+            parse_builtin("/reset @Coder writer"),
+            Some(BuiltinCommand::Reset(vec!["coder".to_string(), "writer".to_string()]))
+        );
+        assert_eq!(parse_builtin("/reset"), Some(BuiltinCommand::Reset(Vec::new())));
+        // Arguments after /agents mean it is not the built-in
+        assert_eq!(parse_builtin("/agents are great"), None);
+        assert_eq!(parse_builtin("reset @coder"), None);
+        assert_eq!(parse_builtin("/deploy now"), None);
+    }
+}
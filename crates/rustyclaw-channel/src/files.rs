@@ -0,0 +1,101 @@
+//! Attachment file names and downloads.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// Strip directories and characters that are unsafe in file names.
+pub fn sanitize_file_name(name: &str) -> String {
+    let base = Path::new(name)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let cleaned: String = base
+        .chars()
+        .map(|c| if "<>:\"/\\|?*".contains(c) || c.is_control() { '_' } else { c })
+        .collect();
+    let trimmed = cleaned.trim().to_string();
+    if trimmed.is_empty() { "file.bin".to_string() } else { trimmed }
+}
+
+pub fn ensure_file_extension(name: &str, fallback_ext: &str) -> String {
+    if Path::new(name).extension().is_some() {
+        name.to_string()
+    } else {
+        format!("{}{}", name, fallback_ext)
+    }
+}
+
+/// Path in `dir` for `preferred_name`, adding `_1`, `_2`, ... on collisions.
+pub fn build_unique_file_path(dir: &Path, preferred_name: &str) -> PathBuf {
+    let clean = sanitize_file_name(preferred_name);
+    let ext = Path::new(&clean)
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let stem = Path::new(&clean)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let mut candidate = dir.join(&clean);
+    let mut counter = 1;
+    while candidate.exists() {
+        candidate = dir.join(format!("{}_{}{}", stem, counter, ext));
+        counter += 1;
+    }
+    candidate
+}
+
+pub async fn download_file(url: &str, dest: &Path) -> Result<()> {
+    let resp = reqwest::get(url).await?.error_for_status()?;
+    let bytes = resp.bytes().await?;
+    if let Some(dir) = dest.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(dest, &bytes)?;
+    Ok(())
+}
+
+/// `[file: ...]` references appended to a queued message.
+pub fn append_file_refs(message: &str, files: &[String]) -> String {
+    if files.is_empty() {
+        return message.to_string();
+    }
+    let refs: Vec<String> = files.iter().map(|f| format!("[file: {}]", f)).collect();
+    if message.is_empty() {
+        refs.join("\n")
+    } else {
+        format!("{}\n\n{}", message, refs.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("../etc/pass:wd"), "pass_wd");
+        assert_eq!(sanitize_file_name("  "), "file.bin");
+        assert_eq!(ensure_file_extension("voice", ".ogg"), "voice.ogg");
+        assert_eq!(ensure_file_extension("a.png", ".bin"), "a.png");
+    }
+
+    #[test]
+    fn test_build_unique_file_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = build_unique_file_path(dir.path(), "report.pdf");
+        assert_eq!(first, dir.path().join("report.pdf"));
+        std::fs::write(&first, "x").unwrap();
+        assert_eq!(build_unique_file_path(dir.path(), "report.pdf"), dir.path().join("report_1.pdf"));
+    }
+
+    #[test]
+    fn test_append_file_refs() {
+        assert_eq!(append_file_refs("hi", &[]), "hi");
+        assert_eq!(append_file_refs("", &["/f/a".to_string()]), "[file: /f/a]");
+        assert_eq!(append_file_refs("hi", &["/f/a".to_string()]), "hi\n\n[file: /f/a]");
+    }
+}
@@ -0,0 +1,63 @@
+//! Shared runtime for channel clients.
+//!
+//! A platform client implements [`ChannelAdapter`] (send, format and a few
+//! optional hooks) and feeds incoming messages to [`receive`]. The runtime
+//! handles the pairing gate, built-in commands, attachment ingestion,
+//! queueing, and polling and chunking of outgoing responses.
+
+pub mod adapter;
+pub mod commands;
+pub mod files;
+pub mod runtime;
+
+pub use adapter::{Attachment, AttachmentSource, ChannelAdapter, Incoming, Outgoing, Undeliverable};
+pub use runtime::{
+    check_outgoing_queue, enqueue, ingest, pairing_gate, poll_outgoing, prepare_dirs, receive, try_ingest,
+    try_receive,
+};
+
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Queue message ID: millisecond timestamp plus a short random suffix.
+pub fn random_id() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let suffix: String = (0..7)
+        .map(|_| {
+            let idx = rng.gen_range(0..36u8);
+            if idx < 10 { (b'0' + idx) as char } else { (b'a' + idx - 10) as char }
+        })
+        .collect();
+    format!("{}_{}", now_millis(), suffix)
+}
+
+pub fn pairing_message(code: &str) -> String {
+    format!(
+        "This sender is not paired yet.\nYour pairing code: {}\nAsk the Rusty Claw owner to approve you with:\nrustyclaw pairing approve {}",
+        code, code
+    )
+}
+
+#[cfg(test)]
+pub(crate) fn test_paths(home: &std::path::Path) -> rustyclaw_core::config::Paths {
+    rustyclaw_core::config::Paths {
+        script_dir: home.to_path_buf(),
+        rustyclaw_home: home.to_path_buf(),
+        queue_incoming: home.join("queue/incoming"),
+        queue_outgoing: home.join("queue/outgoing"),
+        queue_processing: home.join("queue/processing"),
+        log_file: home.join("logs/channel.log"),
+        settings_file: home.join("settings.json"),
+        events_dir: home.join("events"),
+        chats_dir: home.join("chats"),
+        files_dir: home.join("files"),
+        pairing_file: home.join("pairing.json"),
+        skills_dir: home.join("skills"),
+        skills_lock_file: home.join("skills.lock"),
+    }
+}
@@ -0,0 +1,541 @@
+//! Platform-independent message flow: pairing, built-in commands, the
+//! incoming queue, and delivery of outgoing responses.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use rustyclaw_core::channels::{
+    apply_default_route, extract_choices, long_response_mode, pages_dir, save_pages,
+};
+use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::format::split_rendered;
+use rustyclaw_core::logging::log;
+use rustyclaw_core::pairing::ensure_sender_paired;
+use rustyclaw_core::types::{LongResponseMode, MessageData, ResponseData};
+
+use crate::adapter::{Attachment, AttachmentSource, ChannelAdapter, Incoming, Outgoing, Undeliverable};
+use crate::commands::{parse_builtin, run_builtin};
+use crate::files::{append_file_refs, build_unique_file_path};
+use crate::{now_millis, pairing_message, random_id};
+
+/// Write a message to the incoming queue.
+pub fn enqueue(paths: &Paths, data: &MessageData) -> Result<()> {
+    std::fs::create_dir_all(&paths.queue_incoming)?;
+    let queue_file = paths
+        .queue_incoming
+        .join(format!("{}_{}.json", data.channel, data.message_id));
+    std::fs::write(queue_file, serde_json::to_string_pretty(data)?)?;
+    log("INFO", &format!("Queued message {}", data.message_id), &paths.log_file);
+    Ok(())
+}
+
+/// Check that the sender is paired. New senders get a pairing code in reply;
+/// senders already waiting for approval are ignored.
+pub async fn pairing_gate<A: ChannelAdapter + ?Sized>(
+    adapter: &A,
+    paths: &Paths,
+    incoming: &Incoming,
+) -> bool {
+    let channel = adapter.name();
+    let (sender, sender_id) = (&incoming.sender, &incoming.sender_id);
+    let pairing = ensure_sender_paired(&paths.pairing_file, channel, sender_id, sender);
+    if pairing.approved {
+        return true;
+    }
+    if let Some(code) = &pairing.code {
+        if pairing.is_new_pending == Some(true) {
+            log(
+                "INFO",
+                &format!("Blocked unpaired {} sender {} ({}) with code {}", channel, sender, sender_id, code),
+                &paths.log_file,
+            );
+            if let Err(e) = adapter.reply(&incoming.reply_to, &pairing_message(code)).await {
+                log("WARN", &format!("Failed to send pairing code: {}", e), &paths.log_file);
+            }
+            adapter.pairing_requested(sender, sender_id, code).await;
+        } else {
+            log(
+                "INFO",
+                &format!("Blocked pending {} sender {} ({})", channel, sender, sender_id),
+                &paths.log_file,
+            );
+        }
+    }
+    false
+}
+
+/// Run a message through the pairing gate and built-in commands, save its
+/// attachments, and queue it. Returns the queued message, if any.
+pub async fn receive<A: ChannelAdapter + ?Sized>(
+    adapter: &A,
+    paths: &Paths,
+    incoming: Incoming,
+) -> Option<MessageData> {
+    log_queue_error(try_receive(adapter, paths, incoming).await, paths)
+}
+
+/// [`receive`] that reports a failure to queue, for channels that can
+/// redeliver the message later (e.g. by leaving an email unseen).
+pub async fn try_receive<A: ChannelAdapter + ?Sized>(
+    adapter: &A,
+    paths: &Paths,
+    incoming: Incoming,
+) -> Result<Option<MessageData>> {
+    if incoming.text.trim().is_empty() && incoming.attachments.is_empty() {
+        return Ok(None);
+    }
+    if !pairing_gate(adapter, paths, &incoming).await {
+        return Ok(None);
+    }
+    try_ingest(adapter, paths, incoming).await
+}
+
+/// [`receive`] for callers that already ran [`pairing_gate`].
+pub async fn ingest<A: ChannelAdapter + ?Sized>(
+    adapter: &A,
+    paths: &Paths,
+    incoming: Incoming,
+) -> Option<MessageData> {
+    log_queue_error(try_ingest(adapter, paths, incoming).await, paths)
+}
+
+fn log_queue_error(result: Result<Option<MessageData>>, paths: &Paths) -> Option<MessageData> {
+    result.unwrap_or_else(|e| {
+        log("ERROR", &format!("Failed to queue message: {}", e), &paths.log_file);
+        None
+    })
+}
+
+/// [`try_receive`] for callers that already ran [`pairing_gate`].
+pub async fn try_ingest<A: ChannelAdapter + ?Sized>(
+    adapter: &A,
+    paths: &Paths,
+    incoming: Incoming,
+) -> Result<Option<MessageData>> {
+    if incoming.attachments.is_empty() {
+        if let Some(command) = parse_builtin(&incoming.text) {
+            log("INFO", &format!("Command received: {:?}", command), &paths.log_file);
+            let text = run_builtin(paths, &command);
+            if let Err(e) = adapter.reply(&incoming.reply_to, &text).await {
+                log("WARN", &format!("Failed to answer command: {}", e), &paths.log_file);
+            }
+            return Ok(None);
+        }
+    }
+
+    let message_id = random_id();
+    let files = save_attachments(adapter, paths, &message_id, &incoming.attachments).await;
+    let text = incoming.text.trim();
+    if text.is_empty() && files.is_empty() {
+        return Ok(None);
+    }
+
+    let preview: String = text.chars().take(50).collect();
+    let files_note = if files.is_empty() {
+        String::new()
+    } else {
+        format!(" [+{} file(s)]", files.len())
+    };
+    log(
+        "INFO",
+        &format!("Message from {}: {}{}...", incoming.sender, preview, files_note),
+        &paths.log_file,
+    );
+
+    let message = if text.is_empty() {
+        String::new()
+    } else {
+        apply_default_route(text, incoming.default_route.as_deref())
+    };
+    let data = MessageData {
+        channel: adapter.name().to_string(),
+        sender: incoming.sender,
+        sender_id: Some(incoming.sender_id),
+        message: append_file_refs(&message, &files),
+        timestamp: now_millis(),
+        message_id,
+        agent: None,
+        files: if files.is_empty() { None } else { Some(files) },
+        conversation_id: None,
+        from_agent: None,
+        reply_to: Some(incoming.reply_to),
+        priority: None,
+        traceparent: None,
+    };
+    enqueue(paths, &data)?;
+    Ok(Some(data))
+}
+
+/// Store attachments as `{channel}_{message_id}_{name}` in the files directory.
+async fn save_attachments<A: ChannelAdapter + ?Sized>(
+    adapter: &A,
+    paths: &Paths,
+    message_id: &str,
+    attachments: &[Attachment],
+) -> Vec<String> {
+    let mut saved = Vec::new();
+    for attachment in attachments {
+        let filename = format!("{}_{}_{}", adapter.name(), message_id, attachment.name);
+        let dest = build_unique_file_path(&paths.files_dir, &filename);
+        let result = match &attachment.source {
+            AttachmentSource::Remote(reference) => adapter.download(reference, &dest).await,
+            AttachmentSource::Bytes(bytes) => std::fs::create_dir_all(&paths.files_dir)
+                .and_then(|()| std::fs::write(&dest, bytes))
+                .map_err(Into::into),
+        };
+        match result {
+            Ok(()) => {
+                log(
+                    "INFO",
+                    &format!("Saved attachment: {}", dest.file_name().unwrap_or_default().to_string_lossy()),
+                    &paths.log_file,
+                );
+                saved.push(dest.to_string_lossy().to_string());
+            }
+            Err(e) => log(
+                "ERROR",
+                &format!("Failed to save attachment {}: {}", attachment.name, e),
+                &paths.log_file,
+            ),
+        }
+    }
+    saved
+}
+
+/// First wait before retrying a failed send; doubles with each attempt.
+const RETRY_BASE: Duration = Duration::from_secs(5);
+const RETRY_MAX: Duration = Duration::from_secs(300);
+/// A response that still fails after this many sends (about an hour) is dropped.
+const MAX_SEND_ATTEMPTS: u32 = 20;
+
+/// A response whose send failed, waiting for its next attempt.
+struct Retry {
+    attempts: u32,
+    next: Instant,
+}
+
+/// Deliver this channel's responses from the outgoing queue, once.
+pub async fn check_outgoing_queue<A: ChannelAdapter + ?Sized>(adapter: &A, paths: &Paths) {
+    deliver_outgoing(adapter, paths, &mut HashMap::new()).await;
+}
+
+/// A response file is removed once it is sent or [`Undeliverable`]. Other
+/// failures keep it in the queue and are retried with backoff.
+async fn deliver_outgoing<A: ChannelAdapter + ?Sized>(
+    adapter: &A,
+    paths: &Paths,
+    retries: &mut HashMap<PathBuf, Retry>,
+) {
+    let prefix = format!("{}_", adapter.name());
+    let entries = match std::fs::read_dir(&paths.queue_outgoing) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    retries.retain(|path, _| path.exists());
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(&prefix) || !name.ends_with(".json") {
+            continue;
+        }
+        if retries.get(&path).is_some_and(|r| r.next > Instant::now()) {
+            continue;
+        }
+
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        let response: ResponseData = match serde_json::from_str(&raw) {
+            Ok(r) => r,
+            Err(e) => {
+                log("ERROR", &format!("Bad response JSON {}: {}", name, e), &paths.log_file);
+                continue;
+            }
+        };
+        let Err(e) = deliver(adapter, paths, &response).await else {
+            retries.remove(&path);
+            let _ = std::fs::remove_file(&path);
+            continue;
+        };
+        let attempts = retries.get(&path).map_or(1, |r| r.attempts + 1);
+        if e.downcast_ref::<Undeliverable>().is_some() || attempts >= MAX_SEND_ATTEMPTS {
+            log(
+                "ERROR",
+                &format!("Dropping {} response {}: {}", adapter.name(), response.message_id, e),
+                &paths.log_file,
+            );
+            retries.remove(&path);
+            let _ = std::fs::remove_file(&path);
+        } else {
+            let delay = RETRY_BASE.saturating_mul(1 << (attempts - 1).min(6)).min(RETRY_MAX);
+            log(
+                "WARN",
+                &format!(
+                    "Failed to send {} response {} (attempt {}), retrying in {}s: {}",
+                    adapter.name(),
+                    response.message_id,
+                    attempts,
+                    delay.as_secs(),
+                    e
+                ),
+                &paths.log_file,
+            );
+            retries.insert(path, Retry { attempts, next: Instant::now() + delay });
+        }
+    }
+}
+
+async fn deliver<A: ChannelAdapter + ?Sized>(adapter: &A, paths: &Paths, response: &ResponseData) -> Result<()> {
+    // Prefer the persisted reply target; adapters may keep in-memory state
+    // for responses queued before reply targets existed
+    let target = match response.reply_to.clone() {
+        Some(t) => Some(t),
+        None => adapter.fallback_target(&response.message_id).await,
+    };
+    let Some(target) = target else {
+        return Err(anyhow!(Undeliverable("no reply target".to_string())));
+    };
+
+    let files: Vec<PathBuf> = response
+        .files
+        .iter()
+        .flatten()
+        .map(PathBuf::from)
+        .filter(|f| f.exists())
+        .collect();
+
+    let (mut text, mut choices) = extract_choices(&response.message);
+    if adapter.interactive() {
+        if text.is_empty() && !choices.is_empty() {
+            text = "Choose an option:".to_string();
+        }
+    } else if !choices.is_empty() {
+        let options: Vec<String> = choices.iter().map(|c| format!("- {}", c)).collect();
+        text = format!("{}\n\nReply with one of:\n{}", text, options.join("\n"))
+            .trim()
+            .to_string();
+        choices.clear();
+    }
+
+    let chunks = if text.is_empty() {
+        Vec::new()
+    } else {
+        split_rendered(&text, adapter.max_message_len(), |md| adapter.format(md))
+    };
+    let paginate = adapter.interactive()
+        && chunks.len() > 1
+        && get_settings(&paths.settings_file)
+            .map(|s| long_response_mode(&s, adapter.name()) == LongResponseMode::Paginate)
+            .unwrap_or(false);
+    let page_id = if paginate {
+        match save_pages(&pages_dir(paths), &chunks) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                log("WARN", &format!("Failed to save pages: {}", e), &paths.log_file);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let outgoing = Outgoing { response, target: &target, files, chunks, choices, page_id };
+    adapter.send(&outgoing).await?;
+    log(
+        "INFO",
+        &format!("Sent response to {} ({} chars)", response.sender, response.message.len()),
+        &paths.log_file,
+    );
+    Ok(())
+}
+
+/// Poll the outgoing queue forever.
+pub async fn poll_outgoing<A: ChannelAdapter + ?Sized>(adapter: &A, paths: &Paths, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    let mut retries = HashMap::new();
+    loop {
+        interval.tick().await;
+        deliver_outgoing(adapter, paths, &mut retries).await;
+    }
+}
+
+/// Create the queue, files and log directories a channel client writes to.
+pub fn prepare_dirs(paths: &Paths) {
+    let _ = std::fs::create_dir_all(&paths.queue_incoming);
+    let _ = std::fs::create_dir_all(&paths.queue_outgoing);
+    let _ = std::fs::create_dir_all(&paths.files_dir);
+    if let Some(dir) = paths.log_file.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_paths;
+    use async_trait::async_trait;
+    use rustyclaw_core::types::ReplyTarget;
+    use std::sync::Mutex;
+
+    /// Records everything sent, with a small message limit to force chunking.
+    #[derive(Default)]
+    struct MockAdapter {
+        replies: Mutex<Vec<String>>,
+        sent: Mutex<Vec<(Vec<String>, Vec<PathBuf>)>>,
+        /// Fail every send, permanently or not
+        failure: Option<bool>,
+    }
+
+    #[async_trait]
+    impl ChannelAdapter for MockAdapter {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+        fn max_message_len(&self) -> usize {
+            100
+        }
+        fn format(&self, markdown: &str) -> String {
+            markdown.to_string()
+        }
+        async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()> {
+            match self.failure {
+                Some(true) => return Err(anyhow!(Undeliverable("chat deleted".to_string()))),
+                Some(false) => anyhow::bail!("rate limited"),
+                None => {}
+            }
+            self.sent
+                .lock()
+                .unwrap()
+                .push((outgoing.chunks.clone(), outgoing.files.clone()));
+            Ok(())
+        }
+        async fn reply(&self, _target: &ReplyTarget, text: &str) -> Result<()> {
+            self.replies.lock().unwrap().push(text.to_string());
+            Ok(())
+        }
+    }
+
+    fn incoming(text: &str) -> Incoming {
+        Incoming {
+            sender: "Ada".to_string(),
+            sender_id: "u1".to_string(),
+            text: text.to_string(),
+            attachments: Vec::new(),
+            reply_to: ReplyTarget {
+                chat_id: "room".to_string(),
+                reply_to_message_id: Some("m1".to_string()),
+                thread_id: None,
+            },
+            default_route: Some("coder".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receive_pairs_then_queues() {
+        let home = tempfile::tempdir().unwrap();
+        let paths = test_paths(home.path());
+        let adapter = MockAdapter::default();
+
+        // First contact only gets a pairing code
+        assert!(receive(&adapter, &paths, incoming("hello")).await.is_none());
+        let code = {
+            let replies = adapter.replies.lock().unwrap();
+            assert_eq!(replies.len(), 1);
+            replies[0].lines().nth(1).unwrap().rsplit(' ').next().unwrap().to_string()
+        };
+        assert!(rustyclaw_core::pairing::approve_pairing_code(&paths.pairing_file, &code).ok);
+
+        let mut msg = incoming("hello");
+        msg.attachments.push(Attachment {
+            name: "notes.txt".to_string(),
+            source: AttachmentSource::Bytes(b"hi".to_vec()),
+        });
+        let data = receive(&adapter, &paths, msg).await.unwrap();
+        assert_eq!(data.channel, "mock");
+        let files = data.files.clone().unwrap();
+        assert!(files[0].ends_with("_notes.txt"));
+        assert_eq!(data.message, format!("@coder hello\n\n[file: {}]", files[0]));
+        let queued = paths.queue_incoming.join(format!("mock_{}.json", data.message_id));
+        assert!(queued.exists());
+
+        // Built-in commands are answered directly
+        assert!(receive(&adapter, &paths, incoming("/reset")).await.is_none());
+        assert!(adapter.replies.lock().unwrap()[1].starts_with("Usage:"));
+    }
+
+    #[tokio::test]
+    async fn test_outgoing_chunks_and_choices() {
+        let home = tempfile::tempdir().unwrap();
+        let paths = test_paths(home.path());
+        std::fs::create_dir_all(&paths.queue_outgoing).unwrap();
+        let adapter = MockAdapter::default();
+
+        let response = ResponseData {
+            channel: "mock".to_string(),
+            sender: "Ada".to_string(),
+            message: format!("{}\n\n{} [choices: Yes | No]", "a".repeat(80), "b".repeat(80)),
+            original_message: "hi".to_string(),
+            timestamp: 0,
+            message_id: "m1".to_string(),
+            agent: None,
+            files: Some(vec!["/nonexistent/file.txt".to_string()]),
+            reply_to: Some(incoming("").reply_to),
+        };
+        let file = paths.queue_outgoing.join("mock_m1_1.json");
+        std::fs::write(&file, serde_json::to_string(&response).unwrap()).unwrap();
+        // Other channels' responses are left alone
+        let other = paths.queue_outgoing.join("other_m2_1.json");
+        std::fs::write(&other, "{}").unwrap();
+
+        check_outgoing_queue(&adapter, &paths).await;
+
+        let sent = adapter.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        let (chunks, files) = &sent[0];
+        assert!(files.is_empty());
+        assert!(chunks.len() >= 2);
+        assert!(chunks.iter().all(|c| c.len() <= 100));
+        assert!(chunks.last().unwrap().contains("- No"));
+        assert!(!file.exists());
+        assert!(other.exists());
+    }
+
+    #[tokio::test]
+    async fn test_outgoing_failures() {
+        let home = tempfile::tempdir().unwrap();
+        let paths = test_paths(home.path());
+        std::fs::create_dir_all(&paths.queue_outgoing).unwrap();
+        let response = ResponseData {
+            channel: "mock".to_string(),
+            sender: "Ada".to_string(),
+            message: "done".to_string(),
+            original_message: "hi".to_string(),
+            timestamp: 0,
+            message_id: "m1".to_string(),
+            agent: None,
+            files: None,
+            reply_to: Some(incoming("").reply_to),
+        };
+        let file = paths.queue_outgoing.join("mock_m1_1.json");
+        std::fs::write(&file, serde_json::to_string(&response).unwrap()).unwrap();
+
+        // Transient failures keep the response and back off
+        let flaky = MockAdapter { failure: Some(false), ..Default::default() };
+        let mut retries = HashMap::new();
+        deliver_outgoing(&flaky, &paths, &mut retries).await;
+        assert!(file.exists());
+        assert_eq!(retries[&file].attempts, 1);
+        deliver_outgoing(&flaky, &paths, &mut retries).await;
+        assert_eq!(retries[&file].attempts, 1);
+
+        // Permanent failures drop it
+        let gone = MockAdapter { failure: Some(true), ..Default::default() };
+        check_outgoing_queue(&gone, &paths).await;
+        assert!(!file.exists());
+    }
+}
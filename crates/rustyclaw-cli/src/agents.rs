@@ -6,7 +6,7 @@ use dialoguer::{Confirm, Input, Select};
 
 use rustyclaw_core::agent_setup::{ensure_agent_directory, populate_agent_identity, update_agent_teammates};
 use rustyclaw_core::config::{get_agents, get_settings, get_teams, get_workspace_path, Paths};
-use rustyclaw_core::types::{AgentConfig, ProviderModelConfig, Settings};
+use rustyclaw_core::types::{AgentConfig, ProviderModelConfig, ResetPolicy, Settings};
 
 /// List all configured agents
 pub fn list_agents(paths: &Paths) -> Result<()> {
@@ -152,8 +152,9 @@ pub fn add_agent(paths: &Paths) -> Result<()> {
         provider: provider.clone(),
         model: model.clone(),
         working_directory: working_directory.clone(),
-        reset_policy: String::new(),
+        reset_policy: ResetPolicy::default(),
         reset_hour: None,
+        timezone: None,
         idle_timeout_minutes: None,
         context_window: None,
         fallbacks: None,
@@ -4,6 +4,7 @@ use anyhow::{bail, Context, Result};
 use colored::Colorize;
 
 use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::log_files::LogRecord;
 
 const SESSION_NAME: &str = "rustyclaw";
 
@@ -111,6 +112,21 @@ pub fn start(paths: &Paths) -> Result<()> {
         .and_then(|c| c.telegram.as_ref())
         .and_then(|t| t.bot_token.as_deref())
         .unwrap_or("");
+    let slack = settings.channels.as_ref().and_then(|c| c.slack.as_ref());
+    let slack_bot_token = slack.and_then(|s| s.bot_token.as_deref()).unwrap_or("");
+    let slack_app_token = slack.and_then(|s| s.app_token.as_deref()).unwrap_or("");
+    let matrix_token = settings
+        .channels
+        .as_ref()
+        .and_then(|c| c.matrix.as_ref())
+        .and_then(|m| m.access_token.as_deref())
+        .unwrap_or("");
+    let email_password = settings
+        .channels
+        .as_ref()
+        .and_then(|c| c.email.as_ref())
+        .and_then(|e| e.password.as_deref())
+        .unwrap_or("");
 
     // Split for channel clients
     let mut pane_index = 1;
@@ -118,6 +134,14 @@ pub fn start(paths: &Paths) -> Result<()> {
         let token_env = match channel.as_str() {
             "discord" => format!("DISCORD_BOT_TOKEN={}", discord_token),
             "telegram" => format!("TELOXIDE_TOKEN={}", telegram_token),
+            "slack" => format!(
+                "SLACK_BOT_TOKEN={} SLACK_APP_TOKEN={}",
+                slack_bot_token, slack_app_token
+            ),
+            "matrix" => format!("MATRIX_ACCESS_TOKEN={}", matrix_token),
+            "email" => format!("EMAIL_PASSWORD={}", email_password),
+            // API keys are read from settings
+            "http" => String::new(),
             _ => continue,
         };
 
@@ -236,6 +260,18 @@ pub fn stop(paths: &Paths) -> Result<()> {
     let _ = Command::new("pkill")
         .args(["-f", "rustyclaw run telegram"])
         .status();
+    let _ = Command::new("pkill")
+        .args(["-f", "rustyclaw run slack"])
+        .status();
+    let _ = Command::new("pkill")
+        .args(["-f", "rustyclaw run matrix"])
+        .status();
+    let _ = Command::new("pkill")
+        .args(["-f", "rustyclaw run email"])
+        .status();
+    let _ = Command::new("pkill")
+        .args(["-f", "rustyclaw run http"])
+        .status();
     let _ = Command::new("pkill")
         .args(["-f", "rustyclaw run heartbeat"])
         .status();
@@ -356,6 +392,9 @@ pub fn status(paths: &Paths) -> Result<()> {
             if let Some(last_line) = content.lines().rev().next() {
                 println!();
                 println!("  Last activity:");
+                let last_line = LogRecord::parse(last_line, "queue")
+                    .map(|r| r.console_line())
+                    .unwrap_or_else(|| last_line.to_string());
                 println!("    {}", last_line.dimmed());
             }
         }
@@ -4,7 +4,12 @@ use std::process::Command;
 use anyhow::Result;
 use colored::Colorize;
 
-use rustyclaw_core::config::{get_agents, get_settings, get_teams, Paths};
+use rustyclaw_core::config::{
+    get_agents, get_settings, get_teams, get_workspace_path, resolve_working_dir, Paths,
+};
+use rustyclaw_core::skills::{discover_skills, skill_ineligibility_reasons, EligibilityContext};
+use rustyclaw_core::timezone::parse_timezone;
+use rustyclaw_core::types::ResetPolicy;
 
 /// Run the doctor command — check all prerequisites and configuration.
 pub fn run_doctor(paths: &Paths) -> Result<()> {
@@ -78,19 +83,40 @@ pub fn run_doctor(paths: &Paths) -> Result<()> {
             }
         }
 
-        // 4. Check agent working directories
+        // 4. Check agent working directories (resolved the way the queue does)
+        let workspace_path = get_workspace_path(settings);
         for (id, agent) in &agents {
-            let dir = std::path::Path::new(&agent.working_directory);
+            let dir = resolve_working_dir(agent, id, &workspace_path);
             if dir.exists() {
-                print_ok(&format!("Agent '{}' working dir: {}", id, agent.working_directory));
+                print_ok(&format!("Agent '{}' working dir: {}", id, dir.display()));
             } else {
                 print_warn(&format!(
                     "Agent '{}' working dir missing: {} (will be created on first message)",
-                    id, agent.working_directory
+                    id,
+                    dir.display()
                 ));
             }
         }
 
+        // 4b. Check session reset policies and timezones
+        for (id, agent) in &agents {
+            if let Some(ref tz) = agent.timezone {
+                if parse_timezone(tz).is_none() {
+                    print_fail(&format!(
+                        "Agent '{}' timezone '{}' is not a valid IANA timezone (falling back to UTC)",
+                        id, tz
+                    ));
+                    issues += 1;
+                }
+            }
+            if let ResetPolicy::Invalid(ref raw) = agent.reset_policy {
+                if let Err(e) = raw.parse::<ResetPolicy>() {
+                    print_fail(&format!("Agent '{}' reset policy: {} (using \"both\")", id, e));
+                    issues += 1;
+                }
+            }
+        }
+
         // 5. Check teams reference valid agents
         let teams = get_teams(settings);
         for (team_id, team) in &teams {
@@ -123,6 +149,51 @@ pub fn run_doctor(paths: &Paths) -> Result<()> {
             }
         }
 
+        // 5b. Check which skills each agent can use
+        let skills = discover_skills(&[&paths.script_dir.join("skills"), &paths.skills_dir]);
+        if !skills.is_empty() {
+            let overrides = settings.skills.clone().unwrap_or_default();
+            for (id, agent) in &agents {
+                let provider = if agent.provider.is_empty() {
+                    "anthropic"
+                } else {
+                    agent.provider.as_str()
+                };
+                let team_ids: Vec<String> = teams
+                    .iter()
+                    .filter(|(_, team)| team.agents.contains(id))
+                    .map(|(team_id, _)| team_id.clone())
+                    .collect();
+                let working_dir = resolve_working_dir(agent, id, &workspace_path);
+                let ctx = EligibilityContext {
+                    agent_id: id,
+                    provider,
+                    team_ids: &team_ids,
+                    working_dir: &working_dir,
+                };
+                let mut eligible = 0;
+                for skill in &skills {
+                    let reasons = skill_ineligibility_reasons(skill, &overrides, &ctx);
+                    if reasons.is_empty() {
+                        eligible += 1;
+                    } else {
+                        print_warn(&format!(
+                            "Agent '{}' skill '{}' unavailable: {}",
+                            id,
+                            skill.meta.name,
+                            reasons.join("; ")
+                        ));
+                    }
+                }
+                print_ok(&format!(
+                    "Agent '{}': {}/{} skills eligible",
+                    id,
+                    eligible,
+                    skills.len()
+                ));
+            }
+        }
+
         // 6. Check channels
         if let Some(ref channels) = settings.channels {
             if let Some(ref enabled) = channels.enabled {
@@ -156,6 +227,65 @@ pub fn run_doctor(paths: &Paths) -> Result<()> {
                                 issues += 1;
                             }
                         }
+                        "slack" => {
+                            let slack = channels.slack.as_ref();
+                            let has_tokens = [
+                                slack.and_then(|s| s.bot_token.as_ref()),
+                                slack.and_then(|s| s.app_token.as_ref()),
+                            ]
+                            .iter()
+                            .all(|t| t.is_some_and(|t| !t.is_empty()));
+                            if has_tokens {
+                                print_ok("Slack: enabled with bot and app tokens");
+                            } else {
+                                print_fail("Slack: enabled but bot_token and app_token are not both configured");
+                                issues += 1;
+                            }
+                        }
+                        "matrix" => {
+                            let matrix = channels.matrix.as_ref();
+                            let configured = [
+                                matrix.and_then(|m| m.homeserver_url.as_ref()),
+                                matrix.and_then(|m| m.access_token.as_ref()),
+                            ]
+                            .iter()
+                            .all(|v| v.is_some_and(|v| !v.is_empty()));
+                            if configured {
+                                print_ok("Matrix: enabled with homeserver and access token");
+                            } else {
+                                print_fail("Matrix: enabled but homeserver_url and access_token are not both configured");
+                                issues += 1;
+                            }
+                        }
+                        "email" => {
+                            let email = channels.email.as_ref();
+                            let configured = [
+                                email.and_then(|e| e.address.as_ref().or(e.username.as_ref())),
+                                email.and_then(|e| e.imap_host.as_ref()),
+                                email.and_then(|e| e.trusted_authserv_id.as_ref()),
+                            ]
+                            .iter()
+                            .all(|v| v.is_some_and(|v| !v.is_empty()));
+                            if configured {
+                                print_ok("Email: enabled with IMAP mailbox");
+                            } else {
+                                print_fail("Email: enabled but address, imap_host and trusted_authserv_id are not all configured");
+                                issues += 1;
+                            }
+                        }
+                        "http" => {
+                            let key_count = channels
+                                .http
+                                .as_ref()
+                                .map(|h| h.api_keys.iter().filter(|k| !k.key.is_empty()).count())
+                                .unwrap_or(0);
+                            if key_count > 0 {
+                                print_ok(&format!("HTTP API: enabled with {} API key(s)", key_count));
+                            } else {
+                                print_fail("HTTP API: enabled but no API keys configured");
+                                issues += 1;
+                            }
+                        }
                         _ => {}
                     }
                 }
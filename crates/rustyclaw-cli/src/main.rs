@@ -5,8 +5,11 @@ mod pairing_cmd;
 mod messaging;
 mod setup;
 mod viz_server;
+mod viz_auth;
+mod viz_web;
 mod doctor;
 mod cooldown_cmd;
+mod skill_cmd;
 
 use std::env;
 use std::path::PathBuf;
@@ -41,11 +44,29 @@ enum Commands {
         /// The message to send
         message: Vec<String>,
     },
-    /// View logs
+    /// View and search logs
     Logs {
-        /// Log target: queue, discord, telegram, heartbeat, or all
+        /// Log target: queue, discord, telegram, slack, matrix, email, http, heartbeat, or all
         #[arg(default_value = "queue")]
         target: String,
+        /// Only lines about this agent
+        #[arg(long)]
+        agent: Option<String>,
+        /// Only lines newer than this: 30m, 1h, 2d, or an RFC 3339 time
+        #[arg(long)]
+        since: Option<String>,
+        /// Only lines whose message or fields match this regex
+        #[arg(long)]
+        grep: Option<String>,
+        /// Minimum level: debug, info, warn or error
+        #[arg(long)]
+        level: Option<String>,
+        /// Keep printing new lines (the default when no filter is given)
+        #[arg(short, long)]
+        follow: bool,
+        /// Print raw JSON lines
+        #[arg(long)]
+        json: bool,
     },
     /// Reset agent conversation(s)
     Reset {
@@ -72,6 +93,8 @@ enum Commands {
         /// Port for the viz server
         #[arg(long, default_value = "8090")]
         port: u16,
+        #[command(subcommand)]
+        command: Option<VizCommands>,
     },
     /// Run the interactive setup wizard
     Setup,
@@ -82,6 +105,11 @@ enum Commands {
         #[command(subcommand)]
         command: CooldownCommands,
     },
+    /// Manage skill packages
+    Skill {
+        #[command(subcommand)]
+        command: SkillCommands,
+    },
     /// Set the default provider
     Provider {
         /// Provider name (anthropic, openai, opencode)
@@ -109,6 +137,10 @@ enum RunComponent {
     Heartbeat,
     Discord,
     Telegram,
+    Slack,
+    Matrix,
+    Email,
+    Http,
 }
 
 #[derive(Subcommand)]
@@ -172,6 +204,67 @@ enum CooldownCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum SkillCommands {
+    /// Install a skill from a local directory, git URL, or local tarball
+    Install {
+        /// Path, git URL, or .tar/.tar.gz/.tgz archive
+        source: String,
+    },
+    /// List skills with version and enabled state
+    List,
+    /// Enable a skill
+    Enable {
+        /// Skill name
+        name: String,
+    },
+    /// Disable a skill
+    Disable {
+        /// Skill name
+        name: String,
+    },
+    /// Re-fetch installed skills from their sources (all if omitted)
+    Update {
+        /// Skill name
+        name: Option<String>,
+    },
+    /// Remove an installed skill
+    Remove {
+        /// Skill name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum VizCommands {
+    /// Manage visualizer logins
+    User {
+        #[command(subcommand)]
+        command: VizUserCommands,
+    },
+    /// Print the admin login token
+    Token {
+        /// Replace the token with a new one
+        #[arg(long)]
+        rotate: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum VizUserCommands {
+    /// List users
+    List,
+    /// Add a user, or change an existing user's password and role
+    Add {
+        name: String,
+        /// viewer (read-only) or operator (can send messages and edit settings)
+        #[arg(long, default_value = "viewer")]
+        role: viz_auth::Role,
+    },
+    /// Remove a user
+    Remove { name: String },
+}
+
 #[derive(Subcommand)]
 enum PairingCommands {
     /// List pending pairing requests
@@ -262,7 +355,25 @@ fn main() -> Result<()> {
             let msg = message.join(" ");
             messaging::send_message(&msg, &paths)
         }
-        Some(Commands::Logs { target }) => messaging::view_logs(&target, &paths),
+        Some(Commands::Logs {
+            target,
+            agent,
+            since,
+            grep,
+            level,
+            follow,
+            json,
+        }) => {
+            let options = messaging::LogOptions {
+                agent,
+                since,
+                grep,
+                level,
+                follow,
+                json,
+            };
+            messaging::view_logs(&target, &options, &paths)
+        }
         Some(Commands::Reset { agent_ids }) => agents::reset_agents(&agent_ids, &paths),
         Some(Commands::Agent { command }) => match command {
             AgentCommands::List => agents::list_agents(&paths),
@@ -287,10 +398,26 @@ fn main() -> Result<()> {
             PairingCommands::Approve { code } => pairing_cmd::approve(&code, &paths),
             PairingCommands::Unpair { sender_id } => pairing_cmd::unpair(&sender_id, &paths),
         },
-        Some(Commands::Visualize { port }) => {
-            let static_dir = find_viz_dist_dir();
-            viz_server::start_viz_server(&paths, port, static_dir.as_deref())
-        }
+        Some(Commands::Visualize { port, command }) => match command {
+            None => {
+                let static_dir = find_viz_dist_dir();
+                viz_server::start_viz_server(&paths, port, static_dir.as_deref())
+            }
+            Some(VizCommands::User { command }) => match command {
+                VizUserCommands::List => viz_auth::list_users(&paths),
+                VizUserCommands::Add { name, role } => viz_auth::add_user(&paths, &name, role),
+                VizUserCommands::Remove { name } => viz_auth::remove_user(&paths, &name),
+            },
+            Some(VizCommands::Token { rotate }) => viz_auth::show_token(&paths, rotate),
+        },
+        Some(Commands::Skill { command }) => match command {
+            SkillCommands::Install { source } => skill_cmd::install(&source, &paths),
+            SkillCommands::List => skill_cmd::list(&paths),
+            SkillCommands::Enable { name } => skill_cmd::set_enabled(&name, true, &paths),
+            SkillCommands::Disable { name } => skill_cmd::set_enabled(&name, false, &paths),
+            SkillCommands::Update { name } => skill_cmd::update(name.as_deref(), &paths),
+            SkillCommands::Remove { name } => skill_cmd::remove(&name, &paths),
+        },
         Some(Commands::Provider { name, model }) => agents::set_provider(name.as_deref(), model.as_deref(), &paths),
         Some(Commands::Model { name }) => agents::set_model(name.as_deref(), &paths),
         Some(Commands::Run { component }) => {
@@ -302,6 +429,10 @@ fn main() -> Result<()> {
                     RunComponent::Heartbeat => rustyclaw_heartbeat::run((*paths_arc).clone()).await,
                     RunComponent::Discord => rustyclaw_discord::run(paths_arc).await,
                     RunComponent::Telegram => rustyclaw_telegram::run(paths_arc).await,
+                    RunComponent::Slack => rustyclaw_slack::run(paths_arc).await,
+                    RunComponent::Matrix => rustyclaw_matrix::run(paths_arc).await,
+                    RunComponent::Email => rustyclaw_email::run(paths_arc).await,
+                    RunComponent::Http => rustyclaw_http::run(paths_arc).await,
                 }
             })
         }
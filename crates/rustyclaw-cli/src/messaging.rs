@@ -1,10 +1,13 @@
-use std::process::Command;
+use std::io::{Read, Seek, SeekFrom, Write};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::Utc;
 use colored::Colorize;
 use rand::Rng;
+use regex::Regex;
 
 use rustyclaw_core::config::Paths;
+use rustyclaw_core::log_files::{self, LogQuery, LogRecord};
 use rustyclaw_core::types::MessageData;
 
 /// Send a message to the queue (written as JSON to incoming/)
@@ -35,6 +38,9 @@ pub fn send_message(message: &str, paths: &Paths) -> Result<()> {
         files: None,
         conversation_id: None,
         from_agent: None,
+        reply_to: None,
+        priority: None,
+        traceparent: None,
     };
 
     let json = serde_json::to_string_pretty(&msg)?;
@@ -46,80 +52,195 @@ pub fn send_message(message: &str, paths: &Paths) -> Result<()> {
     Ok(())
 }
 
-/// View logs (tail -f)
-pub fn view_logs(target: &str, paths: &Paths) -> Result<()> {
-    let log_dir = paths.rustyclaw_home.join("logs");
+/// Log components that write `logs/<component>.log`.
+const LOG_COMPONENTS: [&str; 8] = [
+    "queue", "discord", "telegram", "slack", "matrix", "email", "http", "heartbeat",
+];
 
-    let log_pattern = match target {
-        "queue" => log_dir.join("queue.log"),
-        "discord" => log_dir.join("discord.log"),
-        "telegram" => log_dir.join("telegram.log"),
-        "heartbeat" => log_dir.join("heartbeat.log"),
-        "all" => {
-            // Tail all log files
-            let pattern = log_dir.join("*.log");
-            println!("Tailing all logs from {}", log_dir.display().to_string().dimmed());
-            println!("{}", "(Ctrl+C to stop)".dimmed());
-            println!();
-
-            let status = Command::new("tail")
-                .args(["-f", &pattern.to_string_lossy()])
-                .status()?;
-
-            if !status.success() {
-                // Try with explicit file list
-                let files: Vec<String> = std::fs::read_dir(&log_dir)
-                    .map(|entries| {
-                        entries
-                            .filter_map(|e| e.ok())
-                            .filter(|e| {
-                                e.path()
-                                    .extension()
-                                    .map(|ext| ext == "log")
-                                    .unwrap_or(false)
-                            })
-                            .map(|e| e.path().to_string_lossy().to_string())
-                            .collect()
-                    })
-                    .unwrap_or_default();
-
-                if files.is_empty() {
-                    println!("{}", "No log files found.".yellow());
-                } else {
-                    let mut args = vec!["-f".to_string()];
-                    args.extend(files);
-                    Command::new("tail").args(&args).status()?;
-                }
-            }
-            return Ok(());
+/// Earlier lines shown before following, like `tail -f`.
+const TAIL_LINES: usize = 20;
+
+/// Filters and output options for `rustyclaw logs`.
+pub struct LogOptions {
+    pub agent: Option<String>,
+    pub since: Option<String>,
+    pub grep: Option<String>,
+    pub level: Option<String>,
+    pub follow: bool,
+    pub json: bool,
+}
+
+/// Query the JSON logs. Without filters this shows the latest lines and
+/// follows, like `tail -f`; with filters it prints matches and exits
+/// unless `--follow` is given.
+pub fn view_logs(target: &str, options: &LogOptions, paths: &Paths) -> Result<()> {
+    match query_logs(target, options, paths) {
+        // The reader went away, e.g. `rustyclaw logs --grep x | head`
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
         }
+        other => other,
+    }
+}
+
+fn query_logs(target: &str, options: &LogOptions, paths: &Paths) -> Result<()> {
+    let log_dir = paths.rustyclaw_home.join("logs");
+
+    let components: Vec<String> = match target {
+        "all" => log_files::components(&log_dir),
+        t if LOG_COMPONENTS.contains(&t) => vec![t.to_string()],
         other => {
             println!(
-                "{} Unknown log target '{}'. Options: queue, discord, telegram, heartbeat, all",
+                "{} Unknown log target '{}'. Options: {}, all",
                 "Error:".red(),
-                other
+                other,
+                LOG_COMPONENTS.join(", ")
             );
             return Ok(());
         }
     };
-
-    if !log_pattern.exists() {
-        println!("{} Log file not found: {}", "Warning:".yellow(), log_pattern.display());
-        println!("Waiting for log output...");
+    if components.is_empty() {
+        println!("{}", "No log files found.".yellow());
+        return Ok(());
     }
 
-    println!(
-        "Tailing {}",
-        log_pattern.display().to_string().dimmed()
-    );
-    println!("{}", "(Ctrl+C to stop)".dimmed());
-    println!();
+    let query = LogQuery {
+        since: options
+            .since
+            .as_deref()
+            .map(|s| log_files::parse_since(s, Utc::now()))
+            .transpose()?,
+        level: options.level.clone(),
+        agent: options.agent.clone(),
+        grep: options
+            .grep
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("Invalid --grep pattern")?,
+    };
+    let follow = options.follow || query.is_empty();
+    let show_component = components.len() > 1;
+
+    // Earlier lines, oldest first across rotated and current files
+    let mut records = Vec::new();
+    let mut followed = Vec::new();
+    for component in &components {
+        let current = log_files::log_path(&log_dir, component);
+        for file in log_files::rotated_files(&log_dir, component)
+            .into_iter()
+            .chain(std::iter::once(current.clone()))
+        {
+            let modified = std::fs::metadata(&file)
+                .and_then(|m| m.modified())
+                .map(chrono::DateTime::<Utc>::from);
+            if let (Some(since), Ok(modified)) = (query.since, modified) {
+                if modified < since {
+                    continue;
+                }
+            }
+            let Ok(content) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            records.extend(
+                content
+                    .lines()
+                    .filter_map(|line| LogRecord::parse(line, component))
+                    .filter(|r| query.matches(r)),
+            );
+        }
+        let offset = std::fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+        followed.push((component.clone(), current, offset));
+    }
+    records.sort_by(|a, b| a.ts.cmp(&b.ts));
+    if follow && query.since.is_none() {
+        let skip = records.len().saturating_sub(TAIL_LINES);
+        records.drain(..skip);
+    }
+    for record in &records {
+        print_log_record(record, show_component, options.json)?;
+    }
+    if !follow {
+        return Ok(());
+    }
 
-    Command::new("tail")
-        .args(["-f", &log_pattern.to_string_lossy()])
-        .status()?;
+    writeln!(
+        std::io::stdout(),
+        "{}",
+        format!("Following {} logs in {} (Ctrl+C to stop)", target, log_dir.display()).dimmed()
+    )?;
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        for (component, path, offset) in followed.iter_mut() {
+            let len = std::fs::metadata(&*path).map(|m| m.len()).unwrap_or(0);
+            if len < *offset {
+                // Rotated: start again at the top of the new file
+                *offset = 0;
+            }
+            if len == *offset {
+                continue;
+            }
+            let Ok(mut file) = std::fs::File::open(&*path) else {
+                continue;
+            };
+            let mut buf = Vec::new();
+            file.seek(SeekFrom::Start(*offset))?;
+            file.take(len - *offset).read_to_end(&mut buf)?;
+            // Leave a partly written last line for the next round
+            let Some(end) = buf.iter().rposition(|b| *b == b'\n') else {
+                continue;
+            };
+            *offset += end as u64 + 1;
+            for line in String::from_utf8_lossy(&buf[..end]).lines() {
+                if let Some(record) = LogRecord::parse(line, component) {
+                    if query.matches(&record) {
+                        print_log_record(&record, show_component, options.json)?;
+                    }
+                }
+            }
+        }
+    }
+}
 
-    Ok(())
+fn print_log_record(record: &LogRecord, show_component: bool, json: bool) -> std::io::Result<()> {
+    let mut out = std::io::stdout().lock();
+    if json {
+        return match serde_json::to_string(record) {
+            Ok(line) => writeln!(out, "{}", line),
+            Err(_) => Ok(()),
+        };
+    }
+    let ts = record
+        .time()
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+        .unwrap_or_else(|| record.ts.clone());
+    let level = format!("{:<5}", record.level);
+    let level = match record.level.as_str() {
+        "ERROR" => level.red().bold(),
+        "WARN" => level.yellow(),
+        "INFO" => level.green(),
+        _ => level.dimmed(),
+    };
+    let mut line = format!("{} {} ", ts.dimmed(), level);
+    if show_component {
+        line.push_str(&format!("{} ", record.component.cyan()));
+    }
+    line.push_str(&record.message);
+    let fields: Vec<String> = record
+        .fields
+        .iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::String(s) => format!("{}={}", key, s),
+            other => format!("{}={}", key, other),
+        })
+        .collect();
+    if !fields.is_empty() {
+        line.push_str(&format!(" {}", fields.join(" ").dimmed()));
+    }
+    writeln!(out, "{}", line)
 }
 
 fn random_suffix() -> String {
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 
 use anyhow::{bail, Result};
 use colored::Colorize;
-use dialoguer::{Confirm, Input, MultiSelect, Select};
+use dialoguer::{Confirm, Input, MultiSelect, Password, Select};
 
 use rustyclaw_core::agent_setup::{ensure_agent_directory, populate_agent_identity, update_agent_teammates};
 use rustyclaw_core::config::{get_agents, get_teams, Paths};
@@ -42,6 +42,12 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
     let mut enabled_channels: Vec<String> = Vec::new();
     let mut discord_token = String::new();
     let mut telegram_token = String::new();
+    let mut slack_bot_token = String::new();
+    let mut slack_app_token = String::new();
+    let mut matrix_homeserver = String::new();
+    let mut matrix_token = String::new();
+    let mut email_config: Option<EmailChannelConfig> = None;
+    let mut http_config: Option<HttpChannelConfig> = None;
 
     let enable_discord = Confirm::new()
         .with_prompt("  Enable Discord?")
@@ -60,6 +66,42 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
         enabled_channels.push("telegram".to_string());
         println!("    {}", "Telegram enabled".green());
     }
+
+    let enable_slack = Confirm::new()
+        .with_prompt("  Enable Slack?")
+        .default(false)
+        .interact()?;
+    if enable_slack {
+        enabled_channels.push("slack".to_string());
+        println!("    {}", "Slack enabled".green());
+    }
+
+    let enable_matrix = Confirm::new()
+        .with_prompt("  Enable Matrix?")
+        .default(false)
+        .interact()?;
+    if enable_matrix {
+        enabled_channels.push("matrix".to_string());
+        println!("    {}", "Matrix enabled".green());
+    }
+
+    let enable_email = Confirm::new()
+        .with_prompt("  Enable Email?")
+        .default(false)
+        .interact()?;
+    if enable_email {
+        enabled_channels.push("email".to_string());
+        println!("    {}", "Email enabled".green());
+    }
+
+    let enable_http = Confirm::new()
+        .with_prompt("  Enable HTTP API (for CI jobs and webhooks)?")
+        .default(false)
+        .interact()?;
+    if enable_http {
+        enabled_channels.push("http".to_string());
+        println!("    {}", "HTTP API enabled".green());
+    }
     println!();
 
     if enabled_channels.is_empty() {
@@ -103,6 +145,94 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
         println!();
     }
 
+    if enabled_channels.contains(&"slack".to_string()) {
+        println!("Enter your Slack tokens (Socket Mode must be enabled for the app):");
+        println!(
+            "{}",
+            "(Bot token under OAuth & Permissions, app-level token with connections:write under Basic Information)".yellow()
+        );
+        slack_bot_token = Input::new()
+            .with_prompt("Bot token (xoxb-...)")
+            .interact_text()?;
+        slack_app_token = Input::new()
+            .with_prompt("App token (xapp-...)")
+            .interact_text()?;
+        if slack_bot_token.is_empty() || slack_app_token.is_empty() {
+            bail!("Slack bot and app tokens are required");
+        }
+        println!("{}", "Slack tokens saved".green());
+        println!();
+    }
+
+    if enabled_channels.contains(&"matrix".to_string()) {
+        println!("Enter your Matrix bot account details:");
+        matrix_homeserver = Input::new()
+            .with_prompt("Homeserver URL (https://matrix.example.org)")
+            .interact_text()?;
+        matrix_token = Input::new()
+            .with_prompt("Access token")
+            .interact_text()?;
+        if matrix_homeserver.is_empty() || matrix_token.is_empty() {
+            bail!("Matrix homeserver URL and access token are required");
+        }
+        println!("{}", "Matrix account saved".green());
+        println!();
+    }
+
+    if enabled_channels.contains(&"email".to_string()) {
+        println!("Enter the bot's mailbox details:");
+        println!(
+            "{}",
+            "(Senders can route with bot+agent@ addresses or a \"@agent ...\" subject)".yellow()
+        );
+        let address: String = Input::new()
+            .with_prompt("Email address")
+            .interact_text()?;
+        let imap_host: String = Input::new()
+            .with_prompt("IMAP host")
+            .interact_text()?;
+        let smtp_host: String = Input::new()
+            .with_prompt("SMTP host")
+            .with_initial_text(imap_host.clone())
+            .interact_text()?;
+        let password: String = Password::new()
+            .with_prompt("Password")
+            .interact()?;
+        println!(
+            "{}",
+            "(The authserv-id is the first word of the Authentication-Results header your mail server adds)".yellow()
+        );
+        let authserv_id: String = Input::new()
+            .with_prompt("Receiving mail server authserv-id (e.g. mx.example.com)")
+            .interact_text()?;
+        if address.is_empty() || imap_host.is_empty() || authserv_id.is_empty() {
+            bail!("Email address, IMAP host and authserv-id are required");
+        }
+        email_config = Some(EmailChannelConfig {
+            username: Some(address.clone()),
+            address: Some(address),
+            password: Some(password),
+            imap_host: Some(imap_host),
+            smtp_host: Some(smtp_host),
+            trusted_authserv_id: Some(authserv_id),
+            ..Default::default()
+        });
+        println!("{}", "Email account saved".green());
+        println!();
+    }
+
+    if enabled_channels.contains(&"http".to_string()) {
+        let key = generate_api_key();
+        println!("HTTP API key (shown once, store it in your CI secrets):");
+        println!("  {}", key.bright_white().bold());
+        println!("{}", "Send requests to http://127.0.0.1:8787/v1/messages with 'Authorization: Bearer <key>'".dimmed());
+        http_config = Some(HttpChannelConfig {
+            api_keys: vec![HttpApiKey { name: "default".to_string(), key }],
+            ..Default::default()
+        });
+        println!();
+    }
+
     // ─── Provider selection ────────────────────────────────────────────
     let providers = &["Anthropic (Claude) - recommended", "OpenAI (Codex/GPT)", "OpenCode"];
     let provider_idx = Select::new()
@@ -202,8 +332,9 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
             provider: provider.to_string(),
             model: model.clone(),
             working_directory: default_agent_dir.to_string_lossy().to_string(),
-            reset_policy: String::new(),
+            reset_policy: ResetPolicy::default(),
             reset_hour: None,
+            timezone: None,
             idle_timeout_minutes: None,
             context_window: None,
             fallbacks: None,
@@ -285,8 +416,9 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
                 provider: agent_provider.to_string(),
                 model: agent_model,
                 working_directory: agent_dir.to_string_lossy().to_string(),
-                reset_policy: String::new(),
+                reset_policy: ResetPolicy::default(),
                 reset_hour: None,
+                timezone: None,
                 idle_timeout_minutes: None,
                 context_window: None,
                 fallbacks: None,
@@ -446,6 +578,10 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
     let channels_config = if !enabled_channels.is_empty()
         || !discord_token.is_empty()
         || !telegram_token.is_empty()
+        || !slack_bot_token.is_empty()
+        || !matrix_token.is_empty()
+        || email_config.is_some()
+        || http_config.is_some()
     {
         Some(ChannelsConfig {
             enabled: if enabled_channels.is_empty() {
@@ -458,6 +594,7 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
             } else {
                 Some(DiscordChannelConfig {
                     bot_token: Some(discord_token),
+                    ..Default::default()
                 })
             },
             telegram: if telegram_token.is_empty() {
@@ -465,8 +602,29 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
             } else {
                 Some(TelegramChannelConfig {
                     bot_token: Some(telegram_token),
+                    ..Default::default()
+                })
+            },
+            slack: if slack_bot_token.is_empty() {
+                None
+            } else {
+                Some(SlackChannelConfig {
+                    bot_token: Some(slack_bot_token),
+                    app_token: Some(slack_app_token),
+                    ..Default::default()
                 })
             },
+            matrix: if matrix_token.is_empty() {
+                None
+            } else {
+                Some(MatrixChannelConfig {
+                    homeserver_url: Some(matrix_homeserver),
+                    access_token: Some(matrix_token),
+                    ..Default::default()
+                })
+            },
+            email: email_config,
+            http: http_config,
         })
     } else {
         None
@@ -487,8 +645,12 @@ pub fn run_setup(paths: &Paths) -> Result<()> {
         },
         monitoring: Some(MonitoringConfig {
             heartbeat_interval: Some(heartbeat_interval),
+            metrics_bind: None,
+            tracing: None,
         }),
         skills: None,
+        viz: None,
+        logging: None,
     };
 
     // ─── Write settings and create directories ─────────────────────────
@@ -645,3 +807,15 @@ fn capitalize(s: &str) -> String {
         Some(c) => c.to_uppercase().to_string() + chars.as_str(),
     }
 }
+
+/// Random API key for the HTTP channel (`rc_` + 40 alphanumerics).
+fn generate_api_key() -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::Rng;
+    let key: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    format!("rc_{}", key)
+}
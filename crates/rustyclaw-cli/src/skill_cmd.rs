@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use colored::Colorize;
+
+use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::skill_packages::{
+    install_skill, load_lockfile, remove_skill, update_skill, verify_skill, InstalledSkill,
+};
+use rustyclaw_core::skills::discover_skills;
+use rustyclaw_core::types::{Settings, SkillOverride};
+
+/// Install a skill from a local directory, git URL, or local tarball.
+pub fn install(source: &str, paths: &Paths) -> Result<()> {
+    let installed = install_skill(source, &paths.skills_dir, &paths.skills_lock_file)?;
+    print_installed(&installed, "installed");
+    println!("  Checksum: {}", installed.locked.checksum.dimmed());
+    println!("  Path:     {}", installed.dir.display().to_string().dimmed());
+    Ok(())
+}
+
+/// List project and installed skills with version, state, and integrity.
+pub fn list(paths: &Paths) -> Result<()> {
+    let settings = get_settings(&paths.settings_file)?;
+    let overrides = settings.skills.clone().unwrap_or_default();
+    let lock = load_lockfile(&paths.skills_lock_file);
+
+    let project_dir = paths.script_dir.join("skills");
+    let skills = discover_skills(&[&project_dir, &paths.skills_dir]);
+
+    if skills.is_empty() {
+        println!("{}", "No skills found.".yellow());
+        println!("Install one with: {}", "rustyclaw skill install <path|git-url|tarball>".green());
+        return Ok(());
+    }
+
+    println!();
+    println!("  {}", "Skills".green().bold());
+    println!();
+
+    for skill in &skills {
+        let name = &skill.meta.name;
+        let enabled = overrides.get(name).map(|o| o.enabled).unwrap_or(true);
+        let state = if enabled { "enabled".green() } else { "disabled".red() };
+        let version = version_label(skill.meta.version.as_deref());
+
+        println!(
+            "  {} {} {} [{}]",
+            if enabled { "●".green() } else { "●".dimmed() },
+            name.bright_white().bold(),
+            version.dimmed(),
+            state
+        );
+        if !skill.meta.description.is_empty() {
+            println!("    {}", skill.meta.description.dimmed());
+        }
+
+        match lock.skills.get(name) {
+            Some(locked) if skill.source_dir.starts_with(&paths.skills_dir) => {
+                let integrity = if verify_skill(&skill.source_dir, locked) {
+                    "checksum ok".green()
+                } else {
+                    "modified locally".yellow()
+                };
+                println!("    Source: {} ({})", locked.source.dimmed(), integrity);
+            }
+            _ => {
+                println!("    Source: {}", skill.source_dir.display().to_string().dimmed());
+            }
+        }
+    }
+
+    println!();
+    Ok(())
+}
+
+/// Enable or disable a skill by writing a `Settings.skills` override.
+pub fn set_enabled(name: &str, enabled: bool, paths: &Paths) -> Result<()> {
+    let project_dir = paths.script_dir.join("skills");
+    let known = discover_skills(&[&project_dir, &paths.skills_dir])
+        .iter()
+        .any(|s| s.meta.name == name);
+    if !known {
+        println!(
+            "{} No skill named '{}' is installed; saving the override anyway.",
+            "!".yellow(),
+            name
+        );
+    }
+
+    let mut settings = get_settings(&paths.settings_file)?;
+    let overrides = settings.skills.get_or_insert_with(HashMap::new);
+    if enabled {
+        // Skills are enabled by default, so drop the override entirely
+        overrides.remove(name);
+    } else {
+        overrides.insert(name.to_string(), SkillOverride { enabled: false });
+    }
+    if overrides.is_empty() {
+        settings.skills = None;
+    }
+    save_settings(&paths.settings_file, &settings)?;
+
+    let state = if enabled { "enabled".green() } else { "disabled".red() };
+    println!("{} Skill '{}' {}.", "✓".green(), name, state);
+    Ok(())
+}
+
+/// Update one skill, or every installed skill when `name` is `None`.
+pub fn update(name: Option<&str>, paths: &Paths) -> Result<()> {
+    let names: Vec<String> = match name {
+        Some(n) => vec![n.to_string()],
+        None => load_lockfile(&paths.skills_lock_file).skills.into_keys().collect(),
+    };
+
+    if names.is_empty() {
+        println!("{}", "No installed skills to update.".yellow());
+        return Ok(());
+    }
+
+    let mut failures = 0;
+    for n in &names {
+        match update_skill(n, &paths.skills_dir, &paths.skills_lock_file) {
+            Ok(result) if result.changed => print_installed(&result, "updated"),
+            Ok(result) => println!(
+                "{} Skill '{}' is up to date ({}).",
+                "✓".green(),
+                result.name,
+                version_label(result.locked.version.as_deref())
+            ),
+            Err(e) => {
+                println!("{} Failed to update '{}': {}", "✗".red(), n, e);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        anyhow::bail!("{} skill(s) failed to update", failures);
+    }
+    Ok(())
+}
+
+/// Remove an installed skill and its lockfile entry.
+pub fn remove(name: &str, paths: &Paths) -> Result<()> {
+    remove_skill(name, &paths.skills_dir, &paths.skills_lock_file)?;
+    println!("{} Skill '{}' removed.", "✓".green(), name);
+    Ok(())
+}
+
+// --- Helpers ---
+
+fn version_label(version: Option<&str>) -> String {
+    version
+        .map(|v| format!("v{}", v))
+        .unwrap_or_else(|| "unversioned".to_string())
+}
+
+fn print_installed(installed: &InstalledSkill, verb: &str) {
+    let version = version_label(installed.locked.version.as_deref());
+    match installed.previous_version.as_deref() {
+        Some(prev) if installed.locked.version.as_deref() != Some(prev) => println!(
+            "{} Skill '{}' {} (v{} → {}).",
+            "✓".green(),
+            installed.name.bright_white(),
+            verb,
+            prev,
+            version
+        ),
+        _ => println!(
+            "{} Skill '{}' {} ({}).",
+            "✓".green(),
+            installed.name.bright_white(),
+            verb,
+            version
+        ),
+    }
+}
+
+fn save_settings(settings_file: &std::path::Path, settings: &Settings) -> Result<()> {
+    if let Some(dir) = settings_file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(settings)?;
+    let tmp = settings_file.with_extension("tmp");
+    std::fs::write(&tmp, &json)?;
+    std::fs::rename(&tmp, settings_file)?;
+    Ok(())
+}
@@ -0,0 +1,530 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use colored::Colorize;
+use dialoguer::Password;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use rustyclaw_core::config::Paths;
+
+const SESSION_COOKIE: &str = "rustyclaw_session";
+const DEFAULT_SESSION_HOURS: u64 = 24;
+
+// ─── Roles and users ────────────────────────────────────────────────────────
+
+/// Viewers can read everything (secrets stay masked); operators can also send
+/// messages and change settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Operator,
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Self::Viewer),
+            "operator" => Ok(Self::Operator),
+            _ => Err(format!("unknown role '{}' (expected viewer or operator)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UserRecord {
+    /// Argon2id PHC string
+    password_hash: String,
+    role: Role,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsersFile {
+    #[serde(default)]
+    users: BTreeMap<String, UserRecord>,
+}
+
+fn users_file(paths: &Paths) -> PathBuf {
+    paths.rustyclaw_home.join("viz_users.json")
+}
+
+fn token_file(paths: &Paths) -> PathBuf {
+    paths.rustyclaw_home.join("viz_token")
+}
+
+fn load_users(file: &Path) -> Result<UsersFile> {
+    if !file.exists() {
+        return Ok(UsersFile::default());
+    }
+    let data = std::fs::read_to_string(file).context("Failed to read viz_users.json")?;
+    serde_json::from_str(&data).context("Failed to parse viz_users.json")
+}
+
+/// Write a file only the owner can read.
+fn write_private(file: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = file.with_extension("tmp");
+    std::fs::write(&tmp, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&tmp, file)?;
+    Ok(())
+}
+
+fn save_users(file: &Path, users: &UsersFile) -> Result<()> {
+    write_private(file, &serde_json::to_string_pretty(users)?)
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt_bytes: [u8; 16] = rand::thread_rng().gen();
+    let salt = SaltString::encode_b64(&salt_bytes).map_err(|e| anyhow::anyhow!("{}", e))?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
+}
+
+/// Compare without short-circuiting, so response timing doesn't leak token prefixes.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn random_token(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// Read the admin token, generating it on first use.
+pub fn admin_token(paths: &Paths) -> Result<String> {
+    let file = token_file(paths);
+    if let Ok(token) = std::fs::read_to_string(&file) {
+        let token = token.trim().to_string();
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+    let token = random_token(32);
+    write_private(&file, &token).context("Failed to write viz_token")?;
+    Ok(token)
+}
+
+// ─── CLI commands ───────────────────────────────────────────────────────────
+
+pub fn add_user(paths: &Paths, name: &str, role: Role) -> Result<()> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        bail!("User name must be non-empty and contain no spaces");
+    }
+    let file = users_file(paths);
+    let mut users = load_users(&file)?;
+    let password = Password::new()
+        .with_prompt(format!("Password for {}", name))
+        .with_confirmation("Repeat password", "Passwords don't match")
+        .interact()?;
+    if password.len() < 8 {
+        bail!("Password must be at least 8 characters");
+    }
+    let replaced = users
+        .users
+        .insert(
+            name.to_string(),
+            UserRecord {
+                password_hash: hash_password(&password)?,
+                role,
+            },
+        )
+        .is_some();
+    save_users(&file, &users)?;
+    let verb = if replaced { "Updated" } else { "Added" };
+    println!("{} {} user {} ({:?})", "✓".green(), verb, name.bright_white(), role);
+    Ok(())
+}
+
+pub fn remove_user(paths: &Paths, name: &str) -> Result<()> {
+    let file = users_file(paths);
+    let mut users = load_users(&file)?;
+    if users.users.remove(name).is_none() {
+        println!("{} User '{}' not found.", "Error:".red(), name);
+        return Ok(());
+    }
+    save_users(&file, &users)?;
+    println!("{} Removed user {}", "✓".green(), name.bright_white());
+    Ok(())
+}
+
+pub fn list_users(paths: &Paths) -> Result<()> {
+    let users = load_users(&users_file(paths))?;
+    if users.users.is_empty() {
+        println!("No visualizer users. Log in with the admin token, or add one with:");
+        println!("  {}", "rustyclaw visualize user add <name> --role operator".green());
+        return Ok(());
+    }
+    for (name, user) in &users.users {
+        println!("  {}  {:?}", name.bright_white(), user.role);
+    }
+    Ok(())
+}
+
+/// Print the admin token, replacing it first if `rotate` is set.
+pub fn show_token(paths: &Paths, rotate: bool) -> Result<()> {
+    if rotate {
+        let _ = std::fs::remove_file(token_file(paths));
+    }
+    let token = admin_token(paths)?;
+    println!("{}", token);
+    if rotate {
+        println!("{}", "Restart the visualizer for the new token to take effect.".dimmed());
+    }
+    Ok(())
+}
+
+// ─── Sessions ───────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub user: String,
+    pub role: Role,
+    expires_at: Instant,
+}
+
+/// Login state for the viz server. Sessions live in memory, so a restart
+/// logs everyone out.
+pub struct VizAuth {
+    paths: Paths,
+    admin_token: String,
+    session_ttl: Duration,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl VizAuth {
+    pub fn new(paths: &Paths, session_hours: Option<u64>) -> Result<Self> {
+        Ok(Self {
+            paths: paths.clone(),
+            admin_token: admin_token(paths)?,
+            session_ttl: Duration::from_secs(session_hours.unwrap_or(DEFAULT_SESSION_HOURS) * 3600),
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn admin_token(&self) -> &str {
+        &self.admin_token
+    }
+
+    fn check_token(&self, token: &str) -> bool {
+        constant_time_eq(token.trim().as_bytes(), self.admin_token.as_bytes())
+    }
+
+    /// Check credentials against the users file. Reads it on every login so
+    /// `rustyclaw visualize user add` takes effect without a restart.
+    fn check_password(&self, name: &str, password: &str) -> Option<Role> {
+        let users = load_users(&users_file(&self.paths)).ok()?;
+        let user = users.users.get(name)?;
+        verify_password(password, &user.password_hash).then_some(user.role)
+    }
+
+    fn create_session(&self, user: &str, role: Role) -> String {
+        let id = random_token(40);
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(
+            id.clone(),
+            Session {
+                user: user.to_string(),
+                role,
+                expires_at: now + self.session_ttl,
+            },
+        );
+        id
+    }
+
+    /// Session from the cookie, or the admin token as a bearer token for scripts.
+    fn authenticate(&self, headers: &HeaderMap) -> Option<Session> {
+        if let Some(token) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        {
+            return self.check_token(token).then(|| Session {
+                user: "admin".to_string(),
+                role: Role::Operator,
+                expires_at: Instant::now() + self.session_ttl,
+            });
+        }
+        let id = session_cookie(headers)?;
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(&id)
+            .filter(|s| s.expires_at > Instant::now())
+            .cloned()
+    }
+
+    fn end_session(&self, headers: &HeaderMap) {
+        if let Some(id) = session_cookie(headers) {
+            self.sessions.lock().unwrap().remove(&id);
+        }
+    }
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == SESSION_COOKIE).then(|| value.to_string())
+        })
+}
+
+fn session_cookie_header(id: &str, max_age_secs: u64) -> HeaderValue {
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+        SESSION_COOKIE, id, max_age_secs
+    );
+    HeaderValue::from_str(&cookie).expect("cookie is ASCII")
+}
+
+fn unauthorized(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Require a session on every route it wraps. Reads (GET/HEAD) need any
+/// role; everything else needs an operator.
+pub async fn require_session(
+    State(auth): State<Arc<VizAuth>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let Some(session) = auth.authenticate(req.headers()) else {
+        return unauthorized(StatusCode::UNAUTHORIZED, "Login required");
+    };
+    let read_only = matches!(*req.method(), Method::GET | Method::HEAD);
+    if !read_only && session.role != Role::Operator {
+        return unauthorized(StatusCode::FORBIDDEN, "Operator role required");
+    }
+    req.extensions_mut().insert(session);
+    next.run(req).await
+}
+
+// ─── Login endpoints ────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    username: Option<String>,
+    password: Option<String>,
+    token: Option<String>,
+}
+
+fn session_response(session: &Session) -> serde_json::Value {
+    serde_json::json!({ "user": session.user, "role": session.role })
+}
+
+/// Log in with the admin token or a username and password; sets the session cookie.
+pub async fn login_handler(
+    State(auth): State<Arc<VizAuth>>,
+    Json(body): Json<LoginRequest>,
+) -> Response {
+    let login = match (&body.token, &body.username, &body.password) {
+        (Some(token), _, _) => auth.check_token(token).then(|| ("admin".to_string(), Role::Operator)),
+        (None, Some(name), Some(password)) => {
+            auth.check_password(name, password).map(|role| (name.clone(), role))
+        }
+        _ => return unauthorized(StatusCode::BAD_REQUEST, "Send a token, or a username and password"),
+    };
+    let Some((user, role)) = login else {
+        // Slow down guessing
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        return unauthorized(StatusCode::UNAUTHORIZED, "Invalid credentials");
+    };
+    let id = auth.create_session(&user, role);
+    let session = Session {
+        user,
+        role,
+        expires_at: Instant::now() + auth.session_ttl,
+    };
+    (
+        [(header::SET_COOKIE, session_cookie_header(&id, auth.session_ttl.as_secs()))],
+        Json(session_response(&session)),
+    )
+        .into_response()
+}
+
+pub async fn logout_handler(State(auth): State<Arc<VizAuth>>, headers: HeaderMap) -> Response {
+    auth.end_session(&headers);
+    (
+        [(header::SET_COOKIE, session_cookie_header("", 0))],
+        Json(serde_json::json!({ "ok": true })),
+    )
+        .into_response()
+}
+
+/// Current user and role, or 401 when not logged in.
+pub async fn me_handler(State(auth): State<Arc<VizAuth>>, headers: HeaderMap) -> Response {
+    match auth.authenticate(&headers) {
+        Some(session) => Json(session_response(&session)).into_response(),
+        None => unauthorized(StatusCode::UNAUTHORIZED, "Login required"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::{middleware, Router};
+
+    fn test_paths(home: &Path) -> Paths {
+        Paths {
+            script_dir: home.to_path_buf(),
+            rustyclaw_home: home.to_path_buf(),
+            queue_incoming: home.join("queue/incoming"),
+            queue_outgoing: home.join("queue/outgoing"),
+            queue_processing: home.join("queue/processing"),
+            log_file: home.join("logs/viz.log"),
+            settings_file: home.join("settings.json"),
+            events_dir: home.join("events"),
+            chats_dir: home.join("chats"),
+            files_dir: home.join("files"),
+            pairing_file: home.join("pairing.json"),
+            skills_dir: home.join("skills"),
+            skills_lock_file: home.join("skills.lock"),
+        }
+    }
+
+    /// Serve one route behind `require_session` and return its URL.
+    async fn serve(auth: Arc<VizAuth>) -> String {
+        let app = Router::new()
+            .route("/api/thing", get(|| async { "read" }).post(|| async { "written" }))
+            .route_layer(middleware::from_fn_with_state(Arc::clone(&auth), require_session))
+            .with_state(auth);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/thing", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    fn cookie(id: &str) -> String {
+        format!("{}={}", SESSION_COOKIE, id)
+    }
+
+    #[tokio::test]
+    async fn test_require_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let auth = Arc::new(VizAuth::new(&test_paths(tmp.path()), None).unwrap());
+        let viewer = auth.create_session("vera", Role::Viewer);
+        let operator = auth.create_session("otto", Role::Operator);
+        let url = serve(Arc::clone(&auth)).await;
+        let client = reqwest::Client::new();
+
+        let status = |req: reqwest::RequestBuilder| async move {
+            req.send().await.unwrap().status().as_u16()
+        };
+        assert_eq!(status(client.get(&url)).await, 401);
+        assert_eq!(status(client.get(&url).header("Cookie", cookie("bogus"))).await, 401);
+        assert_eq!(status(client.get(&url).header("Cookie", cookie(&viewer))).await, 200);
+        assert_eq!(status(client.post(&url).header("Cookie", cookie(&viewer))).await, 403);
+        assert_eq!(status(client.post(&url).header("Cookie", cookie(&operator))).await, 200);
+
+        // The admin token works as a bearer token, anything else does not
+        assert_eq!(status(client.post(&url).bearer_auth(auth.admin_token())).await, 200);
+        assert_eq!(status(client.get(&url).bearer_auth("not-the-token")).await, 401);
+
+        // Logging out ends the session
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_str(&cookie(&viewer)).unwrap());
+        auth.end_session(&headers);
+        assert_eq!(status(client.get(&url).header("Cookie", cookie(&viewer))).await, 401);
+    }
+
+    #[tokio::test]
+    async fn test_expired_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let auth = Arc::new(VizAuth::new(&test_paths(tmp.path()), Some(0)).unwrap());
+        let expired = auth.create_session("otto", Role::Operator);
+        let url = serve(auth).await;
+        let response = reqwest::Client::new()
+            .get(&url)
+            .header("Cookie", cookie(&expired))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+    }
+
+    #[test]
+    fn test_admin_token_is_kept() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = test_paths(tmp.path());
+        let token = admin_token(&paths).unwrap();
+        assert_eq!(token.len(), 32);
+        assert_eq!(admin_token(&paths).unwrap(), token);
+
+        let auth = VizAuth::new(&paths, None).unwrap();
+        assert!(auth.check_token(&format!(" {}\n", token)));
+        assert!(!auth.check_token(&token[..31]));
+        assert!(!auth.check_token(""));
+    }
+
+    #[test]
+    fn test_session_cookie() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(session_cookie(&headers), None);
+        headers.append(header::COOKIE, HeaderValue::from_static("theme=dark; rustyclaw_session_old=x"));
+        assert_eq!(session_cookie(&headers), None);
+        headers.append(header::COOKIE, HeaderValue::from_static("a=1;  rustyclaw_session=abc123 ; b=2"));
+        assert_eq!(session_cookie(&headers).as_deref(), Some("abc123"));
+
+        let set = session_cookie_header("abc123", 60);
+        assert_eq!(
+            set.to_str().unwrap(),
+            "rustyclaw_session=abc123; Path=/; HttpOnly; SameSite=Strict; Max-Age=60"
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"secret", b""));
+    }
+
+    #[test]
+    fn test_password_hash() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+    }
+}
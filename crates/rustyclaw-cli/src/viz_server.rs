@@ -4,28 +4,44 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::State;
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::{get, post};
-use axum::Json;
-use axum::Router;
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{middleware, Extension, Json, Router};
 use colored::Colorize;
 use notify::{Event, EventKind, RecursiveMode, Watcher};
-use rand::Rng;
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
-use tower_http::cors::{Any, CorsLayer};
+use tokio::sync::{broadcast, Mutex};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
 
-use rustyclaw_core::config::{get_agents, get_settings, get_teams, Paths};
-use rustyclaw_core::types::MessageData;
+use rustyclaw_core::chat_history::{self, paginate};
+use rustyclaw_core::config::{
+    get_agents, get_settings, get_teams, get_workspace_path, load_settings,
+    save_settings_with_backup, settings_revision, validate_settings, Paths,
+};
+use rustyclaw_core::event_store::{EventStore, StoredEvent};
+use rustyclaw_core::failover::load_cooldowns;
+use rustyclaw_core::logging::emit_event;
+use rustyclaw_core::metrics::{MetricsAggregator, MetricsSnapshot};
+use rustyclaw_core::types::{MessageData, Settings};
+use rustyclaw_queue::queue_admin::{self, QueueActionError, QueueDir};
+
+use crate::viz_auth::{self, Session, VizAuth};
+use crate::viz_web::{self, WebChannel, WebReply, WebSubscription};
 
 // ─── Server state ───────────────────────────────────────────────────────────
 
 struct VizServerState {
-    tx: broadcast::Sender<String>,
+    /// Live events as `(seq, json)`, fed by the event log tailer
+    tx: broadcast::Sender<(u64, String)>,
     paths: Paths,
+    /// Serializes settings writes so the revision check and the write are atomic
+    settings_lock: Mutex<()>,
+    web: Arc<WebChannel>,
+    /// Aggregated from every event the tailer reads
+    metrics: Arc<std::sync::Mutex<MetricsAggregator>>,
 }
 
 // ─── Settings API response ──────────────────────────────────────────────────
@@ -52,22 +68,98 @@ struct AgentInfo {
 
 // ─── Handlers ───────────────────────────────────────────────────────────────
 
+#[derive(Deserialize)]
+struct WsQuery {
+    /// Replay events after this sequence number before streaming live ones
+    since: Option<u64>,
+    /// Browser client ID; its web chat replies are pushed on this socket
+    client: Option<String>,
+}
+
+/// Most events replayed on connect; older ones are skipped, and the client
+/// is sent a `replay_truncated` message so it starts over.
+const MAX_REPLAY: usize = 2000;
+
 async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<VizServerState>>,
+    Extension(session): Extension<Session>,
+    Query(query): Query<WsQuery>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    let client = query.client.filter(|c| viz_web::valid_client_id(c));
+    let web = client.map(|client| state.web.connect(&session.user, &client));
+    ws.on_upgrade(move |socket| handle_socket(socket, state, query.since, web))
 }
 
-async fn handle_socket(mut socket: WebSocket, state: Arc<VizServerState>) {
+async fn handle_socket(
+    mut socket: WebSocket,
+    state: Arc<VizServerState>,
+    since: Option<u64>,
+    mut web: Option<WebSubscription>,
+) {
+    // Subscribe before reading the log so nothing falls between the two
     let mut rx = state.tx.subscribe();
-    while let Ok(msg) = rx.recv().await {
-        if socket.send(Message::Text(msg.into())).await.is_err() {
-            break;
+    let mut last_sent = since.unwrap_or(0);
+
+    if let Some(since) = since {
+        let store = EventStore::new(&state.paths.events_dir);
+        let mut events = tokio::task::spawn_blocking(move || store.read_since(since))
+            .await
+            .unwrap_or_default();
+        if events.len() > MAX_REPLAY {
+            events.drain(..events.len() - MAX_REPLAY);
+        }
+        // Skipped above, or already deleted from the log
+        if let Some(first) = events.first().filter(|e| e.seq > since + 1) {
+            let notice = serde_json::json!({ "type": "replay_truncated", "firstSeq": first.seq });
+            if socket.send(Message::Text(notice.to_string().into())).await.is_err() {
+                return;
+            }
+        }
+        for event in events {
+            last_sent = event.seq;
+            if socket.send(Message::Text(event.json.into())).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok((seq, json)) => {
+                    if seq <= last_sent {
+                        continue;
+                    }
+                    last_sent = seq;
+                    if socket.send(Message::Text(json.into())).await.is_err() {
+                        break;
+                    }
+                }
+                // Too slow to keep up: the client reconnects with `since`
+                Err(broadcast::error::RecvError::Lagged(_)) => break,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            // Chat replies, starting with those that arrived while the browser was away
+            Some(reply) = next_reply(&mut web) => {
+                if socket.send(Message::Text(reply.json.clone().into())).await.is_err() {
+                    break;
+                }
+                if let Some(web) = &web {
+                    web.delivered(&reply);
+                }
+            }
         }
     }
 }
 
+async fn next_reply(web: &mut Option<WebSubscription>) -> Option<WebReply> {
+    match web {
+        Some(web) => web.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
 async fn get_settings_handler(
     State(state): State<Arc<VizServerState>>,
 ) -> impl IntoResponse {
@@ -136,26 +228,217 @@ fn count_files(dir: &std::path::Path) -> usize {
 
 // ─── Full settings API (with masked tokens) ──────────────────────────────
 
+const MASK: &str = "********";
+
+fn mask(value: &mut Option<String>) {
+    if value.is_some() {
+        *value = Some(MASK.to_string());
+    }
+}
+
+fn mask_secrets(settings: &mut Settings) {
+    let Some(ref mut channels) = settings.channels else {
+        return;
+    };
+    if let Some(ref mut discord) = channels.discord {
+        mask(&mut discord.bot_token);
+    }
+    if let Some(ref mut telegram) = channels.telegram {
+        mask(&mut telegram.bot_token);
+    }
+    if let Some(ref mut slack) = channels.slack {
+        mask(&mut slack.bot_token);
+        mask(&mut slack.app_token);
+    }
+    if let Some(ref mut matrix) = channels.matrix {
+        mask(&mut matrix.access_token);
+    }
+    if let Some(ref mut email) = channels.email {
+        mask(&mut email.password);
+        mask(&mut email.smtp_password);
+    }
+    if let Some(ref mut http) = channels.http {
+        for api_key in &mut http.api_keys {
+            api_key.key = MASK.to_string();
+        }
+    }
+}
+
+fn unmask(value: &mut Option<String>, current: Option<&String>) {
+    if value.as_deref() == Some(MASK) {
+        *value = current.cloned();
+    }
+}
+
+/// Put back secrets the client only saw masked. Returns an error for masked
+/// API keys that don't match an existing key by name.
+fn restore_masked_secrets(settings: &mut Settings, current: &Settings) -> Vec<String> {
+    let mut errors = Vec::new();
+    let Some(ref mut channels) = settings.channels else {
+        return errors;
+    };
+    let old = current.channels.as_ref();
+    if let Some(ref mut discord) = channels.discord {
+        let old = old.and_then(|c| c.discord.as_ref());
+        unmask(&mut discord.bot_token, old.and_then(|d| d.bot_token.as_ref()));
+    }
+    if let Some(ref mut telegram) = channels.telegram {
+        let old = old.and_then(|c| c.telegram.as_ref());
+        unmask(&mut telegram.bot_token, old.and_then(|t| t.bot_token.as_ref()));
+    }
+    if let Some(ref mut slack) = channels.slack {
+        let old = old.and_then(|c| c.slack.as_ref());
+        unmask(&mut slack.bot_token, old.and_then(|s| s.bot_token.as_ref()));
+        unmask(&mut slack.app_token, old.and_then(|s| s.app_token.as_ref()));
+    }
+    if let Some(ref mut matrix) = channels.matrix {
+        let old = old.and_then(|c| c.matrix.as_ref());
+        unmask(&mut matrix.access_token, old.and_then(|m| m.access_token.as_ref()));
+    }
+    if let Some(ref mut email) = channels.email {
+        let old = old.and_then(|c| c.email.as_ref());
+        unmask(&mut email.password, old.and_then(|e| e.password.as_ref()));
+        unmask(&mut email.smtp_password, old.and_then(|e| e.smtp_password.as_ref()));
+    }
+    if let Some(ref mut http) = channels.http {
+        let old_keys = old.and_then(|c| c.http.as_ref()).map(|h| h.api_keys.as_slice()).unwrap_or_default();
+        for api_key in &mut http.api_keys {
+            if api_key.key != MASK {
+                continue;
+            }
+            match old_keys.iter().find(|k| k.name == api_key.name) {
+                Some(old_key) => api_key.key = old_key.key.clone(),
+                None => errors.push(format!(
+                    "channels.http.api_keys: key '{}' is masked but no existing key has that name",
+                    api_key.name
+                )),
+            }
+        }
+    }
+    errors
+}
+
+/// RFC 7396 JSON merge patch: objects merge recursively, `null` deletes.
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch_map) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(Default::default());
+    }
+    let target_map = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch_map {
+        if value.is_null() {
+            target_map.remove(key);
+        } else {
+            merge_patch(target_map.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+        }
+    }
+}
+
+fn json_error(status: StatusCode, body: serde_json::Value) -> Response {
+    (status, Json(body)).into_response()
+}
+
+fn etag(revision: &str) -> String {
+    format!("\"{}\"", revision)
+}
+
 async fn get_full_settings_handler(
     State(state): State<Arc<VizServerState>>,
 ) -> impl IntoResponse {
+    let revision = settings_revision(&state.paths.settings_file).unwrap_or_default();
     let mut settings = get_settings(&state.paths.settings_file).unwrap_or_default();
+    mask_secrets(&mut settings);
+    ([(header::ETAG, etag(&revision))], Json(settings))
+}
 
-    // Mask sensitive fields
-    if let Some(ref mut channels) = settings.channels {
-        if let Some(ref mut discord) = channels.discord {
-            if discord.bot_token.is_some() {
-                discord.bot_token = Some("********".to_string());
-            }
-        }
-        if let Some(ref mut telegram) = channels.telegram {
-            if telegram.bot_token.is_some() {
-                telegram.bot_token = Some("********".to_string());
-            }
+/// Shared write path for PUT and PATCH: check `If-Match` against the current
+/// revision, build the new settings from the current JSON, validate, and save.
+async fn update_settings(
+    state: &VizServerState,
+    headers: &HeaderMap,
+    update: impl FnOnce(serde_json::Value) -> serde_json::Value,
+) -> Response {
+    let _guard = state.settings_lock.lock().await;
+    let file = &state.paths.settings_file;
+
+    let revision = match settings_revision(file) {
+        Ok(r) => r,
+        Err(e) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, serde_json::json!({ "error": e.to_string() })),
+    };
+    let Some(expected) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) else {
+        return json_error(
+            StatusCode::PRECONDITION_REQUIRED,
+            serde_json::json!({ "error": "If-Match header required; use the ETag from GET /api/settings/full" }),
+        );
+    };
+    if expected.trim().trim_start_matches("W/").trim_matches('"') != revision {
+        return json_error(
+            StatusCode::PRECONDITION_FAILED,
+            serde_json::json!({ "error": "Settings changed since they were loaded", "revision": revision }),
+        );
+    }
+
+    // Merging into defaults would overwrite a file the user can still repair
+    let current = match load_settings(file) {
+        Ok(s) => s,
+        Err(e) if e.is::<serde_json::Error>() => {
+            return json_error(
+                StatusCode::CONFLICT,
+                serde_json::json!({ "error": format!("settings.json does not parse; fix it before saving: {}", e) }),
+            );
         }
+        Err(e) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, serde_json::json!({ "error": format!("{:#}", e) })),
+    };
+    let current_value = serde_json::to_value(&current).unwrap_or_default();
+    let updated = update(current_value);
+    let mut settings: Settings = match serde_json::from_value(updated) {
+        Ok(s) => s,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, serde_json::json!({ "error": format!("Invalid settings: {}", e) })),
+    };
+
+    let mut errors = restore_masked_secrets(&mut settings, &current);
+    errors.extend(validate_settings(&settings));
+    if !errors.is_empty() {
+        return json_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            serde_json::json!({ "error": "Settings failed validation", "errors": errors }),
+        );
+    }
+
+    if let Err(e) = save_settings_with_backup(file, &settings) {
+        return json_error(StatusCode::INTERNAL_SERVER_ERROR, serde_json::json!({ "error": format!("{:#}", e) }));
     }
+    let revision = settings_revision(file).unwrap_or_default();
+    (
+        [(header::ETAG, etag(&revision))],
+        Json(serde_json::json!({ "ok": true, "revision": revision })),
+    )
+        .into_response()
+}
 
-    Json(settings)
+/// Replace settings.json. Masked secrets keep their current values.
+async fn put_full_settings_handler(
+    State(state): State<Arc<VizServerState>>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    update_settings(&state, &headers, |_| body).await
+}
+
+/// Apply a JSON merge patch to settings.json.
+async fn patch_full_settings_handler(
+    State(state): State<Arc<VizServerState>>,
+    headers: HeaderMap,
+    Json(patch): Json<serde_json::Value>,
+) -> Response {
+    update_settings(&state, &headers, |mut current| {
+        merge_patch(&mut current, &patch);
+        current
+    })
+    .await
 }
 
 // ─── Queue API ─────────────────────────────────────────────────────────────
@@ -168,12 +451,15 @@ struct QueueMessagesResponse {
 
 #[derive(Serialize)]
 struct QueuedMessage {
+    /// File name in the queue directory, used to address queue actions
+    file: String,
     message_id: String,
     channel: String,
     sender: String,
     message: String,
     agent: Option<String>,
     timestamp: u64,
+    priority: i32,
     status: String,
 }
 
@@ -194,12 +480,14 @@ fn read_queue_dir(dir: &std::path::Path, status: &str) -> Vec<QueuedMessage> {
                         msg.message.clone()
                     };
                     messages.push(QueuedMessage {
+                        file: entry.file_name().to_string_lossy().to_string(),
                         message_id: msg.message_id,
                         channel: msg.channel,
                         sender: msg.sender,
                         message: preview,
                         agent: msg.agent,
                         timestamp: msg.timestamp,
+                        priority: msg.priority.unwrap_or(0),
                         status: status.to_string(),
                     });
                 }
@@ -218,107 +506,274 @@ async fn get_queue_handler(
     Json(QueueMessagesResponse { incoming, processing })
 }
 
-// ─── Send message API ──────────────────────────────────────────────────────
-
 #[derive(Deserialize)]
-struct SendMessageRequest {
-    message: String,
-    agent: Option<String>,
+struct RouteRequest {
+    agent: String,
 }
 
-fn random_suffix() -> String {
-    let mut rng = rand::thread_rng();
-    (0..6)
-        .map(|_| {
-            let idx = rng.gen_range(0..36u8);
-            if idx < 10 { (b'0' + idx) as char } else { (b'a' + idx - 10) as char }
-        })
-        .collect()
+/// Report a queue action: emit the event, or map the error to a status.
+fn queue_action_response(
+    state: &VizServerState,
+    session: &Session,
+    event_type: &str,
+    file: &str,
+    result: Result<MessageData, QueueActionError>,
+) -> Response {
+    match result {
+        Ok(msg) => {
+            emit_event(
+                event_type,
+                serde_json::json!({
+                    "messageId": msg.message_id,
+                    "file": file,
+                    "agent": msg.agent,
+                    "priority": msg.priority.unwrap_or(0),
+                    "user": session.user,
+                }),
+                &state.paths.events_dir,
+            );
+            Json(serde_json::json!({ "ok": true, "messageId": msg.message_id })).into_response()
+        }
+        Err(e) => {
+            let status = match e {
+                QueueActionError::Gone | QueueActionError::InFlight => StatusCode::CONFLICT,
+                QueueActionError::Invalid(_) => StatusCode::BAD_REQUEST,
+                QueueActionError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            json_error(status, serde_json::json!({ "error": e.to_string() }))
+        }
+    }
 }
 
-fn now_millis() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+async fn delete_queue_message_handler(
+    State(state): State<Arc<VizServerState>>,
+    Extension(session): Extension<Session>,
+    Path((dir, file)): Path<(String, String)>,
+) -> Response {
+    let dir: QueueDir = match dir.parse() {
+        Ok(d) => d,
+        Err(e) => return json_error(StatusCode::NOT_FOUND, serde_json::json!({ "error": e })),
+    };
+    let result = queue_admin::delete_message(&state.paths, dir, &file);
+    queue_action_response(&state, &session, "queue_message_deleted", &file, result)
 }
 
-async fn post_send_handler(
+async fn route_queue_message_handler(
     State(state): State<Arc<VizServerState>>,
-    Json(body): Json<SendMessageRequest>,
-) -> impl IntoResponse {
-    if body.message.trim().is_empty() {
-        return (
+    Extension(session): Extension<Session>,
+    Path(file): Path<String>,
+    Json(body): Json<RouteRequest>,
+) -> Response {
+    let settings = get_settings(&state.paths.settings_file).unwrap_or_default();
+    if !get_agents(&settings).contains_key(&body.agent) {
+        return json_error(
             StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "Message cannot be empty" })),
+            serde_json::json!({ "error": format!("Unknown agent '{}'", body.agent) }),
         );
     }
+    let result = queue_admin::reroute_message(&state.paths, &file, &body.agent);
+    queue_action_response(&state, &session, "queue_message_rerouted", &file, result)
+}
+
+async fn bump_queue_message_handler(
+    State(state): State<Arc<VizServerState>>,
+    Extension(session): Extension<Session>,
+    Path(file): Path<String>,
+) -> Response {
+    let result = queue_admin::bump_priority(&state.paths, &file);
+    queue_action_response(&state, &session, "queue_message_bumped", &file, result)
+}
 
-    let _ = std::fs::create_dir_all(&state.paths.queue_incoming);
+async fn requeue_queue_message_handler(
+    State(state): State<Arc<VizServerState>>,
+    Extension(session): Extension<Session>,
+    Path(file): Path<String>,
+) -> Response {
+    let result = queue_admin::requeue_message(&state.paths, &file);
+    queue_action_response(&state, &session, "queue_message_requeued", &file, result)
+}
 
-    let now = now_millis();
-    let message_id = format!("web-{}-{}", now, random_suffix());
-
-    let msg = MessageData {
-        channel: "web".to_string(),
-        sender: "web-user".to_string(),
-        sender_id: Some("web".to_string()),
-        message: body.message,
-        timestamp: now,
-        message_id: message_id.clone(),
-        agent: body.agent,
-        files: None,
-        conversation_id: None,
-        from_agent: None,
-    };
+// ─── History API ───────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    team: Option<String>,
+    agent: Option<String>,
+    q: Option<String>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+const DEFAULT_PER_PAGE: usize = 20;
 
-    let json = match serde_json::to_string_pretty(&msg) {
-        Ok(j) => j,
-        Err(_) => {
-            return (
+async fn list_conversations_handler(
+    State(state): State<Arc<VizServerState>>,
+    Query(query): Query<HistoryQuery>,
+) -> impl IntoResponse {
+    let conversations = chat_history::list_conversations(
+        &state.paths.chats_dir,
+        query.team.as_deref().filter(|t| !t.is_empty()),
+        query.q.as_deref(),
+    );
+    Json(paginate(
+        conversations,
+        query.page.unwrap_or(1),
+        query.per_page.unwrap_or(DEFAULT_PER_PAGE),
+    ))
+}
+
+async fn get_conversation_handler(
+    State(state): State<Arc<VizServerState>>,
+    Path((team_id, id)): Path<(String, String)>,
+) -> Response {
+    match chat_history::load_conversation(&state.paths.chats_dir, &team_id, &id) {
+        Ok(record) => Json(record).into_response(),
+        Err(_) => json_error(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({ "error": "Conversation not found" }),
+        ),
+    }
+}
+
+/// Transcripts for one agent, or for every agent merged newest first.
+async fn list_transcripts_handler(
+    State(state): State<Arc<VizServerState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let settings = match get_settings(&state.paths.settings_file) {
+        Ok(s) => s,
+        Err(e) => {
+            return json_error(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": "Failed to serialize message" })),
+                serde_json::json!({ "error": e.to_string() }),
             )
         }
     };
+    let agents = get_agents(&settings);
+    let workspace = get_workspace_path(&settings);
+    let agent_ids: Vec<&String> = match query.agent.as_deref().filter(|a| !a.is_empty()) {
+        Some(id) => match agents.get_key_value(id) {
+            Some((id, _)) => vec![id],
+            None => {
+                return json_error(
+                    StatusCode::NOT_FOUND,
+                    serde_json::json!({ "error": format!("Agent '{}' not found", id) }),
+                )
+            }
+        },
+        None => agents.keys().collect(),
+    };
 
-    let filepath = state.paths.queue_incoming.join(format!("{}.json", message_id));
-    match std::fs::write(&filepath, &json) {
-        Ok(_) => (
-            StatusCode::OK,
-            Json(serde_json::json!({ "ok": true, "messageId": message_id })),
-        ),
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": "Failed to write message file" })),
-        ),
+    let mut entries = Vec::new();
+    for agent_id in agent_ids {
+        let dir = workspace.join(agent_id).join(".rustyclaw/transcripts");
+        entries.extend(chat_history::search_transcripts(&dir, query.q.as_deref()));
     }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+    Json(paginate(
+        entries,
+        query.page.unwrap_or(1),
+        query.per_page.unwrap_or(DEFAULT_PER_PAGE),
+    ))
+    .into_response()
 }
 
-// ─── File watcher for events directory ──────────────────────────────────────
+// ─── Metrics API ───────────────────────────────────────────────────────────
 
-fn start_event_watcher(
+#[derive(Deserialize)]
+struct MetricsQuery {
+    hours: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActiveCooldown {
+    key: String,
+    until: u64,
+    error_count: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetricsResponse {
+    #[serde(flatten)]
+    snapshot: MetricsSnapshot,
+    /// Models in cooldown right now, from `cooldowns.json`
+    active_cooldowns: Vec<ActiveCooldown>,
+}
+
+async fn get_metrics_handler(
+    State(state): State<Arc<VizServerState>>,
+    Query(query): Query<MetricsQuery>,
+) -> impl IntoResponse {
+    let now = now_millis();
+    let snapshot = state
+        .metrics
+        .lock()
+        .unwrap()
+        .snapshot(now, query.hours.unwrap_or(24));
+    let mut active_cooldowns: Vec<ActiveCooldown> =
+        load_cooldowns(&state.paths.rustyclaw_home.join("cooldowns.json"))
+            .into_iter()
+            .filter(|(_, c)| c.until > now)
+            .map(|(key, c)| ActiveCooldown { key, until: c.until, error_count: c.error_count })
+            .collect();
+    active_cooldowns.sort_by(|a, b| a.key.cmp(&b.key));
+    Json(MetricsResponse { snapshot, active_cooldowns })
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// ─── Event log tailer ──────────────────────────────────────────────────────
+
+/// Stream events appended to the log into `tx` and `metrics`. Events
+/// already in the log only go to `metrics`. The watcher only wakes the
+/// tailer; the tailer's cursor decides what is new.
+fn start_event_tailer(
     events_dir: PathBuf,
-    tx: broadcast::Sender<String>,
+    tx: broadcast::Sender<(u64, String)>,
+    metrics: Arc<std::sync::Mutex<MetricsAggregator>>,
 ) -> Result<notify::RecommendedWatcher> {
     std::fs::create_dir_all(&events_dir)?;
 
-    let events_dir_clone = events_dir.clone();
-    let tx_watcher = tx.clone();
+    // Remove one-file-per-event files left by older versions
+    if let Ok(entries) = std::fs::read_dir(&events_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().map(|e| e == "json").unwrap_or(false) {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+
+    let record = move |events: &[StoredEvent]| {
+        let mut metrics = metrics.lock().unwrap();
+        for event in events {
+            if let Ok(value) = serde_json::from_str::<serde_json::Value>(&event.json) {
+                metrics.record(&value);
+            }
+        }
+    };
+    let mut tail = EventStore::new(&events_dir).tail(0);
+    record(&tail.read_new());
+
+    let tail = std::sync::Mutex::new(tail);
     let mut watcher = notify::recommended_watcher(
         move |res: std::result::Result<Event, notify::Error>| {
             if let Ok(event) = res {
-                if matches!(event.kind, EventKind::Create(_)) {
-                    for path in &event.paths {
-                        if path.extension().map(|e| e == "json").unwrap_or(false) {
-                            // Small delay to ensure file is fully written
-                            std::thread::sleep(std::time::Duration::from_millis(50));
-                            if let Ok(content) = std::fs::read_to_string(path) {
-                                let _ = tx_watcher.send(content.trim().to_string());
-                            }
-                            // Clean up old event files
-                            let _ = std::fs::remove_file(path);
-                        }
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    let Ok(mut tail) = tail.lock() else {
+                        return;
+                    };
+                    let events = tail.read_new();
+                    record(&events);
+                    for stored in events {
+                        let _ = tx.send((stored.seq, stored.json));
                     }
                 }
             }
@@ -326,27 +781,6 @@ fn start_event_watcher(
     )?;
 
     watcher.watch(&events_dir, RecursiveMode::NonRecursive)?;
-
-    // Also process any existing recent events
-    if let Ok(entries) = std::fs::read_dir(&events_dir_clone) {
-        let cutoff = std::time::SystemTime::now()
-            .checked_sub(std::time::Duration::from_secs(30))
-            .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().map(|e| e == "json").unwrap_or(false) {
-                let modified = entry.metadata().and_then(|m| m.modified()).ok();
-                if modified.map(|m| m >= cutoff).unwrap_or(false) {
-                    if let Ok(content) = std::fs::read_to_string(&path) {
-                        let _ = tx.send(content.trim().to_string());
-                    }
-                }
-                let _ = std::fs::remove_file(&path);
-            }
-        }
-    }
-
     Ok(watcher)
 }
 
@@ -357,31 +791,84 @@ pub fn start_viz_server(paths: &Paths, port: u16, static_dir: Option<&str>) -> R
     let rt = tokio::runtime::Runtime::new()?;
 
     rt.block_on(async {
-        let (tx, _rx) = broadcast::channel::<String>(256);
+        let (tx, _rx) = broadcast::channel::<(u64, String)>(256);
+
+        let web = WebChannel::new(paths);
+        web.start();
+        let metrics = Arc::new(std::sync::Mutex::new(MetricsAggregator::new()));
 
         let state = Arc::new(VizServerState {
             tx: tx.clone(),
             paths: paths.clone(),
+            settings_lock: Mutex::new(()),
+            web: Arc::clone(&web),
+            metrics: Arc::clone(&metrics),
         });
 
-        // Start event file watcher
-        let _watcher = start_event_watcher(paths.events_dir.clone(), tx)?;
+        // Stream new events from the event log
+        let _watcher = start_event_tailer(paths.events_dir.clone(), tx, metrics)?;
 
-        // Build router
-        let cors = CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(Any)
-            .allow_headers(Any);
+        let viz_config = get_settings(&paths.settings_file)?.viz.unwrap_or_default();
+        let auth = Arc::new(VizAuth::new(paths, viz_config.session_hours)?);
 
+        // Everything but login needs a session; writes need an operator
         let mut app = Router::new()
             .route("/ws", get(ws_handler))
             .route("/api/settings", get(get_settings_handler))
             .route("/api/status", get(get_status_handler))
-            .route("/api/settings/full", get(get_full_settings_handler))
+            .route(
+                "/api/settings/full",
+                get(get_full_settings_handler)
+                    .put(put_full_settings_handler)
+                    .patch(patch_full_settings_handler),
+            )
             .route("/api/queue", get(get_queue_handler))
-            .route("/api/send", post(post_send_handler))
-            .layer(cors)
-            .with_state(state);
+            .route("/api/queue/{dir}/{file}", delete(delete_queue_message_handler))
+            .route("/api/queue/incoming/{file}/route", post(route_queue_message_handler))
+            .route("/api/queue/incoming/{file}/bump", post(bump_queue_message_handler))
+            .route("/api/queue/processing/{file}/requeue", post(requeue_queue_message_handler))
+            .route("/api/conversations", get(list_conversations_handler))
+            .route("/api/conversations/{team}/{id}", get(get_conversation_handler))
+            .route("/api/transcripts", get(list_transcripts_handler))
+            .route("/api/metrics", get(get_metrics_handler))
+            .with_state(state)
+            .merge(
+                Router::new()
+                    .route("/api/send", post(viz_web::send_handler))
+                    .route(
+                        "/api/web/upload",
+                        post(viz_web::upload_handler)
+                            .layer(DefaultBodyLimit::max(viz_web::MAX_UPLOAD_BYTES)),
+                    )
+                    .route("/api/web/files/{token}", get(viz_web::download_handler))
+                    .with_state(web),
+            )
+            .route_layer(middleware::from_fn_with_state(Arc::clone(&auth), viz_auth::require_session))
+            .merge(
+                Router::new()
+                    .route("/api/login", post(viz_auth::login_handler))
+                    .route("/api/logout", post(viz_auth::logout_handler))
+                    .route("/api/me", get(viz_auth::me_handler))
+                    .with_state(Arc::clone(&auth)),
+            );
+
+        // Cross-origin access only for configured origins
+        let origins: Vec<HeaderValue> = viz_config
+            .allowed_origins
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|o| HeaderValue::from_str(o.trim_end_matches('/')).ok())
+            .collect();
+        if !origins.is_empty() {
+            app = app.layer(
+                CorsLayer::new()
+                    .allow_origin(AllowOrigin::list(origins))
+                    .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+                    .allow_headers([header::CONTENT_TYPE, header::IF_MATCH, header::AUTHORIZATION])
+                    .expose_headers([header::ETAG])
+                    .allow_credentials(true),
+            );
+        }
 
         // Serve static files if a directory is provided
         if let Some(dir) = static_dir {
@@ -414,6 +901,10 @@ pub fn start_viz_server(paths: &Paths, port: u16, static_dir: Option<&str>) -> R
             "  WebSocket: {}",
             format!("ws://localhost:{}/ws", port).bright_white()
         );
+        println!(
+            "  Log in at {}",
+            format!("http://localhost:{}/?token={}", port, auth.admin_token()).bright_white()
+        );
         println!("  {}", "(Ctrl+C to stop)".dimmed());
 
         axum::serve(listener, app).await?;
@@ -421,3 +912,130 @@ pub fn start_viz_server(paths: &Paths, port: u16, static_dir: Option<&str>) -> R
         Ok::<_, anyhow::Error>(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings_with_secrets() -> Settings {
+        serde_json::from_value(json!({
+            "channels": {
+                "discord": { "bot_token": "discord-secret" },
+                "telegram": { "bot_token": "telegram-secret" },
+                "slack": { "bot_token": "xoxb-secret", "app_token": "xapp-secret" },
+                "matrix": { "homeserver_url": "https://matrix.example", "access_token": "matrix-secret" },
+                "email": { "password": "imap-secret", "smtp_password": "smtp-secret" },
+                "http": { "api_keys": [
+                    { "name": "ci", "key": "http-ci-secret" },
+                    { "name": "bot", "key": "http-bot-secret" }
+                ] }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mask_secrets() {
+        let mut settings = settings_with_secrets();
+        mask_secrets(&mut settings);
+        let masked = serde_json::to_string(&settings).unwrap();
+        assert!(!masked.contains("secret"), "secret left unmasked: {}", masked);
+        assert!(masked.contains("https://matrix.example"));
+
+        let channels = settings.channels.unwrap();
+        let mask = Some(MASK.to_string());
+        assert_eq!(channels.discord.unwrap().bot_token, mask);
+        assert_eq!(channels.telegram.unwrap().bot_token, mask);
+        let slack = channels.slack.unwrap();
+        assert_eq!(slack.bot_token, mask);
+        assert_eq!(slack.app_token, mask);
+        assert_eq!(channels.matrix.unwrap().access_token, mask);
+        let email = channels.email.unwrap();
+        assert_eq!(email.password, mask);
+        assert_eq!(email.smtp_password, mask);
+        let http = channels.http.unwrap();
+        assert!(http.api_keys.iter().all(|k| k.key == MASK));
+        assert_eq!(http.api_keys[1].name, "bot");
+    }
+
+    #[test]
+    fn test_mask_secrets_leaves_unset_fields_unset() {
+        let mut settings: Settings =
+            serde_json::from_value(json!({ "channels": { "email": { "password": "imap-secret" } } })).unwrap();
+        mask_secrets(&mut settings);
+        let email = settings.channels.unwrap().email.unwrap();
+        assert_eq!(email.password.as_deref(), Some(MASK));
+        assert_eq!(email.smtp_password, None);
+    }
+
+    #[test]
+    fn test_restore_masked_secrets() {
+        let current = settings_with_secrets();
+        let mut saved = current.clone();
+        mask_secrets(&mut saved);
+        {
+            let channels = saved.channels.as_mut().unwrap();
+            channels.slack.as_mut().unwrap().app_token = Some("xapp-rotated".to_string());
+            channels.email.as_mut().unwrap().smtp_password = None;
+            channels.http.as_mut().unwrap().api_keys[0].key = "http-ci-rotated".to_string();
+        }
+
+        let errors = restore_masked_secrets(&mut saved, &current);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let channels = saved.channels.unwrap();
+        assert_eq!(channels.discord.unwrap().bot_token.as_deref(), Some("discord-secret"));
+        assert_eq!(channels.telegram.unwrap().bot_token.as_deref(), Some("telegram-secret"));
+        let slack = channels.slack.unwrap();
+        assert_eq!(slack.bot_token.as_deref(), Some("xoxb-secret"));
+        assert_eq!(slack.app_token.as_deref(), Some("xapp-rotated"));
+        assert_eq!(channels.matrix.unwrap().access_token.as_deref(), Some("matrix-secret"));
+        let email = channels.email.unwrap();
+        assert_eq!(email.password.as_deref(), Some("imap-secret"));
+        assert_eq!(email.smtp_password, None);
+        let keys = channels.http.unwrap().api_keys;
+        assert_eq!(keys[0].key, "http-ci-rotated");
+        assert_eq!(keys[1].key, "http-bot-secret");
+    }
+
+    #[test]
+    fn test_restore_masked_api_key_needs_existing_name() {
+        let current = settings_with_secrets();
+        let mut saved = current.clone();
+        mask_secrets(&mut saved);
+        saved.channels.as_mut().unwrap().http.as_mut().unwrap().api_keys[1].name = "renamed".to_string();
+
+        let errors = restore_masked_secrets(&mut saved, &current);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("'renamed'"));
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut target = json!({
+            "workspace": { "path": "/srv", "name": "main" },
+            "channels": { "discord": { "bot_token": "t" }, "enabled": ["discord"] }
+        });
+        merge_patch(
+            &mut target,
+            &json!({
+                "workspace": { "name": null, "extra": { "a": 1 } },
+                "channels": { "discord": null, "enabled": ["slack"] },
+                "missing": null
+            }),
+        );
+        assert_eq!(
+            target,
+            json!({
+                "workspace": { "path": "/srv", "extra": { "a": 1 } },
+                "channels": { "enabled": ["slack"] }
+            })
+        );
+
+        // A non-object patch replaces the target outright
+        merge_patch(&mut target, &json!(["x"]));
+        assert_eq!(target, json!(["x"]));
+        merge_patch(&mut target, &json!({ "a": { "b": 1 } }));
+        assert_eq!(target, json!({ "a": { "b": 1 } }));
+    }
+}
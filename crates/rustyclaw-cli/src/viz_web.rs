@@ -0,0 +1,553 @@
+//! The `web` channel: chat with agents from the dashboard.
+//!
+//! Messages sent from a browser go through the shared channel runtime like
+//! any other channel. The viz server delivers `web_*` responses from the
+//! outgoing queue by pushing them over `/ws` to the browser that sent the
+//! message. Each browser has its own client ID, and replies are only pushed
+//! to sockets with the same user and client ID.
+//!
+//! Replies and the download links in them are kept under `web/` until the
+//! browser has received them, so they survive restarts and slow sockets.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use rustyclaw_channel::{
+    ingest, poll_outgoing, random_id, Attachment, AttachmentSource, ChannelAdapter, Incoming,
+    Outgoing,
+};
+use rustyclaw_core::config::Paths;
+use rustyclaw_core::types::ReplyTarget;
+
+use crate::viz_auth::Session;
+
+/// Largest file accepted by `/api/web/upload`.
+pub const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+/// Upload bytes one user may have in memory, received or still arriving.
+const MAX_USER_UPLOAD_BYTES: usize = 100 * 1024 * 1024;
+/// Uploads not attached to a message within this time are dropped.
+const UPLOAD_TTL: Duration = Duration::from_secs(3600);
+/// Undelivered replies kept per browser; older ones are dropped.
+const MAX_PENDING_REPLIES: usize = 100;
+/// Undelivered replies and download links expire after a week.
+const RETENTION_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// A reply on its way to one browser; persisted under `web/replies/`
+/// until a socket has sent it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebReply {
+    /// Sorts by creation time; also the file name
+    pub id: String,
+    pub user: String,
+    pub client: String,
+    /// `web_response` frame as sent over the socket
+    pub json: String,
+    pub created_at: u64,
+}
+
+/// A file attached to a reply; persisted under `web/downloads/<token>.json`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Download {
+    user: String,
+    path: PathBuf,
+    created_at: u64,
+}
+
+#[derive(Serialize)]
+struct FileLink {
+    name: String,
+    url: String,
+}
+
+struct Upload {
+    user: String,
+    name: String,
+    bytes: Bytes,
+    at: Instant,
+}
+
+pub struct WebChannel {
+    paths: Paths,
+    tx: broadcast::Sender<WebReply>,
+    /// Upload ID -> file waiting to be attached to a message
+    uploads: Mutex<HashMap<String, Upload>>,
+    /// Bytes of uploads still arriving, per user. Locked after `uploads`.
+    receiving: Mutex<HashMap<String, usize>>,
+}
+
+/// Client IDs are generated by the browser; keep them short and plain.
+pub fn valid_client_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The reply target encodes the browser and the user it belongs to.
+fn reply_target(user: &str, client: &str) -> ReplyTarget {
+    ReplyTarget {
+        chat_id: format!("{}:{}", client, user),
+        ..Default::default()
+    }
+}
+
+fn parse_target(target: &ReplyTarget) -> Option<(String, String)> {
+    let (client, user) = target.chat_id.split_once(':')?;
+    Some((user.to_string(), client.to_string()))
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Download tokens come from `random_id`; anything else is not a file name.
+fn valid_token(token: &str) -> bool {
+    !token.is_empty() && token.len() <= 64 && token.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &std::path::Path) -> Option<T> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+impl WebChannel {
+    pub fn new(paths: &Paths) -> Arc<Self> {
+        let (tx, _rx) = broadcast::channel(64);
+        Arc::new(Self {
+            paths: paths.clone(),
+            tx,
+            uploads: Mutex::new(HashMap::new()),
+            receiving: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn replies_dir(&self) -> PathBuf {
+        self.paths.rustyclaw_home.join("web/replies")
+    }
+
+    fn downloads_dir(&self) -> PathBuf {
+        self.paths.rustyclaw_home.join("web/downloads")
+    }
+
+    /// Deliver `web_*` responses from the outgoing queue until the server stops.
+    pub fn start(self: &Arc<Self>) {
+        let web = Arc::clone(self);
+        tokio::spawn(async move {
+            poll_outgoing(&*web, &web.paths, Duration::from_millis(500)).await
+        });
+    }
+
+    /// Subscribe a socket to a browser's replies, starting with the ones
+    /// it has not received yet.
+    pub fn connect(self: &Arc<Self>, user: &str, client: &str) -> WebSubscription {
+        let rx = self.tx.subscribe();
+        let pending: VecDeque<WebReply> = self.undelivered(user, client).into();
+        let loaded = pending.iter().map(|r| r.id.clone()).collect();
+        WebSubscription {
+            web: Arc::clone(self),
+            key: (user.to_string(), client.to_string()),
+            rx,
+            pending,
+            loaded,
+        }
+    }
+
+    /// Store a frame until the browser has it, and push it to open sockets.
+    fn push(&self, user: &str, client: &str, frame: serde_json::Value) -> Result<()> {
+        let created_at = now_millis();
+        let reply = WebReply {
+            id: format!("{:013}{}", created_at, random_id()),
+            user: user.to_string(),
+            client: client.to_string(),
+            json: frame.to_string(),
+            created_at,
+        };
+        let dir = self.replies_dir();
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(format!("{}.json", reply.id)), serde_json::to_string(&reply)?)?;
+
+        let mut queued = self.undelivered(user, client);
+        if queued.len() > MAX_PENDING_REPLIES {
+            for old in queued.drain(..queued.len() - MAX_PENDING_REPLIES) {
+                self.delivered(&old);
+            }
+        }
+        let _ = self.tx.send(reply);
+        Ok(())
+    }
+
+    /// A browser's stored replies, oldest first. Expired ones are removed.
+    fn undelivered(&self, user: &str, client: &str) -> Vec<WebReply> {
+        let Ok(entries) = std::fs::read_dir(self.replies_dir()) else {
+            return Vec::new();
+        };
+        let cutoff = now_millis().saturating_sub(RETENTION_MS);
+        let mut replies: Vec<WebReply> = entries
+            .flatten()
+            .filter_map(|entry| read_json::<WebReply>(&entry.path()))
+            .filter(|reply| {
+                if reply.created_at < cutoff {
+                    self.delivered(reply);
+                    return false;
+                }
+                reply.user == user && reply.client == client
+            })
+            .collect();
+        replies.sort_by(|a, b| a.id.cmp(&b.id));
+        replies
+    }
+
+    fn delivered(&self, reply: &WebReply) {
+        let _ = std::fs::remove_file(self.replies_dir().join(format!("{}.json", reply.id)));
+    }
+
+    fn file_links(&self, user: &str, files: &[PathBuf]) -> Result<Vec<FileLink>> {
+        let dir = self.downloads_dir();
+        std::fs::create_dir_all(&dir)?;
+        self.prune_downloads();
+        files
+            .iter()
+            .map(|path| {
+                let token = random_id();
+                let download = Download {
+                    user: user.to_string(),
+                    path: path.clone(),
+                    created_at: now_millis(),
+                };
+                std::fs::write(dir.join(format!("{}.json", token)), serde_json::to_string(&download)?)?;
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                Ok(FileLink {
+                    url: format!("/api/web/files/{}", token),
+                    name,
+                })
+            })
+            .collect()
+    }
+
+    /// The file behind a download token, if it is the user's and not expired.
+    fn download(&self, user: &str, token: &str) -> Option<PathBuf> {
+        if !valid_token(token) {
+            return None;
+        }
+        let download: Download = read_json(&self.downloads_dir().join(format!("{}.json", token)))?;
+        let cutoff = now_millis().saturating_sub(RETENTION_MS);
+        (download.user == user && download.created_at >= cutoff).then_some(download.path)
+    }
+
+    fn prune_downloads(&self) {
+        let Ok(entries) = std::fs::read_dir(self.downloads_dir()) else {
+            return;
+        };
+        let cutoff = now_millis().saturating_sub(RETENTION_MS);
+        for entry in entries.flatten() {
+            let expired = read_json::<Download>(&entry.path()).is_none_or(|d| d.created_at < cutoff);
+            if expired {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// One socket's view of the replies for its browser.
+pub struct WebSubscription {
+    web: Arc<WebChannel>,
+    key: (String, String),
+    rx: broadcast::Receiver<WebReply>,
+    /// Stored replies, sent before new ones
+    pending: VecDeque<WebReply>,
+    /// Replies read from storage, so their broadcast is not sent twice
+    loaded: HashSet<String>,
+}
+
+impl WebSubscription {
+    /// Next reply for this browser; `None` once the channel is gone. After
+    /// falling behind, the missed replies are read back from storage.
+    pub async fn recv(&mut self) -> Option<WebReply> {
+        loop {
+            if let Some(reply) = self.pending.pop_front() {
+                return Some(reply);
+            }
+            match self.rx.recv().await {
+                Ok(reply) if reply.user == self.key.0 && reply.client == self.key.1 => {
+                    if !self.loaded.remove(&reply.id) {
+                        return Some(reply);
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    for reply in self.web.undelivered(&self.key.0, &self.key.1) {
+                        if self.loaded.insert(reply.id.clone()) {
+                            self.pending.push_back(reply);
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Forget a reply once the socket has sent it.
+    pub fn delivered(&self, reply: &WebReply) {
+        self.web.delivered(reply);
+    }
+}
+
+#[async_trait]
+impl ChannelAdapter for WebChannel {
+    fn name(&self) -> &'static str {
+        "web"
+    }
+
+    /// The browser shows the whole response at once.
+    fn max_message_len(&self) -> usize {
+        usize::MAX
+    }
+
+    fn format(&self, markdown: &str) -> String {
+        markdown.to_string()
+    }
+
+    /// Choices are shown as buttons.
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()> {
+        let response = outgoing.response;
+        let (user, client) =
+            parse_target(outgoing.target).context("reply target is not a web client")?;
+        let frame = serde_json::json!({
+            "type": "web_response",
+            "messageId": response.message_id,
+            "agent": response.agent,
+            "message": outgoing.chunks.join("\n\n"),
+            "choices": outgoing.choices,
+            "files": self.file_links(&user, &outgoing.files)?,
+            "timestamp": response.timestamp,
+        });
+        self.push(&user, &client, frame)
+    }
+
+    async fn reply(&self, target: &ReplyTarget, text: &str) -> Result<()> {
+        let (user, client) = parse_target(target).context("reply target is not a web client")?;
+        let frame = serde_json::json!({
+            "type": "web_response",
+            "message": text,
+            "timestamp": now_millis(),
+        });
+        self.push(&user, &client, frame)
+    }
+}
+
+// ─── HTTP handlers ─────────────────────────────────────────────────────────
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct SendMessageRequest {
+    message: String,
+    /// Agent or team for messages that don't start with `@id`
+    agent: Option<String>,
+    /// Browser client ID that should receive the reply
+    client: Option<String>,
+    /// IDs returned by `/api/web/upload`
+    #[serde(default)]
+    files: Vec<String>,
+}
+
+/// Queue a message from the dashboard. Built-in commands are answered
+/// straight away over the socket.
+pub async fn send_handler(
+    State(web): State<Arc<WebChannel>>,
+    Extension(session): Extension<Session>,
+    Json(body): Json<SendMessageRequest>,
+) -> Response {
+    let client = body.client.unwrap_or_else(|| "api".to_string());
+    if !valid_client_id(&client) {
+        return json_error(StatusCode::BAD_REQUEST, "Invalid client ID");
+    }
+
+    let mut attachments = Vec::new();
+    {
+        let mut uploads = web.uploads.lock().unwrap();
+        for id in &body.files {
+            match uploads.remove(id) {
+                Some(upload) if upload.user == session.user => attachments.push(Attachment {
+                    name: upload.name,
+                    source: AttachmentSource::Bytes(upload.bytes.to_vec()),
+                }),
+                _ => return json_error(StatusCode::BAD_REQUEST, &format!("Unknown upload '{}'", id)),
+            }
+        }
+    }
+    if body.message.trim().is_empty() && attachments.is_empty() {
+        return json_error(StatusCode::BAD_REQUEST, "Message cannot be empty");
+    }
+
+    let incoming = Incoming {
+        sender: session.user.clone(),
+        sender_id: format!("{}:{}", session.user, client),
+        text: body.message,
+        attachments,
+        reply_to: reply_target(&session.user, &client),
+        default_route: body.agent.filter(|a| !a.is_empty()),
+    };
+    match ingest(&*web, &web.paths, incoming).await {
+        Some(msg) => Json(serde_json::json!({ "ok": true, "messageId": msg.message_id })).into_response(),
+        None => Json(serde_json::json!({ "ok": true })).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    name: String,
+}
+
+/// Bytes of one upload still arriving, counted against its user until dropped.
+struct Receiving<'a> {
+    web: &'a WebChannel,
+    user: String,
+    bytes: usize,
+}
+
+impl Receiving<'_> {
+    /// Count `len` more bytes; false if that would take the user over
+    /// `MAX_USER_UPLOAD_BYTES`.
+    fn reserve(&mut self, len: usize) -> bool {
+        let uploads = self.web.uploads.lock().unwrap();
+        let mut receiving = self.web.receiving.lock().unwrap();
+        let held: usize = uploads
+            .values()
+            .filter(|u| u.user == self.user && u.at.elapsed() < UPLOAD_TTL)
+            .map(|u| u.bytes.len())
+            .sum();
+        let arriving = receiving.entry(self.user.clone()).or_default();
+        if held + *arriving + len > MAX_USER_UPLOAD_BYTES {
+            return false;
+        }
+        *arriving += len;
+        self.bytes += len;
+        true
+    }
+}
+
+impl Drop for Receiving<'_> {
+    fn drop(&mut self) {
+        let mut receiving = self.web.receiving.lock().unwrap();
+        if let Some(arriving) = receiving.get_mut(&self.user) {
+            *arriving -= self.bytes;
+            if *arriving == 0 {
+                receiving.remove(&self.user);
+            }
+        }
+    }
+}
+
+/// Hold a file until the message it belongs to is sent. The body is the
+/// raw file content, read as it arrives so size limits apply before it is
+/// all in memory.
+pub async fn upload_handler(
+    State(web): State<Arc<WebChannel>>,
+    Extension(session): Extension<Session>,
+    Query(query): Query<UploadQuery>,
+    body: Body,
+) -> Response {
+    // Keep only the last path component of the browser's file name
+    let name = query
+        .name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if name.is_empty() || name.starts_with('.') {
+        return json_error(StatusCode::BAD_REQUEST, "Invalid file name");
+    }
+
+    let mut receiving = Receiving {
+        web: &web,
+        user: session.user.clone(),
+        bytes: 0,
+    };
+    let mut data = Vec::new();
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let Ok(chunk) = chunk else {
+            return json_error(StatusCode::BAD_REQUEST, "Upload interrupted");
+        };
+        if data.len() + chunk.len() > MAX_UPLOAD_BYTES {
+            return json_error(StatusCode::PAYLOAD_TOO_LARGE, "File is too large");
+        }
+        if !receiving.reserve(chunk.len()) {
+            return json_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Too many uploads waiting to be sent; send them or try again later",
+            );
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let id = random_id();
+    let mut uploads = web.uploads.lock().unwrap();
+    uploads.retain(|_, u| u.at.elapsed() < UPLOAD_TTL);
+    uploads.insert(
+        id.clone(),
+        Upload {
+            user: session.user,
+            name: name.clone(),
+            bytes: Bytes::from(data),
+            at: Instant::now(),
+        },
+    );
+    drop(uploads);
+    drop(receiving);
+    Json(serde_json::json!({ "id": id, "name": name })).into_response()
+}
+
+/// Download a file attached to a reply.
+pub async fn download_handler(
+    State(web): State<Arc<WebChannel>>,
+    Extension(session): Extension<Session>,
+    Path(token): Path<String>,
+) -> Response {
+    let Some(path) = web.download(&session.user, &token) else {
+        return json_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    let Ok(bytes) = tokio::fs::read(&path).await else {
+        return json_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().replace('"', ""))
+        .unwrap_or_default();
+    (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name)),
+        ],
+        bytes,
+    )
+        .into_response()
+}
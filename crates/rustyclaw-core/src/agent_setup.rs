@@ -315,6 +315,7 @@ pub fn update_agent_teammates(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::ResetPolicy;
     use tempfile::TempDir;
 
     #[test]
@@ -395,8 +396,9 @@ mod tests {
                 provider: "anthropic".to_string(),
                 model: "sonnet".to_string(),
                 working_directory: "/tmp/coder".to_string(),
-                reset_policy: String::new(),
+                reset_policy: ResetPolicy::default(),
                 reset_hour: None,
+                timezone: None,
                 idle_timeout_minutes: None,
                 context_window: None,
                 fallbacks: None,
@@ -412,8 +414,9 @@ mod tests {
                 provider: "anthropic".to_string(),
                 model: "opus".to_string(),
                 working_directory: "/tmp/reviewer".to_string(),
-                reset_policy: String::new(),
+                reset_policy: ResetPolicy::default(),
                 reset_hour: None,
+                timezone: None,
                 idle_timeout_minutes: None,
                 context_window: None,
                 fallbacks: None,
@@ -430,8 +433,9 @@ mod tests {
                 provider: "anthropic".to_string(),
                 model: "haiku".to_string(),
                 working_directory: "/tmp/tester".to_string(),
-                reset_policy: String::new(),
+                reset_policy: ResetPolicy::default(),
                 reset_hour: None,
+                timezone: None,
                 idle_timeout_minutes: None,
                 context_window: None,
                 fallbacks: None,
@@ -0,0 +1,621 @@
+//! Channel-specific activation and routing rules shared by the channel bots.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Paths;
+use crate::types::{
+    AgentConfig, DiscordChannelConfig, LongResponseMode, MatrixChannelConfig, Settings,
+    TeamConfig, TelegramChannelConfig,
+};
+
+/// Maximum number of choices rendered as buttons for one response.
+pub const MAX_CHOICES: usize = 10;
+
+/// Paginated responses and saved choices older than this are pruned.
+const PAGE_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Telegram allows at most 100 bot commands.
+const MAX_TELEGRAM_COMMANDS: usize = 100;
+
+/// Built-in Telegram commands, registered before per-agent and per-team ones.
+const TELEGRAM_BASE_COMMANDS: &[(&str, &str)] = &[
+    ("agent", "List agents and pick one for this chat"),
+    ("team", "List teams"),
+    ("reset", "Reset an agent's conversation"),
+];
+
+/// Default agent or team for a Discord guild channel.
+///
+/// A per-channel entry wins over the guild default. For threads, pass the
+/// parent channel ID.
+pub fn discord_default_route<'a>(
+    config: &'a DiscordChannelConfig,
+    guild_id: &str,
+    channel_id: &str,
+) -> Option<&'a str> {
+    let guild = config.guilds.as_ref()?.get(guild_id)?;
+    guild
+        .channels
+        .as_ref()
+        .and_then(|c| c.get(channel_id))
+        .or(guild.default_agent.as_ref())
+        .map(|s| s.as_str())
+        .filter(|s| !s.is_empty())
+}
+
+/// Whether the bot answers in a Discord guild channel without being mentioned.
+///
+/// True for allowlisted channels and for guilds with `require_mention: false`.
+pub fn discord_responds_without_mention(
+    config: &DiscordChannelConfig,
+    guild_id: &str,
+    channel_id: &str,
+) -> bool {
+    let allowlisted = config
+        .allowed_channels
+        .as_ref()
+        .map(|c| c.iter().any(|id| id == channel_id))
+        .unwrap_or(false);
+    let guild_open = config
+        .guilds
+        .as_ref()
+        .and_then(|g| g.get(guild_id))
+        .and_then(|g| g.require_mention)
+        .map(|required| !required)
+        .unwrap_or(false);
+    allowlisted || guild_open
+}
+
+/// Whether guild conversations should be moved into their own thread.
+pub fn discord_threads_enabled(config: &DiscordChannelConfig) -> bool {
+    config.use_threads.unwrap_or(true)
+}
+
+/// Default agent or team for a Matrix room. A per-room entry wins over
+/// `default_agent`.
+pub fn matrix_default_route<'a>(config: &'a MatrixChannelConfig, room_id: &str) -> Option<&'a str> {
+    config
+        .rooms
+        .as_ref()
+        .and_then(|r| r.get(room_id))
+        .or(config.default_agent.as_ref())
+        .map(|s| s.as_str())
+        .filter(|s| !s.is_empty())
+}
+
+/// Whether the bot answers in a Matrix group room without being mentioned.
+pub fn matrix_room_allowed(config: &MatrixChannelConfig, room_id: &str) -> bool {
+    config
+        .allowed_rooms
+        .as_ref()
+        .is_some_and(|rooms| rooms.iter().any(|id| id == room_id))
+}
+
+/// Prefix a message with a default `@agent` route unless it already names one.
+pub fn apply_default_route(message: &str, default_route: Option<&str>) -> String {
+    match default_route {
+        Some(route) if !message.trim_start().starts_with('@') => {
+            format!("@{} {}", route, message.trim_start())
+        }
+        _ => message.to_string(),
+    }
+}
+
+/// Pull a `[choices: A | B | C]` tag out of a response.
+///
+/// Returns the text without the tag and the choice labels (at most
+/// [`MAX_CHOICES`]). Channels that support buttons render the choices as
+/// buttons; clicking one sends the label back as the user's reply.
+pub fn extract_choices(text: &str) -> (String, Vec<String>) {
+    let re = Regex::new(r"\[choices:\s*([^\]]+)\]").unwrap();
+    let Some(caps) = re.captures(text) else {
+        return (text.to_string(), Vec::new());
+    };
+    let choices: Vec<String> = caps[1]
+        .split('|')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .take(MAX_CHOICES)
+        .collect();
+    let stripped = re.replace_all(text, "").trim().to_string();
+    (stripped, choices)
+}
+
+/// Agent and team IDs matching a partial input, for command autocomplete.
+///
+/// Returns `(label, id)` pairs sorted by ID, agents before teams.
+pub fn route_suggestions(
+    agents: &HashMap<String, AgentConfig>,
+    teams: &HashMap<String, TeamConfig>,
+    partial: &str,
+    limit: usize,
+) -> Vec<(String, String)> {
+    let needle = partial.trim().trim_start_matches('@').to_lowercase();
+    let mut agent_ids: Vec<&String> = agents
+        .keys()
+        .filter(|id| id.to_lowercase().contains(&needle))
+        .collect();
+    agent_ids.sort();
+    let mut team_ids: Vec<&String> = teams
+        .keys()
+        .filter(|id| id.to_lowercase().contains(&needle))
+        .collect();
+    team_ids.sort();
+
+    agent_ids
+        .into_iter()
+        .map(|id| (format!("@{} — {}", id, agents[id].name), id.clone()))
+        .chain(
+            team_ids
+                .into_iter()
+                .map(|id| (format!("@{} — team {}", id, teams[id].name), id.clone())),
+        )
+        .take(limit)
+        .collect()
+}
+
+/// Whether the Telegram bot may answer in a group chat.
+pub fn telegram_group_allowed(config: &TelegramChannelConfig, chat_id: &str) -> bool {
+    match &config.allowed_groups {
+        Some(groups) => groups.iter().any(|g| g == chat_id),
+        None => true,
+    }
+}
+
+/// Whether group messages must address the bot (mention, reply, or command).
+pub fn telegram_requires_mention(config: &TelegramChannelConfig) -> bool {
+    config.require_mention.unwrap_or(true)
+}
+
+/// Convert an agent or team ID into a valid Telegram command name
+/// (lowercase `a-z`, `0-9`, `_`, at most 32 characters).
+pub fn telegram_command_name(id: &str) -> Option<String> {
+    let name: String = id
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .take(32)
+        .collect();
+    if name.trim_matches('_').is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Commands to register with Telegram's `setMyCommands`: the built-in
+/// commands, then one per agent and team (`/coder`, `/dev`).
+pub fn telegram_commands(
+    agents: &HashMap<String, AgentConfig>,
+    teams: &HashMap<String, TeamConfig>,
+) -> Vec<(String, String)> {
+    let mut commands: Vec<(String, String)> = TELEGRAM_BASE_COMMANDS
+        .iter()
+        .map(|(c, d)| (c.to_string(), d.to_string()))
+        .collect();
+
+    let mut agent_ids: Vec<&String> = agents.keys().collect();
+    agent_ids.sort();
+    let mut team_ids: Vec<&String> = teams.keys().collect();
+    team_ids.sort();
+    let entries = agent_ids
+        .into_iter()
+        .map(|id| (id, format!("Ask {}", agents[id].name)))
+        .chain(team_ids.into_iter().map(|id| (id, format!("Ask team {}", teams[id].name))));
+
+    for (id, description) in entries {
+        if let Some(name) = telegram_command_name(id) {
+            if !commands.iter().any(|(c, _)| *c == name) {
+                commands.push((name, description));
+            }
+        }
+    }
+    commands.truncate(MAX_TELEGRAM_COMMANDS);
+    commands
+}
+
+/// Map a Telegram command back to the agent or team ID it was generated from.
+pub fn telegram_command_route(
+    command: &str,
+    agents: &HashMap<String, AgentConfig>,
+    teams: &HashMap<String, TeamConfig>,
+) -> Option<String> {
+    agents
+        .keys()
+        .chain(teams.keys())
+        .find(|id| telegram_command_name(id).as_deref() == Some(command))
+        .cloned()
+}
+
+/// Split `/command@botname args` (or `!command args`) into the lowercase
+/// command and its arguments.
+///
+/// Returns `None` for non-commands and for commands addressed to another bot.
+pub fn parse_telegram_command(text: &str, bot_username: &str) -> Option<(String, String)> {
+    let text = text.trim();
+    let rest = text.strip_prefix('/').or_else(|| text.strip_prefix('!'))?;
+    let (head, args) = match rest.split_once(char::is_whitespace) {
+        Some((h, a)) => (h, a.trim()),
+        None => (rest, ""),
+    };
+    let command = match head.split_once('@') {
+        Some((cmd, target)) if target.eq_ignore_ascii_case(bot_username) => cmd,
+        Some(_) => return None,
+        None => head,
+    };
+    if command.is_empty() {
+        return None;
+    }
+    Some((command.to_lowercase(), args.to_string()))
+}
+
+/// Remove `@bot_username` from a message. Returns the cleaned text and whether
+/// the bot was mentioned.
+pub fn strip_telegram_mention(text: &str, bot_username: &str) -> (String, bool) {
+    if bot_username.is_empty() {
+        return (text.to_string(), false);
+    }
+    let re = Regex::new(&format!(r"(?i)@{}\b", regex::escape(bot_username))).unwrap();
+    if !re.is_match(text) {
+        return (text.to_string(), false);
+    }
+    let cleaned = re.replace_all(text, "");
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    (cleaned, true)
+}
+
+/// Build a thread title from the first line of a message.
+pub fn thread_title(message: &str, max_chars: usize) -> String {
+    let first_line = message.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+    if first_line.is_empty() {
+        return "Conversation".to_string();
+    }
+    if first_line.chars().count() <= max_chars {
+        return first_line.to_string();
+    }
+    let truncated: String = first_line.chars().take(max_chars.saturating_sub(3)).collect();
+    format!("{}...", truncated.trim_end())
+}
+
+/// How `channel` delivers responses longer than one message.
+pub fn long_response_mode(settings: &Settings, channel: &str) -> LongResponseMode {
+    let channels = settings.channels.as_ref();
+    let mode = match channel {
+        "discord" => channels.and_then(|c| c.discord.as_ref()).and_then(|d| d.long_responses),
+        "telegram" => channels.and_then(|c| c.telegram.as_ref()).and_then(|t| t.long_responses),
+        "slack" => channels.and_then(|c| c.slack.as_ref()).and_then(|s| s.long_responses),
+        "matrix" => channels.and_then(|c| c.matrix.as_ref()).and_then(|m| m.long_responses),
+        // No length limit; always send the full answer
+        "email" | "http" => Some(LongResponseMode::Split),
+        _ => None,
+    };
+    mode.unwrap_or_default()
+}
+
+/// Directory holding the pages of paginated responses.
+pub fn pages_dir(paths: &Paths) -> PathBuf {
+    paths.files_dir.join("pages")
+}
+
+/// Store the pages of a response so page buttons keep working after a
+/// restart. Returns the ID used in button payloads.
+pub fn save_pages(dir: &Path, pages: &[String]) -> Result<String> {
+    save_json(dir, pages)
+}
+
+/// Load the pages saved under `id`.
+pub fn load_pages(dir: &Path, id: &str) -> Option<Vec<String>> {
+    load_json(dir, id)
+}
+
+/// Directory holding the choices of `[choices: ...]` buttons.
+pub fn choices_dir(paths: &Paths) -> PathBuf {
+    paths.files_dir.join("choices")
+}
+
+/// The choices offered in one response, and the agent that offered them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedChoices {
+    pub agent: Option<String>,
+    pub choices: Vec<String>,
+}
+
+/// Store the choices of a response, so buttons only carry `choice:<id>:<n>`.
+/// Platforms cap button payloads (Discord 100 chars, Telegram 64 bytes), too
+/// short for the choice text itself. Returns the ID used in button payloads.
+pub fn save_choices(dir: &Path, agent: Option<&str>, choices: &[String]) -> Result<String> {
+    save_json(
+        dir,
+        &SavedChoices {
+            agent: agent.map(str::to_string),
+            choices: choices.to_vec(),
+        },
+    )
+}
+
+/// Look up choice `index` saved under `id`: the agent it was offered by and
+/// the choice text.
+pub fn load_choice(dir: &Path, id: &str, index: usize) -> Option<(Option<String>, String)> {
+    let saved: SavedChoices = load_json(dir, id)?;
+    let choice = saved.choices.get(index)?.clone();
+    Some((saved.agent, choice))
+}
+
+/// Parse a `choice:<id>:<index>` button payload.
+pub fn parse_choice_payload(payload: &str) -> Option<(&str, usize)> {
+    let rest = payload.strip_prefix("choice:")?;
+    let (id, index) = rest.split_once(':')?;
+    Some((id, index.parse().ok()?))
+}
+
+fn save_json<T: Serialize + ?Sized>(dir: &Path, value: &T) -> Result<String> {
+    std::fs::create_dir_all(dir)?;
+    prune_pages(dir);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let id = format!("{:x}{:04x}", now.as_millis(), rand::random::<u16>());
+    std::fs::write(dir.join(format!("{}.json", id)), serde_json::to_string(value)?)?;
+    Ok(id)
+}
+
+fn load_json<T: DeserializeOwned>(dir: &Path, id: &str) -> Option<T> {
+    if !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let raw = std::fs::read_to_string(dir.join(format!("{}.json", id))).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+fn prune_pages(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age.as_secs() > PAGE_RETENTION_SECS);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Parse a `page:<id>:<index>` button payload.
+pub fn parse_page_payload(payload: &str) -> Option<(&str, usize)> {
+    let rest = payload.strip_prefix("page:")?;
+    let (id, index) = rest.split_once(':')?;
+    Some((id, index.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DiscordGuildConfig;
+
+    fn agent(name: &str) -> AgentConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "provider": "anthropic",
+            "model": "sonnet",
+            "working_directory": "",
+        }))
+        .unwrap()
+    }
+
+    fn config() -> DiscordChannelConfig {
+        let mut channels = HashMap::new();
+        channels.insert("200".to_string(), "dev".to_string());
+        let mut guilds = HashMap::new();
+        guilds.insert(
+            "1".to_string(),
+            DiscordGuildConfig {
+                default_agent: Some("assistant".to_string()),
+                channels: Some(channels),
+                require_mention: None,
+            },
+        );
+        guilds.insert(
+            "2".to_string(),
+            DiscordGuildConfig {
+                require_mention: Some(false),
+                ..Default::default()
+            },
+        );
+        DiscordChannelConfig {
+            allowed_channels: Some(vec!["300".to_string()]),
+            guilds: Some(guilds),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_discord_default_route() {
+        let cfg = config();
+        assert_eq!(discord_default_route(&cfg, "1", "200"), Some("dev"));
+        assert_eq!(discord_default_route(&cfg, "1", "999"), Some("assistant"));
+        assert_eq!(discord_default_route(&cfg, "2", "200"), None);
+        assert_eq!(discord_default_route(&cfg, "unknown", "200"), None);
+    }
+
+    #[test]
+    fn test_discord_mention_gating() {
+        let cfg = config();
+        assert!(discord_responds_without_mention(&cfg, "1", "300"));
+        assert!(!discord_responds_without_mention(&cfg, "1", "200"));
+        assert!(discord_responds_without_mention(&cfg, "2", "anything"));
+        assert!(!discord_responds_without_mention(&DiscordChannelConfig::default(), "1", "300"));
+    }
+
+    #[test]
+    fn test_matrix_routing() {
+        let cfg: MatrixChannelConfig = serde_json::from_value(serde_json::json!({
+            "default_agent": "assistant",
+            "rooms": { "!dev:example.org": "dev" },
+            "allowed_rooms": ["!dev:example.org"]
+        }))
+        .unwrap();
+        assert_eq!(matrix_default_route(&cfg, "!dev:example.org"), Some("dev"));
+        assert_eq!(matrix_default_route(&cfg, "!other:example.org"), Some("assistant"));
+        assert_eq!(matrix_default_route(&MatrixChannelConfig::default(), "!dev:example.org"), None);
+        assert!(matrix_room_allowed(&cfg, "!dev:example.org"));
+        assert!(!matrix_room_allowed(&cfg, "!other:example.org"));
+    }
+
+    #[test]
+    fn test_apply_default_route() {
+        assert_eq!(apply_default_route("hello", Some("dev")), "@dev hello");
+        assert_eq!(apply_default_route("@coder fix it", Some("dev")), "@coder fix it");
+        assert_eq!(apply_default_route("hello", None), "hello");
+    }
+
+    #[test]
+    fn test_thread_title() {
+        assert_eq!(thread_title("\nFix the build\nmore", 50), "Fix the build");
+        assert_eq!(thread_title("", 50), "Conversation");
+        assert_eq!(thread_title("abcdefghij", 8), "abcde...");
+    }
+
+    #[test]
+    fn test_extract_choices() {
+        let (text, choices) = extract_choices("Deploy now?\n[choices: Yes | No | Later]");
+        assert_eq!(text, "Deploy now?");
+        assert_eq!(choices, vec!["Yes", "No", "Later"]);
+
+        let (text, choices) = extract_choices("No question here");
+        assert_eq!(text, "No question here");
+        assert!(choices.is_empty());
+    }
+
+    #[test]
+    fn test_route_suggestions() {
+        let mut agents = HashMap::new();
+        agents.insert("coder".to_string(), agent("Coder"));
+        agents.insert("reviewer".to_string(), agent("Reviewer"));
+        let mut teams = HashMap::new();
+        teams.insert(
+            "dev".to_string(),
+            TeamConfig {
+                name: "Dev".to_string(),
+                agents: vec!["coder".to_string()],
+                leader_agent: "coder".to_string(),
+                description: None,
+            },
+        );
+
+        let all = route_suggestions(&agents, &teams, "", 25);
+        let ids: Vec<&str> = all.iter().map(|(_, id)| id.as_str()).collect();
+        assert_eq!(ids, vec!["coder", "reviewer", "dev"]);
+
+        let filtered = route_suggestions(&agents, &teams, "@REV", 25);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].1, "reviewer");
+
+        assert_eq!(route_suggestions(&agents, &teams, "", 2).len(), 2);
+    }
+
+    #[test]
+    fn test_telegram_group_gating() {
+        let mut cfg = TelegramChannelConfig::default();
+        assert!(telegram_group_allowed(&cfg, "-100"));
+        assert!(telegram_requires_mention(&cfg));
+        cfg.allowed_groups = Some(vec!["-100".to_string()]);
+        cfg.require_mention = Some(false);
+        assert!(telegram_group_allowed(&cfg, "-100"));
+        assert!(!telegram_group_allowed(&cfg, "-200"));
+        assert!(!telegram_requires_mention(&cfg));
+    }
+
+    #[test]
+    fn test_telegram_commands() {
+        let mut agents = HashMap::new();
+        agents.insert("code-bot".to_string(), agent("Coder"));
+        agents.insert("reset".to_string(), agent("Clashes with built-in"));
+        let teams = HashMap::new();
+
+        let commands = telegram_commands(&agents, &teams);
+        let names: Vec<&str> = commands.iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(names, vec!["agent", "team", "reset", "code_bot"]);
+        assert_eq!(
+            telegram_command_route("code_bot", &agents, &teams).as_deref(),
+            Some("code-bot")
+        );
+        assert_eq!(telegram_command_name("---"), None);
+    }
+
+    #[test]
+    fn test_parse_telegram_command() {
+        assert_eq!(
+            parse_telegram_command("/Reset@MyBot coder", "mybot"),
+            Some(("reset".to_string(), "coder".to_string()))
+        );
+        assert_eq!(
+            parse_telegram_command("/agent", "mybot"),
+            Some(("agent".to_string(), String::new()))
+        );
+        assert_eq!(
+            parse_telegram_command("!team", "mybot"),
+            Some(("team".to_string(), String::new()))
+        );
+        assert_eq!(parse_telegram_command("/agent@otherbot", "mybot"), None);
+        assert_eq!(parse_telegram_command("hello", "mybot"), None);
+    }
+
+    #[test]
+    fn test_strip_telegram_mention() {
+        assert_eq!(
+            strip_telegram_mention("@MyBot fix the build", "mybot"),
+            ("fix the build".to_string(), true)
+        );
+        assert_eq!(
+            strip_telegram_mention("hello @mybotx", "mybot"),
+            ("hello @mybotx".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_long_response_mode() {
+        let mut settings = Settings::default();
+        assert_eq!(long_response_mode(&settings, "discord"), LongResponseMode::Attach);
+        settings.channels = Some(serde_json::from_value(serde_json::json!({
+            "telegram": { "long_responses": "paginate" }
+        })).unwrap());
+        assert_eq!(long_response_mode(&settings, "telegram"), LongResponseMode::Paginate);
+        assert_eq!(long_response_mode(&settings, "discord"), LongResponseMode::Attach);
+        assert_eq!(long_response_mode(&settings, "email"), LongResponseMode::Split);
+    }
+
+    #[test]
+    fn test_pages_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let pages = vec!["one".to_string(), "two".to_string()];
+        let id = save_pages(dir.path(), &pages).unwrap();
+        assert_eq!(load_pages(dir.path(), &id), Some(pages));
+        assert_eq!(load_pages(dir.path(), "../etc"), None);
+        assert_eq!(parse_page_payload(&format!("page:{}:1", id)), Some((id.as_str(), 1)));
+        assert_eq!(parse_page_payload("page:abc"), None);
+    }
+
+    #[test]
+    fn test_choices_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let long = format!("{} A", "x".repeat(120));
+        let choices = vec![long.clone(), format!("{} B", "x".repeat(120))];
+        let id = save_choices(dir.path(), Some("coder"), &choices).unwrap();
+        let payload = format!("choice:{}:0", id);
+        assert!(payload.len() <= 64);
+        assert_eq!(parse_choice_payload(&payload), Some((id.as_str(), 0)));
+        assert_eq!(load_choice(dir.path(), &id, 0), Some((Some("coder".to_string()), long)));
+        assert_eq!(load_choice(dir.path(), &id, 2), None);
+        assert_eq!(parse_choice_payload("choice:coder:Yes"), None);
+    }
+}
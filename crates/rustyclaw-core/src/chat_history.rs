@@ -0,0 +1,346 @@
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::transcript::TranscriptEntry;
+use crate::types::ChainStep;
+
+/// Structured copy of a team conversation, saved as `<id>.json` next to
+/// the human-readable `<id>.md` in `chats/<team_id>/`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatRecord {
+    #[serde(rename = "teamId")]
+    pub team_id: String,
+    #[serde(rename = "teamName")]
+    pub team_name: String,
+    /// RFC 3339 time the conversation was saved
+    pub date: String,
+    pub channel: String,
+    pub sender: String,
+    /// The user message that started the conversation
+    pub message: String,
+    /// Epoch millis when the conversation started (0 if unknown)
+    #[serde(rename = "startedAt", default)]
+    pub started_at: u64,
+    pub steps: Vec<ChainStep>,
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+/// One row of the conversation list.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSummary {
+    /// File stem, e.g. `2026-02-17T10-30-00`
+    pub id: String,
+    #[serde(rename = "teamId")]
+    pub team_id: String,
+    #[serde(rename = "teamName")]
+    pub team_name: String,
+    pub date: String,
+    pub channel: String,
+    pub sender: String,
+    pub preview: String,
+    pub agents: Vec<String>,
+    pub steps: usize,
+}
+
+/// A page of results plus the total match count.
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub page: usize,
+    #[serde(rename = "perPage")]
+    pub per_page: usize,
+}
+
+/// Slice `items` into 1-based page `page`. `per_page` is clamped to 1..=200.
+pub fn paginate<T>(items: Vec<T>, page: usize, per_page: usize) -> Page<T> {
+    let page = page.max(1);
+    let per_page = per_page.clamp(1, 200);
+    let total = items.len();
+    let items = items
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .collect();
+    Page { items, total, page, per_page }
+}
+
+/// Team and conversation IDs become path components; reject anything that
+/// could escape the chats directory.
+fn is_safe_component(s: &str) -> bool {
+    !s.is_empty()
+        && s != "."
+        && s != ".."
+        && !s.contains(['/', '\\'])
+}
+
+/// Parse a chat history markdown file written by the queue processor.
+/// Older files have no JSON sidecar, so handoff sources and timings are
+/// unknown; `started_at` and per-step times stay 0.
+pub fn parse_chat_markdown(team_id: &str, text: &str) -> ChatRecord {
+    let mut record = ChatRecord {
+        team_id: team_id.to_string(),
+        ..Default::default()
+    };
+    let mut sections = text.split("\n------\n\n## ");
+
+    for line in sections.next().unwrap_or_default().lines() {
+        if let Some(rest) = line.strip_prefix("# Team Conversation: ") {
+            let name = match rest.rfind(" (@") {
+                Some(idx) => &rest[..idx],
+                None => rest,
+            };
+            record.team_name = name.to_string();
+        } else if let Some(rest) = line.strip_prefix("**Date:** ") {
+            record.date = rest.trim().to_string();
+        } else if let Some(rest) = line.strip_prefix("**Channel:** ") {
+            let (channel, sender) = rest.split_once(" | **Sender:** ").unwrap_or((rest, ""));
+            record.channel = channel.trim().to_string();
+            record.sender = sender.trim().to_string();
+        }
+    }
+
+    for section in sections {
+        let (heading, body) = section.split_once('\n').unwrap_or((section, ""));
+        let body = body.trim().to_string();
+        if heading == "User Message" {
+            record.message = body;
+            continue;
+        }
+        let agent_id = match (heading.rfind("(@"), heading.ends_with(')')) {
+            (Some(idx), true) => &heading[idx + 2..heading.len() - 1],
+            _ => heading.trim_start_matches('@'),
+        };
+        record.steps.push(ChainStep {
+            agent_id: agent_id.to_string(),
+            response: body,
+            from_agent: None,
+            started_at: 0,
+            finished_at: 0,
+        });
+    }
+    record
+}
+
+/// Load one conversation, preferring the JSON sidecar over the markdown.
+pub fn load_conversation(chats_dir: &Path, team_id: &str, id: &str) -> anyhow::Result<ChatRecord> {
+    if !is_safe_component(team_id) || !is_safe_component(id) {
+        anyhow::bail!("Invalid conversation path");
+    }
+    let dir = chats_dir.join(team_id);
+    let json_path = dir.join(format!("{}.json", id));
+    if let Ok(content) = std::fs::read_to_string(&json_path) {
+        return Ok(serde_json::from_str(&content)?);
+    }
+    let content = std::fs::read_to_string(dir.join(format!("{}.md", id)))?;
+    Ok(parse_chat_markdown(team_id, &content))
+}
+
+fn matches_query(record: &ChatRecord, query: &str) -> bool {
+    let contains = |s: &str| s.to_lowercase().contains(query);
+    contains(&record.message)
+        || contains(&record.sender)
+        || record
+            .steps
+            .iter()
+            .any(|s| contains(&s.response) || contains(&s.agent_id))
+}
+
+/// List saved conversations, newest first. `team` restricts to one team;
+/// `query` is a case-insensitive substring matched against the user
+/// message, sender, agent IDs and responses.
+pub fn list_conversations(
+    chats_dir: &Path,
+    team: Option<&str>,
+    query: Option<&str>,
+) -> Vec<ConversationSummary> {
+    let query = query.map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty());
+    let team_dirs: Vec<String> = match team {
+        Some(t) if is_safe_component(t) => vec![t.to_string()],
+        Some(_) => return Vec::new(),
+        None => std::fs::read_dir(chats_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect(),
+    };
+
+    let mut summaries = Vec::new();
+    for team_id in team_dirs {
+        let Ok(entries) = std::fs::read_dir(chats_dir.join(&team_id)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("md") {
+                continue;
+            }
+            let Some(id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let Ok(record) = load_conversation(chats_dir, &team_id, &id) else {
+                continue;
+            };
+            if let Some(q) = &query {
+                if !matches_query(&record, q) {
+                    continue;
+                }
+            }
+            let mut agents: Vec<String> = Vec::new();
+            for step in &record.steps {
+                if !agents.contains(&step.agent_id) {
+                    agents.push(step.agent_id.clone());
+                }
+            }
+            summaries.push(ConversationSummary {
+                id,
+                team_id: team_id.clone(),
+                team_name: record.team_name,
+                date: record.date,
+                channel: record.channel,
+                sender: record.sender,
+                preview: record.message.chars().take(120).collect(),
+                agents,
+                steps: record.steps.len(),
+            });
+        }
+    }
+    // IDs are timestamps, so a reverse sort on (id, team) is newest first
+    summaries.sort_by(|a, b| b.id.cmp(&a.id).then_with(|| a.team_id.cmp(&b.team_id)));
+    summaries
+}
+
+/// Read an agent's transcript entries, newest first, optionally filtered
+/// by a case-insensitive substring of the content or sender.
+pub fn search_transcripts(transcripts_dir: &Path, query: Option<&str>) -> Vec<TranscriptEntry> {
+    let query = query.map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty());
+    let mut files: Vec<_> = std::fs::read_dir(transcripts_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("jsonl"))
+        .collect();
+    files.sort();
+
+    let mut entries = Vec::new();
+    for path in files {
+        let Ok(file) = std::fs::File::open(&path) else {
+            continue;
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let Ok(entry) = serde_json::from_str::<TranscriptEntry>(&line) else {
+                continue;
+            };
+            if let Some(q) = &query {
+                let sender = entry.sender.as_deref().unwrap_or_default().to_lowercase();
+                if !entry.content.to_lowercase().contains(q) && !sender.contains(q) {
+                    continue;
+                }
+            }
+            entries.push(entry);
+        }
+    }
+    entries.reverse();
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SAMPLE: &str = "# Team Conversation: Dev Team (@dev)\n\
+**Date:** 2026-02-17T10:30:00+00:00\n\
+**Channel:** discord | **Sender:** alice\n\
+**Messages:** 2\n\
+\n\
+------\n\
+\n\
+## User Message\n\
+\n\
+Fix the login bug\n\
+\n\
+------\n\
+\n\
+## Coder (@coder)\n\
+\n\
+Done. [@reviewer: please check]\n\
+\n\
+------\n\
+\n\
+## @reviewer\n\
+\n\
+Looks good\n\
+------\n\
+still part of the review\n";
+
+    #[test]
+    fn test_parse_chat_markdown() {
+        let record = parse_chat_markdown("dev", SAMPLE);
+        assert_eq!(record.team_name, "Dev Team");
+        assert_eq!(record.date, "2026-02-17T10:30:00+00:00");
+        assert_eq!(record.channel, "discord");
+        assert_eq!(record.sender, "alice");
+        assert_eq!(record.message, "Fix the login bug");
+        assert_eq!(record.steps.len(), 2);
+        assert_eq!(record.steps[0].agent_id, "coder");
+        assert_eq!(record.steps[1].agent_id, "reviewer");
+        assert!(record.steps[1].response.contains("still part of the review"));
+    }
+
+    #[test]
+    fn test_list_and_load_conversations() {
+        let tmp = TempDir::new().unwrap();
+        let dev = tmp.path().join("dev");
+        std::fs::create_dir_all(&dev).unwrap();
+        std::fs::write(dev.join("2026-02-17T10-30-00.md"), SAMPLE).unwrap();
+
+        let json = ChatRecord {
+            team_id: "dev".to_string(),
+            team_name: "Dev Team".to_string(),
+            message: "Deploy the release".to_string(),
+            steps: vec![ChainStep {
+                agent_id: "ops".to_string(),
+                response: "Deployed".to_string(),
+                from_agent: Some("coder".to_string()),
+                started_at: 1000,
+                finished_at: 4000,
+            }],
+            ..Default::default()
+        };
+        std::fs::write(dev.join("2026-02-18T09-00-00.md"), "ignored").unwrap();
+        std::fs::write(
+            dev.join("2026-02-18T09-00-00.json"),
+            serde_json::to_string(&json).unwrap(),
+        )
+        .unwrap();
+
+        let all = list_conversations(tmp.path(), None, None);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].id, "2026-02-18T09-00-00");
+        assert_eq!(all[0].agents, vec!["ops"]);
+
+        let hits = list_conversations(tmp.path(), Some("dev"), Some("LOGIN"));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "2026-02-17T10-30-00");
+
+        let loaded = load_conversation(tmp.path(), "dev", "2026-02-18T09-00-00").unwrap();
+        assert_eq!(loaded.steps[0].from_agent.as_deref(), Some("coder"));
+        assert!(load_conversation(tmp.path(), "..", "x").is_err());
+        assert!(list_conversations(tmp.path(), Some("../dev"), None).is_empty());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let page = paginate((1..=45).collect::<Vec<_>>(), 3, 20);
+        assert_eq!(page.items, vec![41, 42, 43, 44, 45]);
+        assert_eq!(page.total, 45);
+        assert!(paginate(vec![1, 2], 5, 20).items.is_empty());
+    }
+}
@@ -2,8 +2,12 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
 
-use crate::types::{AgentConfig, Settings, TeamConfig};
+use crate::types::{AgentConfig, ResetPolicy, Settings, TeamConfig};
+
+pub const PROVIDERS: &[&str] = &["anthropic", "openai", "opencode"];
+pub const CHANNELS: &[&str] = &["discord", "telegram", "slack", "matrix", "email", "http"];
 
 /// All resolved paths for Rusty Claw directories
 #[derive(Debug, Clone)]
@@ -21,6 +25,10 @@ pub struct Paths {
     pub chats_dir: PathBuf,
     pub files_dir: PathBuf,
     pub pairing_file: PathBuf,
+    /// Globally installed skill packages
+    pub skills_dir: PathBuf,
+    /// Lockfile recording installed skill versions, sources, and checksums
+    pub skills_lock_file: PathBuf,
 }
 
 impl Paths {
@@ -51,6 +59,8 @@ impl Paths {
             chats_dir: rustyclaw_home.join("chats"),
             files_dir: rustyclaw_home.join("files"),
             pairing_file: rustyclaw_home.join("pairing.json"),
+            skills_dir: rustyclaw_home.join("skills"),
+            skills_lock_file: rustyclaw_home.join("skills.lock"),
             rustyclaw_home,
         }
     }
@@ -76,6 +86,22 @@ fn dirs_home() -> PathBuf {
 
 /// Load and parse settings.json
 pub fn get_settings(settings_file: &Path) -> Result<Settings> {
+    match load_settings(settings_file) {
+        Err(e) if e.is::<serde_json::Error>() => {
+            eprintln!(
+                "[WARN] settings.json contains invalid JSON: {}",
+                e
+            );
+            eprintln!("[ERROR] Could not parse settings.json — returning empty config");
+            Ok(Settings::default())
+        }
+        result => result,
+    }
+}
+
+/// Like [`get_settings`], but a file that doesn't parse is an error rather
+/// than an empty config. Use this before writing settings back.
+pub fn load_settings(settings_file: &Path) -> Result<Settings> {
     if !settings_file.exists() {
         return Ok(Settings::default());
     }
@@ -83,17 +109,7 @@ pub fn get_settings(settings_file: &Path) -> Result<Settings> {
     let data =
         std::fs::read_to_string(settings_file).context("Failed to read settings.json")?;
 
-    let mut settings: Settings = match serde_json::from_str(&data) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!(
-                "[WARN] settings.json contains invalid JSON: {}",
-                e
-            );
-            eprintln!("[ERROR] Could not parse settings.json — returning empty config");
-            return Ok(Settings::default());
-        }
-    };
+    let mut settings: Settings = serde_json::from_str(&data)?;
 
     // Auto-detect provider if not specified
     if let Some(ref mut models) = settings.models {
@@ -161,8 +177,9 @@ pub fn get_default_agent_from_models(settings: &Settings) -> AgentConfig {
         provider,
         model,
         working_directory: default_agent_dir,
-        reset_policy: String::new(),
+        reset_policy: ResetPolicy::default(),
         reset_hour: None,
+        timezone: None,
         idle_timeout_minutes: None,
         context_window: None,
         fallbacks: None,
@@ -200,6 +217,154 @@ pub fn get_workspace_path(settings: &Settings) -> PathBuf {
         .unwrap_or_else(|| dirs_home().join("rustyclaw-workspace"))
 }
 
+/// Resolve an agent's working directory (relative paths are under the workspace).
+pub fn resolve_working_dir(agent: &AgentConfig, agent_id: &str, workspace_path: &Path) -> PathBuf {
+    if agent.working_directory.is_empty() {
+        workspace_path.join(agent_id)
+    } else {
+        let wd = PathBuf::from(&agent.working_directory);
+        if wd.is_absolute() {
+            wd
+        } else {
+            workspace_path.join(&agent.working_directory)
+        }
+    }
+}
+
+/// Agent, team and skill IDs: ASCII letters, digits, `-` and `_`.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Check settings for mistakes the types alone don't catch: unknown providers
+/// and channels, bad reset policies, and teams pointing at missing agents.
+/// Returns one message per problem; empty means valid.
+pub fn validate_settings(settings: &Settings) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if let Some(provider) = settings.models.as_ref().and_then(|m| m.provider.as_deref()) {
+        if !PROVIDERS.contains(&provider) {
+            errors.push(format!("models.provider: unknown provider '{}'", provider));
+        }
+    }
+
+    for channel in settings.channels.as_ref().and_then(|c| c.enabled.as_ref()).into_iter().flatten() {
+        if !CHANNELS.contains(&channel.as_str()) {
+            errors.push(format!("channels.enabled: unknown channel '{}'", channel));
+        }
+    }
+
+    let agents = get_agents(settings);
+    let mut agent_ids: Vec<&String> = agents.keys().collect();
+    agent_ids.sort();
+    for id in agent_ids {
+        let agent = &agents[id];
+        let at = format!("agents.{}", id);
+        if !is_valid_id(id) {
+            errors.push(format!("{}: ID may only contain letters, digits, '-' and '_'", at));
+        }
+        if agent.name.trim().is_empty() {
+            errors.push(format!("{}.name: must not be empty", at));
+        }
+        if !PROVIDERS.contains(&agent.provider.as_str()) {
+            errors.push(format!("{}.provider: unknown provider '{}'", at, agent.provider));
+        }
+        if agent.model.trim().is_empty() {
+            errors.push(format!("{}.model: must not be empty", at));
+        }
+        if let ResetPolicy::Invalid(raw) = &agent.reset_policy {
+            if let Err(e) = raw.parse::<ResetPolicy>() {
+                errors.push(format!("{}.reset_policy: {}", at, e));
+            }
+        }
+        if agent.reset_hour.is_some_and(|h| h > 23) {
+            errors.push(format!("{}.reset_hour: must be between 0 and 23", at));
+        }
+        if let Some(tz) = &agent.timezone {
+            if crate::timezone::parse_timezone(tz).is_none() {
+                errors.push(format!("{}.timezone: unknown timezone '{}'", at, tz));
+            }
+        }
+        if agent.idle_timeout_minutes == Some(0) {
+            errors.push(format!("{}.idle_timeout_minutes: must be greater than 0", at));
+        }
+        if agent.context_window == Some(0) {
+            errors.push(format!("{}.context_window: must be greater than 0", at));
+        }
+        if agent.fallbacks.iter().flatten().any(|f| f.trim().is_empty()) {
+            errors.push(format!("{}.fallbacks: entries must not be empty", at));
+        }
+        if agent.route_patterns.iter().flatten().any(|p| p.trim().is_empty()) {
+            errors.push(format!("{}.route_patterns: entries must not be empty", at));
+        }
+    }
+
+    let teams = get_teams(settings);
+    let mut team_ids: Vec<&String> = teams.keys().collect();
+    team_ids.sort();
+    for id in team_ids {
+        let team = &teams[id];
+        let at = format!("teams.{}", id);
+        if !is_valid_id(id) {
+            errors.push(format!("{}: ID may only contain letters, digits, '-' and '_'", at));
+        }
+        if agents.contains_key(id) {
+            errors.push(format!("{}: ID is already used by an agent", at));
+        }
+        if team.name.trim().is_empty() {
+            errors.push(format!("{}.name: must not be empty", at));
+        }
+        if team.agents.is_empty() {
+            errors.push(format!("{}.agents: a team needs at least one agent", at));
+        }
+        for member in &team.agents {
+            if !agents.contains_key(member) {
+                errors.push(format!("{}.agents: unknown agent '{}'", at, member));
+            }
+        }
+        if !team.agents.contains(&team.leader_agent) {
+            errors.push(format!("{}.leader_agent: '{}' is not a member of the team", at, team.leader_agent));
+        }
+    }
+
+    for name in settings.skills.iter().flat_map(|s| s.keys()) {
+        if crate::skill_packages::validate_skill_name(name).is_err() {
+            errors.push(format!("skills.{}: invalid skill name", name));
+        }
+    }
+
+    errors
+}
+
+/// Content hash of settings.json, used as a revision for optimistic
+/// concurrency. A missing file has the hash of empty content.
+pub fn settings_revision(settings_file: &Path) -> Result<String> {
+    let data = match std::fs::read(settings_file) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).context("Failed to read settings.json"),
+    };
+    let digest = Sha256::digest(&data);
+    Ok(digest.iter().take(12).map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Write settings.json atomically, keeping the previous version as
+/// `settings.json.bak`.
+pub fn save_settings_with_backup(settings_file: &Path, settings: &Settings) -> Result<()> {
+    if let Some(dir) = settings_file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if settings_file.exists() {
+        std::fs::copy(settings_file, settings_file.with_extension("json.bak"))
+            .context("Failed to back up settings.json")?;
+    }
+    let json = serde_json::to_string_pretty(settings)?;
+    let tmp = settings_file.with_extension("tmp");
+    std::fs::write(&tmp, &json)?;
+    std::fs::rename(&tmp, settings_file).context("Failed to replace settings.json")?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -228,6 +393,15 @@ mod tests {
         assert!(settings.teams.is_none());
     }
 
+    #[test]
+    fn test_load_settings_invalid_json() {
+        let tmp = TempDir::new().unwrap();
+        let file = write_settings(tmp.path(), "{ \"agents\": ");
+        assert!(get_settings(&file).unwrap().agents.is_none());
+        assert!(load_settings(&file).is_err());
+        assert!(load_settings(&tmp.path().join("missing.json")).is_ok());
+    }
+
     #[test]
     fn test_get_settings_with_agents() {
         let tmp = TempDir::new().unwrap();
@@ -277,10 +451,77 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolve_working_dir() {
+        let mut agent = get_default_agent_from_models(&Settings::default());
+        let workspace = Path::new("/ws");
+        agent.working_directory = String::new();
+        assert_eq!(resolve_working_dir(&agent, "coder", workspace), PathBuf::from("/ws/coder"));
+        agent.working_directory = "projects/app".to_string();
+        assert_eq!(resolve_working_dir(&agent, "coder", workspace), PathBuf::from("/ws/projects/app"));
+        agent.working_directory = "/srv/app".to_string();
+        assert_eq!(resolve_working_dir(&agent, "coder", workspace), PathBuf::from("/srv/app"));
+    }
+
     #[test]
     fn test_get_teams_empty() {
         let settings = Settings::default();
         let teams = get_teams(&settings);
         assert!(teams.is_empty());
     }
+
+    #[test]
+    fn test_validate_settings() {
+        let settings: Settings = serde_json::from_str(
+            r#"{
+                "channels": { "enabled": ["discord", "irc"] },
+                "agents": {
+                    "coder": { "name": "Coder", "provider": "anthropic", "model": "opus", "working_directory": "/tmp/coder" },
+                    "bad": { "name": "", "provider": "gemini", "model": "x", "working_directory": "/tmp/bad",
+                             "reset_policy": "cron:not a cron", "timezone": "Mars/Base", "reset_hour": 30 }
+                },
+                "teams": {
+                    "dev": { "name": "Dev", "agents": ["coder", "ghost"], "leader_agent": "writer" },
+                    "coder": { "name": "Clash", "agents": ["coder"], "leader_agent": "coder" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let errors = validate_settings(&settings);
+        for expected in [
+            "channels.enabled: unknown channel 'irc'",
+            "agents.bad.name",
+            "agents.bad.provider: unknown provider 'gemini'",
+            "agents.bad.reset_policy",
+            "agents.bad.reset_hour",
+            "agents.bad.timezone",
+            "teams.dev.agents: unknown agent 'ghost'",
+            "teams.dev.leader_agent",
+            "teams.coder: ID is already used by an agent",
+        ] {
+            assert!(errors.iter().any(|e| e.starts_with(expected)), "missing {}: {:?}", expected, errors);
+        }
+        assert_eq!(errors.len(), 9, "{:?}", errors);
+        assert!(validate_settings(&Settings::default()).is_empty());
+    }
+
+    #[test]
+    fn test_save_settings_with_backup() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("settings.json");
+        let empty_revision = settings_revision(&file).unwrap();
+
+        save_settings_with_backup(&file, &Settings::default()).unwrap();
+        assert!(!file.with_extension("json.bak").exists());
+        let first_revision = settings_revision(&file).unwrap();
+        assert_ne!(first_revision, empty_revision);
+
+        let settings = Settings {
+            skills: Some(HashMap::new()),
+            ..Default::default()
+        };
+        save_settings_with_backup(&file, &settings).unwrap();
+        assert_eq!(std::fs::read_to_string(file.with_extension("json.bak")).unwrap(), "{}");
+        assert_ne!(settings_revision(&file).unwrap(), first_revision);
+    }
 }
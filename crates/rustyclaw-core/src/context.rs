@@ -1,9 +1,10 @@
-use std::collections::HashMap;
 use std::path::Path;
 
+use chrono_tz::Tz;
+
 use crate::skills::{self, Skill};
+use crate::timezone;
 use crate::transcript;
-use crate::types::SkillOverride;
 
 /// Maximum characters of transcript history to include in context.
 pub const MAX_TRANSCRIPT_CONTEXT_CHARS: usize = 8000;
@@ -42,12 +43,16 @@ fn read_optional_file_capped(path: &Path, max_chars: usize) -> Option<String> {
 ///
 /// Returns a string to prepend to the user message, or empty string if no files
 /// have content. The format uses XML-style tags that all LLM providers understand.
+/// Daily notes and transcripts are resolved by date in the agent's timezone `tz`.
+/// `skills` should already be filtered for eligibility; they are listed as an
+/// index, with full instructions only for those whose triggers match `message`.
 pub fn build_context_preamble(
     agent_dir: &Path,
     _agent_id: &str,
     max_transcript_chars: usize,
-    skill_dirs: &[&Path],
-    skill_overrides: &HashMap<String, SkillOverride>,
+    tz: Tz,
+    skills: &[Skill],
+    message: Option<&str>,
 ) -> String {
     let rustyclaw_dir = agent_dir.join(".rustyclaw");
 
@@ -72,7 +77,7 @@ pub fn build_context_preamble(
     }
 
     // Daily notes (today)
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let today = timezone::today_string(tz);
     let daily_path = rustyclaw_dir.join("memory").join(format!("{}.md", today));
     if let Some(content) = read_optional_file(&daily_path) {
         sections.push(format!(
@@ -85,6 +90,7 @@ pub fn build_context_preamble(
     let transcript_context = transcript::read_recent_transcript_context(
         &rustyclaw_dir.join("transcripts"),
         max_transcript_chars,
+        tz,
     );
     if !transcript_context.is_empty() {
         sections.push(format!(
@@ -94,16 +100,9 @@ pub fn build_context_preamble(
     }
 
     // Skills injection
-    if !skill_dirs.is_empty() {
-        let all_skills = skills::discover_skills(skill_dirs);
-        let eligible: Vec<Skill> = all_skills
-            .into_iter()
-            .filter(|s| skills::is_skill_eligible(s, skill_overrides))
-            .collect();
-        let skills_text = skills::format_skills_for_context(&eligible);
-        if !skills_text.is_empty() {
-            sections.push(format!("<skills>\n{}\n</skills>", skills_text));
-        }
+    let skills_text = skills::format_skills_for_context(skills, message);
+    if !skills_text.is_empty() {
+        sections.push(format!("<skills>\n{}\n</skills>", skills_text));
     }
 
     if sections.is_empty() {
@@ -121,7 +120,7 @@ mod tests {
     #[test]
     fn test_empty_agent_dir() {
         let tmp = TempDir::new().unwrap();
-        let result = build_context_preamble(tmp.path(), "test-agent", 8000, &[], &HashMap::new());
+        let result = build_context_preamble(tmp.path(), "test-agent", 8000, Tz::UTC, &[], None);
         assert!(result.is_empty());
     }
 
@@ -132,7 +131,7 @@ mod tests {
         std::fs::create_dir_all(&rustyclaw).unwrap();
         std::fs::write(rustyclaw.join("IDENTITY.md"), "I am a test agent").unwrap();
 
-        let result = build_context_preamble(tmp.path(), "test-agent", 8000, &[], &HashMap::new());
+        let result = build_context_preamble(tmp.path(), "test-agent", 8000, Tz::UTC, &[], None);
         assert!(result.contains("<context>"));
         assert!(result.contains("<identity>"));
         assert!(result.contains("I am a test agent"));
@@ -148,7 +147,7 @@ mod tests {
         std::fs::write(rustyclaw.join("IDENTITY.md"), "").unwrap();
         std::fs::write(rustyclaw.join("USER.md"), "  \n  ").unwrap();
 
-        let result = build_context_preamble(tmp.path(), "test-agent", 8000, &[], &HashMap::new());
+        let result = build_context_preamble(tmp.path(), "test-agent", 8000, Tz::UTC, &[], None);
         assert!(result.is_empty());
     }
 
@@ -162,7 +161,7 @@ mod tests {
         let large_content = "x".repeat(20_000);
         std::fs::write(rustyclaw.join("MEMORY.md"), &large_content).unwrap();
 
-        let result = build_context_preamble(tmp.path(), "test-agent", 8000, &[], &HashMap::new());
+        let result = build_context_preamble(tmp.path(), "test-agent", 8000, Tz::UTC, &[], None);
         assert!(result.contains("<memory>"));
         assert!(result.contains("[truncated, 20000 chars total]"));
     }
@@ -179,14 +178,14 @@ mod tests {
         std::fs::write(rustyclaw.join("TOOLS.md"), "tools content").unwrap();
         std::fs::write(rustyclaw.join("MEMORY.md"), "memory content").unwrap();
 
-        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let today = timezone::today_string(Tz::UTC);
         std::fs::write(
             rustyclaw.join("memory").join(format!("{}.md", today)),
             "daily notes",
         )
         .unwrap();
 
-        let result = build_context_preamble(tmp.path(), "test-agent", 8000, &[], &HashMap::new());
+        let result = build_context_preamble(tmp.path(), "test-agent", 8000, Tz::UTC, &[], None);
         assert!(result.contains("<identity>"));
         assert!(result.contains("<user>"));
         assert!(result.contains("<tools>"));
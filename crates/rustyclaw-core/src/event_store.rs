@@ -0,0 +1,347 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+
+/// Start a new segment once the current one reaches this size.
+pub const DEFAULT_SEGMENT_BYTES: u64 = 4 * 1024 * 1024;
+/// Keep at most this many segments.
+pub const DEFAULT_MAX_SEGMENTS: usize = 16;
+/// Delete segments last written more than this long ago.
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(7 * 24 * 3600);
+
+const LOCK_FILE: &str = "events.lock";
+
+/// Append-only event log in `events_dir`: JSONL segments named
+/// `events-<first seq>.jsonl`. Every event gets a `seq` field that
+/// increases by one per event across all writer processes. The last
+/// assigned sequence number lives in `events.lock`, which writers hold an
+/// exclusive lock on while appending.
+#[derive(Debug, Clone)]
+pub struct EventStore {
+    dir: PathBuf,
+    pub segment_bytes: u64,
+    pub max_segments: usize,
+    pub retention: Duration,
+}
+
+/// One event as stored: its sequence number and the raw JSON line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredEvent {
+    pub seq: u64,
+    pub json: String,
+}
+
+#[derive(Deserialize)]
+struct SeqOnly {
+    seq: u64,
+}
+
+fn segment_name(first_seq: u64) -> String {
+    format!("events-{:020}.jsonl", first_seq)
+}
+
+fn parse_segment_name(name: &str) -> Option<u64> {
+    name.strip_prefix("events-")?.strip_suffix(".jsonl")?.parse().ok()
+}
+
+impl EventStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            segment_bytes: DEFAULT_SEGMENT_BYTES,
+            max_segments: DEFAULT_MAX_SEGMENTS,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    /// Segments as `(first_seq, path)`, oldest first.
+    fn segments(&self) -> Vec<(u64, PathBuf)> {
+        let mut segments: Vec<(u64, PathBuf)> = std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| {
+                let first = parse_segment_name(&e.file_name().to_string_lossy())?;
+                Some((first, e.path()))
+            })
+            .collect();
+        segments.sort();
+        segments
+    }
+
+    /// Highest sequence number written so far (0 for an empty log).
+    pub fn last_seq(&self) -> u64 {
+        let from_lock = std::fs::read_to_string(self.dir.join(LOCK_FILE))
+            .ok()
+            .and_then(|s| s.trim().parse().ok());
+        from_lock.unwrap_or_else(|| self.scan_last_seq())
+    }
+
+    /// Last sequence number found in the segments themselves, used when
+    /// the lock file is missing.
+    fn scan_last_seq(&self) -> u64 {
+        let Some((first, _)) = self.segments().pop() else {
+            return 0;
+        };
+        let mut tail = self.tail(first.saturating_sub(1));
+        tail.read_new().last().map(|e| e.seq).unwrap_or(first.saturating_sub(1))
+    }
+
+    /// Append an event object, adding its `seq`. Returns the sequence number.
+    pub fn append(&self, mut event: serde_json::Value) -> anyhow::Result<u64> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut lock = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.dir.join(LOCK_FILE))?;
+        lock.lock()?;
+
+        let mut counter = String::new();
+        lock.read_to_string(&mut counter)?;
+        let seq = match counter.trim().parse::<u64>() {
+            Ok(n) => n,
+            Err(_) => self.scan_last_seq(),
+        } + 1;
+        // Record the number before appending, so a crash leaves a gap
+        // rather than a duplicate
+        lock.set_len(0)?;
+        lock.seek(SeekFrom::Start(0))?;
+        write!(lock, "{}", seq)?;
+
+        let obj = event
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("Event must be a JSON object"))?;
+        obj.insert("seq".to_string(), serde_json::json!(seq));
+        let line = serde_json::to_string(&event)? + "\n";
+
+        let segment = match self.segments().pop() {
+            Some((_, path))
+                if std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0) < self.segment_bytes =>
+            {
+                path
+            }
+            _ => {
+                let path = self.dir.join(segment_name(seq));
+                self.apply_retention();
+                path
+            }
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(&segment)?;
+        file.write_all(line.as_bytes())?;
+
+        lock.unlock()?;
+        Ok(seq)
+    }
+
+    /// Drop old segments, always leaving room for the one being started.
+    fn apply_retention(&self) {
+        let segments = self.segments();
+        let cutoff = SystemTime::now().checked_sub(self.retention);
+        let excess = (segments.len() + 1).saturating_sub(self.max_segments);
+        for (i, (_, path)) in segments.iter().enumerate() {
+            let expired = cutoff.is_some_and(|cutoff| {
+                std::fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|modified| modified < cutoff)
+            });
+            if i < excess || expired {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    /// A cursor that reads events with `seq > since`.
+    pub fn tail(&self, since: u64) -> EventTail {
+        EventTail {
+            store: self.clone(),
+            seq: since,
+            position: None,
+        }
+    }
+
+    /// All retained events with `seq > since`, oldest first.
+    pub fn read_since(&self, since: u64) -> Vec<StoredEvent> {
+        self.tail(since).read_new()
+    }
+}
+
+/// Reads new events as they are appended, remembering the segment and
+/// byte offset so each call only reads what was added since the last one.
+#[derive(Debug, Clone)]
+pub struct EventTail {
+    store: EventStore,
+    /// Sequence number of the last event returned
+    pub seq: u64,
+    /// `(first_seq of segment, byte offset)` of the next unread line
+    position: Option<(u64, u64)>,
+}
+
+impl EventTail {
+    pub fn read_new(&mut self) -> Vec<StoredEvent> {
+        let segments = self.store.segments();
+        // Resume in the current segment, or (fresh or after it was deleted)
+        // in the last segment that can hold `seq + 1`
+        let start = match self.position {
+            Some((current, _)) if segments.iter().any(|(f, _)| *f == current) => current,
+            _ => {
+                self.position = None;
+                segments
+                    .iter()
+                    .map(|(f, _)| *f)
+                    .rfind(|f| *f <= self.seq + 1)
+                    .unwrap_or(0)
+            }
+        };
+
+        let mut events = Vec::new();
+        for (first, path) in segments.iter().filter(|(f, _)| *f >= start) {
+            let offset = match self.position {
+                Some((current, offset)) if current == *first => offset,
+                _ => 0,
+            };
+            let offset = read_segment(path, offset, |line| {
+                if let Ok(SeqOnly { seq }) = serde_json::from_str(line) {
+                    if seq > self.seq {
+                        self.seq = seq;
+                        events.push(StoredEvent { seq, json: line.to_string() });
+                    }
+                }
+            });
+            self.position = Some((*first, offset));
+        }
+        events
+    }
+}
+
+/// Call `f` for every complete line from `offset`; returns the offset just
+/// past the last complete line, so a half-written line is read next time.
+fn read_segment(path: &Path, mut offset: u64, mut f: impl FnMut(&str)) -> u64 {
+    let Ok(mut file) = File::open(path) else {
+        return offset;
+    };
+    if file.seek(SeekFrom::Start(offset)).is_err() {
+        return offset;
+    }
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(n) if n > 0 && line.ends_with('\n') => {
+                offset += n as u64;
+                f(line.trim_end());
+            }
+            _ => break,
+        }
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_assigns_sequence_numbers() {
+        let tmp = TempDir::new().unwrap();
+        let store = EventStore::new(tmp.path());
+        assert_eq!(store.last_seq(), 0);
+
+        for i in 1..=3 {
+            let seq = store.append(serde_json::json!({"type": "test", "n": i})).unwrap();
+            assert_eq!(seq, i);
+        }
+        assert_eq!(store.last_seq(), 3);
+
+        let events = store.read_since(1);
+        assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2, 3]);
+        let parsed: serde_json::Value = serde_json::from_str(&events[0].json).unwrap();
+        assert_eq!(parsed["seq"], 2);
+        assert_eq!(parsed["n"], 2);
+
+        // Losing the lock file falls back to scanning the segments
+        std::fs::remove_file(tmp.path().join(LOCK_FILE)).unwrap();
+        assert_eq!(store.append(serde_json::json!({"type": "test"})).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_concurrent_appends_get_unique_sequence_numbers() {
+        let tmp = TempDir::new().unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let dir = tmp.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let store = EventStore::new(&dir);
+                    for _ in 0..25 {
+                        store.append(serde_json::json!({"type": "test"})).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let seqs: Vec<u64> = EventStore::new(tmp.path())
+            .read_since(0)
+            .iter()
+            .map(|e| e.seq)
+            .collect();
+        assert_eq!(seqs, (1..=100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rotation_and_retention() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = EventStore::new(tmp.path());
+        store.segment_bytes = 100;
+        store.max_segments = 3;
+
+        for _ in 0..20 {
+            store
+                .append(serde_json::json!({"type": "test", "pad": "x".repeat(60)}))
+                .unwrap();
+        }
+        let segments = store.segments();
+        assert_eq!(segments.len(), 3);
+        assert!(segments[0].0 > 1);
+
+        // Events from deleted segments are gone; the rest are contiguous
+        let events = store.read_since(0);
+        assert_eq!(events.last().unwrap().seq, 20);
+        assert_eq!(events.first().unwrap().seq, segments[0].0);
+    }
+
+    #[test]
+    fn test_tail_reads_only_new_events() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = EventStore::new(tmp.path());
+        store.segment_bytes = 100;
+
+        store.append(serde_json::json!({"type": "a"})).unwrap();
+        let mut tail = store.tail(store.last_seq());
+        assert!(tail.read_new().is_empty());
+
+        for _ in 0..5 {
+            store
+                .append(serde_json::json!({"type": "b", "pad": "x".repeat(60)}))
+                .unwrap();
+        }
+        let seqs: Vec<u64> = tail.read_new().iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![2, 3, 4, 5, 6]);
+        assert!(tail.read_new().is_empty());
+
+        // A partially written line is left for the next read
+        let (_, last) = store.segments().pop().unwrap();
+        let mut f = OpenOptions::new().append(true).open(&last).unwrap();
+        write!(f, "{{\"seq\":7,\"type\":\"c\"").unwrap();
+        assert!(tail.read_new().is_empty());
+        writeln!(f, "}}").unwrap();
+        assert_eq!(tail.read_new()[0].seq, 7);
+    }
+}
@@ -13,6 +13,18 @@ pub enum FailoverReason {
     Unknown,
 }
 
+impl FailoverReason {
+    /// Short snake_case name, used as a metrics label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailoverReason::RateLimit => "rate_limit",
+            FailoverReason::Auth => "auth",
+            FailoverReason::Timeout => "timeout",
+            FailoverReason::Unknown => "unknown",
+        }
+    }
+}
+
 /// Cooldown entry for a specific provider:model combination.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CooldownEntry {
@@ -0,0 +1,500 @@
+//! Markdown conversion and message splitting for chat channels.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+static HEADING_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^#{1,6}\s+(.+)$").unwrap());
+static HEADING_LEVEL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(#{1,6})\s+(.+)$").unwrap());
+static DEEP_HEADING_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^#{4,}\s+(.+)$").unwrap());
+static CODE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`]+)`").unwrap());
+static LINK_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"!?\[([^\]]+)\]\(([^)\s]+)\)").unwrap());
+static BOLD_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\*\*(.+?)\*\*").unwrap());
+static STRIKE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"~~(.+?)~~").unwrap());
+/// `*italic*` or `_italic_`; underscores must sit on word boundaries.
+static ITALIC_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(^|[^\w*])\*([^*\s][^*]*?)\*|(^|[^\w])_([^_\s][^_]*?)_([^\w]|$)").unwrap()
+});
+/// `*italic*` only; Slack already uses `_` for italics.
+static STAR_ITALIC_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(^|[^\w*])\*([^*\s][^*]*?)\*").unwrap());
+
+/// A unit of text that the splitter keeps together when it can.
+struct Block {
+    /// Opening fence line (e.g. "```rust") for fenced code blocks.
+    fence: Option<String>,
+    lines: Vec<String>,
+}
+
+impl Block {
+    fn render(&self) -> String {
+        match &self.fence {
+            Some(open) => format!("{}\n{}\n```", open, self.lines.join("\n")),
+            None => self.lines.join("\n"),
+        }
+    }
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+/// Break markdown into fenced code blocks and single lines.
+fn parse_blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if is_fence(line) {
+            let mut body = Vec::new();
+            for inner in lines.by_ref() {
+                if is_fence(inner) {
+                    break;
+                }
+                body.push(inner.to_string());
+            }
+            blocks.push(Block {
+                fence: Some(line.trim_start().to_string()),
+                lines: body,
+            });
+        } else {
+            blocks.push(Block {
+                fence: None,
+                lines: vec![line.to_string()],
+            });
+        }
+    }
+    blocks
+}
+
+/// Hard-split a single over-long line at whitespace, respecting UTF-8 boundaries.
+fn split_line(line: &str, max_len: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while rest.len() > max_len {
+        let mut end = max_len;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map(char::len_utf8).unwrap_or(1);
+        }
+        let cut = match rest[..end].rfind(' ') {
+            Some(i) if i > 0 => i,
+            _ => end,
+        };
+        pieces.push(rest[..cut].to_string());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces
+}
+
+/// Pack lines into chunks of at most `max_len` bytes, wrapping each chunk in
+/// the fence when `fence` is set.
+fn pack_lines(lines: &[String], fence: Option<&str>, max_len: usize, out: &mut Vec<String>) {
+    // Room taken by "```lang\n" and "\n```"
+    let overhead = fence.map(|f| f.len() + 5).unwrap_or(0);
+    let budget = max_len.saturating_sub(overhead).max(1);
+    let wrap = |body: &str| match fence {
+        Some(f) => format!("{}\n{}\n```", f, body),
+        None => body.to_string(),
+    };
+
+    let mut current = String::new();
+    for line in lines {
+        for piece in split_line(line, budget) {
+            if !current.is_empty() && current.len() + 1 + piece.len() > budget {
+                out.push(wrap(&current));
+                current.clear();
+            }
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() || out.is_empty() {
+        out.push(wrap(&current));
+    }
+}
+
+/// Split markdown into chunks of at most `max_len` bytes.
+///
+/// Fenced code blocks are kept whole when they fit in one chunk. Larger
+/// blocks are split between lines, and every piece is re-wrapped in the
+/// original fence so each message renders on its own.
+pub fn split_formatted(text: &str, max_len: usize) -> Vec<String> {
+    if text.len() <= max_len {
+        return vec![text.to_string()];
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for block in parse_blocks(text) {
+        let rendered = block.render();
+        if current.is_empty() && rendered.len() <= max_len {
+            current = rendered;
+        } else if !current.is_empty() && current.len() + 1 + rendered.len() <= max_len {
+            current.push('\n');
+            current.push_str(&rendered);
+        } else {
+            if !current.is_empty() {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if rendered.len() <= max_len {
+                current = rendered;
+            } else {
+                pack_lines(&block.lines, block.fence.as_deref(), max_len, &mut chunks);
+            }
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks.retain(|c| !c.trim().is_empty());
+    chunks
+}
+
+/// Split markdown so that each chunk, once rendered, fits in `max_len` bytes.
+///
+/// Rendering can grow text (HTML escaping), so chunks that still overflow
+/// are split again with a smaller source budget. Returns the markdown
+/// chunks; callers render them and can fall back to the source.
+pub fn split_rendered(text: &str, max_len: usize, render: impl Fn(&str) -> String) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut pending = vec![(text.to_string(), max_len)];
+    while let Some((source, budget)) = pending.pop() {
+        if render(&source).len() <= max_len || budget <= 64 {
+            out.push(source);
+            continue;
+        }
+        let pieces = split_formatted(&source, budget / 2);
+        // Push in reverse so pieces come back out in order
+        for piece in pieces.into_iter().rev() {
+            pending.push((piece, budget / 2));
+        }
+    }
+    out
+}
+
+/// Adapt agent markdown for Discord.
+///
+/// Discord renders most markdown already. Deep headings become bold,
+/// tables go into code blocks so columns line up, and `@everyone`/`@here`
+/// are defused. Code blocks are left untouched.
+pub fn to_discord_markdown(md: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut in_fence = false;
+    let mut in_table = false;
+
+    for line in md.lines() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            out.push(line.to_string());
+            continue;
+        }
+        if in_fence {
+            out.push(line.to_string());
+            continue;
+        }
+
+        let is_table_row = line.trim_start().starts_with('|');
+        if is_table_row != in_table {
+            out.push("```".to_string());
+            in_table = is_table_row;
+        }
+        if in_table {
+            out.push(line.to_string());
+            continue;
+        }
+
+        let line = DEEP_HEADING_RE.replace(line, "**$1**");
+        let line = line
+            .replace("@everyone", "@\u{200B}everyone")
+            .replace("@here", "@\u{200B}here");
+        out.push(line);
+    }
+    if in_table {
+        out.push("```".to_string());
+    }
+    out.join("\n")
+}
+
+/// Escape text for Telegram's HTML parse mode.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Convert inline markdown (code, links, bold, italic, strikethrough) on one line.
+fn inline_to_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for caps in CODE_RE.captures_iter(text) {
+        let m = caps.get(0).unwrap();
+        out += &inline_styles_to_html(&text[last..m.start()]);
+        out += &format!("<code>{}</code>", escape_html(&caps[1]));
+        last = m.end();
+    }
+    out += &inline_styles_to_html(&text[last..]);
+    out
+}
+
+fn inline_styles_to_html(text: &str) -> String {
+    let escaped = escape_html(text);
+
+    let s = LINK_RE.replace_all(&escaped, r#"<a href="$2">$1</a>"#);
+    let s = BOLD_RE.replace_all(&s, "<b>$1</b>");
+    let s = ITALIC_RE.replace_all(&s, |c: &regex::Captures| {
+        if let Some(body) = c.get(2) {
+            format!("{}<i>{}</i>", &c[1], body.as_str())
+        } else {
+            format!("{}<i>{}</i>{}", &c[3], &c[4], &c[5])
+        }
+    });
+    STRIKE_RE.replace_all(&s, "<s>$1</s>").to_string()
+}
+
+/// Convert agent markdown to Telegram's HTML parse mode.
+///
+/// Handles fenced and inline code, headings, block quotes, links, bold,
+/// italic and strikethrough. Everything else is escaped as plain text.
+pub fn to_telegram_html(md: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut quote: Vec<String> = Vec::new();
+
+    let flush_quote = |quote: &mut Vec<String>, out: &mut Vec<String>| {
+        if !quote.is_empty() {
+            out.push(format!("<blockquote>{}</blockquote>", quote.join("\n")));
+            quote.clear();
+        }
+    };
+
+    for block in parse_blocks(md) {
+        if let Some(open) = &block.fence {
+            flush_quote(&mut quote, &mut out);
+            let lang = open.trim_start_matches('`').trim();
+            let body = escape_html(&block.lines.join("\n"));
+            if lang.is_empty() {
+                out.push(format!("<pre>{}</pre>", body));
+            } else {
+                out.push(format!(
+                    "<pre><code class=\"language-{}\">{}</code></pre>",
+                    escape_html(lang),
+                    body
+                ));
+            }
+            continue;
+        }
+
+        let line = &block.lines[0];
+        if let Some(rest) = line.strip_prefix('>') {
+            quote.push(inline_to_html(rest.trim_start()));
+            continue;
+        }
+        flush_quote(&mut quote, &mut out);
+        match HEADING_RE.captures(line) {
+            Some(caps) => out.push(format!("<b>{}</b>", inline_to_html(&caps[1]))),
+            None => out.push(inline_to_html(line)),
+        }
+    }
+    flush_quote(&mut quote, &mut out);
+    out.join("\n")
+}
+
+/// Convert agent markdown to the HTML subset Matrix clients render in
+/// `formatted_body`. Unlike Telegram, line breaks need explicit `<br>`.
+pub fn to_matrix_html(md: &str) -> String {
+    // (html, is_block): block elements carry their own line breaks
+    let mut out: Vec<(String, bool)> = Vec::new();
+    let mut quote: Vec<String> = Vec::new();
+
+    let flush_quote = |quote: &mut Vec<String>, out: &mut Vec<(String, bool)>| {
+        if !quote.is_empty() {
+            out.push((format!("<blockquote>{}</blockquote>", quote.join("<br>")), true));
+            quote.clear();
+        }
+    };
+
+    for block in parse_blocks(md) {
+        if let Some(open) = &block.fence {
+            flush_quote(&mut quote, &mut out);
+            let lang = open.trim_start_matches('`').trim();
+            let body = escape_html(&block.lines.join("\n"));
+            let class = if lang.is_empty() {
+                String::new()
+            } else {
+                format!(" class=\"language-{}\"", escape_html(lang))
+            };
+            out.push((format!("<pre><code{}>{}</code></pre>", class, body), true));
+            continue;
+        }
+
+        let line = &block.lines[0];
+        if let Some(rest) = line.strip_prefix('>') {
+            quote.push(inline_to_html(rest.trim_start()));
+            continue;
+        }
+        flush_quote(&mut quote, &mut out);
+        match HEADING_LEVEL_RE.captures(line) {
+            Some(caps) => {
+                let level = caps[1].len();
+                out.push((format!("<h{}>{}</h{}>", level, inline_to_html(&caps[2]), level), true));
+            }
+            None => out.push((inline_to_html(line), false)),
+        }
+    }
+    flush_quote(&mut quote, &mut out);
+
+    let mut html = String::new();
+    for (i, (part, block)) in out.iter().enumerate() {
+        if i > 0 && !block && !out[i - 1].1 {
+            html.push_str("<br>");
+        }
+        html.push_str(part);
+    }
+    html
+}
+
+/// Convert agent markdown to Slack mrkdwn.
+///
+/// Slack uses `*bold*`, `_italic_`, `~strike~` and `<url|text>` links, and
+/// needs `&`, `<` and `>` escaped. Headings become bold lines. Code is
+/// escaped but otherwise left alone.
+pub fn to_slack_mrkdwn(md: &str) -> String {
+    let escape = |t: &str| t.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+
+    let mut out: Vec<String> = Vec::new();
+    for block in parse_blocks(md) {
+        if block.fence.is_some() {
+            out.push(format!("```\n{}\n```", escape(&block.lines.join("\n"))));
+            continue;
+        }
+        let line = &block.lines[0];
+        let (line, heading) = match HEADING_RE.captures(line) {
+            Some(caps) => (caps[1].to_string(), true),
+            None => (line.clone(), false),
+        };
+
+        let mut converted = String::new();
+        let mut last = 0;
+        for m in CODE_RE.find_iter(&line) {
+            converted += &slack_inline_styles(&escape(&line[last..m.start()]));
+            converted += &escape(m.as_str());
+            last = m.end();
+        }
+        converted += &slack_inline_styles(&escape(&line[last..]));
+        if heading {
+            converted = format!("*{}*", converted);
+        }
+        out.push(converted);
+    }
+    out.join("\n")
+}
+
+fn slack_inline_styles(text: &str) -> String {
+
+    let s = LINK_RE.replace_all(text, "<$2|$1>");
+    // Bold goes through a placeholder so the italic pass can't see it
+    let s = BOLD_RE.replace_all(&s, "\u{1}$1\u{1}");
+    let s = STAR_ITALIC_RE.replace_all(&s, "${1}_${2}_");
+    let s = STRIKE_RE.replace_all(&s, "~$1~");
+    s.replace('\u{1}', "*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_keeps_code_block_intact() {
+        let code = format!("```rust\n{}\n```", "let x = 1;\n".repeat(10).trim_end());
+        let text = format!("{}\n{}\n{}", "intro line ".repeat(10), code, "outro");
+        let chunks = split_formatted(&text, 200);
+        assert!(chunks.iter().all(|c| c.len() <= 200));
+        assert!(chunks.iter().any(|c| c.contains(&code)), "{:?}", chunks);
+    }
+
+    #[test]
+    fn test_split_rewraps_oversized_code_block() {
+        let body: Vec<String> = (0..40).map(|i| format!("line {}", i)).collect();
+        let text = format!("```py\n{}\n```", body.join("\n"));
+        let chunks = split_formatted(&text, 100);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 100);
+            assert!(chunk.starts_with("```py\n"));
+            assert!(chunk.ends_with("\n```"));
+        }
+    }
+
+    #[test]
+    fn test_split_long_line_on_char_boundary() {
+        let text = "é".repeat(150);
+        let chunks = split_formatted(&text, 101);
+        assert!(chunks.iter().all(|c| c.len() <= 101));
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn test_split_rendered_fits_after_escaping() {
+        let text = "<>".repeat(100);
+        let chunks = split_rendered(&text, 300, to_telegram_html);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| to_telegram_html(c).len() <= 300));
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn test_to_telegram_html() {
+        let md = "# Title\nUse **bold**, *it*, `a<b>` and [docs](https://x.io?a=1&b=2)\n> quoted\n```rust\nfn x() -> &str {}\n```";
+        let html = to_telegram_html(md);
+        assert_eq!(
+            html,
+            "<b>Title</b>\n\
+             Use <b>bold</b>, <i>it</i>, <code>a&lt;b&gt;</code> and <a href=\"https://x.io?a=1&amp;b=2\">docs</a>\n\
+             <blockquote>quoted</blockquote>\n\
+             <pre><code class=\"language-rust\">fn x() -&gt; &amp;str {}</code></pre>"
+        );
+    }
+
+    #[test]
+    fn test_telegram_html_leaves_snake_case() {
+        assert_eq!(to_telegram_html("call my_func_name now"), "call my_func_name now");
+        assert_eq!(to_telegram_html("def __init__(self)"), "def __init__(self)");
+        assert_eq!(to_telegram_html("an _emphasis_ here"), "an <i>emphasis</i> here");
+    }
+
+    #[test]
+    fn test_to_discord_markdown() {
+        let md = "#### Deep\n| a | b |\n|---|---|\nhi @everyone\n```\n| not a table |\n```";
+        assert_eq!(
+            to_discord_markdown(md),
+            "**Deep**\n```\n| a | b |\n|---|---|\n```\nhi @\u{200B}everyone\n```\n| not a table |\n```"
+        );
+    }
+
+    #[test]
+    fn test_to_slack_mrkdwn() {
+        let md = "## Plan\nUse **bold**, *it*, ~~old~~ and [docs](https://x.io)\n`a<b>` & more\n```\nif a < b {}\n```";
+        assert_eq!(
+            to_slack_mrkdwn(md),
+            "*Plan*\n\
+             Use *bold*, _it_, ~old~ and <https://x.io|docs>\n\
+             `a&lt;b&gt;` &amp; more\n\
+             ```\nif a &lt; b {}\n```"
+        );
+    }
+    #[test]
+    fn test_to_matrix_html() {
+        let md = "## Plan\nline **one**\nline two\n> quoted\n```rust\nlet a = 1 < 2;\n```\nafter";
+        assert_eq!(
+            to_matrix_html(md),
+            "<h2>Plan</h2>line <b>one</b><br>line two\
+             <blockquote>quoted</blockquote>\
+             <pre><code class=\"language-rust\">let a = 1 &lt; 2;</code></pre>after"
+        );
+    }
+}
@@ -4,11 +4,19 @@ pub mod models;
 pub mod routing;
 pub mod pairing;
 pub mod logging;
+pub mod log_files;
+pub mod event_store;
+pub mod metrics;
 pub mod agent_setup;
 pub mod transcript;
+pub mod chat_history;
 pub mod context;
 pub mod session;
 pub mod compaction;
 pub mod failover;
 pub mod skills;
+pub mod skill_packages;
 pub mod smart_routing;
+pub mod timezone;
+pub mod channels;
+pub mod format;
@@ -0,0 +1,474 @@
+//! JSON log files in `logs/`: one file per component, rotated by size and
+//! time, plus parsing and filtering for `rustyclaw logs`.
+//!
+//! Each line is a [`LogRecord`]. The current file is `<component>.log`.
+//! Rotated files are renamed to `<component>.log.<YYYYMMDD-HHMMSS>`, so
+//! `tail -f logs/*.log` only follows the current files.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::types::LoggingConfig;
+
+const DEFAULT_MAX_SIZE_MB: u64 = 10;
+const DEFAULT_RETENTION_DAYS: u64 = 7;
+const DEFAULT_MAX_FILES: usize = 10;
+
+// ─── Records ───────────────────────────────────────────────────────────────
+
+/// One log line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// RFC 3339 time in UTC, with milliseconds
+    pub ts: String,
+    pub level: String,
+    pub component: String,
+    /// Module that logged the line, e.g. `rustyclaw_queue::processor`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub module: String,
+    pub message: String,
+    /// Structured context such as `agent_id`, `message_id` and `conversation_id`
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+}
+
+/// Current time in the format used for `ts`.
+pub fn timestamp() -> String {
+    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+impl LogRecord {
+    /// Parse a JSON line. Lines in the old `[ts] [LEVEL] message` format are
+    /// also accepted, so files written before the switch stay searchable.
+    pub fn parse(line: &str, component: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            return serde_json::from_str(line).ok();
+        }
+        let rest = line.strip_prefix('[')?;
+        let (ts, rest) = rest.split_once("] [")?;
+        let (level, message) = rest.split_once("] ")?;
+        DateTime::parse_from_rfc3339(ts).ok()?;
+        Some(Self {
+            ts: ts.to_string(),
+            level: level.to_string(),
+            component: component.to_string(),
+            module: String::new(),
+            message: message.to_string(),
+            fields: Map::new(),
+        })
+    }
+
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.ts)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    }
+
+    /// The `[ts] [LEVEL] message` line printed to the console.
+    pub fn console_line(&self) -> String {
+        let mut line = format!("[{}] [{}] {}", self.ts, self.level, self.message);
+        for (key, value) in &self.fields {
+            match value {
+                Value::String(s) => line.push_str(&format!(" {}={}", key, s)),
+                other => line.push_str(&format!(" {}={}", key, other)),
+            }
+        }
+        line
+    }
+}
+
+/// Rank of a level name; higher is more severe. Unknown names rank as INFO.
+pub fn level_rank(level: &str) -> u8 {
+    match level.to_ascii_uppercase().as_str() {
+        "TRACE" => 0,
+        "DEBUG" => 1,
+        "WARN" | "WARNING" => 3,
+        "ERROR" => 4,
+        _ => 2,
+    }
+}
+
+/// Parse `30s`, `15m`, `1h` or `2d` as a time that far back, or an RFC 3339 time.
+pub fn parse_since(value: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let Ok(amount) = amount.parse::<i64>() else {
+        bail!("Invalid time '{}': use e.g. 30m, 1h, 2d or an RFC 3339 time", value);
+    };
+    let delta = match unit {
+        "s" => chrono::Duration::seconds(amount),
+        "m" => chrono::Duration::minutes(amount),
+        "h" | "" => chrono::Duration::hours(amount),
+        "d" => chrono::Duration::days(amount),
+        _ => bail!("Invalid time unit in '{}': use s, m, h or d", value),
+    };
+    Ok(now - delta)
+}
+
+/// Filters for `rustyclaw logs`.
+#[derive(Debug, Default)]
+pub struct LogQuery {
+    pub since: Option<DateTime<Utc>>,
+    /// Minimum level, e.g. `WARN`
+    pub level: Option<String>,
+    /// Matches the `agent_id`, `from_agent` or `to_agent` field
+    pub agent: Option<String>,
+    /// Matched against the message and field values
+    pub grep: Option<Regex>,
+}
+
+impl LogQuery {
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.level.is_none() && self.agent.is_none() && self.grep.is_none()
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        if let Some(since) = self.since {
+            if record.time().is_none_or(|t| t < since) {
+                return false;
+            }
+        }
+        if let Some(level) = &self.level {
+            if level_rank(&record.level) < level_rank(level) {
+                return false;
+            }
+        }
+        if let Some(agent) = &self.agent {
+            let is_agent = ["agent_id", "from_agent", "to_agent"]
+                .iter()
+                .any(|key| record.fields.get(*key).and_then(Value::as_str) == Some(agent.as_str()));
+            if !is_agent {
+                return false;
+            }
+        }
+        if let Some(re) = &self.grep {
+            let in_fields = record.fields.values().any(|v| match v {
+                Value::String(s) => re.is_match(s),
+                other => re.is_match(&other.to_string()),
+            });
+            if !re.is_match(&record.message) && !in_fields {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// ─── Files ─────────────────────────────────────────────────────────────────
+
+/// The current log file of a component.
+pub fn log_path(logs_dir: &Path, component: &str) -> PathBuf {
+    logs_dir.join(format!("{}.log", component))
+}
+
+/// Rotated files of a component, oldest first.
+pub fn rotated_files(logs_dir: &Path, component: &str) -> Vec<PathBuf> {
+    let prefix = format!("{}.log.", component);
+    let mut files: Vec<PathBuf> = std::fs::read_dir(logs_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    // The suffix is a timestamp, so name order is age order
+    files.sort();
+    files
+}
+
+/// Components with a current or rotated log file in `logs_dir`.
+pub fn components(logs_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(logs_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    name.split_once(".log").map(|(c, _)| c.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Period {
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl Period {
+    fn key(self, t: DateTime<Utc>) -> String {
+        match self {
+            Period::Hourly => t.format("%Y-%m-%dT%H").to_string(),
+            Period::Daily => t.format("%Y-%m-%d").to_string(),
+            Period::Never => String::new(),
+        }
+    }
+}
+
+/// When to rotate and how many rotated files to keep.
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    max_bytes: u64,
+    period: Period,
+    retention: Duration,
+    max_files: usize,
+}
+
+impl Rotation {
+    pub fn from_config(config: Option<&LoggingConfig>) -> Self {
+        let config = config.cloned().unwrap_or_default();
+        Self {
+            max_bytes: config.max_size_mb.unwrap_or(DEFAULT_MAX_SIZE_MB).max(1) * 1024 * 1024,
+            period: match config.rotation.as_deref() {
+                Some("hourly") => Period::Hourly,
+                Some("never") => Period::Never,
+                _ => Period::Daily,
+            },
+            retention: Duration::from_secs(
+                config.retention_days.unwrap_or(DEFAULT_RETENTION_DAYS) * 24 * 60 * 60,
+            ),
+            max_files: config.max_files.unwrap_or(DEFAULT_MAX_FILES),
+        }
+    }
+}
+
+/// Append-only log file that rotates itself.
+///
+/// Other processes may append to the same file (`log()` from CLI commands),
+/// so its size is read from disk on every write rather than counted.
+pub struct RotatingFile {
+    logs_dir: PathBuf,
+    component: String,
+    rotation: Rotation,
+    file: Option<File>,
+    period_key: String,
+}
+
+impl RotatingFile {
+    pub fn new(logs_dir: &Path, component: &str, rotation: Rotation) -> Self {
+        let path = log_path(logs_dir, component);
+        // An existing file belongs to the period it was last written in
+        let modified = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(Utc::now);
+        Self {
+            logs_dir: logs_dir.to_path_buf(),
+            component: component.to_string(),
+            period_key: rotation.period.key(modified),
+            rotation,
+            file: None,
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        log_path(&self.logs_dir, &self.component)
+    }
+
+    /// Append one line, rotating first if it is due.
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let now = Utc::now();
+        let key = self.rotation.period.key(now);
+        let len = line.len() as u64 + 1;
+        let size = match std::fs::metadata(self.path()) {
+            Ok(meta) => meta.len(),
+            Err(_) => {
+                // Removed or moved away; don't keep writing to the old inode
+                self.file = None;
+                0
+            }
+        };
+        if size > 0 && (key != self.period_key || size + len > self.rotation.max_bytes) {
+            self.rotate(now)?;
+        }
+        self.period_key = key;
+
+        if self.file.is_none() {
+            std::fs::create_dir_all(&self.logs_dir)?;
+            self.file = Some(OpenOptions::new().create(true).append(true).open(self.path())?);
+        }
+        if let Some(f) = self.file.as_mut() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> std::io::Result<()> {
+        self.file = None;
+        let stamp = now.format("%Y%m%d-%H%M%S").to_string();
+        let mut target = self.logs_dir.join(format!("{}.log.{}", self.component, stamp));
+        let mut n = 1;
+        while target.exists() {
+            target = self.logs_dir.join(format!("{}.log.{}-{}", self.component, stamp, n));
+            n += 1;
+        }
+        std::fs::rename(self.path(), &target)?;
+        self.prune();
+        Ok(())
+    }
+
+    /// Delete rotated files past the retention period or the file limit.
+    fn prune(&self) {
+        let mut files = rotated_files(&self.logs_dir, &self.component);
+        let now = SystemTime::now();
+        files.retain(|path| {
+            let expired = std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| now.duration_since(t).ok())
+                .is_some_and(|age| age > self.rotation.retention);
+            if expired {
+                let _ = std::fs::remove_file(path);
+            }
+            !expired
+        });
+        let excess = files.len().saturating_sub(self.rotation.max_files);
+        for path in &files[..excess] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(level: &str, message: &str, fields: Value) -> LogRecord {
+        LogRecord {
+            ts: "2026-10-18T12:00:00.000Z".to_string(),
+            level: level.to_string(),
+            component: "queue".to_string(),
+            module: "rustyclaw_queue::processor".to_string(),
+            message: message.to_string(),
+            fields: fields.as_object().cloned().unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_json_and_legacy_lines() {
+        let r = record("INFO", "Routing", serde_json::json!({"agent_id": "coder"}));
+        let line = serde_json::to_string(&r).unwrap();
+        assert!(line.starts_with(r#"{"ts":"2026-10-18T12:00:00.000Z","level":"INFO""#));
+        assert_eq!(LogRecord::parse(&line, "queue"), Some(r));
+
+        let legacy = LogRecord::parse("[2026-10-18T12:00:00.000Z] [WARN] Something broke", "discord").unwrap();
+        assert_eq!(legacy.level, "WARN");
+        assert_eq!(legacy.component, "discord");
+        assert_eq!(legacy.message, "Something broke");
+
+        assert!(LogRecord::parse("not a log line", "queue").is_none());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = DateTime::parse_from_rfc3339("2026-10-18T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_since("1h", now).unwrap().to_rfc3339(), "2026-10-18T11:00:00+00:00");
+        assert_eq!(parse_since("30m", now).unwrap().to_rfc3339(), "2026-10-18T11:30:00+00:00");
+        assert_eq!(parse_since("2d", now).unwrap().to_rfc3339(), "2026-10-16T12:00:00+00:00");
+        assert_eq!(
+            parse_since("2026-10-01T00:00:00Z", now).unwrap().to_rfc3339(),
+            "2026-10-01T00:00:00+00:00"
+        );
+        assert!(parse_since("1w", now).is_err());
+        assert!(parse_since("soon", now).is_err());
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let r = record("WARN", "Primary model failed", serde_json::json!({"agent_id": "coder", "message_id": "m1"}));
+        assert!(LogQuery::default().matches(&r));
+        assert!(LogQuery { agent: Some("coder".into()), ..Default::default() }.matches(&r));
+        assert!(!LogQuery { agent: Some("reviewer".into()), ..Default::default() }.matches(&r));
+        assert!(LogQuery { level: Some("warn".into()), ..Default::default() }.matches(&r));
+        assert!(!LogQuery { level: Some("error".into()), ..Default::default() }.matches(&r));
+        assert!(LogQuery { grep: Some(Regex::new("model").unwrap()), ..Default::default() }.matches(&r));
+        assert!(LogQuery { grep: Some(Regex::new("^m1$").unwrap()), ..Default::default() }.matches(&r));
+        assert!(!LogQuery { grep: Some(Regex::new("timeout").unwrap()), ..Default::default() }.matches(&r));
+
+        let since = DateTime::parse_from_rfc3339("2026-10-18T12:30:00Z").unwrap().with_timezone(&Utc);
+        assert!(!LogQuery { since: Some(since), ..Default::default() }.matches(&r));
+    }
+
+    #[test]
+    fn test_rotates_by_size_and_prunes() {
+        let tmp = TempDir::new().unwrap();
+        let rotation = Rotation {
+            max_bytes: 100,
+            period: Period::Never,
+            retention: Duration::from_secs(3600),
+            max_files: 2,
+        };
+        let mut file = RotatingFile::new(tmp.path(), "queue", rotation);
+        let line = "x".repeat(59);
+        for _ in 0..5 {
+            file.write_line(&line).unwrap();
+        }
+        // Each file holds one 60-byte line; two rotated files are kept
+        assert_eq!(rotated_files(tmp.path(), "queue").len(), 2);
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap().len(), 60);
+        assert_eq!(components(tmp.path()), vec!["queue".to_string()]);
+    }
+
+    #[test]
+    fn test_rotation_counts_lines_from_other_writers() {
+        let tmp = TempDir::new().unwrap();
+        let rotation = Rotation {
+            max_bytes: 100,
+            period: Period::Never,
+            retention: Duration::from_secs(3600),
+            max_files: 2,
+        };
+        let mut file = RotatingFile::new(tmp.path(), "queue", rotation);
+        file.write_line("first").unwrap();
+        // A CLI process appends to the same file
+        let mut other = OpenOptions::new().append(true).open(file.path()).unwrap();
+        writeln!(other, "{}", "x".repeat(89)).unwrap();
+        file.write_line("second").unwrap();
+
+        let rotated = rotated_files(tmp.path(), "queue");
+        assert_eq!(rotated.len(), 1);
+        assert!(std::fs::read_to_string(&rotated[0]).unwrap().starts_with("first\n"));
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), "second\n");
+
+        // Moved away by someone else: the next line starts a new file
+        std::fs::remove_file(file.path()).unwrap();
+        file.write_line("third").unwrap();
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), "third\n");
+    }
+
+    #[test]
+    fn test_rotates_when_period_changes() {
+        let tmp = TempDir::new().unwrap();
+        let mut file = RotatingFile::new(tmp.path(), "heartbeat", Rotation::from_config(None));
+        file.write_line("first").unwrap();
+        file.period_key = "2000-01-01".to_string();
+        file.write_line("second").unwrap();
+        let rotated = rotated_files(tmp.path(), "heartbeat");
+        assert_eq!(rotated.len(), 1);
+        assert_eq!(std::fs::read_to_string(&rotated[0]).unwrap(), "first\n");
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), "second\n");
+    }
+}
@@ -1,67 +1,309 @@
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::panic::Location;
 use std::path::Path;
+use std::sync::{Mutex, OnceLock};
 
-use chrono::Utc;
-use rand::Rng;
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Registry;
 
-/// Log a message to console and append to the log file.
+use crate::config::{get_settings, Paths};
+use crate::event_store::EventStore;
+use crate::log_files::{level_rank, timestamp, LogRecord, RotatingFile, Rotation};
+use crate::types::LoggingConfig;
+
+/// Set once `init` has installed the JSON logger for this process.
+static INSTALLED: OnceLock<()> = OnceLock::new();
+
+/// A layer installed next to the JSON logger, such as trace export.
+pub type ExtraLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Install the structured logger for a long-running component.
+///
+/// Every `tracing` event, and every `log()` call, becomes one JSON line in
+/// `logs/<component>.log` carrying the fields of the spans it happened in
+/// (e.g. `agent_id`, `message_id`, `conversation_id`). Levels, rotation and
+/// retention come from the `logging` section of settings.json.
+pub fn init(component: &str, paths: &Paths) {
+    init_with(component, paths, Vec::new());
+}
+
+/// Like [`init`], with more layers seeing the same spans and events.
+pub fn init_with(component: &str, paths: &Paths, extra: Vec<ExtraLayer>) {
+    let config = get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.logging);
+    let logs_dir = paths
+        .log_file
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| paths.rustyclaw_home.join("logs"));
+    let layer = JsonLayer::new(
+        component,
+        RotatingFile::new(&logs_dir, component, Rotation::from_config(config.as_ref())),
+        Levels::from_config(config.as_ref()),
+    );
+    let mut layers: Vec<ExtraLayer> = vec![Box::new(layer)];
+    layers.extend(extra);
+    let subscriber = tracing_subscriber::registry().with(layers);
+    if tracing::subscriber::set_global_default(subscriber).is_ok() {
+        let _ = INSTALLED.set(());
+    }
+}
+
+/// Log a message to console and append it to the log file.
+///
+/// Once `init` has run this is a `tracing` event, so it picks up the
+/// current span's fields and goes to the component's rotating log file.
+/// Otherwise (CLI commands, tests) the JSON line is appended to `log_file`.
+#[track_caller]
 pub fn log(level: &str, message: &str, log_file: &Path) {
-    let timestamp = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
-    let log_message = format!("[{}] [{}] {}", timestamp, level, message);
-    println!("{}", log_message);
+    let module = module_from_file(Location::caller().file());
+    if INSTALLED.get().is_some() {
+        match level_rank(level) {
+            0 => tracing::trace!(module = %module, "{}", message),
+            1 => tracing::debug!(module = %module, "{}", message),
+            3 => tracing::warn!(module = %module, "{}", message),
+            4 => tracing::error!(module = %module, "{}", message),
+            _ => tracing::info!(module = %module, "{}", message),
+        }
+        return;
+    }
+
+    let record = LogRecord {
+        ts: timestamp(),
+        level: level.to_ascii_uppercase(),
+        component: log_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        module,
+        message: message.to_string(),
+        fields: Map::new(),
+    };
+    println!("{}", record.console_line());
 
     if let Some(dir) = log_file.parent() {
         let _ = std::fs::create_dir_all(dir);
     }
-    if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(log_file) {
-        let _ = writeln!(f, "{}", log_message);
+    if let (Ok(mut f), Ok(line)) = (
+        OpenOptions::new().create(true).append(true).open(log_file),
+        serde_json::to_string(&record),
+    ) {
+        let _ = writeln!(f, "{}", line);
     }
 }
 
-/// Emit a structured event for the team visualizer.
-/// Events are written as JSON files to events_dir, watched by the visualizer.
-/// Best-effort: never panics or breaks the caller.
-pub fn emit_event(event_type: &str, data: serde_json::Value, events_dir: &Path) {
-    let _ = (|| -> Result<(), Box<dyn std::error::Error>> {
-        std::fs::create_dir_all(events_dir)?;
-
-        let mut event = data;
-        if let Some(obj) = event.as_object_mut() {
-            obj.insert("type".to_string(), serde_json::json!(event_type));
-            obj.insert(
-                "timestamp".to_string(),
-                serde_json::json!(
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_millis() as u64
-                ),
-            );
+/// Module path for a source file, e.g. `crates/rustyclaw-queue/src/processor.rs`
+/// becomes `rustyclaw_queue::processor`.
+fn module_from_file(file: &str) -> String {
+    let file = format!("/{}", file.replace('\\', "/"));
+    let Some((krate, rest)) = file.rsplit_once("/src/") else {
+        return String::new();
+    };
+    let krate = krate.rsplit('/').next().unwrap_or_default().replace('-', "_");
+    let mut parts: Vec<&str> = rest.trim_end_matches(".rs").split('/').collect();
+    if matches!(parts.last(), Some(&"lib") | Some(&"main") | Some(&"mod")) {
+        parts.pop();
+    }
+    std::iter::once(krate.as_str())
+        .chain(parts)
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+// ─── JSON layer ────────────────────────────────────────────────────────────
+
+/// Level thresholds by module path prefix.
+struct Levels {
+    /// Threshold for Rusty Claw's own modules
+    default: LevelFilter,
+    /// `(module prefix, threshold)`, matched longest prefix first
+    overrides: Vec<(String, LevelFilter)>,
+}
+
+impl Levels {
+    fn from_config(config: Option<&LoggingConfig>) -> Self {
+        let parse = |s: &str| s.parse::<LevelFilter>().ok();
+        let default = config
+            .and_then(|c| c.level.as_deref())
+            .and_then(parse)
+            .unwrap_or(LevelFilter::INFO);
+        let mut overrides: Vec<(String, LevelFilter)> = config
+            .and_then(|c| c.levels.as_ref())
+            .map(|levels| {
+                levels
+                    .iter()
+                    .filter_map(|(module, level)| Some((module.replace('-', "_"), parse(level)?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        overrides.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Self { default, overrides }
+    }
+
+    fn threshold(&self, module: &str) -> LevelFilter {
+        self.overrides
+            .iter()
+            .find(|(prefix, _)| {
+                module == prefix
+                    || module
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(if module.starts_with("rustyclaw") {
+                self.default
+            } else {
+                // Dependencies only get a say when something is wrong
+                LevelFilter::WARN
+            })
+    }
+}
+
+/// Span fields, stored in the span's extensions.
+struct SpanFields(Map<String, Value>);
+
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+/// Writes each event as a JSON line to the component's log file and a
+/// readable line to stdout.
+struct JsonLayer {
+    component: String,
+    file: Mutex<RotatingFile>,
+    levels: Levels,
+}
+
+impl JsonLayer {
+    fn new(component: &str, file: RotatingFile, levels: Levels) -> Self {
+        Self {
+            component: component.to_string(),
+            file: Mutex::new(file),
+            levels,
+        }
+    }
+}
+
+impl<S> Layer<S> for JsonLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        let module = metadata.module_path().unwrap_or_else(|| metadata.target());
+        if metadata.is_span() {
+            return module.starts_with("rustyclaw");
+        }
+        // Events from `log()` carry their real module as a field; checked in on_event
+        module == "rustyclaw_core::logging" || *metadata.level() <= self.levels.threshold(module)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(fields));
         }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(SpanFields(fields)) = span.extensions_mut().get_mut::<SpanFields>() {
+                values.record(&mut FieldVisitor(fields));
+            }
+        }
+    }
 
-        let mut rng = rand::thread_rng();
-        let random_suffix: String = (0..6)
-            .map(|_| {
-                let idx = rng.gen_range(0..36);
-                if idx < 10 {
-                    (b'0' + idx) as char
-                } else {
-                    (b'a' + idx - 10) as char
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(SpanFields(span_fields)) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.clone());
                 }
-            })
-            .collect();
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis();
-        let filename = format!("{}-{}.json", now, random_suffix);
-
-        let content = serde_json::to_string(&event)? + "\n";
-        std::fs::write(events_dir.join(filename), content)?;
-        Ok(())
-    })();
+            }
+        }
+        event.record(&mut FieldVisitor(&mut fields));
+
+        let module = match fields.remove("module") {
+            Some(Value::String(module)) => module,
+            _ => metadata.module_path().unwrap_or_default().to_string(),
+        };
+        if *metadata.level() > self.levels.threshold(&module) {
+            return;
+        }
+        let message = match fields.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        let record = LogRecord {
+            ts: timestamp(),
+            level: metadata.level().to_string(),
+            component: self.component.clone(),
+            module,
+            message,
+            fields,
+        };
+        println!("{}", record.console_line());
+        if let (Ok(mut file), Ok(line)) = (self.file.lock(), serde_json::to_string(&record)) {
+            let _ = file.write_line(&line);
+        }
+    }
+}
+
+/// Emit a structured event for the team visualizer.
+/// Events are appended to the event log in events_dir (see `EventStore`),
+/// which the visualizer tails and replays.
+/// Best-effort: never panics or breaks the caller.
+pub fn emit_event(event_type: &str, data: serde_json::Value, events_dir: &Path) {
+    let mut event = data;
+    if let Some(obj) = event.as_object_mut() {
+        obj.insert("type".to_string(), serde_json::json!(event_type));
+        obj.insert(
+            "timestamp".to_string(),
+            serde_json::json!(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64
+            ),
+        );
+    }
+    let _ = EventStore::new(events_dir).append(event);
 }
 
 #[cfg(test)]
@@ -78,7 +320,9 @@ mod tests {
         assert!(log_file.exists());
 
         let content = std::fs::read_to_string(&log_file).unwrap();
-        assert!(content.contains("[INFO]"));
+        assert!(content.contains(r#""level":"INFO""#));
+        assert!(content.contains(r#""component":"test""#));
+        assert!(content.contains(r#""module":"rustyclaw_core::logging""#));
         assert!(content.contains("test message"));
     }
 
@@ -97,7 +341,7 @@ mod tests {
     }
 
     #[test]
-    fn test_emit_event_creates_file() {
+    fn test_emit_event_appends_to_log() {
         let tmp = TempDir::new().unwrap();
         let events_dir = tmp.path().join("events");
 
@@ -106,18 +350,73 @@ mod tests {
             serde_json::json!({"key": "value"}),
             &events_dir,
         );
+        emit_event("second", serde_json::json!({}), &events_dir);
 
-        assert!(events_dir.exists());
-        let files: Vec<_> = std::fs::read_dir(&events_dir)
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .collect();
-        assert_eq!(files.len(), 1);
+        let events = EventStore::new(&events_dir).read_since(0);
+        assert_eq!(events.len(), 2);
 
-        let content = std::fs::read_to_string(files[0].path()).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(content.trim()).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&events[0].json).unwrap();
         assert_eq!(parsed["type"], "test_event");
         assert_eq!(parsed["key"], "value");
+        assert_eq!(parsed["seq"], 1);
         assert!(parsed["timestamp"].is_number());
     }
+
+    #[test]
+    fn test_module_from_file() {
+        assert_eq!(module_from_file("crates/rustyclaw-queue/src/processor.rs"), "rustyclaw_queue::processor");
+        assert_eq!(module_from_file("crates/rustyclaw-core/src/lib.rs"), "rustyclaw_core");
+        assert_eq!(module_from_file("/build/crates/rustyclaw-cli/src/viz/mod.rs"), "rustyclaw_cli::viz");
+        assert_eq!(module_from_file("build.rs"), "");
+    }
+
+    #[test]
+    fn test_levels_longest_prefix_wins() {
+        let config = LoggingConfig {
+            level: Some("warn".to_string()),
+            levels: Some(
+                [
+                    ("rustyclaw_queue".to_string(), "info".to_string()),
+                    ("rustyclaw-queue::processor".to_string(), "debug".to_string()),
+                    ("hyper".to_string(), "error".to_string()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            ..Default::default()
+        };
+        let levels = Levels::from_config(Some(&config));
+        assert_eq!(levels.threshold("rustyclaw_core::routing"), LevelFilter::WARN);
+        assert_eq!(levels.threshold("rustyclaw_queue::invoke"), LevelFilter::INFO);
+        assert_eq!(levels.threshold("rustyclaw_queue::processor"), LevelFilter::DEBUG);
+        assert_eq!(levels.threshold("rustyclaw_queue_extra"), LevelFilter::WARN);
+        assert_eq!(levels.threshold("hyper::client"), LevelFilter::ERROR);
+        assert_eq!(levels.threshold("reqwest"), LevelFilter::WARN);
+    }
+
+    #[test]
+    fn test_json_layer_records_span_fields() {
+        let tmp = TempDir::new().unwrap();
+        let file = RotatingFile::new(tmp.path(), "queue", Rotation::from_config(None));
+        let layer = JsonLayer::new("queue", file, Levels::from_config(None));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("message", message_id = "m1", agent_id = tracing::field::Empty);
+            let _guard = span.enter();
+            span.record("agent_id", "coder");
+            tracing::info!(module = "rustyclaw_queue::processor", "Routing to agent");
+            tracing::debug!("filtered out at the default level");
+        });
+
+        let content = std::fs::read_to_string(tmp.path().join("queue.log")).unwrap();
+        assert_eq!(content.lines().count(), 1);
+        let record: LogRecord = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(record.level, "INFO");
+        assert_eq!(record.component, "queue");
+        assert_eq!(record.module, "rustyclaw_queue::processor");
+        assert_eq!(record.message, "Routing to agent");
+        assert_eq!(record.fields["message_id"], "m1");
+        assert_eq!(record.fields["agent_id"], "coder");
+    }
 }
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde::Serialize;
+use serde_json::Value;
+
+pub const HOUR_MS: u64 = 3_600_000;
+/// Data older than this is dropped; matches the event log retention.
+pub const MAX_AGE_MS: u64 = 7 * 24 * HOUR_MS;
+/// Latency samples kept per agent or model.
+const MAX_SAMPLES: usize = 10_000;
+
+/// Counters for one hour.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HourCounts {
+    /// Messages picked up by the queue processor
+    pub received: u64,
+    /// Responses sent back to a channel
+    pub responded: u64,
+    /// Agent steps that failed on every model
+    pub failures: u64,
+    /// Model invocations that failed (each starts a cooldown)
+    pub model_errors: u64,
+    /// Invocations of a fallback model
+    pub failovers: u64,
+    pub compactions: u64,
+}
+
+impl HourCounts {
+    fn add(&mut self, other: &HourCounts) {
+        self.received += other.received;
+        self.responded += other.responded;
+        self.failures += other.failures;
+        self.model_errors += other.model_errors;
+        self.failovers += other.failovers;
+        self.compactions += other.compactions;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Sample {
+    at: u64,
+    duration_ms: u64,
+    ok: bool,
+}
+
+#[derive(Debug, Clone)]
+struct ModelFailure {
+    at: u64,
+    key: String,
+    agent_id: String,
+    reason: String,
+    cooldown_until: Option<u64>,
+}
+
+/// Aggregates events from the event log into hourly counters and latency
+/// samples. Feed it every event in order with [`record`](Self::record).
+#[derive(Debug, Clone, Default)]
+pub struct MetricsAggregator {
+    /// Hour start (epoch ms) -> counters
+    hours: BTreeMap<u64, HourCounts>,
+    /// Agent ID -> whole-step durations
+    steps: HashMap<String, VecDeque<Sample>>,
+    /// `provider:model` -> single invocation durations
+    invocations: HashMap<String, VecDeque<Sample>>,
+    failures: VecDeque<ModelFailure>,
+    /// `(time, agent ID)` of each compaction
+    compactions: VecDeque<(u64, String)>,
+}
+
+/// p50/p95 latency for one agent or model. Failed attempts are counted but
+/// left out of the percentiles.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStats {
+    pub key: String,
+    pub count: u64,
+    pub failures: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+/// A model taken out of rotation after a failed invocation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CooldownPeriod {
+    /// `provider:model`
+    pub key: String,
+    pub agent_id: String,
+    pub reason: String,
+    pub started: u64,
+    pub until: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HourBucket {
+    pub start: u64,
+    #[serde(flatten)]
+    pub counts: HourCounts,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSnapshot {
+    pub since: u64,
+    pub until: u64,
+    pub totals: HourCounts,
+    /// One bucket per hour, oldest first, including empty hours
+    pub hours: Vec<HourBucket>,
+    pub agents: Vec<LatencyStats>,
+    pub models: Vec<LatencyStats>,
+    pub failures_by_reason: BTreeMap<String, u64>,
+    pub cooldowns: Vec<CooldownPeriod>,
+    pub compactions_by_agent: BTreeMap<String, u64>,
+}
+
+fn push_sample(samples: &mut VecDeque<Sample>, sample: Sample) {
+    samples.push_back(sample);
+    if samples.len() > MAX_SAMPLES {
+        samples.pop_front();
+    }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn latency_stats(samples: &HashMap<String, VecDeque<Sample>>, since: u64) -> Vec<LatencyStats> {
+    let mut stats: Vec<LatencyStats> = samples
+        .iter()
+        .filter_map(|(key, samples)| {
+            let recent: Vec<&Sample> = samples.iter().filter(|s| s.at >= since).collect();
+            if recent.is_empty() {
+                return None;
+            }
+            let mut durations: Vec<u64> =
+                recent.iter().filter(|s| s.ok).map(|s| s.duration_ms).collect();
+            durations.sort_unstable();
+            Some(LatencyStats {
+                key: key.clone(),
+                count: recent.len() as u64,
+                failures: recent.iter().filter(|s| !s.ok).count() as u64,
+                p50_ms: percentile(&durations, 0.5),
+                p95_ms: percentile(&durations, 0.95),
+            })
+        })
+        .collect();
+    stats.sort_by(|a, b| a.key.cmp(&b.key));
+    stats
+}
+
+impl MetricsAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one event. Events without a timestamp are ignored.
+    pub fn record(&mut self, event: &Value) {
+        let Some(at) = event.get("timestamp").and_then(Value::as_u64) else {
+            return;
+        };
+        let str_field = |key: &str| event.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+        let hour = self.hours.entry(at / HOUR_MS * HOUR_MS).or_default();
+
+        match event.get("type").and_then(Value::as_str).unwrap_or_default() {
+            "message_received" => hour.received += 1,
+            "response_ready" => hour.responded += 1,
+            "chain_step_done" => {
+                let failed = event.get("failed").and_then(Value::as_bool).unwrap_or(false);
+                if failed {
+                    hour.failures += 1;
+                }
+                // Older events have no duration
+                if let Some(duration_ms) = event.get("durationMs").and_then(Value::as_u64) {
+                    let samples = self.steps.entry(str_field("agentId")).or_default();
+                    push_sample(samples, Sample { at, duration_ms, ok: !failed });
+                }
+            }
+            "agent_invocation" => {
+                let ok = event.get("ok").and_then(Value::as_bool).unwrap_or(true);
+                let key = format!("{}:{}", str_field("provider"), str_field("model"));
+                if event.get("fallback").and_then(Value::as_bool).unwrap_or(false) {
+                    hour.failovers += 1;
+                }
+                if !ok {
+                    hour.model_errors += 1;
+                    self.failures.push_back(ModelFailure {
+                        at,
+                        key: key.clone(),
+                        agent_id: str_field("agentId"),
+                        reason: event
+                            .get("reason")
+                            .and_then(Value::as_str)
+                            .unwrap_or("Unknown")
+                            .to_string(),
+                        cooldown_until: event.get("cooldownUntil").and_then(Value::as_u64),
+                    });
+                }
+                let duration_ms = event.get("durationMs").and_then(Value::as_u64).unwrap_or(0);
+                push_sample(self.invocations.entry(key).or_default(), Sample { at, duration_ms, ok });
+            }
+            "compaction" => {
+                hour.compactions += 1;
+                self.compactions.push_back((at, str_field("agentId")));
+            }
+            _ => {}
+        }
+        self.prune(at.saturating_sub(MAX_AGE_MS));
+    }
+
+    fn prune(&mut self, cutoff: u64) {
+        self.hours = self.hours.split_off(&(cutoff / HOUR_MS * HOUR_MS));
+        for samples in self.steps.values_mut().chain(self.invocations.values_mut()) {
+            while samples.front().is_some_and(|s| s.at < cutoff) {
+                samples.pop_front();
+            }
+        }
+        while self.failures.front().is_some_and(|f| f.at < cutoff) {
+            self.failures.pop_front();
+        }
+        while self.compactions.front().is_some_and(|(at, _)| *at < cutoff) {
+            self.compactions.pop_front();
+        }
+    }
+
+    /// Metrics for the `hours` hours up to `now` (including the current hour).
+    pub fn snapshot(&self, now: u64, hours: u64) -> MetricsSnapshot {
+        let hours = hours.clamp(1, MAX_AGE_MS / HOUR_MS);
+        let current = now / HOUR_MS * HOUR_MS;
+        let since = current.saturating_sub((hours - 1) * HOUR_MS);
+
+        let mut totals = HourCounts::default();
+        let buckets = (0..hours)
+            .map(|i| {
+                let start = since + i * HOUR_MS;
+                let counts = self.hours.get(&start).copied().unwrap_or_default();
+                totals.add(&counts);
+                HourBucket { start, counts }
+            })
+            .collect();
+
+        let recent_failures = self.failures.iter().filter(|f| f.at >= since);
+        let mut failures_by_reason = BTreeMap::new();
+        let mut cooldowns = Vec::new();
+        for failure in recent_failures {
+            *failures_by_reason.entry(failure.reason.clone()).or_insert(0) += 1;
+            if let Some(until) = failure.cooldown_until {
+                cooldowns.push(CooldownPeriod {
+                    key: failure.key.clone(),
+                    agent_id: failure.agent_id.clone(),
+                    reason: failure.reason.clone(),
+                    started: failure.at,
+                    until,
+                });
+            }
+        }
+
+        let mut compactions_by_agent = BTreeMap::new();
+        for (_, agent_id) in self.compactions.iter().filter(|(at, _)| *at >= since) {
+            *compactions_by_agent.entry(agent_id.clone()).or_insert(0) += 1;
+        }
+
+        MetricsSnapshot {
+            since,
+            until: now,
+            totals,
+            hours: buckets,
+            agents: latency_stats(&self.steps, since),
+            models: latency_stats(&self.invocations, since),
+            failures_by_reason,
+            cooldowns,
+            compactions_by_agent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const T0: u64 = 1_000 * HOUR_MS;
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&values, 0.5), 50);
+        assert_eq!(percentile(&values, 0.95), 95);
+        assert_eq!(percentile(&[7], 0.95), 7);
+        assert_eq!(percentile(&[], 0.5), 0);
+    }
+
+    #[test]
+    fn test_snapshot_aggregates_events() {
+        let mut m = MetricsAggregator::new();
+        m.record(&json!({"type": "message_received", "timestamp": T0 + 10}));
+        for (i, ms) in [100u64, 200, 300, 400].iter().enumerate() {
+            m.record(&json!({
+                "type": "chain_step_done", "timestamp": T0 + 20 + i as u64,
+                "agentId": "coder", "durationMs": ms, "failed": false,
+            }));
+        }
+        m.record(&json!({
+            "type": "chain_step_done", "timestamp": T0 + 30,
+            "agentId": "coder", "durationMs": 5, "failed": true,
+        }));
+        m.record(&json!({
+            "type": "agent_invocation", "timestamp": T0 + 40, "agentId": "coder",
+            "provider": "anthropic", "model": "opus", "durationMs": 50, "ok": false,
+            "fallback": false, "reason": "RateLimit", "cooldownUntil": T0 + 60_040,
+        }));
+        m.record(&json!({
+            "type": "agent_invocation", "timestamp": T0 + 50, "agentId": "coder",
+            "provider": "anthropic", "model": "sonnet", "durationMs": 900, "ok": true,
+            "fallback": true,
+        }));
+        m.record(&json!({"type": "response_ready", "timestamp": T0 + HOUR_MS + 1}));
+        m.record(&json!({"type": "compaction", "timestamp": T0 + HOUR_MS + 2, "agentId": "coder"}));
+
+        let snap = m.snapshot(T0 + HOUR_MS + 5, 3);
+        assert_eq!(snap.hours.len(), 3);
+        assert_eq!(snap.hours[0].start, T0 - HOUR_MS);
+        assert_eq!(snap.hours[1].counts.received, 1);
+        assert_eq!(snap.hours[2].counts.responded, 1);
+        assert_eq!(snap.totals.failures, 1);
+        assert_eq!(snap.totals.failovers, 1);
+        assert_eq!(snap.totals.model_errors, 1);
+        assert_eq!(snap.totals.compactions, 1);
+
+        let coder = &snap.agents[0];
+        assert_eq!((coder.count, coder.failures), (5, 1));
+        assert_eq!((coder.p50_ms, coder.p95_ms), (200, 400));
+        let keys: Vec<&str> = snap.models.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, vec!["anthropic:opus", "anthropic:sonnet"]);
+        assert_eq!(snap.failures_by_reason.get("RateLimit"), Some(&1));
+        assert_eq!(snap.cooldowns[0].until, T0 + 60_040);
+        assert_eq!(snap.compactions_by_agent.get("coder"), Some(&1));
+
+        // A window covering only the last hour leaves out the earlier steps
+        let last = m.snapshot(T0 + HOUR_MS + 5, 1);
+        assert!(last.agents.is_empty());
+        assert_eq!(last.totals.received, 0);
+    }
+
+    #[test]
+    fn test_old_data_is_pruned() {
+        let mut m = MetricsAggregator::new();
+        m.record(&json!({"type": "message_received", "timestamp": T0}));
+        m.record(&json!({"type": "message_received", "timestamp": T0 + MAX_AGE_MS + HOUR_MS}));
+        assert_eq!(m.hours.len(), 1);
+    }
+}
@@ -255,6 +255,23 @@ pub fn approve_pairing_code(pairing_file: &Path, code: &str) -> PairingApproveRe
     }
 }
 
+/// Reject a pending pairing code. Returns the removed entry, if any.
+pub fn deny_pairing_code(pairing_file: &Path, code: &str) -> Option<PairingPendingEntry> {
+    let normalized = code.trim().to_uppercase();
+    if normalized.is_empty() {
+        return None;
+    }
+
+    let mut state = load_pairing_state(pairing_file);
+    let idx = state
+        .pending
+        .iter()
+        .position(|e| e.code.to_uppercase() == normalized)?;
+    let removed = state.pending.remove(idx);
+    let _ = save_pairing_state(pairing_file, &state);
+    Some(removed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -312,6 +329,18 @@ mod tests {
         assert!(result.reason.unwrap().contains("not found"));
     }
 
+    #[test]
+    fn test_deny_pairing_code() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("pairing.json");
+
+        let code = ensure_sender_paired(&file, "discord", "42", "Bob").code.unwrap();
+        let denied = deny_pairing_code(&file, &code.to_lowercase()).unwrap();
+        assert_eq!(denied.sender_id, "42");
+        assert!(load_pairing_state(&file).pending.is_empty());
+        assert!(deny_pairing_code(&file, &code).is_none());
+    }
+
     #[test]
     fn test_approve_empty_code() {
         let tmp = TempDir::new().unwrap();
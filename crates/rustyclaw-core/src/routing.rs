@@ -427,6 +427,7 @@ pub fn parse_agent_routing(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::ResetPolicy;
 
     fn sample_agents() -> HashMap<String, AgentConfig> {
         let mut agents = HashMap::new();
@@ -437,8 +438,9 @@ mod tests {
                 provider: "anthropic".to_string(),
                 model: "sonnet".to_string(),
                 working_directory: "/tmp/coder".to_string(),
-                reset_policy: String::new(),
+                reset_policy: ResetPolicy::default(),
                 reset_hour: None,
+                timezone: None,
                 idle_timeout_minutes: None,
                 context_window: None,
                 fallbacks: None,
@@ -454,8 +456,9 @@ mod tests {
                 provider: "anthropic".to_string(),
                 model: "opus".to_string(),
                 working_directory: "/tmp/reviewer".to_string(),
-                reset_policy: String::new(),
+                reset_policy: ResetPolicy::default(),
                 reset_hour: None,
+                timezone: None,
                 idle_timeout_minutes: None,
                 context_window: None,
                 fallbacks: None,
@@ -471,8 +474,9 @@ mod tests {
                 provider: "openai".to_string(),
                 model: "gpt-5.3-codex".to_string(),
                 working_directory: "/tmp/tester".to_string(),
-                reset_policy: String::new(),
+                reset_policy: ResetPolicy::default(),
                 reset_hour: None,
+                timezone: None,
                 idle_timeout_minutes: None,
                 context_window: None,
                 fallbacks: None,
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 use std::path::Path;
+use std::str::FromStr;
 
 use anyhow::Result;
 use chrono::Utc;
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
-use crate::types::AgentConfig;
+use crate::timezone::{self, resolve_timezone};
+use crate::types::{AgentConfig, ReplyTarget, ResetPolicy};
 
 const DEFAULT_RESET_HOUR: u8 = 4;
 const DEFAULT_IDLE_TIMEOUT_MINUTES: u64 = 120;
@@ -23,6 +26,14 @@ pub struct SessionEntry {
     /// Number of times this session has been compacted.
     #[serde(default)]
     pub compaction_count: u32,
+    /// Number of messages handled in this session (for `messages:<n>` resets).
+    #[serde(default)]
+    pub message_count: u32,
+    /// The provider CLI's own session/thread ID (Claude session, Codex thread,
+    /// OpenCode session), captured from its JSON output. Resuming this exact ID
+    /// keeps senders isolated instead of continuing the CLI's last conversation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_session_id: Option<String>,
 }
 
 /// Build a session key from agent, channel, and sender.
@@ -30,6 +41,25 @@ pub fn resolve_session_key(agent_id: &str, channel: &str, sender: &str) -> Strin
     format!("{}:{}:{}", agent_id, channel, sender)
 }
 
+/// Identity a session is scoped to: the sender, or the thread when the message
+/// arrived in a channel thread (everyone in a thread shares one conversation).
+pub fn session_scope(sender: &str, reply_to: Option<&ReplyTarget>) -> String {
+    match reply_to.and_then(|r| r.thread_id.as_deref()) {
+        Some(thread_id) if !thread_id.is_empty() => {
+            // Topic IDs are only unique within a chat, so include the chat. A
+            // thread that is its own chat (Discord) keeps the original key so
+            // existing thread sessions still resolve.
+            let chat_id = reply_to.map(|r| r.chat_id.as_str()).unwrap_or("");
+            if chat_id.is_empty() || chat_id == thread_id {
+                format!("thread:{}", thread_id)
+            } else {
+                format!("thread:{}:{}", chat_id, thread_id)
+            }
+        }
+        _ => sender.to_string(),
+    }
+}
+
 /// Load the session store from `{agent_dir}/.rustyclaw/sessions.json`.
 pub fn load_sessions(agent_dir: &Path) -> HashMap<String, SessionEntry> {
     let path = agent_dir.join(".rustyclaw/sessions.json");
@@ -56,24 +86,40 @@ pub fn save_sessions(agent_dir: &Path, store: &HashMap<String, SessionEntry>) ->
 /// Returns true if the session is fresh, false if it should be reset.
 pub fn evaluate_session_freshness(entry: &SessionEntry, agent: &AgentConfig) -> bool {
     let now = now_millis();
-    let policy = resolve_reset_policy(agent);
-
-    match policy.as_str() {
-        "manual" => true, // never auto-reset
-        "daily" => !is_stale_daily(entry.updated_at, now, resolve_reset_hour(agent)),
-        "idle" => !is_stale_idle(entry.updated_at, now, resolve_idle_timeout(agent)),
-        // "both" or default: reset on either condition
-        _ => {
-            !is_stale_daily(entry.updated_at, now, resolve_reset_hour(agent))
+    let tz = resolve_timezone(agent);
+
+    match &agent.reset_policy {
+        ResetPolicy::Manual => true, // never auto-reset
+        ResetPolicy::Daily => !is_stale_daily(entry.updated_at, now, resolve_reset_hour(agent), tz),
+        ResetPolicy::Idle => !is_stale_idle(entry.updated_at, now, resolve_idle_timeout(agent)),
+        ResetPolicy::Both | ResetPolicy::Invalid(_) => {
+            !is_stale_daily(entry.updated_at, now, resolve_reset_hour(agent), tz)
                 && !is_stale_idle(entry.updated_at, now, resolve_idle_timeout(agent))
         }
+        ResetPolicy::Cron(expr) => !is_stale_cron(entry.updated_at, now, expr, tz),
+        ResetPolicy::AfterMessages(max) => entry.message_count < *max,
+        ResetPolicy::AfterCompactions(max) => entry.compaction_count < *max,
     }
 }
 
-/// Resolve whether an agent invocation should reset, and return the session ID.
-/// Also checks the legacy reset_flag file.
+/// Parse a cron expression. Accepts standard 5-field expressions
+/// (`min hour dom month dow`) as well as the 6/7-field form with seconds.
+pub fn parse_cron_schedule(expr: &str) -> Result<cron::Schedule, String> {
+    let expr = expr.trim();
+    let normalized = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+    cron::Schedule::from_str(&normalized).map_err(|e| format!("invalid cron expression '{}': {}", expr, e))
+}
+
+/// Resolve whether an agent invocation should reset, and which provider session
+/// to resume. Also checks the legacy reset_flag file.
 ///
-/// Returns (should_reset, session_id).
+/// Returns (should_reset, provider_session_id). The provider session ID is `None`
+/// when the session resets or no provider session has been captured yet, in which
+/// case the CLI must start a brand new conversation.
 pub fn resolve_should_reset(
     agent_dir: &Path,
     agent_id: &str,
@@ -81,7 +127,7 @@ pub fn resolve_should_reset(
     channel: &str,
     sender: &str,
     workspace_path: &Path,
-) -> (bool, String) {
+) -> (bool, Option<String>) {
     // Check legacy reset_flag
     let reset_flag = crate::routing::get_agent_reset_flag(agent_id, workspace_path);
     let flag_reset = reset_flag.exists();
@@ -95,16 +141,22 @@ pub fn resolve_should_reset(
     if let Some(entry) = sessions.get(&session_key) {
         let fresh = evaluate_session_freshness(entry, agent);
         let should_reset = flag_reset || !fresh;
-        (should_reset, entry.session_id.clone())
+        let resume_id = if should_reset {
+            None
+        } else {
+            entry.provider_session_id.clone()
+        };
+        (should_reset, resume_id)
     } else {
         // No session exists — this is a new session (reset = true to start fresh)
-        let session_id = generate_session_id();
-        (true, session_id)
+        (true, None)
     }
 }
 
 /// Update a session entry after a successful invocation.
-/// Creates the entry if it doesn't exist.
+/// Creates the entry if it doesn't exist. `provider_session_id` is the session/thread
+/// ID reported by the provider CLI, if it could be captured.
+#[allow(clippy::too_many_arguments)]
 pub fn update_session(
     agent_dir: &Path,
     agent_id: &str,
@@ -113,6 +165,7 @@ pub fn update_session(
     message_chars: usize,
     response_chars: usize,
     was_reset: bool,
+    provider_session_id: Option<&str>,
 ) -> Result<SessionEntry> {
     let session_key = resolve_session_key(agent_id, channel, sender);
     let mut sessions = load_sessions(agent_dir);
@@ -125,29 +178,30 @@ pub fn update_session(
         sender: sender.to_string(),
         total_chars: 0,
         compaction_count: 0,
+        message_count: 0,
+        provider_session_id: None,
     });
 
     if was_reset {
         entry.session_id = generate_session_id();
         entry.total_chars = 0;
+        entry.compaction_count = 0;
+        entry.message_count = 0;
+        entry.provider_session_id = None;
+    }
+    if let Some(id) = provider_session_id {
+        entry.provider_session_id = Some(id.to_string());
     }
 
     entry.updated_at = now;
     entry.total_chars += (message_chars + response_chars) as u64;
+    entry.message_count += 1;
 
     let result = entry.clone();
     save_sessions(agent_dir, &sessions)?;
     Ok(result)
 }
 
-fn resolve_reset_policy(agent: &AgentConfig) -> String {
-    if agent.reset_policy.is_empty() {
-        "both".to_string()
-    } else {
-        agent.reset_policy.clone()
-    }
-}
-
 fn resolve_reset_hour(agent: &AgentConfig) -> u8 {
     agent.reset_hour.unwrap_or(DEFAULT_RESET_HOUR)
 }
@@ -156,26 +210,30 @@ fn resolve_idle_timeout(agent: &AgentConfig) -> u64 {
     agent.idle_timeout_minutes.unwrap_or(DEFAULT_IDLE_TIMEOUT_MINUTES)
 }
 
-/// Check if session is stale based on daily reset at a specific UTC hour.
-fn is_stale_daily(updated_at: u64, now: u64, reset_hour: u8) -> bool {
-    let now_dt = match chrono::DateTime::from_timestamp((now / 1000) as i64, 0) {
-        Some(dt) => dt,
-        None => return false,
+/// Check if session is stale based on daily reset at a specific hour in `tz`.
+fn is_stale_daily(updated_at: u64, now: u64, reset_hour: u8, tz: Tz) -> bool {
+    let Some(now_dt) = timezone::datetime_from_millis(now, tz) else {
+        return false;
     };
 
-    // Today's reset time in UTC
-    let today_reset = now_dt
-        .date_naive()
-        .and_hms_opt(reset_hour as u32, 0, 0)
-        .map(|dt| dt.and_utc().timestamp_millis() as u64);
+    let reset_on = |date: chrono::NaiveDate| {
+        date.and_hms_opt(reset_hour as u32, 0, 0)
+            .and_then(|naive| naive.and_local_timezone(tz).earliest())
+            .map(|dt| dt.timestamp_millis() as u64)
+    };
 
-    let Some(reset_at) = today_reset else {
+    // Today's reset time in the agent's timezone
+    let today = now_dt.date_naive();
+    let Some(reset_at) = reset_on(today) else {
         return false;
     };
 
     // If we haven't passed today's reset hour yet, use yesterday's reset time
     let effective_reset = if now < reset_at {
-        reset_at.saturating_sub(24 * 60 * 60 * 1000)
+        match today.pred_opt().and_then(reset_on) {
+            Some(r) => r,
+            None => return false,
+        }
     } else {
         reset_at
     };
@@ -183,6 +241,21 @@ fn is_stale_daily(updated_at: u64, now: u64, reset_hour: u8) -> bool {
     updated_at < effective_reset
 }
 
+/// Check if session is stale based on a cron schedule evaluated in `tz`:
+/// stale if the schedule fired at least once since the last update.
+fn is_stale_cron(updated_at: u64, now: u64, expr: &str, tz: Tz) -> bool {
+    let Ok(schedule) = parse_cron_schedule(expr) else {
+        return false;
+    };
+    let Some(updated_dt) = timezone::datetime_from_millis(updated_at, tz) else {
+        return false;
+    };
+    schedule
+        .after(&updated_dt)
+        .next()
+        .is_some_and(|fire| (fire.timestamp_millis() as u64) <= now)
+}
+
 /// Check if session is stale based on idle timeout.
 fn is_stale_idle(updated_at: u64, now: u64, timeout_minutes: u64) -> bool {
     let idle_ms = now.saturating_sub(updated_at);
@@ -213,8 +286,9 @@ mod tests {
             provider: "anthropic".to_string(),
             model: "sonnet".to_string(),
             working_directory: String::new(),
-            reset_policy: policy.to_string(),
+            reset_policy: policy.parse().unwrap(),
             reset_hour: Some(4),
+            timezone: None,
             idle_timeout_minutes: Some(60),
             context_window: None,
             fallbacks: None,
@@ -230,6 +304,28 @@ mod tests {
         assert_eq!(key, "coder:discord:user123");
     }
 
+    #[test]
+    fn test_session_scope_uses_thread() {
+        assert_eq!(session_scope("alice", None), "alice");
+        let dm = ReplyTarget {
+            chat_id: "42".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(session_scope("alice", Some(&dm)), "alice");
+        let thread = ReplyTarget {
+            chat_id: "900".to_string(),
+            thread_id: Some("900".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(session_scope("alice", Some(&thread)), "thread:900");
+        let topic = ReplyTarget {
+            chat_id: "-100123".to_string(),
+            thread_id: Some("7".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(session_scope("alice", Some(&topic)), "thread:-100123:7");
+    }
+
     #[test]
     fn test_save_and_load_sessions() {
         let tmp = TempDir::new().unwrap();
@@ -246,6 +342,8 @@ mod tests {
                 sender: "user1".to_string(),
                 total_chars: 5000,
                 compaction_count: 0,
+                message_count: 3,
+                provider_session_id: Some("claude-abc".to_string()),
             },
         );
 
@@ -254,6 +352,10 @@ mod tests {
         assert_eq!(loaded.len(), 1);
         assert_eq!(loaded["coder:discord:user1"].session_id, "sess-123");
         assert_eq!(loaded["coder:discord:user1"].total_chars, 5000);
+        assert_eq!(
+            loaded["coder:discord:user1"].provider_session_id.as_deref(),
+            Some("claude-abc")
+        );
     }
 
     #[test]
@@ -266,6 +368,8 @@ mod tests {
             sender: "user".to_string(),
             total_chars: 0,
             compaction_count: 0,
+            message_count: 0,
+            provider_session_id: None,
         };
         // Manual policy never auto-resets
         assert!(evaluate_session_freshness(&entry, &agent));
@@ -282,6 +386,8 @@ mod tests {
             sender: "user".to_string(),
             total_chars: 0,
             compaction_count: 0,
+            message_count: 0,
+            provider_session_id: None,
         };
         assert!(!evaluate_session_freshness(&entry, &agent));
     }
@@ -297,6 +403,8 @@ mod tests {
             sender: "user".to_string(),
             total_chars: 0,
             compaction_count: 0,
+            message_count: 0,
+            provider_session_id: None,
         };
         assert!(evaluate_session_freshness(&entry, &agent));
     }
@@ -308,7 +416,7 @@ mod tests {
         std::fs::create_dir_all(agent_dir.join(".rustyclaw")).unwrap();
 
         let entry =
-            update_session(&agent_dir, "coder", "discord", "user1", 100, 200, true).unwrap();
+            update_session(&agent_dir, "coder", "discord", "user1", 100, 200, true, None).unwrap();
         assert_eq!(entry.total_chars, 300);
         assert_eq!(entry.channel, "discord");
         assert_eq!(entry.sender, "user1");
@@ -324,9 +432,9 @@ mod tests {
         let agent_dir = tmp.path().join("coder");
         std::fs::create_dir_all(agent_dir.join(".rustyclaw")).unwrap();
 
-        update_session(&agent_dir, "coder", "discord", "user1", 100, 200, true).unwrap();
+        update_session(&agent_dir, "coder", "discord", "user1", 100, 200, true, None).unwrap();
         let entry =
-            update_session(&agent_dir, "coder", "discord", "user1", 50, 150, false).unwrap();
+            update_session(&agent_dir, "coder", "discord", "user1", 50, 150, false, None).unwrap();
         assert_eq!(entry.total_chars, 500); // 300 + 200
     }
 
@@ -336,9 +444,9 @@ mod tests {
         let agent_dir = tmp.path().join("coder");
         std::fs::create_dir_all(agent_dir.join(".rustyclaw")).unwrap();
 
-        update_session(&agent_dir, "coder", "discord", "user1", 100, 200, true).unwrap();
+        update_session(&agent_dir, "coder", "discord", "user1", 100, 200, true, None).unwrap();
         let entry =
-            update_session(&agent_dir, "coder", "discord", "user1", 50, 150, true).unwrap();
+            update_session(&agent_dir, "coder", "discord", "user1", 50, 150, true, None).unwrap();
         // Reset clears total_chars, then adds new message + response
         assert_eq!(entry.total_chars, 200); // 0 + 50 + 150
     }
@@ -347,9 +455,9 @@ mod tests {
     fn test_is_stale_daily() {
         let now = 1708250000000u64; // some arbitrary time
         // Very old — should be stale
-        assert!(is_stale_daily(1000, now, 4));
+        assert!(is_stale_daily(1000, now, 4, Tz::UTC));
         // Recent — should be fresh
-        assert!(!is_stale_daily(now - 1000, now, 4));
+        assert!(!is_stale_daily(now - 1000, now, 4, Tz::UTC));
     }
 
     #[test]
@@ -367,9 +475,9 @@ mod tests {
         let agent_dir = tmp.path().join("coder");
         std::fs::create_dir_all(agent_dir.join(".rustyclaw")).unwrap();
 
-        update_session(&agent_dir, "coder", "discord", "alice", 100, 200, true).unwrap();
-        update_session(&agent_dir, "coder", "discord", "bob", 50, 50, true).unwrap();
-        update_session(&agent_dir, "coder", "telegram", "alice", 75, 75, true).unwrap();
+        update_session(&agent_dir, "coder", "discord", "alice", 100, 200, true, None).unwrap();
+        update_session(&agent_dir, "coder", "discord", "bob", 50, 50, true, None).unwrap();
+        update_session(&agent_dir, "coder", "telegram", "alice", 75, 75, true, None).unwrap();
 
         let sessions = load_sessions(&agent_dir);
         assert_eq!(sessions.len(), 3);
@@ -377,4 +485,151 @@ mod tests {
         assert_eq!(sessions["coder:discord:bob"].total_chars, 100);
         assert_eq!(sessions["coder:telegram:alice"].total_chars, 150);
     }
+
+    #[test]
+    fn test_resolve_should_reset_resumes_provider_session_per_sender() {
+        let tmp = TempDir::new().unwrap();
+        let agent_dir = tmp.path().join("coder");
+        let agent = test_agent("manual");
+
+        // Unknown sender: fresh provider session
+        let (reset, resume) =
+            resolve_should_reset(&agent_dir, "coder", &agent, "telegram", "alice", tmp.path());
+        assert!(reset);
+        assert!(resume.is_none());
+
+        update_session(&agent_dir, "coder", "telegram", "alice", 10, 10, true, Some("sess-alice"))
+            .unwrap();
+        update_session(&agent_dir, "coder", "telegram", "bob", 10, 10, true, Some("sess-bob"))
+            .unwrap();
+
+        let (reset, resume) =
+            resolve_should_reset(&agent_dir, "coder", &agent, "telegram", "alice", tmp.path());
+        assert!(!reset);
+        assert_eq!(resume.as_deref(), Some("sess-alice"));
+
+        let (_, resume) =
+            resolve_should_reset(&agent_dir, "coder", &agent, "telegram", "bob", tmp.path());
+        assert_eq!(resume.as_deref(), Some("sess-bob"));
+    }
+
+    #[test]
+    fn test_update_session_reset_drops_provider_session() {
+        let tmp = TempDir::new().unwrap();
+        let agent_dir = tmp.path().join("coder");
+
+        update_session(&agent_dir, "coder", "discord", "user1", 1, 1, true, Some("old")).unwrap();
+        let entry = update_session(&agent_dir, "coder", "discord", "user1", 1, 1, true, None).unwrap();
+        assert!(entry.provider_session_id.is_none());
+
+        let entry =
+            update_session(&agent_dir, "coder", "discord", "user1", 1, 1, false, Some("new"))
+                .unwrap();
+        assert_eq!(entry.provider_session_id.as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn test_reset_policy_parse_roundtrip() {
+        for raw in ["daily", "idle", "both", "manual", "cron:0 4 * * 1", "messages:50", "compactions:3"] {
+            let policy: ResetPolicy = raw.parse().unwrap();
+            assert_eq!(policy.to_string(), raw);
+        }
+        assert_eq!("".parse::<ResetPolicy>().unwrap(), ResetPolicy::Both);
+        assert_eq!("messages:50".parse::<ResetPolicy>().unwrap(), ResetPolicy::AfterMessages(50));
+        assert!("weekly".parse::<ResetPolicy>().is_err());
+        assert!("messages:0".parse::<ResetPolicy>().is_err());
+        assert!("cron:".parse::<ResetPolicy>().is_err());
+
+        assert!("cron:not a cron".parse::<ResetPolicy>().is_err());
+
+        // Loading settings keeps the value as written instead of failing
+        let agent: AgentConfig = serde_json::from_str(
+            r#"{"name":"A","provider":"anthropic","model":"sonnet","working_directory":"",
+                "reset_policy":"cron:0 4 * *"}"#,
+        )
+        .unwrap();
+        assert_eq!(agent.reset_policy, ResetPolicy::Invalid("cron:0 4 * *".to_string()));
+        let saved = serde_json::to_value(&agent).unwrap();
+        assert_eq!(saved["reset_policy"], "cron:0 4 * *");
+    }
+
+    #[test]
+    fn test_reset_policy_from_settings_json() {
+        let agent: AgentConfig = serde_json::from_str(
+            r#"{"name":"A","provider":"anthropic","model":"sonnet","working_directory":"",
+                "reset_policy":"cron:30 3 * * *","timezone":"Europe/Berlin"}"#,
+        )
+        .unwrap();
+        assert_eq!(agent.reset_policy, ResetPolicy::Cron("30 3 * * *".to_string()));
+        assert_eq!(resolve_timezone(&agent), Tz::Europe__Berlin);
+
+        // Missing policy defaults to "both"
+        let agent: AgentConfig = serde_json::from_str(
+            r#"{"name":"A","provider":"anthropic","model":"sonnet","working_directory":""}"#,
+        )
+        .unwrap();
+        assert_eq!(agent.reset_policy, ResetPolicy::Both);
+    }
+
+    #[test]
+    fn test_is_stale_daily_respects_timezone() {
+        // 04:00 in New York on 2024-02-18 is 09:00 UTC (EST, UTC-5)
+        let reset = 1708246800000u64;
+        // Updated a second before the local reset, checked a second after: stale
+        assert!(is_stale_daily(reset - 1000, reset + 1000, 4, Tz::America__New_York));
+        // Both before the local reset: the last reset was the day before
+        assert!(!is_stale_daily(reset - 2000, reset - 1000, 4, Tz::America__New_York));
+        // Updated right after the local reset: fresh
+        assert!(!is_stale_daily(reset, reset + 1000, 4, Tz::America__New_York));
+        // With reset_hour 4 in UTC the reset was five hours earlier, so neither is stale
+        assert!(!is_stale_daily(reset - 1000, reset + 1000, 4, Tz::UTC));
+    }
+
+    #[test]
+    fn test_is_stale_cron() {
+        // 2024-02-17 23:30 UTC
+        let now = 1708212600000u64;
+        // Hourly on the hour: fired at 23:00 after an update at 22:30
+        assert!(is_stale_cron(now - 3_600_000, now, "0 * * * *", Tz::UTC));
+        // Updated at 23:10, next fire at 00:00 is still ahead
+        assert!(!is_stale_cron(now - 1_200_000, now, "0 * * * *", Tz::UTC));
+        // Invalid expressions never reset
+        assert!(!is_stale_cron(0, now, "not a cron", Tz::UTC));
+        assert!(parse_cron_schedule("0 0 4 * * *").is_ok());
+    }
+
+    #[test]
+    fn test_after_messages_and_compactions_policies() {
+        let mut entry = SessionEntry {
+            session_id: "sess-1".to_string(),
+            updated_at: now_millis(),
+            channel: "discord".to_string(),
+            sender: "user".to_string(),
+            total_chars: 0,
+            compaction_count: 0,
+            message_count: 4,
+            provider_session_id: None,
+        };
+        assert!(evaluate_session_freshness(&entry, &test_agent("messages:5")));
+        entry.message_count = 5;
+        assert!(!evaluate_session_freshness(&entry, &test_agent("messages:5")));
+
+        assert!(evaluate_session_freshness(&entry, &test_agent("compactions:2")));
+        entry.compaction_count = 2;
+        assert!(!evaluate_session_freshness(&entry, &test_agent("compactions:2")));
+    }
+
+    #[test]
+    fn test_update_session_counts_messages_and_resets_counters() {
+        let tmp = TempDir::new().unwrap();
+        let agent_dir = tmp.path().join("coder");
+
+        update_session(&agent_dir, "coder", "discord", "u", 1, 1, true, None).unwrap();
+        let entry = update_session(&agent_dir, "coder", "discord", "u", 1, 1, false, None).unwrap();
+        assert_eq!(entry.message_count, 2);
+
+        let entry = update_session(&agent_dir, "coder", "discord", "u", 1, 1, true, None).unwrap();
+        assert_eq!(entry.message_count, 1);
+        assert_eq!(entry.compaction_count, 0);
+    }
 }
@@ -0,0 +1,548 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::agent_setup::copy_dir_sync;
+use crate::skills::{parse_skill_frontmatter, SkillMeta};
+
+/// Where an installed skill package came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkillSourceKind {
+    /// A local directory containing SKILL.md
+    Dir,
+    /// A git repository, cloned with the `git` CLI
+    Git,
+    /// A local `.tar`, `.tar.gz`, or `.tgz` archive
+    Tarball,
+}
+
+/// A single installed skill recorded in the lockfile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedSkill {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Original install source (path or URL), used by `update`
+    pub source: String,
+    pub source_kind: SkillSourceKind,
+    /// `sha256:<hex>` over the installed skill directory contents
+    pub checksum: String,
+    pub installed_at: u64,
+}
+
+/// The skills lockfile (`~/.rustyclaw/skills.lock`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkillLockfile {
+    #[serde(default)]
+    pub skills: BTreeMap<String, LockedSkill>,
+}
+
+/// Result of installing or updating a skill package.
+#[derive(Debug, Clone)]
+pub struct InstalledSkill {
+    pub name: String,
+    pub dir: PathBuf,
+    pub locked: LockedSkill,
+    /// Version recorded before this install, if the skill was already installed
+    pub previous_version: Option<String>,
+    /// False when an update found the source unchanged
+    pub changed: bool,
+}
+
+/// A skill unpacked into a staging directory, not yet moved into place.
+struct StagedSkill {
+    staging_dir: PathBuf,
+    root: PathBuf,
+    meta: SkillMeta,
+    checksum: String,
+}
+
+impl Drop for StagedSkill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.staging_dir);
+    }
+}
+
+/// Load the lockfile. Returns an empty lockfile if missing or invalid.
+pub fn load_lockfile(lock_file: &Path) -> SkillLockfile {
+    match std::fs::read_to_string(lock_file) {
+        Ok(raw) => serde_json::from_str(&raw).unwrap_or_default(),
+        Err(_) => SkillLockfile::default(),
+    }
+}
+
+/// Save the lockfile atomically (write to tmp, then rename).
+pub fn save_lockfile(lock_file: &Path, lock: &SkillLockfile) -> Result<()> {
+    if let Some(dir) = lock_file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = lock_file.with_extension("tmp");
+    let json = serde_json::to_string_pretty(lock)?;
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, lock_file)?;
+    Ok(())
+}
+
+/// Guess the source kind from a path or URL.
+pub fn detect_source_kind(source: &str) -> SkillSourceKind {
+    let lower = source.to_lowercase();
+    if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") || lower.ends_with(".tar") {
+        SkillSourceKind::Tarball
+    } else if lower.starts_with("http://")
+        || lower.starts_with("https://")
+        || lower.starts_with("ssh://")
+        || lower.starts_with("git://")
+        || lower.starts_with("git@")
+        || lower.ends_with(".git")
+    {
+        SkillSourceKind::Git
+    } else {
+        SkillSourceKind::Dir
+    }
+}
+
+/// Install a skill package from a local directory, git URL, or local tarball
+/// into `skills_dir/<name>`, replacing any existing copy, and record it in the lockfile.
+pub fn install_skill(source: &str, skills_dir: &Path, lock_file: &Path) -> Result<InstalledSkill> {
+    let kind = detect_source_kind(source);
+    let staged = stage_skill(source, kind, skills_dir)?;
+    commit_staged(staged, source, kind, skills_dir, lock_file)
+}
+
+/// Re-fetch an installed skill from its recorded source. Leaves the installed
+/// copy untouched when the source's checksum hasn't changed.
+pub fn update_skill(name: &str, skills_dir: &Path, lock_file: &Path) -> Result<InstalledSkill> {
+    let lock = load_lockfile(lock_file);
+    let Some(entry) = lock.skills.get(name).cloned() else {
+        bail!("Skill '{}' is not installed as a package", name);
+    };
+
+    let staged = stage_skill(&entry.source, entry.source_kind, skills_dir)?;
+    if staged.meta.name != name {
+        bail!(
+            "Source {} now provides skill '{}' instead of '{}'",
+            entry.source,
+            staged.meta.name,
+            name
+        );
+    }
+
+    let dir = skills_dir.join(name);
+    let installed_checksum = hash_skill_dir(&dir).ok();
+    if staged.checksum == entry.checksum && installed_checksum.as_deref() == Some(&entry.checksum) {
+        return Ok(InstalledSkill {
+            name: name.to_string(),
+            dir,
+            previous_version: entry.version.clone(),
+            locked: entry,
+            changed: false,
+        });
+    }
+
+    commit_staged(staged, &entry.source, entry.source_kind, skills_dir, lock_file)
+}
+
+/// Remove an installed skill directory and its lockfile entry.
+pub fn remove_skill(name: &str, skills_dir: &Path, lock_file: &Path) -> Result<()> {
+    validate_skill_name(name)?;
+    let mut lock = load_lockfile(lock_file);
+    let dir = skills_dir.join(name);
+    let was_locked = lock.skills.remove(name).is_some();
+
+    if !was_locked && !dir.exists() {
+        bail!("Skill '{}' is not installed", name);
+    }
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)
+            .with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+    save_lockfile(lock_file, &lock)?;
+    Ok(())
+}
+
+/// Check whether an installed skill still matches its locked checksum.
+/// Returns false if the directory was modified or is missing.
+pub fn verify_skill(dir: &Path, locked: &LockedSkill) -> bool {
+    hash_skill_dir(dir)
+        .map(|c| c == locked.checksum)
+        .unwrap_or(false)
+}
+
+/// Compute a `sha256:<hex>` checksum over a skill directory. Files are hashed in
+/// sorted relative-path order; `.git` directories are ignored.
+pub fn hash_skill_dir(dir: &Path) -> Result<String> {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for rel in &files {
+        hasher.update(rel.to_string_lossy().replace('\\', "/").as_bytes());
+        hasher.update([0u8]);
+        hasher.update(std::fs::read(dir.join(rel))?);
+        hasher.update([0u8]);
+    }
+    let digest = hasher.finalize();
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("sha256:{}", hex))
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            collect_files(root, &path, out)?;
+        } else {
+            out.push(path.strip_prefix(root)?.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Skill names become directory names, so keep them to a safe character set.
+pub(crate) fn validate_skill_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid {
+        bail!("Invalid skill name '{}'", name);
+    }
+    Ok(())
+}
+
+/// Fetch a source into a fresh staging directory under `skills_dir` and parse it.
+fn stage_skill(source: &str, kind: SkillSourceKind, skills_dir: &Path) -> Result<StagedSkill> {
+    std::fs::create_dir_all(skills_dir)?;
+    let rand_part: u32 = rand::random();
+    let staging_dir = skills_dir.join(format!(".staging-{:08x}", rand_part));
+    let fetched = staging_dir.join("src");
+
+    // Construct the guard first so the staging dir is cleaned up on any error
+    let mut staged = StagedSkill {
+        staging_dir: staging_dir.clone(),
+        root: PathBuf::new(),
+        meta: SkillMeta::default(),
+        checksum: String::new(),
+    };
+    std::fs::create_dir_all(&staging_dir)?;
+
+    match kind {
+        SkillSourceKind::Dir => {
+            let src = Path::new(source);
+            if !src.is_dir() {
+                bail!("Skill source directory not found: {}", source);
+            }
+            copy_dir_sync(src, &fetched)?;
+            let _ = std::fs::remove_dir_all(fetched.join(".git"));
+        }
+        SkillSourceKind::Tarball => {
+            if source.contains("://") {
+                bail!("Remote tarballs are not supported; download {} first", source);
+            }
+            unpack_tarball(Path::new(source), &fetched)?;
+        }
+        SkillSourceKind::Git => {
+            let output = std::process::Command::new("git")
+                .args(["clone", "--depth", "1", "--quiet", source])
+                .arg(&fetched)
+                .stdin(std::process::Stdio::null())
+                .output()
+                .context("Failed to run git (is it installed?)")?;
+            if !output.status.success() {
+                bail!(
+                    "git clone failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            let _ = std::fs::remove_dir_all(fetched.join(".git"));
+        }
+    }
+
+    let root = find_skill_root(&fetched)
+        .with_context(|| format!("No SKILL.md found in {}", source))?;
+    let content = std::fs::read_to_string(root.join("SKILL.md"))?;
+    let Some((meta, _)) = parse_skill_frontmatter(&content) else {
+        bail!("SKILL.md in {} has no valid frontmatter (a name is required)", source);
+    };
+    validate_skill_name(&meta.name)?;
+
+    staged.checksum = hash_skill_dir(&root)?;
+    staged.root = root;
+    staged.meta = meta;
+    Ok(staged)
+}
+
+/// Move a staged skill into `skills_dir/<name>` and record it in the lockfile.
+fn commit_staged(
+    staged: StagedSkill,
+    source: &str,
+    kind: SkillSourceKind,
+    skills_dir: &Path,
+    lock_file: &Path,
+) -> Result<InstalledSkill> {
+    let name = staged.meta.name.clone();
+    let dest = skills_dir.join(&name);
+
+    // Swap in the new copy, keeping the old one until the rename succeeds
+    let backup = staged.staging_dir.join("previous");
+    if dest.exists() {
+        std::fs::rename(&dest, &backup)?;
+    }
+    if let Err(e) = std::fs::rename(&staged.root, &dest) {
+        if backup.exists() {
+            let _ = std::fs::rename(&backup, &dest);
+        }
+        return Err(e).with_context(|| format!("Failed to install skill into {}", dest.display()));
+    }
+
+    let mut lock = load_lockfile(lock_file);
+    let locked = LockedSkill {
+        version: staged.meta.version.clone(),
+        source: normalize_source(source, kind),
+        source_kind: kind,
+        checksum: staged.checksum.clone(),
+        installed_at: now_millis(),
+    };
+    let previous_version = lock
+        .skills
+        .insert(name.clone(), locked.clone())
+        .and_then(|prev| prev.version);
+    save_lockfile(lock_file, &lock)?;
+
+    Ok(InstalledSkill {
+        name,
+        dir: dest,
+        locked,
+        previous_version,
+        changed: true,
+    })
+}
+
+/// Store local sources as absolute paths so `update` works from any cwd.
+fn normalize_source(source: &str, kind: SkillSourceKind) -> String {
+    if kind == SkillSourceKind::Git {
+        return source.to_string();
+    }
+    std::fs::canonicalize(source)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| source.to_string())
+}
+
+/// Locate the directory holding SKILL.md: either the root itself or its
+/// single top-level subdirectory (as produced by most tarballs).
+fn find_skill_root(dir: &Path) -> Option<PathBuf> {
+    if dir.join("SKILL.md").is_file() {
+        return Some(dir.to_path_buf());
+    }
+    let subdirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    match subdirs.as_slice() {
+        [only] if only.join("SKILL.md").is_file() => Some(only.clone()),
+        _ => None,
+    }
+}
+
+fn unpack_tarball(archive: &Path, dest: &Path) -> Result<()> {
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("Failed to open tarball {}", archive.display()))?;
+    let name = archive.to_string_lossy().to_lowercase();
+    let reader: Box<dyn Read> = if name.ends_with(".gz") || name.ends_with(".tgz") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    std::fs::create_dir_all(dest)?;
+    // `unpack` refuses entries that would escape `dest`
+    tar::Archive::new(reader)
+        .unpack(dest)
+        .with_context(|| format!("Failed to unpack {}", archive.display()))?;
+    Ok(())
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_skill(dir: &Path, name: &str, version: &str, body: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(
+            dir.join("SKILL.md"),
+            format!(
+                "---\nname: {}\ndescription: Test skill\nversion: {}\n---\n{}",
+                name, version, body
+            ),
+        )
+        .unwrap();
+    }
+
+    fn make_tarball(src_dir: &Path, prefix: &str, out: &Path) {
+        let file = std::fs::File::create(out).unwrap();
+        let enc = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(enc);
+        builder.append_dir_all(prefix, src_dir).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_detect_source_kind() {
+        assert_eq!(detect_source_kind("./skills/github"), SkillSourceKind::Dir);
+        assert_eq!(detect_source_kind("/tmp/github.tar.gz"), SkillSourceKind::Tarball);
+        assert_eq!(detect_source_kind("github.tgz"), SkillSourceKind::Tarball);
+        assert_eq!(
+            detect_source_kind("https://github.com/acme/skill-github"),
+            SkillSourceKind::Git
+        );
+        assert_eq!(detect_source_kind("git@github.com:acme/skill.git"), SkillSourceKind::Git);
+    }
+
+    #[test]
+    fn test_install_from_directory() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src/github");
+        write_skill(&src, "github", "1.0.0", "Use gh.");
+        std::fs::write(src.join("helper.sh"), "echo hi").unwrap();
+
+        let skills_dir = tmp.path().join("home/skills");
+        let lock_file = tmp.path().join("home/skills.lock");
+        let installed =
+            install_skill(src.to_str().unwrap(), &skills_dir, &lock_file).unwrap();
+
+        assert_eq!(installed.name, "github");
+        assert!(skills_dir.join("github/SKILL.md").exists());
+        assert!(skills_dir.join("github/helper.sh").exists());
+
+        let lock = load_lockfile(&lock_file);
+        let entry = &lock.skills["github"];
+        assert_eq!(entry.version.as_deref(), Some("1.0.0"));
+        assert_eq!(entry.source_kind, SkillSourceKind::Dir);
+        assert!(entry.checksum.starts_with("sha256:"));
+        assert!(verify_skill(&skills_dir.join("github"), entry));
+
+        // No staging directories left behind
+        let leftovers: Vec<_> = std::fs::read_dir(&skills_dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with(".staging"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_install_from_tarball_with_top_level_dir() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        write_skill(&src, "notes", "0.2.0", "Take notes.");
+        let tarball = tmp.path().join("notes-0.2.0.tar.gz");
+        make_tarball(&src, "notes-0.2.0", &tarball);
+
+        let skills_dir = tmp.path().join("skills");
+        let lock_file = tmp.path().join("skills.lock");
+        let installed =
+            install_skill(tarball.to_str().unwrap(), &skills_dir, &lock_file).unwrap();
+
+        assert_eq!(installed.name, "notes");
+        assert_eq!(installed.locked.source_kind, SkillSourceKind::Tarball);
+        assert!(skills_dir.join("notes/SKILL.md").exists());
+    }
+
+    #[test]
+    fn test_install_rejects_missing_skill_md() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("empty");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("README.md"), "nothing here").unwrap();
+
+        let skills_dir = tmp.path().join("skills");
+        let err = install_skill(src.to_str().unwrap(), &skills_dir, &tmp.path().join("l"))
+            .unwrap_err();
+        assert!(err.to_string().contains("No SKILL.md"));
+        assert_eq!(std::fs::read_dir(&skills_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_install_rejects_unsafe_name() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("evil");
+        write_skill(&src, "../escape", "1.0.0", "x");
+        assert!(install_skill(
+            src.to_str().unwrap(),
+            &tmp.path().join("skills"),
+            &tmp.path().join("l")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_update_skill_detects_changes() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src/github");
+        write_skill(&src, "github", "1.0.0", "v1");
+        let skills_dir = tmp.path().join("skills");
+        let lock_file = tmp.path().join("skills.lock");
+        install_skill(src.to_str().unwrap(), &skills_dir, &lock_file).unwrap();
+
+        let unchanged = update_skill("github", &skills_dir, &lock_file).unwrap();
+        assert!(!unchanged.changed);
+
+        write_skill(&src, "github", "1.1.0", "v2");
+        let updated = update_skill("github", &skills_dir, &lock_file).unwrap();
+        assert!(updated.changed);
+        assert_eq!(updated.previous_version.as_deref(), Some("1.0.0"));
+        assert_eq!(updated.locked.version.as_deref(), Some("1.1.0"));
+        let content = std::fs::read_to_string(skills_dir.join("github/SKILL.md")).unwrap();
+        assert!(content.contains("v2"));
+
+        assert!(update_skill("missing", &skills_dir, &lock_file).is_err());
+    }
+
+    #[test]
+    fn test_verify_detects_local_modification() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src/notes");
+        write_skill(&src, "notes", "1.0.0", "Take notes.");
+        let skills_dir = tmp.path().join("skills");
+        let lock_file = tmp.path().join("skills.lock");
+        install_skill(src.to_str().unwrap(), &skills_dir, &lock_file).unwrap();
+
+        std::fs::write(skills_dir.join("notes/extra.md"), "local edit").unwrap();
+        let lock = load_lockfile(&lock_file);
+        assert!(!verify_skill(&skills_dir.join("notes"), &lock.skills["notes"]));
+    }
+
+    #[test]
+    fn test_remove_skill() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src/notes");
+        write_skill(&src, "notes", "1.0.0", "Take notes.");
+        let skills_dir = tmp.path().join("skills");
+        let lock_file = tmp.path().join("skills.lock");
+        install_skill(src.to_str().unwrap(), &skills_dir, &lock_file).unwrap();
+
+        remove_skill("notes", &skills_dir, &lock_file).unwrap();
+        assert!(!skills_dir.join("notes").exists());
+        assert!(load_lockfile(&lock_file).skills.is_empty());
+        assert!(remove_skill("notes", &skills_dir, &lock_file).is_err());
+    }
+}
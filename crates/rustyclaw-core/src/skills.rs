@@ -1,15 +1,35 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Deserialize;
 
 use crate::types::SkillOverride;
 
+/// `[load_skill: a, b]` tags in agent responses.
+static SKILL_REQUEST_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[load_skill:\s*([^\]]+)\]").unwrap());
+
 /// Parsed metadata from a SKILL.md frontmatter.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct SkillMeta {
     pub name: String,
     pub description: String,
+    pub version: Option<String>,
     pub requires_bins: Vec<String>,
     pub requires_env: Vec<String>,
+    /// Files that must exist (absolute, or relative to the agent's working directory)
+    pub requires_files: Vec<String>,
+    /// Providers this skill works with ("anthropic", "openai", "opencode"). Empty = all.
+    pub providers: Vec<String>,
+    /// Agent IDs allowed to use this skill. Empty = all.
+    pub agents: Vec<String>,
+    /// Team IDs whose members may use this skill. Empty = all.
+    pub teams: Vec<String>,
+    /// Keywords that pull the full instructions into the prompt. Empty = index only.
+    pub triggers: Vec<String>,
+    /// Operating systems this skill supports (e.g. "linux", "macos"). Empty = all.
+    pub os: Vec<String>,
 }
 
 /// A discovered skill with its metadata and instructions.
@@ -20,6 +40,62 @@ pub struct Skill {
     pub source_dir: PathBuf,
 }
 
+/// Who a skill is being evaluated for. Built per invocation by the queue processor.
+#[derive(Debug, Clone)]
+pub struct EligibilityContext<'a> {
+    pub agent_id: &'a str,
+    pub provider: &'a str,
+    /// IDs of the teams the agent belongs to
+    pub team_ids: &'a [String],
+    /// Base directory for relative `requires.files` entries
+    pub working_dir: &'a Path,
+}
+
+/// Raw YAML frontmatter, deserialized leniently (every field optional).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Frontmatter {
+    name: Option<String>,
+    description: Option<String>,
+    version: Option<serde_yaml::Value>,
+    requires: Requires,
+    providers: StringList,
+    agents: StringList,
+    teams: StringList,
+    triggers: StringList,
+    os: StringList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Requires {
+    bins: StringList,
+    env: StringList,
+    files: StringList,
+}
+
+/// Accepts either a YAML list or a single scalar (`os: linux`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(from = "Option<OneOrMany>")]
+struct StringList(Vec<String>);
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<Option<OneOrMany>> for StringList {
+    fn from(value: Option<OneOrMany>) -> Self {
+        match value {
+            None => StringList(Vec::new()),
+            Some(OneOrMany::One(s)) => StringList(vec![s]),
+            Some(OneOrMany::Many(v)) => StringList(v),
+        }
+    }
+}
+
 /// Discover skills from multiple directories.
 /// Later directories take precedence on name collisions.
 pub fn discover_skills(dirs: &[&Path]) -> Vec<Skill> {
@@ -72,11 +148,19 @@ pub fn discover_skills(dirs: &[&Path]) -> Vec<Skill> {
 /// ---
 /// name: github
 /// description: Interact with GitHub
+/// version: 1.2.0
+/// providers: [anthropic, openai]
+/// agents: [coder]
+/// teams: [dev]
+/// triggers: [github, pull request]
+/// os: [linux, macos]
 /// requires:
 ///   bins:
 ///     - gh
 ///   env:
 ///     - GITHUB_TOKEN
+///   files:
+///     - .git
 /// ---
 ///
 /// # Instructions markdown here...
@@ -93,111 +177,149 @@ pub fn parse_skill_frontmatter(content: &str) -> Option<(SkillMeta, String)> {
     let frontmatter = &rest[..end_idx];
     let body = rest[end_idx + 4..].trim();
 
-    // Simple YAML parsing (no dependency on a YAML crate)
-    let mut name = String::new();
-    let mut description = String::new();
-    let mut requires_bins = Vec::new();
-    let mut requires_env = Vec::new();
-
-    let mut in_bins = false;
-    let mut in_env = false;
-
-    for line in frontmatter.lines() {
-        let stripped = line.trim();
-
-        // Detect section changes
-        if stripped.starts_with("name:") {
-            name = stripped.trim_start_matches("name:").trim().to_string();
-            in_bins = false;
-            in_env = false;
-            continue;
-        }
-        if stripped.starts_with("description:") {
-            description = stripped
-                .trim_start_matches("description:")
-                .trim()
-                .trim_matches('"')
-                .to_string();
-            in_bins = false;
-            in_env = false;
-            continue;
-        }
-        if stripped == "requires:" {
-            in_bins = false;
-            in_env = false;
-            continue;
-        }
-        if stripped == "bins:" {
-            in_bins = true;
-            in_env = false;
-            continue;
-        }
-        if stripped == "env:" {
-            in_bins = false;
-            in_env = true;
-            continue;
-        }
-
-        // Collect list items
-        if stripped.starts_with("- ") {
-            let value = stripped.trim_start_matches("- ").trim().to_string();
-            if in_bins {
-                requires_bins.push(value);
-            } else if in_env {
-                requires_env.push(value);
-            }
-            continue;
-        }
-
-        // Any other key resets list context
-        if stripped.contains(':') && !stripped.starts_with('-') {
-            in_bins = false;
-            in_env = false;
-        }
-    }
+    let raw: Frontmatter = if frontmatter.trim().is_empty() {
+        Frontmatter::default()
+    } else {
+        serde_yaml::from_str(frontmatter).ok()?
+    };
 
+    let name = raw.name.map(|n| n.trim().to_string()).unwrap_or_default();
     if name.is_empty() {
         return None;
     }
 
+    // Versions like `1.0` parse as numbers in YAML; keep their text form
+    let version = raw.version.and_then(|v| match v {
+        serde_yaml::Value::String(s) => Some(s),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    });
+
     Some((
         SkillMeta {
             name,
-            description,
-            requires_bins,
-            requires_env,
+            description: raw.description.map(|d| d.trim().to_string()).unwrap_or_default(),
+            version,
+            requires_bins: raw.requires.bins.0,
+            requires_env: raw.requires.env.0,
+            requires_files: raw.requires.files.0,
+            providers: raw.providers.0,
+            agents: raw.agents.0,
+            teams: raw.teams.0,
+            triggers: raw.triggers.0,
+            os: raw.os.0,
         },
         body.to_string(),
     ))
 }
 
-/// Check if a skill is eligible based on binary availability, env vars, and overrides.
+/// Check if a skill is eligible for this agent.
 pub fn is_skill_eligible(
     skill: &Skill,
     overrides: &HashMap<String, SkillOverride>,
+    ctx: &EligibilityContext,
 ) -> bool {
+    skill_ineligibility_reasons(skill, overrides, ctx).is_empty()
+}
+
+/// Explain why a skill is not eligible. Returns an empty list when it is.
+/// Checks overrides, OS, provider, agent/team allowlists, and required
+/// binaries, env vars and files.
+pub fn skill_ineligibility_reasons(
+    skill: &Skill,
+    overrides: &HashMap<String, SkillOverride>,
+    ctx: &EligibilityContext,
+) -> Vec<String> {
+    let meta = &skill.meta;
+    let mut reasons = Vec::new();
+
     // Check explicit override
-    if let Some(ov) = overrides.get(&skill.meta.name) {
+    if let Some(ov) = overrides.get(&meta.name) {
         if !ov.enabled {
-            return false;
+            reasons.push("disabled in settings".to_string());
+        }
+    }
+
+    if !meta.os.is_empty() && !meta.os.iter().any(|os| os_matches(os)) {
+        reasons.push(format!(
+            "requires OS {} (running on {})",
+            meta.os.join("/"),
+            std::env::consts::OS
+        ));
+    }
+
+    if !meta.providers.is_empty()
+        && !meta.providers.iter().any(|p| p.eq_ignore_ascii_case(ctx.provider))
+    {
+        reasons.push(format!(
+            "restricted to providers {} (agent uses {})",
+            meta.providers.join(", "),
+            ctx.provider
+        ));
+    }
+
+    // Agent and team allowlists: allowed if either list admits the agent
+    if !meta.agents.is_empty() || !meta.teams.is_empty() {
+        let agent_ok = meta.agents.iter().any(|a| a == ctx.agent_id);
+        let team_ok = meta.teams.iter().any(|t| ctx.team_ids.contains(t));
+        if !agent_ok && !team_ok {
+            let mut allowed = Vec::new();
+            if !meta.agents.is_empty() {
+                allowed.push(format!("agents {}", meta.agents.join(", ")));
+            }
+            if !meta.teams.is_empty() {
+                allowed.push(format!("teams {}", meta.teams.join(", ")));
+            }
+            reasons.push(format!("restricted to {}", allowed.join(" and ")));
         }
     }
 
     // Check required binaries
-    for bin in &skill.meta.requires_bins {
+    for bin in &meta.requires_bins {
         if !check_bin_available(bin) {
-            return false;
+            reasons.push(format!("missing binary '{}'", bin));
         }
     }
 
     // Check required env vars
-    for var in &skill.meta.requires_env {
+    for var in &meta.requires_env {
         if std::env::var(var).is_err() {
-            return false;
+            reasons.push(format!("missing env var {}", var));
         }
     }
 
-    true
+    // Check required files
+    for file in &meta.requires_files {
+        let path = Path::new(file);
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            ctx.working_dir.join(path)
+        };
+        if !path.exists() {
+            reasons.push(format!("missing file {}", path.display()));
+        }
+    }
+
+    reasons
+}
+
+/// Check whether a message contains any trigger keyword
+/// (case-insensitive, word boundary).
+pub fn matches_triggers(message: &str, triggers: &[String]) -> bool {
+    triggers.iter().any(|trigger| {
+        let re_str = format!(r"(?i)\b{}\b", regex::escape(trigger.trim()));
+        Regex::new(&re_str)
+            .map(|re| re.is_match(message))
+            .unwrap_or(false)
+    })
+}
+
+/// Match an `os` entry against the current platform. Accepts `darwin` for macOS.
+fn os_matches(os: &str) -> bool {
+    let os = os.trim().to_lowercase();
+    let current = std::env::consts::OS;
+    os == current || (os == "darwin" && current == "macos")
 }
 
 /// Check if a binary is available on PATH.
@@ -212,11 +334,40 @@ pub fn check_bin_available(bin: &str) -> bool {
 }
 
 /// Format eligible skills into a context string for injection.
-pub fn format_skills_for_context(skills: &[Skill]) -> String {
+///
+/// Every skill is listed in a compact index (name and description). Full
+/// instructions are only included for skills whose triggers match `message`;
+/// the agent can request the rest with a `[load_skill: name]` tag.
+pub fn format_skills_for_context(skills: &[Skill], message: Option<&str>) -> String {
     if skills.is_empty() {
         return String::new();
     }
 
+    let mut index = vec![
+        "Available skills. To load a skill's full instructions, reply with only `[load_skill: name]`."
+            .to_string(),
+    ];
+    let mut triggered: Vec<&Skill> = Vec::new();
+    for skill in skills {
+        index.push(format!("- {}: {}", skill.meta.name, skill.meta.description));
+        let is_triggered = message
+            .map(|m| matches_triggers(m, &skill.meta.triggers))
+            .unwrap_or(false);
+        if is_triggered {
+            triggered.push(skill);
+        }
+    }
+
+    let mut text = index.join("\n");
+    if !triggered.is_empty() {
+        text.push_str("\n\n");
+        text.push_str(&format_skill_instructions(&triggered));
+    }
+    text
+}
+
+/// Format the full instructions of the given skills.
+pub fn format_skill_instructions(skills: &[&Skill]) -> String {
     let mut parts = Vec::new();
     for skill in skills {
         parts.push(format!(
@@ -228,16 +379,53 @@ pub fn format_skills_for_context(skills: &[Skill]) -> String {
     parts.join("\n\n---\n\n")
 }
 
+/// Extract skill names requested with `[load_skill: name]` tags (deduplicated, in order).
+pub fn extract_skill_requests(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for cap in SKILL_REQUEST_RE.captures_iter(text) {
+        for name in cap[1].split(',') {
+            let name = name.trim().to_string();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Remove `[load_skill: ...]` tags from a response.
+pub fn strip_skill_requests(text: &str) -> String {
+    SKILL_REQUEST_RE.replace_all(text, "").trim().to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    fn test_ctx() -> EligibilityContext<'static> {
+        EligibilityContext {
+            agent_id: "coder",
+            provider: "anthropic",
+            team_ids: &[],
+            working_dir: Path::new("/"),
+        }
+    }
+
+    fn skill_with(meta: SkillMeta) -> Skill {
+        Skill {
+            meta,
+            instructions: String::new(),
+            source_dir: PathBuf::new(),
+        }
+    }
+
     #[test]
     fn test_parse_skill_frontmatter() {
         let content = r#"---
 name: github
 description: "Interact with GitHub using the gh CLI"
+version: "1.2.0"
 requires:
   bins:
     - gh
@@ -252,6 +440,7 @@ Use `gh` to manage repos and PRs.
         let (meta, body) = parse_skill_frontmatter(content).unwrap();
         assert_eq!(meta.name, "github");
         assert_eq!(meta.description, "Interact with GitHub using the gh CLI");
+        assert_eq!(meta.version.as_deref(), Some("1.2.0"));
         assert_eq!(meta.requires_bins, vec!["gh"]);
         assert_eq!(meta.requires_env, vec!["GITHUB_TOKEN"]);
         assert!(body.contains("GitHub Skill"));
@@ -349,8 +538,7 @@ Just a simple skill.
             meta: SkillMeta {
                 name: "test".to_string(),
                 description: "test".to_string(),
-                requires_bins: vec![],
-                requires_env: vec![],
+                ..Default::default()
             },
             instructions: String::new(),
             source_dir: PathBuf::new(),
@@ -359,7 +547,7 @@ Just a simple skill.
         let mut overrides = HashMap::new();
         overrides.insert("test".to_string(), SkillOverride { enabled: false });
 
-        assert!(!is_skill_eligible(&skill, &overrides));
+        assert!(!is_skill_eligible(&skill, &overrides, &test_ctx()));
     }
 
     #[test]
@@ -368,14 +556,141 @@ Just a simple skill.
             meta: SkillMeta {
                 name: "test".to_string(),
                 description: "test".to_string(),
-                requires_bins: vec![],
                 requires_env: vec!["RUSTYCLAW_NONEXISTENT_VAR_12345".to_string()],
+                ..Default::default()
             },
             instructions: String::new(),
             source_dir: PathBuf::new(),
         };
 
-        assert!(!is_skill_eligible(&skill, &HashMap::new()));
+        assert!(!is_skill_eligible(&skill, &HashMap::new(), &test_ctx()));
+    }
+
+    #[test]
+    fn test_parse_skill_full_yaml() {
+        let content = r#"---
+name: 'deploy'
+description: >
+  Deploy services
+  to production
+version: 2.0
+providers: [anthropic, openai]
+agents: [ops]
+teams:
+  - infra
+triggers: [deploy, "roll back"]
+os: linux
+requires:
+  bins: [kubectl]
+  files:
+    - .kube/config
+---
+Body"#;
+        let (meta, body) = parse_skill_frontmatter(content).unwrap();
+        assert_eq!(meta.name, "deploy");
+        assert_eq!(meta.description, "Deploy services to production");
+        assert_eq!(meta.version.as_deref(), Some("2.0"));
+        assert_eq!(meta.providers, vec!["anthropic", "openai"]);
+        assert_eq!(meta.agents, vec!["ops"]);
+        assert_eq!(meta.teams, vec!["infra"]);
+        assert_eq!(meta.triggers, vec!["deploy", "roll back"]);
+        assert_eq!(meta.os, vec!["linux"]);
+        assert_eq!(meta.requires_bins, vec!["kubectl"]);
+        assert_eq!(meta.requires_files, vec![".kube/config"]);
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn test_parse_skill_invalid_yaml() {
+        let content = "---\nname: [unclosed\n---\nbody";
+        assert!(parse_skill_frontmatter(content).is_none());
+    }
+
+    #[test]
+    fn test_skill_eligibility_provider() {
+        let skill = skill_with(SkillMeta {
+            name: "t".to_string(),
+            providers: vec!["openai".to_string()],
+            ..Default::default()
+        });
+        let reasons = skill_ineligibility_reasons(&skill, &HashMap::new(), &test_ctx());
+        assert_eq!(reasons.len(), 1);
+        assert!(reasons[0].contains("providers openai"));
+    }
+
+    #[test]
+    fn test_skill_eligibility_agent_or_team() {
+        let skill = skill_with(SkillMeta {
+            name: "t".to_string(),
+            agents: vec!["ops".to_string()],
+            teams: vec!["dev".to_string()],
+            ..Default::default()
+        });
+        assert!(!is_skill_eligible(&skill, &HashMap::new(), &test_ctx()));
+
+        let teams = vec!["dev".to_string()];
+        let ctx = EligibilityContext {
+            team_ids: &teams,
+            ..test_ctx()
+        };
+        assert!(is_skill_eligible(&skill, &HashMap::new(), &ctx));
+    }
+
+    #[test]
+    fn test_skill_eligibility_files() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(tmp.path().join("Cargo.toml"), "").unwrap();
+        let skill = skill_with(SkillMeta {
+            name: "t".to_string(),
+            requires_files: vec!["Cargo.toml".to_string()],
+            ..Default::default()
+        });
+        let ctx = EligibilityContext {
+            working_dir: tmp.path(),
+            ..test_ctx()
+        };
+        assert!(is_skill_eligible(&skill, &HashMap::new(), &ctx));
+
+        let other = TempDir::new().unwrap();
+        let ctx = EligibilityContext {
+            working_dir: other.path(),
+            ..test_ctx()
+        };
+        assert!(!is_skill_eligible(&skill, &HashMap::new(), &ctx));
+    }
+
+    #[test]
+    fn test_skill_eligibility_os() {
+        let skill = skill_with(SkillMeta {
+            name: "t".to_string(),
+            os: vec![std::env::consts::OS.to_string()],
+            ..Default::default()
+        });
+        assert!(is_skill_eligible(&skill, &HashMap::new(), &test_ctx()));
+
+        let skill = skill_with(SkillMeta {
+            name: "t".to_string(),
+            os: vec!["plan9".to_string()],
+            ..Default::default()
+        });
+        assert!(!is_skill_eligible(&skill, &HashMap::new(), &test_ctx()));
+    }
+
+    #[test]
+    fn test_matches_triggers() {
+        let triggers = vec!["deploy".to_string(), "pull request".to_string()];
+        assert!(matches_triggers("Please DEPLOY the api", &triggers));
+        assert!(matches_triggers("open a pull request", &triggers));
+        assert!(!matches_triggers("redeployment notes", &triggers));
+        assert!(!matches_triggers("anything", &[]));
+    }
+
+    #[test]
+    fn test_extract_skill_requests() {
+        let text = "Let me check.\n[load_skill: github]\n[load_skill: deploy, github]";
+        assert_eq!(extract_skill_requests(text), vec!["github", "deploy"]);
+        assert!(extract_skill_requests("no tags here").is_empty());
+        assert_eq!(strip_skill_requests(text), "Let me check.");
     }
 
     #[test]
@@ -388,25 +703,40 @@ Just a simple skill.
 
     #[test]
     fn test_format_skills_empty() {
-        assert!(format_skills_for_context(&[]).is_empty());
+        assert!(format_skills_for_context(&[], None).is_empty());
+    }
+
+    #[test]
+    fn test_format_skills_index_only() {
+        let skills = vec![skill_with(SkillMeta {
+            name: "github".to_string(),
+            description: "GitHub CLI".to_string(),
+            triggers: vec!["github".to_string()],
+            ..Default::default()
+        })];
+
+        let result = format_skills_for_context(&skills, Some("what's the weather?"));
+        assert!(result.contains("- github: GitHub CLI"));
+        assert!(result.contains("[load_skill: name]"));
+        assert!(!result.contains("### github"));
     }
 
     #[test]
-    fn test_format_skills_nonempty() {
+    fn test_format_skills_triggered() {
         let skills = vec![Skill {
             meta: SkillMeta {
                 name: "github".to_string(),
                 description: "GitHub CLI".to_string(),
-                requires_bins: vec![],
-                requires_env: vec![],
+                triggers: vec!["github".to_string()],
+                ..Default::default()
             },
             instructions: "Use gh to manage repos.".to_string(),
             source_dir: PathBuf::new(),
         }];
 
-        let result = format_skills_for_context(&skills);
+        let result = format_skills_for_context(&skills, Some("Check my GitHub issues"));
+        assert!(result.contains("- github: GitHub CLI"));
         assert!(result.contains("### github"));
-        assert!(result.contains("GitHub CLI"));
         assert!(result.contains("Use gh to manage repos."));
     }
 }
@@ -65,6 +65,7 @@ pub fn match_agent_by_content(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::ResetPolicy;
 
     fn make_agent(name: &str, patterns: Option<Vec<&str>>, priority: u32) -> AgentConfig {
         AgentConfig {
@@ -72,8 +73,9 @@ mod tests {
             provider: "anthropic".to_string(),
             model: "sonnet".to_string(),
             working_directory: String::new(),
-            reset_policy: String::new(),
+            reset_policy: ResetPolicy::default(),
             reset_hour: None,
+            timezone: None,
             idle_timeout_minutes: None,
             context_window: None,
             fallbacks: None,
@@ -0,0 +1,59 @@
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
+
+use crate::types::AgentConfig;
+
+/// Timezone used when an agent has none configured (or an invalid one).
+pub const DEFAULT_TIMEZONE: Tz = Tz::UTC;
+
+/// Parse an IANA timezone name, e.g. "America/New_York".
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.trim().parse::<Tz>().ok()
+}
+
+/// Resolve the effective timezone for an agent. Falls back to UTC when unset
+/// or unparseable, so daily resets, daily notes, and transcripts always agree.
+pub fn resolve_timezone(agent: &AgentConfig) -> Tz {
+    agent
+        .timezone
+        .as_deref()
+        .and_then(parse_timezone)
+        .unwrap_or(DEFAULT_TIMEZONE)
+}
+
+/// Convert a millisecond timestamp into a datetime in the given timezone.
+pub fn datetime_from_millis(millis: u64, tz: Tz) -> Option<DateTime<Tz>> {
+    DateTime::from_timestamp_millis(millis as i64).map(|dt| dt.with_timezone(&tz))
+}
+
+/// Current time in the given timezone.
+pub fn now_in(tz: Tz) -> DateTime<Tz> {
+    tz.from_utc_datetime(&chrono::Utc::now().naive_utc())
+}
+
+/// Today's date as `YYYY-MM-DD` in the given timezone.
+pub fn today_string(tz: Tz) -> String {
+    now_in(tz).format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("Europe/Berlin"), Some(Tz::Europe__Berlin));
+        assert_eq!(parse_timezone("UTC"), Some(Tz::UTC));
+        assert!(parse_timezone("Mars/Olympus_Mons").is_none());
+    }
+
+    #[test]
+    fn test_datetime_from_millis_crosses_midnight() {
+        // 2024-02-17 23:30 UTC is already the 18th in Tokyo
+        let millis = 1708212600000;
+        let utc = datetime_from_millis(millis, Tz::UTC).unwrap();
+        let tokyo = datetime_from_millis(millis, Tz::Asia__Tokyo).unwrap();
+        assert_eq!(utc.format("%Y-%m-%d").to_string(), "2024-02-17");
+        assert_eq!(tokyo.format("%Y-%m-%d").to_string(), "2024-02-18");
+    }
+}
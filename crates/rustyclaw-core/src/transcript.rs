@@ -2,9 +2,11 @@ use std::fs::OpenOptions;
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 
-use chrono::Utc;
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
+use crate::timezone;
+
 /// A single transcript entry (one line in a JSONL file).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranscriptEntry {
@@ -29,19 +31,19 @@ pub struct TranscriptEntry {
     pub chars_before: Option<u64>,
 }
 
-/// Get the transcript file path for today.
-pub fn transcript_file_for_today(transcripts_dir: &Path) -> PathBuf {
-    let today = Utc::now().format("%Y-%m-%d").to_string();
-    transcripts_dir.join(format!("{}.jsonl", today))
+/// Get the transcript file path for today in the agent's timezone.
+pub fn transcript_file_for_today(transcripts_dir: &Path, tz: Tz) -> PathBuf {
+    transcripts_dir.join(format!("{}.jsonl", timezone::today_string(tz)))
 }
 
-/// Append a transcript entry to today's JSONL file.
+/// Append a transcript entry to today's JSONL file (rotated at midnight in `tz`).
 pub fn append_transcript_entry(
     transcripts_dir: &Path,
     entry: &TranscriptEntry,
+    tz: Tz,
 ) -> anyhow::Result<()> {
     std::fs::create_dir_all(transcripts_dir)?;
-    let file_path = transcript_file_for_today(transcripts_dir);
+    let file_path = transcript_file_for_today(transcripts_dir, tz);
 
     let mut file = OpenOptions::new()
         .create(true)
@@ -59,12 +61,13 @@ pub fn append_transcript_entry(
 pub fn read_recent_transcript_context(
     transcripts_dir: &Path,
     max_chars: usize,
+    tz: Tz,
 ) -> String {
     if max_chars == 0 || !transcripts_dir.exists() {
         return String::new();
     }
 
-    let today = Utc::now();
+    let today = timezone::now_in(tz);
     let yesterday = today - chrono::Duration::days(1);
 
     let today_file = transcripts_dir.join(format!("{}.jsonl", today.format("%Y-%m-%d")));
@@ -96,7 +99,7 @@ pub fn read_recent_transcript_context(
     let mut total_chars = 0;
 
     for entry in entries.iter().rev() {
-        let formatted = format_transcript_entry(entry);
+        let formatted = format_transcript_entry(entry, tz);
         let entry_chars = formatted.len();
 
         if total_chars + entry_chars > max_chars && !lines.is_empty() {
@@ -113,8 +116,8 @@ pub fn read_recent_transcript_context(
 }
 
 /// Format a single transcript entry for human-readable context injection.
-fn format_transcript_entry(entry: &TranscriptEntry) -> String {
-    let timestamp = chrono::DateTime::from_timestamp((entry.timestamp / 1000) as i64, 0)
+fn format_transcript_entry(entry: &TranscriptEntry, tz: Tz) -> String {
+    let timestamp = timezone::datetime_from_millis(entry.timestamp, tz)
         .map(|dt| dt.format("%H:%M").to_string())
         .unwrap_or_else(|| "??:??".to_string());
 
@@ -159,9 +162,9 @@ mod tests {
             chars_before: None,
         };
 
-        append_transcript_entry(&dir, &entry).unwrap();
+        append_transcript_entry(&dir, &entry, Tz::UTC).unwrap();
 
-        let context = read_recent_transcript_context(&dir, 10000);
+        let context = read_recent_transcript_context(&dir, 10000, Tz::UTC);
         assert!(context.contains("user: Fix the bug"));
     }
 
@@ -169,7 +172,7 @@ mod tests {
     fn test_read_empty_transcripts() {
         let tmp = TempDir::new().unwrap();
         let dir = tmp.path().join("transcripts");
-        let context = read_recent_transcript_context(&dir, 10000);
+        let context = read_recent_transcript_context(&dir, 10000, Tz::UTC);
         assert!(context.is_empty());
     }
 
@@ -192,14 +195,26 @@ mod tests {
                 entry_type: None,
                 chars_before: None,
             };
-            append_transcript_entry(&dir, &entry).unwrap();
+            append_transcript_entry(&dir, &entry, Tz::UTC).unwrap();
         }
 
         // Read with small budget
-        let context = read_recent_transcript_context(&dir, 200);
+        let context = read_recent_transcript_context(&dir, 200, Tz::UTC);
         assert!(!context.is_empty());
         // Should not contain all 20 entries
         let line_count = context.lines().count();
         assert!(line_count < 20);
     }
+
+    #[test]
+    fn test_transcript_file_uses_timezone() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("transcripts");
+        let tz: Tz = "Pacific/Kiritimati".parse().unwrap();
+        let expected = format!("{}.jsonl", timezone::today_string(tz));
+        assert_eq!(
+            transcript_file_for_today(&dir, tz).file_name().unwrap().to_string_lossy(),
+            expected
+        );
+    }
 }
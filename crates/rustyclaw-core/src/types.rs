@@ -10,12 +10,16 @@ pub struct AgentConfig {
     /// Model shortname or full ID (e.g. "sonnet", "opus", "gpt-5.3-codex")
     pub model: String,
     pub working_directory: String,
-    /// Session reset policy: "daily", "idle", "both" (default), or "manual"
+    /// Session reset policy. See [`ResetPolicy`] for the accepted forms.
     #[serde(default)]
-    pub reset_policy: String,
-    /// Hour (0-23 UTC) at which daily reset triggers. Default: 4
+    pub reset_policy: ResetPolicy,
+    /// Hour (0-23, in `timezone`) at which daily reset triggers. Default: 4
     #[serde(default)]
     pub reset_hour: Option<u8>,
+    /// IANA timezone (e.g. "Europe/Berlin") for daily/cron resets, daily notes,
+    /// and transcript file rotation. Default: "UTC"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
     /// Minutes of inactivity before idle reset triggers. Default: 120
     #[serde(default)]
     pub idle_timeout_minutes: Option<u64>,
@@ -36,6 +40,101 @@ pub struct AgentConfig {
     pub route_priority: u32,
 }
 
+/// Session reset policy for an agent.
+///
+/// Stored in settings.json as a string: `"daily"`, `"idle"`, `"both"` (default),
+/// `"manual"`, `"cron:<expr>"` (5- or 6-field cron expression), `"messages:<n>"`
+/// (reset after N messages), or `"compactions:<n>"` (reset after N compactions).
+/// Values that don't parse load as [`ResetPolicy::Invalid`], so one typo
+/// doesn't make the whole of settings.json unreadable; `validate_settings`
+/// reports them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ResetPolicy {
+    /// Reset once a day at `reset_hour`
+    Daily,
+    /// Reset after `idle_timeout_minutes` of inactivity
+    Idle,
+    /// Reset on either the daily or idle condition
+    #[default]
+    Both,
+    /// Never reset automatically
+    Manual,
+    /// Reset whenever the cron schedule fires
+    Cron(String),
+    /// Reset once the session has handled this many messages
+    AfterMessages(u32),
+    /// Reset once the session has been compacted this many times
+    AfterCompactions(u32),
+    /// A value that doesn't parse, kept as written so saving settings doesn't
+    /// replace it. Behaves like `Both`.
+    Invalid(String),
+}
+
+impl std::str::FromStr for ResetPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parse_count = |n: &str| {
+            n.trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("invalid count in reset policy: {}", s))
+        };
+        match s {
+            "" | "both" => Ok(Self::Both),
+            "daily" => Ok(Self::Daily),
+            "idle" => Ok(Self::Idle),
+            "manual" => Ok(Self::Manual),
+            _ => {
+                if let Some(expr) = s.strip_prefix("cron:") {
+                    let expr = expr.trim();
+                    if expr.is_empty() {
+                        return Err("empty cron expression in reset policy".to_string());
+                    }
+                    crate::session::parse_cron_schedule(expr)?;
+                    Ok(Self::Cron(expr.to_string()))
+                } else if let Some(n) = s.strip_prefix("messages:") {
+                    Ok(Self::AfterMessages(parse_count(n)?))
+                } else if let Some(n) = s.strip_prefix("compactions:") {
+                    Ok(Self::AfterCompactions(parse_count(n)?))
+                } else {
+                    Err(format!("unknown reset policy: {}", s))
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ResetPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Daily => write!(f, "daily"),
+            Self::Idle => write!(f, "idle"),
+            Self::Both => write!(f, "both"),
+            Self::Manual => write!(f, "manual"),
+            Self::Cron(expr) => write!(f, "cron:{}", expr),
+            Self::AfterMessages(n) => write!(f, "messages:{}", n),
+            Self::AfterCompactions(n) => write!(f, "compactions:{}", n),
+            Self::Invalid(raw) => write!(f, "{}", raw),
+        }
+    }
+}
+
+impl From<String> for ResetPolicy {
+    fn from(s: String) -> Self {
+        s.parse().unwrap_or(Self::Invalid(s))
+    }
+}
+
+impl From<ResetPolicy> for String {
+    fn from(policy: ResetPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
 /// Team configuration from settings.json
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TeamConfig {
@@ -52,6 +151,15 @@ pub struct ChainStep {
     #[serde(rename = "agentId")]
     pub agent_id: String,
     pub response: String,
+    /// Agent that handed off to this one; `None` for the user's message
+    #[serde(rename = "fromAgent", default, skip_serializing_if = "Option::is_none")]
+    pub from_agent: Option<String>,
+    /// Epoch millis when the agent was invoked (0 if unknown)
+    #[serde(rename = "startedAt", default)]
+    pub started_at: u64,
+    /// Epoch millis when the response was recorded (0 if unknown)
+    #[serde(rename = "finishedAt", default)]
+    pub finished_at: u64,
 }
 
 /// Root settings.json structure
@@ -72,6 +180,48 @@ pub struct Settings {
     /// Skill overrides: enable/disable specific skills
     #[serde(skip_serializing_if = "Option::is_none")]
     pub skills: Option<HashMap<String, SkillOverride>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viz: Option<VizConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingConfig>,
+}
+
+/// Levels and rotation for the JSON log files in `logs/`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Level for Rusty Claw modules: error, warn, info, debug or trace
+    /// (default: info)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// Per-module levels, keyed by module path prefix, e.g.
+    /// `rustyclaw_queue::invoke` or `serenity` (other crates default to warn)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub levels: Option<HashMap<String, String>>,
+    /// Rotate when a file grows past this many megabytes (default: 10)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+    /// Also rotate on a schedule: daily, hourly or never (default: daily)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<String>,
+    /// Delete rotated files older than this many days (default: 7)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<u64>,
+    /// Rotated files kept per component (default: 10)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+}
+
+/// Access settings for the browser visualizer. Logins use the admin token in
+/// `viz_token` or the users in `viz_users.json`, both under RUSTYCLAW_HOME.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VizConfig {
+    /// Origins allowed to call the API from another site, e.g.
+    /// `https://ops.example.com` (default: same origin only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<String>>,
+    /// Hours a login stays valid (default: 24)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_hours: Option<u64>,
 }
 
 /// Per-skill override in settings.json
@@ -101,18 +251,183 @@ pub struct ChannelsConfig {
     pub discord: Option<DiscordChannelConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub telegram: Option<TelegramChannelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slack: Option<SlackChannelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<MatrixChannelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<EmailChannelConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpChannelConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DiscordChannelConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bot_token: Option<String>,
+    /// Guild channel IDs where the bot responds without being mentioned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_channels: Option<Vec<String>>,
+    /// Start one thread per conversation in guild channels (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_threads: Option<bool>,
+    /// Per-guild settings, keyed by guild ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guilds: Option<HashMap<String, DiscordGuildConfig>>,
+    /// Discord user IDs allowed to approve pairings from the bot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_user_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_responses: Option<LongResponseMode>,
+}
+
+/// Routing and activation settings for one Discord guild.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscordGuildConfig {
+    /// Agent or team ID that handles messages without an explicit @mention
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_agent: Option<String>,
+    /// Per-channel default agent or team ID, keyed by channel ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<HashMap<String, String>>,
+    /// Require a bot mention in every channel of this guild (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_mention: Option<bool>,
+}
+
+/// Slack connects over Socket Mode, which needs an app-level token
+/// (`xapp-`, `connections:write`) alongside the bot token (`xoxb-`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SlackChannelConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bot_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_token: Option<String>,
+    /// Channel IDs where the bot responds without being mentioned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_channels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_responses: Option<LongResponseMode>,
+    /// Web API base URL override, e.g. a local mock server (default: https://slack.com/api)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_base_url: Option<String>,
+}
+
+/// Matrix logs in with an access token for an existing bot account. The
+/// token must belong to a device, which holds the encryption keys.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatrixChannelConfig {
+    /// Client-server API base, e.g. `https://matrix.example.org`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homeserver_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    /// Agent or team ID that handles messages without an explicit @mention
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_agent: Option<String>,
+    /// Per-room default agent or team ID, keyed by room ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rooms: Option<HashMap<String, String>>,
+    /// Room IDs where the bot responds without being mentioned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_rooms: Option<Vec<String>>,
+    /// Accept room invites automatically (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_join: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_responses: Option<LongResponseMode>,
+}
+
+/// Email polls an IMAP mailbox and replies over SMTP. One account is used
+/// for both unless the `smtp_` credentials are set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmailChannelConfig {
+    /// Bot address, used as `From` and as the base for `bot+agent@` routing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imap_host: Option<String>,
+    /// Default: 993 with TLS, 143 without
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imap_port: Option<u16>,
+    /// Connect to IMAP over TLS (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imap_tls: Option<bool>,
+    /// Mailbox to poll (default: INBOX)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailbox: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_host: Option<String>,
+    /// Default: 587 (STARTTLS), 465 uses implicit TLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_port: Option<u16>,
+    /// Use TLS for SMTP (default: true); disable for local test servers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_password: Option<String>,
+    /// Seconds between mailbox polls (default: 30)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_interval_secs: Option<u64>,
+    /// authserv-id of the receiving MTA (e.g. `mx.example.org`). Only mail
+    /// with a passing DMARC or aligned DKIM result in an
+    /// `Authentication-Results` header from this server is accepted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trusted_authserv_id: Option<String>,
+}
+
+/// HTTP API channel: `POST /v1/messages` for CI jobs, webhooks and tools.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpChannelConfig {
+    /// Listen address (default: 127.0.0.1:8787)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind: Option<String>,
+    /// Keys accepted in `Authorization: Bearer` or `X-API-Key`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_keys: Vec<HttpApiKey>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpApiKey {
+    /// Label for logs; requests made with a key can only read their own messages
+    pub name: String,
+    pub key: String,
+}
+
+/// How a chat channel delivers responses that do not fit in one message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LongResponseMode {
+    /// Send a preview and attach the full response as a `.md` file
+    #[default]
+    Attach,
+    /// Send the full response as several messages
+    Split,
+    /// Send one message with next/previous buttons
+    Paginate,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TelegramChannelConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bot_token: Option<String>,
+    /// Group chat IDs the bot may answer in. Unset = any group it is added to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_groups: Option<Vec<String>>,
+    /// In groups, only answer when mentioned, replied to, or commanded (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_mention: Option<bool>,
+    /// Telegram user IDs allowed to approve pairings from the bot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_user_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_responses: Option<LongResponseMode>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -134,10 +449,28 @@ pub struct ProviderModelConfig {
     pub model: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MonitoringConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub heartbeat_interval: Option<u64>,
+    /// Address for the queue processor's Prometheus endpoint, e.g.
+    /// `127.0.0.1:9464` (default: not served)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_bind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracing: Option<TracingConfig>,
+}
+
+/// Where the queue processor exports trace spans. Tracing is off unless at
+/// least one destination is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TracingConfig {
+    /// OTLP/HTTP collector, e.g. `http://localhost:4318` for Jaeger
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    /// JSONL file of OTLP JSON batches; relative to RUSTYCLAW_HOME
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
 }
 
 /// Queue message format — written as JSON to incoming/processing/outgoing directories
@@ -162,6 +495,15 @@ pub struct MessageData {
     /// Internal: which agent sent this internal message
     #[serde(rename = "fromAgent", skip_serializing_if = "Option::is_none")]
     pub from_agent: Option<String>,
+    /// Where the channel client should deliver the reply
+    #[serde(rename = "replyTo", skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<ReplyTarget>,
+    /// Higher runs first among an agent's waiting messages (default 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// W3C trace context of the span that queued this message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
 }
 
 /// Outgoing response format
@@ -180,6 +522,24 @@ pub struct ResponseData {
     pub agent: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<String>>,
+    /// Reply destination echoed from the incoming message
+    #[serde(rename = "replyTo", skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<ReplyTarget>,
+}
+
+/// Reply destination recorded by a channel client when it queues a message.
+/// Carried through the queue so delivery never depends on bot memory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplyTarget {
+    /// Chat, channel, or room ID to send the reply to
+    #[serde(rename = "chatId")]
+    pub chat_id: String,
+    /// Platform message ID to reply to
+    #[serde(rename = "replyToMessageId", skip_serializing_if = "Option::is_none")]
+    pub reply_to_message_id: Option<String>,
+    /// Thread or forum topic ID within the chat
+    #[serde(rename = "threadId", skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
 }
 
 /// Metadata for a file in the queue directory
@@ -210,6 +570,8 @@ pub struct Conversation {
     pub start_time: u64,
     /// Track how many mentions each agent sent out (for inbox draining)
     pub outgoing_mentions: HashMap<String, u32>,
+    /// Reply destination from the originating message
+    pub reply_to: Option<ReplyTarget>,
 }
 
 /// Team context for a conversation
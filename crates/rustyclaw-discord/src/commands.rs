@@ -0,0 +1,460 @@
+//! Discord application commands, autocomplete, and button components.
+
+use serenity::all::{
+    ButtonStyle, Command, CommandInteraction, CommandOptionType, ComponentInteraction,
+    CreateActionRow, CreateAutocompleteResponse, CreateButton, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, Http, ResolvedValue, UserId,
+};
+use serenity::prelude::*;
+
+use rustyclaw_core::channels::{
+    apply_default_route, choices_dir, load_choice, load_pages, pages_dir, parse_choice_payload,
+    parse_page_payload, route_suggestions, save_choices,
+};
+use rustyclaw_core::config::{get_agents, get_settings, get_teams, Paths};
+use rustyclaw_core::logging::log;
+use rustyclaw_core::pairing::{approve_pairing_code, deny_pairing_code, ensure_sender_paired};
+use rustyclaw_core::types::{MessageData, ReplyTarget};
+
+use rustyclaw_channel::commands::{agent_list_text, reset_agents, team_list_text};
+use rustyclaw_channel::{now_millis, pairing_message, random_id};
+use rustyclaw_core::format::to_discord_markdown;
+
+use crate::Handler;
+
+/// Discord allows at most 25 autocomplete choices.
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+
+/// Register the global slash commands. Re-registering on every start keeps
+/// them in sync with this list.
+pub(crate) async fn register(ctx: &Context, paths: &Paths) {
+    let route_option = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::String, name, description)
+            .required(true)
+            .set_autocomplete(true)
+    };
+
+    let commands = vec![
+        CreateCommand::new("agents").description("List configured agents"),
+        CreateCommand::new("teams").description("List configured teams"),
+        CreateCommand::new("reset")
+            .description("Reset an agent's conversation")
+            .add_option(route_option("agent", "Agent to reset")),
+        CreateCommand::new("send")
+            .description("Send a message to an agent or team")
+            .add_option(route_option("agent", "Agent or team ID"))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "message", "Message to send")
+                    .required(true),
+            ),
+        CreateCommand::new("status").description("Show queue status and your pending messages"),
+        CreateCommand::new("cancel").description("Cancel your messages that are still queued"),
+    ];
+
+    match Command::set_global_commands(&ctx.http, commands).await {
+        Ok(registered) => log(
+            "INFO",
+            &format!("Registered {} slash commands", registered.len()),
+            &paths.log_file,
+        ),
+        Err(e) => log(
+            "ERROR",
+            &format!("Failed to register slash commands: {}", e),
+            &paths.log_file,
+        ),
+    }
+}
+
+/// Answer a slash command.
+pub(crate) async fn handle_command(handler: &Handler, ctx: &Context, cmd: &CommandInteraction) {
+    let paths = &handler.paths;
+    let user_id = cmd.user.id.to_string();
+    let user_name = cmd.user.name.clone();
+    log(
+        "INFO",
+        &format!("Slash command /{} from {}", cmd.data.name, user_name),
+        &paths.log_file,
+    );
+
+    let pairing = ensure_sender_paired(&paths.pairing_file, "discord", &user_id, &user_name);
+    if !pairing.approved {
+        let text = match &pairing.code {
+            Some(code) => {
+                if pairing.is_new_pending == Some(true) {
+                    notify_admins_of_pairing(&ctx.http, paths, &user_name, &user_id, code).await;
+                }
+                pairing_message(code)
+            }
+            None => "This sender is not paired yet.".to_string(),
+        };
+        respond(ctx, cmd, text, true).await;
+        return;
+    }
+
+    let option = |name: &str| -> Option<String> {
+        cmd.data.options().into_iter().find_map(|o| match o.value {
+            ResolvedValue::String(v) if o.name == name => Some(v.to_string()),
+            _ => None,
+        })
+    };
+
+    match cmd.data.name.as_str() {
+        "agents" => respond(ctx, cmd, agent_list_text(paths), true).await,
+        "teams" => respond(ctx, cmd, team_list_text(paths), true).await,
+        "reset" => {
+            let agent = option("agent").unwrap_or_default();
+            let text = reset_agents(paths, &[agent]);
+            respond(ctx, cmd, text, false).await;
+        }
+        "send" => {
+            let route = option("agent").unwrap_or_default();
+            let message = option("message").unwrap_or_default();
+            let route = route.trim_start_matches('@').to_string();
+            let queue_data = MessageData {
+                channel: "discord".to_string(),
+                sender: user_name.clone(),
+                sender_id: Some(user_id),
+                message: apply_default_route(&message, Some(&route)),
+                timestamp: now_millis(),
+                message_id: random_id(),
+                agent: None,
+                files: None,
+                conversation_id: None,
+                from_agent: None,
+                reply_to: Some(ReplyTarget {
+                    chat_id: cmd.channel_id.to_string(),
+                    reply_to_message_id: None,
+                    thread_id: None,
+                }),
+                priority: None,
+                traceparent: None,
+            };
+            if handler.enqueue(&queue_data, cmd.channel_id).await {
+                respond(ctx, cmd, format!("**{}** → @{}: {}", user_name, route, message), false).await;
+            } else {
+                respond(ctx, cmd, "Could not queue the message.".to_string(), true).await;
+            }
+        }
+        "status" => respond(ctx, cmd, status_text(handler, &user_id).await, true).await,
+        "cancel" => {
+            let cancelled = cancel_queued(handler, &user_id).await;
+            let text = if cancelled == 0 {
+                "Nothing to cancel. Messages already being processed cannot be stopped.".to_string()
+            } else {
+                format!("Cancelled {} queued message(s).", cancelled)
+            };
+            respond(ctx, cmd, text, true).await;
+        }
+        _ => respond(ctx, cmd, "Unknown command.".to_string(), true).await,
+    }
+}
+
+/// Suggest agent and team IDs while the user types a route option.
+pub(crate) async fn handle_autocomplete(handler: &Handler, ctx: &Context, ac: &CommandInteraction) {
+    let partial = ac
+        .data
+        .autocomplete()
+        .map(|o| o.value.to_string())
+        .unwrap_or_default();
+    let settings = match get_settings(&handler.paths.settings_file) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let agents = get_agents(&settings);
+    // /reset only applies to agents
+    let teams = if ac.data.name == "reset" {
+        Default::default()
+    } else {
+        get_teams(&settings)
+    };
+
+    let mut response = CreateAutocompleteResponse::new();
+    for (label, id) in route_suggestions(&agents, &teams, &partial, MAX_AUTOCOMPLETE_CHOICES) {
+        response = response.add_string_choice(label, id);
+    }
+    let _ = ac
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await;
+}
+
+/// Handle a button click: pairing approval or a `[choices: ...]` answer.
+pub(crate) async fn handle_component(handler: &Handler, ctx: &Context, comp: &ComponentInteraction) {
+    let paths = &handler.paths;
+    let custom_id = comp.data.custom_id.as_str();
+    let user_id = comp.user.id.to_string();
+    let user_name = comp.user.name.clone();
+
+    if let Some((action, code)) = custom_id
+        .strip_prefix("pair_")
+        .and_then(|rest| rest.split_once(':'))
+    {
+        if !is_admin(paths, &comp.user.id) {
+            reply_ephemeral(ctx, comp, "Only admins can approve pairings.").await;
+            return;
+        }
+        let text = match action {
+            "approve" => {
+                let result = approve_pairing_code(&paths.pairing_file, code);
+                match result.entry {
+                    Some(entry) if result.ok => {
+                        format!("Approved {} ({}) — by {}.", entry.sender, entry.sender_id, user_name)
+                    }
+                    _ => result.reason.unwrap_or_else(|| "Approval failed.".to_string()),
+                }
+            }
+            _ => match deny_pairing_code(&paths.pairing_file, code) {
+                Some(entry) => format!("Denied {} ({}) — by {}.", entry.sender, entry.sender_id, user_name),
+                None => format!("Pairing code not found: {}", code),
+            },
+        };
+        log("INFO", &format!("Pairing {}: {}", action, text), &paths.log_file);
+        update_message(ctx, comp, text).await;
+        return;
+    }
+
+    if let Some((page_id, index)) = parse_page_payload(custom_id) {
+        let Some(pages) = load_pages(&pages_dir(paths), page_id) else {
+            reply_ephemeral(ctx, comp, "This response is no longer available.").await;
+            return;
+        };
+        let Some(page) = pages.get(index) else {
+            return;
+        };
+        let message = CreateInteractionResponseMessage::new()
+            .content(to_discord_markdown(page))
+            .components(vec![page_buttons(page_id, index, pages.len())]);
+        let _ = comp
+            .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(message))
+            .await;
+        return;
+    }
+
+    if let Some((choice_id, index)) = parse_choice_payload(custom_id) {
+        let pairing = ensure_sender_paired(&paths.pairing_file, "discord", &user_id, &user_name);
+        if !pairing.approved {
+            reply_ephemeral(ctx, comp, "You need to be paired before answering.").await;
+            return;
+        }
+
+        let Some((agent, choice)) = load_choice(&choices_dir(paths), choice_id, index) else {
+            reply_ephemeral(ctx, comp, "This choice is no longer available.").await;
+            return;
+        };
+        let queue_data = MessageData {
+            channel: "discord".to_string(),
+            sender: user_name.clone(),
+            sender_id: Some(user_id),
+            message: apply_default_route(&choice, agent.as_deref()),
+            timestamp: now_millis(),
+            message_id: random_id(),
+            agent: None,
+            files: None,
+            conversation_id: None,
+            from_agent: None,
+            reply_to: Some(ReplyTarget {
+                chat_id: comp.channel_id.to_string(),
+                reply_to_message_id: Some(comp.message.id.to_string()),
+                thread_id: None,
+            }),
+            priority: None,
+            traceparent: None,
+        };
+        if handler.enqueue(&queue_data, comp.channel_id).await {
+            let text = format!("{}\n\n**{}** chose: {}", comp.message.content, user_name, choice);
+            update_message(ctx, comp, text).await;
+        } else {
+            reply_ephemeral(ctx, comp, "Could not queue your answer.").await;
+        }
+    }
+}
+
+/// Buttons for the choices of a `[choices: ...]` tag, five per row. The
+/// choices are saved on disk and each button carries only its index.
+pub(crate) fn choice_buttons(
+    paths: &Paths,
+    agent: Option<&str>,
+    choices: &[String],
+) -> anyhow::Result<Vec<CreateActionRow>> {
+    let id = save_choices(&choices_dir(paths), agent, choices)?;
+    let buttons: Vec<CreateButton> = choices
+        .iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            let label: String = choice.chars().take(80).collect();
+            CreateButton::new(format!("choice:{}:{}", id, i))
+                .label(label)
+                .style(ButtonStyle::Primary)
+        })
+        .collect();
+    Ok(buttons
+        .chunks(5)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect())
+}
+
+/// Previous/next buttons for page `index` of a paginated response.
+pub(crate) fn page_buttons(page_id: &str, index: usize, total: usize) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("page:{}:{}", page_id, index.saturating_sub(1)))
+            .label("◀ Prev")
+            .style(ButtonStyle::Secondary)
+            .disabled(index == 0),
+        CreateButton::new(format!("page_count:{}", page_id))
+            .label(format!("{}/{}", index + 1, total))
+            .style(ButtonStyle::Secondary)
+            .disabled(true),
+        CreateButton::new(format!("page:{}:{}", page_id, index + 1))
+            .label("Next ▶")
+            .style(ButtonStyle::Secondary)
+            .disabled(index + 1 >= total),
+    ])
+}
+
+/// DM every configured admin an approve/deny prompt for a new pairing request.
+pub(crate) async fn notify_admins_of_pairing(
+    http: &Http,
+    paths: &Paths,
+    sender: &str,
+    sender_id: &str,
+    code: &str,
+) {
+    let buttons = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("pair_approve:{}", code))
+            .label("Approve")
+            .style(ButtonStyle::Success),
+        CreateButton::new(format!("pair_deny:{}", code))
+            .label("Deny")
+            .style(ButtonStyle::Danger),
+    ])];
+    let text = format!(
+        "Pairing request from **{}** ({}) with code `{}`.",
+        sender, sender_id, code
+    );
+
+    for admin in admin_ids(paths) {
+        let Ok(channel) = admin.create_dm_channel(http).await else {
+            continue;
+        };
+        let builder = CreateMessage::new().content(&text).components(buttons.clone());
+        if let Err(e) = channel.send_message(http, builder).await {
+            log(
+                "WARN",
+                &format!("Failed to notify admin {} of pairing: {}", admin, e),
+                &paths.log_file,
+            );
+        }
+    }
+}
+
+// --- Helpers ---
+
+fn admin_ids(paths: &Paths) -> Vec<UserId> {
+    get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.channels)
+        .and_then(|c| c.discord)
+        .and_then(|d| d.admin_user_ids)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|id| id.parse::<u64>().ok())
+        .filter(|id| *id != 0)
+        .map(UserId::new)
+        .collect()
+}
+
+fn is_admin(paths: &Paths, user_id: &UserId) -> bool {
+    admin_ids(paths).contains(user_id)
+}
+
+/// Queue counts plus this user's own queued and in-flight Discord messages.
+async fn status_text(handler: &Handler, user_id: &str) -> String {
+    let paths = &handler.paths;
+    let count = |dir: &std::path::Path| {
+        std::fs::read_dir(dir)
+            .map(|entries| entries.flatten().count())
+            .unwrap_or(0)
+    };
+    let mine = |dir: &std::path::Path| user_queue_files(dir, user_id).len();
+
+    format!(
+        "**Queue**\nIncoming: {}\nProcessing: {}\nOutgoing: {}\n\n**Your messages**\nQueued: {}\nProcessing: {}\nAwaiting reply in this bot: {}",
+        count(&paths.queue_incoming),
+        count(&paths.queue_processing),
+        count(&paths.queue_outgoing),
+        mine(&paths.queue_incoming),
+        mine(&paths.queue_processing),
+        handler
+            .pending
+            .lock()
+            .await
+            .values()
+            .filter(|p| p.sender_id.as_deref() == Some(user_id))
+            .count(),
+    )
+}
+
+/// Remove this user's Discord messages that have not been picked up yet.
+async fn cancel_queued(handler: &Handler, user_id: &str) -> usize {
+    let files = user_queue_files(&handler.paths.queue_incoming, user_id);
+    let mut cancelled = 0;
+    let mut pending = handler.pending.lock().await;
+    for (path, message_id) in files {
+        if std::fs::remove_file(&path).is_ok() {
+            pending.remove(&message_id);
+            cancelled += 1;
+        }
+    }
+    if cancelled > 0 {
+        log(
+            "INFO",
+            &format!("Cancelled {} queued message(s) for {}", cancelled, user_id),
+            &handler.paths.log_file,
+        );
+    }
+    cancelled
+}
+
+/// Discord queue files in `dir` sent by `user_id`, with their message IDs.
+fn user_queue_files(dir: &std::path::Path, user_id: &str) -> Vec<(std::path::PathBuf, String)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("discord_"))
+        .filter_map(|e| {
+            let raw = std::fs::read_to_string(e.path()).ok()?;
+            let data: MessageData = serde_json::from_str(&raw).ok()?;
+            (data.sender_id.as_deref() == Some(user_id)).then(|| (e.path(), data.message_id))
+        })
+        .collect()
+}
+
+async fn respond(ctx: &Context, cmd: &CommandInteraction, text: String, ephemeral: bool) {
+    let message = CreateInteractionResponseMessage::new()
+        .content(text)
+        .ephemeral(ephemeral);
+    let _ = cmd
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await;
+}
+
+async fn reply_ephemeral(ctx: &Context, comp: &ComponentInteraction, text: &str) {
+    let message = CreateInteractionResponseMessage::new()
+        .content(text)
+        .ephemeral(true);
+    let _ = comp
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await;
+}
+
+/// Replace the clicked message's text and remove its buttons.
+async fn update_message(ctx: &Context, comp: &ComponentInteraction, text: String) {
+    let message = CreateInteractionResponseMessage::new()
+        .content(text)
+        .components(Vec::new());
+    let _ = comp
+        .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(message))
+        .await;
+}
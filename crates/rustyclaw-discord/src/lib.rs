@@ -1,486 +1,401 @@
 use std::collections::HashMap;
 use std::env;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
-use anyhow::Result;
-use regex::Regex;
+use anyhow::{Context as _, Result};
 use serenity::async_trait;
-use serenity::model::channel::Message;
+use serenity::builder::CreateThread;
+use serenity::model::channel::{Channel, Message};
+use serenity::model::application::Interaction;
 use serenity::model::gateway::Ready;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
 use serenity::http::Http;
 use serenity::prelude::*;
 use tokio::sync::Mutex;
 
-use rustyclaw_core::config::{get_agents, get_settings, get_teams, get_workspace_path, Paths};
-use rustyclaw_core::logging::log;
-use rustyclaw_core::pairing::ensure_sender_paired;
-use rustyclaw_core::types::{MessageData, ResponseData};
-
+use rustyclaw_channel::{
+    ingest, now_millis, pairing_gate, poll_outgoing, prepare_dirs, Attachment, AttachmentSource,
+    ChannelAdapter, Incoming, Outgoing,
+};
+use rustyclaw_core::channels::{
+    discord_default_route, discord_responds_without_mention, discord_threads_enabled,
+    thread_title,
+};
+use rustyclaw_core::format::to_discord_markdown;
+use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::logging::{self, log};
+use rustyclaw_core::types::{MessageData, ReplyTarget};
+
+mod commands;
+
+/// In-flight message, tracked only to keep the typing indicator alive.
+/// Reply routing comes from `ResponseData.reply_to`.
 struct PendingMessage {
-    channel_id: serenity::model::id::ChannelId,
-    message_id: serenity::model::id::MessageId,
+    channel_id: ChannelId,
+    /// Discord user who sent the message
+    sender_id: Option<String>,
     timestamp: u64,
 }
 
+/// How a guild message should be handled once it passes mention gating.
+struct GuildContext {
+    /// Set when the message was posted inside a thread
+    thread_id: Option<ChannelId>,
+    /// Default agent or team for the channel (or its parent, for threads)
+    default_route: Option<String>,
+    use_threads: bool,
+}
+
 struct Handler {
     paths: Arc<Paths>,
     pending: Arc<Mutex<HashMap<String, PendingMessage>>>,
+    bot_id: OnceLock<UserId>,
 }
 
-fn now_millis() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
-}
-
-fn sanitize_file_name(name: &str) -> String {
-    let base = Path::new(name)
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .to_string();
-    let cleaned: String = base
-        .chars()
-        .map(|c| if "<>:\"/\\|?*".contains(c) || c.is_control() { '_' } else { c })
-        .collect();
-    let trimmed = cleaned.trim().to_string();
-    if trimmed.is_empty() {
-        "file.bin".to_string()
-    } else {
-        trimmed
+/// Remove `<@bot>` / `<@!bot>` mentions from message content.
+fn strip_bot_mention(content: &str, bot_id: Option<&UserId>) -> String {
+    match bot_id {
+        Some(id) => content
+            .replace(&format!("<@{}>", id), "")
+            .replace(&format!("<@!{}>", id), "")
+            .trim()
+            .to_string(),
+        None => content.trim().to_string(),
     }
 }
 
-fn build_unique_file_path(dir: &Path, preferred_name: &str) -> PathBuf {
-    let clean = sanitize_file_name(preferred_name);
-    let ext = Path::new(&clean)
-        .extension()
-        .map(|e| format!(".{}", e.to_string_lossy()))
-        .unwrap_or_default();
-    let stem = Path::new(&clean)
-        .file_stem()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .to_string();
-    let mut candidate = dir.join(&clean);
-    let mut counter = 1;
-    while candidate.exists() {
-        candidate = dir.join(format!("{}_{}{}", stem, counter, ext));
-        counter += 1;
-    }
-    candidate
-}
-
-fn split_message(text: &str, max_length: usize) -> Vec<String> {
-    if text.len() <= max_length {
-        return vec![text.to_string()];
-    }
-
-    let mut chunks = Vec::new();
-    let mut remaining = text.to_string();
-
-    while !remaining.is_empty() {
-        if remaining.len() <= max_length {
-            chunks.push(remaining);
-            break;
+impl Handler {
+    /// The outgoing side of the bot, sharing this handler's in-flight state.
+    fn adapter(&self, http: &Arc<Http>) -> DiscordAdapter {
+        DiscordAdapter {
+            http: Arc::clone(http),
+            paths: Arc::clone(&self.paths),
+            pending: Arc::clone(&self.pending),
+            sent: Mutex::default(),
         }
+    }
 
-        let mut split_index = remaining[..max_length].rfind('\n').unwrap_or(0);
-        if split_index == 0 {
-            split_index = remaining[..max_length].rfind(' ').unwrap_or(0);
-        }
-        if split_index == 0 {
-            split_index = max_length;
+    /// Write a message to the incoming queue and keep typing in `typing_channel`
+    /// until the response arrives. Returns false if the message could not be queued.
+    async fn enqueue(&self, queue_data: &MessageData, typing_channel: ChannelId) -> bool {
+        if let Err(e) = rustyclaw_channel::enqueue(&self.paths, queue_data) {
+            log("ERROR", &format!("Failed to queue message: {}", e), &self.paths.log_file);
+            return false;
         }
-
-        chunks.push(remaining[..split_index].to_string());
-        remaining = remaining[split_index..].trim_start_matches('\n').to_string();
+        self.track_pending(queue_data, typing_channel).await;
+        true
     }
 
-    chunks
-}
-
-fn pairing_message(code: &str) -> String {
-    format!(
-        "This sender is not paired yet.\nYour pairing code: {}\nAsk the Rusty Claw owner to approve you with:\nrustyclaw pairing approve {}",
-        code, code
-    )
-}
-
-fn get_agent_list_text(paths: &Paths) -> String {
-    let settings = match get_settings(&paths.settings_file) {
-        Ok(s) => s,
-        Err(_) => return "Could not load agent configuration.".to_string(),
-    };
-    let agents = get_agents(&settings);
-    if agents.len() <= 1 && agents.contains_key("default") {
-        return "No agents configured. Using default single-agent mode.\n\nConfigure agents in `.rustyclaw/settings.json` or run `rustyclaw agent add`.".to_string();
-    }
-    let mut text = "**Available Agents:**\n".to_string();
-    for (id, agent) in &agents {
-        text += &format!("\n**@{}** - {}", id, agent.name);
-        text += &format!("\n  Provider: {}/{}", agent.provider, agent.model);
-        text += &format!("\n  Directory: {}", agent.working_directory);
+    async fn track_pending(&self, queue_data: &MessageData, typing_channel: ChannelId) {
+        let mut pending = self.pending.lock().await;
+        pending.insert(
+            queue_data.message_id.clone(),
+            PendingMessage {
+                channel_id: typing_channel,
+                sender_id: queue_data.sender_id.clone(),
+                timestamp: now_millis(),
+            },
+        );
+        let ten_minutes_ago = now_millis().saturating_sub(10 * 60 * 1000);
+        pending.retain(|_, v| v.timestamp >= ten_minutes_ago);
     }
-    text += "\n\nUsage: Start your message with `@agent_id` to route to a specific agent.";
-    text
-}
 
-fn get_team_list_text(paths: &Paths) -> String {
-    let settings = match get_settings(&paths.settings_file) {
-        Ok(s) => s,
-        Err(_) => return "Could not load team configuration.".to_string(),
-    };
-    let teams = get_teams(&settings);
-    if teams.is_empty() {
-        return "No teams configured.\n\nCreate a team with `rustyclaw team add`.".to_string();
-    }
-    let mut text = "**Available Teams:**\n".to_string();
-    for (id, team) in &teams {
-        text += &format!("\n**@{}** - {}", id, team.name);
-        text += &format!("\n  Agents: {}", team.agents.join(", "));
-        text += &format!("\n  Leader: @{}", team.leader_agent);
-    }
-    text += "\n\nUsage: Start your message with `@team_id` to route to a team.";
-    text
-}
+    /// Decide whether to answer a guild message. Returns `None` when the bot was
+    /// not mentioned, the channel is not allowlisted, and the message is not in
+    /// a thread the bot started.
+    async fn resolve_guild_context(
+        &self,
+        ctx: &Context,
+        msg: &Message,
+        guild_id: GuildId,
+    ) -> Option<GuildContext> {
+        let config = get_settings(&self.paths.settings_file)
+            .ok()
+            .and_then(|s| s.channels)
+            .and_then(|c| c.discord)
+            .unwrap_or_default();
+        let bot_id = self.bot_id.get().copied();
+
+        // Threads are routed and allowlisted by their parent channel
+        let (thread_id, parent_id, bot_owns_thread) = match msg.channel_id.to_channel(ctx).await {
+            Ok(Channel::Guild(gc)) if gc.thread_metadata.is_some() => (
+                Some(msg.channel_id),
+                gc.parent_id.unwrap_or(msg.channel_id),
+                bot_id.is_some() && gc.owner_id == bot_id,
+            ),
+            _ => (None, msg.channel_id, false),
+        };
 
-async fn download_file(url: &str, dest: &Path) -> Result<()> {
-    let resp = reqwest::get(url).await?;
-    let bytes = resp.bytes().await?;
-    if let Some(dir) = dest.parent() {
-        std::fs::create_dir_all(dir)?;
-    }
-    std::fs::write(dest, &bytes)?;
-    Ok(())
-}
+        let mentioned = bot_id
+            .map(|id| msg.mentions.iter().any(|u| u.id == id))
+            .unwrap_or(false);
+        let guild = guild_id.to_string();
+        let parent = parent_id.to_string();
+        if !mentioned
+            && !bot_owns_thread
+            && !discord_responds_without_mention(&config, &guild, &parent)
+        {
+            return None;
+        }
 
-fn random_id() -> String {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    let suffix: String = (0..7)
-        .map(|_| {
-            let idx = rng.gen_range(0..36u8);
-            if idx < 10 { (b'0' + idx) as char } else { (b'a' + idx - 10) as char }
+        Some(GuildContext {
+            thread_id,
+            default_route: discord_default_route(&config, &guild, &parent).map(String::from),
+            use_threads: discord_threads_enabled(&config),
         })
-        .collect();
-    format!("{}_{}", now_millis(), suffix)
+    }
 }
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _ctx: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         log(
             "INFO",
             &format!("Discord bot connected as {}", ready.user.name),
             &self.paths.log_file,
         );
-        log("INFO", "Listening for DMs...", &self.paths.log_file);
+        let _ = self.bot_id.set(ready.user.id);
+        commands::register(&ctx, &self.paths).await;
+        log("INFO", "Listening for DMs and guild mentions...", &self.paths.log_file);
     }
 
-    async fn message(&self, ctx: Context, msg: Message) {
-        if msg.author.bot {
-            return;
-        }
-        if msg.guild_id.is_some() {
-            return;
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::Command(cmd) => commands::handle_command(self, &ctx, &cmd).await,
+            Interaction::Autocomplete(ac) => commands::handle_autocomplete(self, &ctx, &ac).await,
+            Interaction::Component(comp) => commands::handle_component(self, &ctx, &comp).await,
+            _ => {}
         }
+    }
 
-        let has_attachments = !msg.attachments.is_empty();
-        let has_content = !msg.content.trim().is_empty();
-        if !has_content && !has_attachments {
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.author.bot {
             return;
         }
 
-        let sender = &msg.author.name;
-        let sender_id = msg.author.id.to_string();
-        let message_id = random_id();
+        // Guild messages need a mention, an allowlisted channel, or a bot thread
+        let guild_ctx = match msg.guild_id {
+            Some(guild_id) => match self.resolve_guild_context(&ctx, &msg, guild_id).await {
+                Some(g) => Some(g),
+                None => return,
+            },
+            None => None,
+        };
+        let content = if guild_ctx.is_some() {
+            strip_bot_mention(&msg.content, self.bot_id.get())
+        } else {
+            msg.content.clone()
+        };
 
-        let mut downloaded_files: Vec<String> = Vec::new();
-        if has_attachments {
-            for attachment in &msg.attachments {
-                let att_name = if attachment.filename.is_empty() {
+        let attachments = msg
+            .attachments
+            .iter()
+            .map(|attachment| Attachment {
+                name: if attachment.filename.is_empty() {
                     format!("discord_{}.bin", now_millis())
                 } else {
                     attachment.filename.clone()
-                };
-                let filename = format!("discord_{}_{}", message_id, att_name);
-                let local_path = build_unique_file_path(&self.paths.files_dir, &filename);
-
-                match download_file(&attachment.url, &local_path).await {
-                    Ok(_) => {
-                        log(
-                            "INFO",
-                            &format!("Downloaded attachment: {}", local_path.file_name().unwrap_or_default().to_string_lossy()),
-                            &self.paths.log_file,
-                        );
-                        downloaded_files.push(local_path.to_string_lossy().to_string());
-                    }
-                    Err(e) => {
-                        log(
-                            "ERROR",
-                            &format!("Failed to download attachment {}: {}", att_name, e),
-                            &self.paths.log_file,
-                        );
-                    }
-                }
-            }
-        }
-
-        let preview: String = msg.content.chars().take(50).collect();
-        let files_note = if !downloaded_files.is_empty() {
-            format!(" [+{} file(s)]", downloaded_files.len())
-        } else {
-            String::new()
+                },
+                source: AttachmentSource::Remote(attachment.url.clone()),
+            })
+            .collect();
+        let mut incoming = Incoming {
+            sender: msg.author.name.clone(),
+            sender_id: msg.author.id.to_string(),
+            text: content,
+            attachments,
+            reply_to: ReplyTarget {
+                chat_id: msg.channel_id.to_string(),
+                reply_to_message_id: Some(msg.id.to_string()),
+                thread_id: None,
+            },
+            default_route: guild_ctx.as_ref().and_then(|g| g.default_route.clone()),
         };
-        log(
-            "INFO",
-            &format!("Message from {}: {}{}...", sender, preview, files_note),
-            &self.paths.log_file,
-        );
-
-        let pairing = ensure_sender_paired(
-            &self.paths.pairing_file,
-            "discord",
-            &sender_id,
-            sender,
-        );
-        if !pairing.approved {
-            if let Some(code) = &pairing.code {
-                if pairing.is_new_pending == Some(true) {
-                    log(
-                        "INFO",
-                        &format!("Blocked unpaired Discord sender {} ({}) with code {}", sender, sender_id, code),
-                        &self.paths.log_file,
-                    );
-                    let _ = msg.reply(&ctx.http, pairing_message(code)).await;
-                } else {
-                    log(
-                        "INFO",
-                        &format!("Blocked pending Discord sender {} ({})", sender, sender_id),
-                        &self.paths.log_file,
-                    );
-                }
-            }
+        if incoming.text.trim().is_empty() && incoming.attachments.is_empty() {
             return;
         }
-
-        let agent_re = Regex::new(r"(?i)^[!/]agent$").unwrap();
-        if agent_re.is_match(msg.content.trim()) {
-            log("INFO", "Agent list command received", &self.paths.log_file);
-            let text = get_agent_list_text(&self.paths);
-            let _ = msg.reply(&ctx.http, text).await;
+        let adapter = self.adapter(&ctx.http);
+        if !pairing_gate(&adapter, &self.paths, &incoming).await {
             return;
         }
 
-        let team_re = Regex::new(r"(?i)^[!/]team$").unwrap();
-        if team_re.is_match(msg.content.trim()) {
-            log("INFO", "Team list command received", &self.paths.log_file);
-            let text = get_team_list_text(&self.paths);
-            let _ = msg.reply(&ctx.http, text).await;
-            return;
-        }
-
-        let reset_bare_re = Regex::new(r"(?i)^[!/]reset$").unwrap();
-        if reset_bare_re.is_match(msg.content.trim()) {
-            let _ = msg
-                .reply(&ctx.http, "Usage: `/reset @agent_id [@agent_id2 ...]`\nSpecify which agent(s) to reset.")
-                .await;
-            return;
-        }
-
-        let reset_re = Regex::new(r"(?i)^[!/]reset\s+(.+)$").unwrap();
-        if let Some(caps) = reset_re.captures(msg.content.trim()) {
-            log("INFO", "Per-agent reset command received", &self.paths.log_file);
-            let args_str = &caps[1];
-            let agent_args: Vec<String> = args_str
-                .split_whitespace()
-                .map(|a| a.trim_start_matches('@').to_lowercase())
-                .collect();
-
-            match get_settings(&self.paths.settings_file) {
-                Ok(settings) => {
-                    let agents = get_agents(&settings);
-                    let workspace_path = get_workspace_path(&settings);
-                    let mut results = Vec::new();
-                    for agent_id in &agent_args {
-                        if !agents.contains_key(agent_id) {
-                            results.push(format!("Agent '{}' not found.", agent_id));
-                            continue;
-                        }
-                        let flag_dir = workspace_path.join(agent_id);
-                        let _ = std::fs::create_dir_all(&flag_dir);
-                        let _ = std::fs::write(flag_dir.join("reset_flag"), "reset");
-                        results.push(format!(
-                            "Reset @{} ({}).",
-                            agent_id,
-                            agents[agent_id].name
-                        ));
+        // Reply in the message's thread, a new thread, or the channel itself
+        match &guild_ctx {
+            Some(GuildContext { thread_id: Some(thread_id), .. }) => {
+                incoming.reply_to.chat_id = thread_id.to_string();
+                incoming.reply_to.thread_id = Some(thread_id.to_string());
+            }
+            Some(g) if g.use_threads => {
+                let builder = CreateThread::new(thread_title(&incoming.text, 90));
+                match msg.channel_id.create_thread_from_message(&ctx.http, msg.id, builder).await {
+                    Ok(thread) => {
+                        incoming.reply_to = ReplyTarget {
+                            chat_id: thread.id.to_string(),
+                            reply_to_message_id: None,
+                            thread_id: Some(thread.id.to_string()),
+                        };
+                    }
+                    Err(e) => {
+                        log("WARN", &format!("Failed to create thread: {}", e), &self.paths.log_file);
                     }
-                    let _ = msg.reply(&ctx.http, results.join("\n")).await;
-                }
-                Err(_) => {
-                    let _ = msg.reply(&ctx.http, "Could not process reset command. Check settings.").await;
                 }
             }
-            return;
+            _ => {}
         }
+        let reply_channel = parse_reply_target(&incoming.reply_to)
+            .map(|(channel_id, _)| channel_id)
+            .unwrap_or(msg.channel_id);
 
-        let _ = msg.channel_id.broadcast_typing(&ctx.http).await;
-
-        let mut full_message = msg.content.clone();
-        if !downloaded_files.is_empty() {
-            let file_refs: Vec<String> = downloaded_files.iter().map(|f| format!("[file: {}]", f)).collect();
-            let refs_str = file_refs.join("\n");
-            if full_message.is_empty() {
-                full_message = refs_str;
-            } else {
-                full_message = format!("{}\n\n{}", full_message, refs_str);
-            }
-        }
-
-        let queue_data = MessageData {
-            channel: "discord".to_string(),
-            sender: sender.clone(),
-            sender_id: Some(sender_id),
-            message: full_message,
-            timestamp: now_millis(),
-            message_id: message_id.clone(),
-            agent: None,
-            files: if downloaded_files.is_empty() { None } else { Some(downloaded_files) },
-            conversation_id: None,
-            from_agent: None,
-        };
-
-        let queue_file = self.paths.queue_incoming.join(format!("discord_{}.json", message_id));
-        let _ = std::fs::create_dir_all(&self.paths.queue_incoming);
-        match serde_json::to_string_pretty(&queue_data) {
-            Ok(json) => {
-                let _ = std::fs::write(&queue_file, json);
-            }
-            Err(e) => {
-                log("ERROR", &format!("Failed to serialize queue data: {}", e), &self.paths.log_file);
-                return;
-            }
+        if let Some(data) = ingest(&adapter, &self.paths, incoming).await {
+            let _ = reply_channel.broadcast_typing(&ctx.http).await;
+            self.track_pending(&data, reply_channel).await;
         }
+    }
+}
 
-        log("INFO", &format!("Queued message {}", message_id), &self.paths.log_file);
+/// Convert a persisted reply target into Discord IDs.
+fn parse_reply_target(target: &ReplyTarget) -> Option<(ChannelId, Option<MessageId>)> {
+    let channel_id = target.chat_id.parse::<u64>().ok().filter(|id| *id != 0)?;
+    let reply_id = target
+        .reply_to_message_id
+        .as_deref()
+        .and_then(|id| id.parse::<u64>().ok())
+        .filter(|id| *id != 0)
+        .map(MessageId::new);
+    Some((ChannelId::new(channel_id), reply_id))
+}
 
-        self.pending.lock().await.insert(
-            message_id.clone(),
-            PendingMessage {
-                channel_id: msg.channel_id,
-                message_id: msg.id,
-                timestamp: now_millis(),
-            },
-        );
+/// Delivers responses and notices through the Discord HTTP API.
+struct DiscordAdapter {
+    http: Arc<Http>,
+    paths: Arc<Paths>,
+    pending: Arc<Mutex<HashMap<String, PendingMessage>>>,
+    /// Messages already posted for a response that failed part-way, so its
+    /// retry resumes at the step that failed instead of repeating them
+    sent: Mutex<HashMap<String, usize>>,
+}
 
-        let ten_minutes_ago = now_millis().saturating_sub(10 * 60 * 1000);
-        self.pending
-            .lock()
-            .await
-            .retain(|_, v| v.timestamp >= ten_minutes_ago);
+impl DiscordAdapter {
+    /// Post one step of a response unless an earlier attempt already did.
+    async fn send_step(
+        &self,
+        key: &str,
+        step: &mut usize,
+        channel_id: ChannelId,
+        builder: serenity::builder::CreateMessage,
+    ) -> Result<()> {
+        *step += 1;
+        let mut sent = self.sent.lock().await;
+        if sent.get(key).is_some_and(|done| *done >= *step) {
+            return Ok(());
+        }
+        channel_id.send_message(&self.http, builder).await?;
+        sent.insert(key.to_string(), *step);
+        Ok(())
     }
 }
 
-async fn check_outgoing_queue(
-    http: &Arc<Http>,
-    paths: &Paths,
-    pending: &Arc<Mutex<HashMap<String, PendingMessage>>>,
-) {
-    let outgoing = &paths.queue_outgoing;
-    let entries = match std::fs::read_dir(outgoing) {
-        Ok(e) => e,
-        Err(_) => return,
-    };
-
-    for entry in entries.flatten() {
-        let file_path = entry.path();
-        let name = entry.file_name().to_string_lossy().to_string();
-        if !name.starts_with("discord_") || !name.ends_with(".json") {
-            continue;
-        }
+#[async_trait]
+impl ChannelAdapter for DiscordAdapter {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
 
-        let raw = match std::fs::read_to_string(&file_path) {
-            Ok(r) => r,
-            Err(_) => continue,
-        };
-        let response_data: ResponseData = match serde_json::from_str(&raw) {
-            Ok(r) => r,
-            Err(e) => {
-                log("ERROR", &format!("Bad response JSON {}: {}", name, e), &paths.log_file);
-                continue;
-            }
-        };
+    fn max_message_len(&self) -> usize {
+        2000
+    }
 
-        let msg_id = &response_data.message_id;
-        let response_text = &response_data.message;
+    fn format(&self, markdown: &str) -> String {
+        to_discord_markdown(markdown)
+    }
 
-        let pending_lock = pending.lock().await;
-        let channel_id = pending_lock.get(msg_id).map(|p| p.channel_id);
-        drop(pending_lock);
+    fn interactive(&self) -> bool {
+        true
+    }
 
-        if let Some(channel_id) = channel_id {
-            if let Some(files) = &response_data.files {
-                for fp in files {
-                    if !Path::new(fp).exists() {
-                        continue;
-                    }
-                    let attachment = serenity::builder::CreateAttachment::path(fp).await;
-                    if let Ok(att) = attachment {
-                        let builder = serenity::builder::CreateMessage::new().add_file(att);
-                        let _ = channel_id.send_message(http, builder).await;
-                        log("INFO", &format!("Sent file to Discord: {}", Path::new(fp).file_name().unwrap_or_default().to_string_lossy()), &paths.log_file);
-                    }
+    async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()> {
+        let (channel_id, reply_msg_id) =
+            parse_reply_target(outgoing.target).context("invalid Discord reply target")?;
+        let key = format!("{}:{}", outgoing.response.message_id, outgoing.response.timestamp);
+        let mut step = 0;
+
+        for fp in &outgoing.files {
+            if let Ok(att) = serenity::builder::CreateAttachment::path(fp).await {
+                let builder = serenity::builder::CreateMessage::new().add_file(att);
+                let name = fp.file_name().unwrap_or_default().to_string_lossy();
+                match self.send_step(&key, &mut step, channel_id, builder).await {
+                    Ok(()) => log("INFO", &format!("Sent file to Discord: {}", name), &self.paths.log_file),
+                    Err(e) => log("WARN", &format!("Failed to send file {} to Discord: {}", name, e), &self.paths.log_file),
                 }
             }
+        }
 
-            if !response_text.is_empty() {
-                let chunks = split_message(response_text, 2000);
-                for (i, chunk) in chunks.iter().enumerate() {
-                    if i == 0 {
-                        let pending_lock = pending.lock().await;
-                        if let Some(p) = pending_lock.get(msg_id) {
-                            let builder = serenity::builder::CreateMessage::new()
-                                .content(chunk)
-                                .reference_message((channel_id, p.message_id));
-                            let _ = channel_id.send_message(http, builder).await;
-                        } else {
-                            let builder = serenity::builder::CreateMessage::new().content(chunk);
-                            let _ = channel_id.send_message(http, builder).await;
-                        }
-                        drop(pending_lock);
-                    } else {
-                        let builder = serenity::builder::CreateMessage::new().content(chunk);
-                        let _ = channel_id.send_message(http, builder).await;
+        // Choices become buttons on the last chunk
+        let chunks = &outgoing.chunks;
+        let choices = &outgoing.choices;
+        let agent = outgoing.response.agent.as_deref();
+        if let Some(page_id) = &outgoing.page_id {
+            let mut builder = serenity::builder::CreateMessage::new()
+                .content(self.format(&chunks[0]))
+                .components(vec![commands::page_buttons(page_id, 0, chunks.len())]);
+            if let Some(reply_id) = reply_msg_id {
+                builder = builder.reference_message((channel_id, reply_id));
+            }
+            self.send_step(&key, &mut step, channel_id, builder).await?;
+            if !choices.is_empty() {
+                let builder = serenity::builder::CreateMessage::new()
+                    .content("Choose an option:")
+                    .components(commands::choice_buttons(&self.paths, agent, choices)?);
+                self.send_step(&key, &mut step, channel_id, builder).await?;
+            }
+        } else {
+            let last = chunks.len().saturating_sub(1);
+            for (i, chunk) in chunks.iter().enumerate() {
+                let mut builder = serenity::builder::CreateMessage::new().content(self.format(chunk));
+                if i == 0 {
+                    if let Some(reply_id) = reply_msg_id {
+                        builder = builder.reference_message((channel_id, reply_id));
                     }
                 }
+                if i == last && !choices.is_empty() {
+                    builder = builder.components(commands::choice_buttons(&self.paths, agent, choices)?);
+                }
+                self.send_step(&key, &mut step, channel_id, builder).await?;
             }
+        }
 
-            log(
-                "INFO",
-                &format!("Sent response to {} ({} chars)", response_data.sender, response_text.len()),
-                &paths.log_file,
-            );
+        self.sent.lock().await.remove(&key);
+        self.pending.lock().await.remove(&outgoing.response.message_id);
+        Ok(())
+    }
 
-            pending.lock().await.remove(msg_id);
-            let _ = std::fs::remove_file(&file_path);
-        } else {
-            log("WARN", &format!("No pending message for {} and no senderId, cleaning up", msg_id), &paths.log_file);
-            let _ = std::fs::remove_file(&file_path);
+    async fn reply(&self, target: &ReplyTarget, text: &str) -> Result<()> {
+        let (channel_id, reply_id) = parse_reply_target(target).context("invalid Discord reply target")?;
+        let mut builder = serenity::builder::CreateMessage::new().content(self.format(text));
+        if let Some(reply_id) = reply_id {
+            builder = builder.reference_message((channel_id, reply_id));
         }
+        channel_id.send_message(&self.http, builder).await?;
+        Ok(())
+    }
+
+    async fn pairing_requested(&self, sender: &str, sender_id: &str, code: &str) {
+        commands::notify_admins_of_pairing(&self.http, &self.paths, sender, sender_id, code).await;
+    }
+
+    async fn fallback_target(&self, message_id: &str) -> Option<ReplyTarget> {
+        self.pending.lock().await.get(message_id).map(|p| ReplyTarget {
+            chat_id: p.channel_id.to_string(),
+            reply_to_message_id: None,
+            thread_id: None,
+        })
     }
 }
 
 pub async fn run(paths: Arc<Paths>) -> Result<()> {
-    let _ = std::fs::create_dir_all(&paths.queue_incoming);
-    let _ = std::fs::create_dir_all(&paths.queue_outgoing);
-    let _ = std::fs::create_dir_all(&paths.files_dir);
-    if let Some(dir) = paths.log_file.parent() {
-        let _ = std::fs::create_dir_all(dir);
-    }
+    prepare_dirs(&paths);
+    logging::init("discord", &paths);
 
     let token = env::var("DISCORD_BOT_TOKEN")
         .expect("DISCORD_BOT_TOKEN environment variable not set");
@@ -493,12 +408,14 @@ pub async fn run(paths: Arc<Paths>) -> Result<()> {
         Arc::new(Mutex::new(HashMap::new()));
 
     let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT;
 
     let handler = Handler {
         paths: Arc::clone(&paths),
         pending: Arc::clone(&pending),
+        bot_id: OnceLock::new(),
     };
 
     log("INFO", "Starting Discord client...", &paths.log_file);
@@ -508,21 +425,18 @@ pub async fn run(paths: Arc<Paths>) -> Result<()> {
         .await
         .expect("Error creating Discord client");
 
-    let http_poll = client.http.clone();
-    let paths_poll = Arc::clone(&paths);
-    let pending_poll = Arc::clone(&pending);
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
-        loop {
-            interval.tick().await;
-            check_outgoing_queue(&http_poll, &paths_poll, &pending_poll).await;
-        }
-    });
+    let adapter = DiscordAdapter {
+        http: client.http.clone(),
+        paths: Arc::clone(&paths),
+        pending: Arc::clone(&pending),
+        sent: Mutex::default(),
+    };
+    tokio::spawn(async move { poll_outgoing(&adapter, &adapter.paths, Duration::from_secs(1)).await });
 
     let http_typing = client.http.clone();
     let pending_typing = Arc::clone(&pending);
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(8));
+        let mut interval = tokio::time::interval(Duration::from_secs(8));
         loop {
             interval.tick().await;
             let pending_lock = pending_typing.lock().await;
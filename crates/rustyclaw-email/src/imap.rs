@@ -0,0 +1,177 @@
+//! Minimal IMAP4rev1 client: just enough to log in, find unseen messages,
+//! fetch them and mark them seen.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// One untagged response line, with any `{n}` literals it carried.
+#[derive(Debug, Default)]
+pub struct Untagged {
+    pub text: String,
+    pub literals: Vec<Vec<u8>>,
+}
+
+pub struct ImapSession {
+    stream: BufReader<Box<dyn Stream>>,
+    next_tag: u32,
+}
+
+/// Quote a string argument, escaping `\` and `"`.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Byte count of a trailing `{n}` literal marker, if the line ends with one.
+fn literal_len(line: &str) -> Option<usize> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let start = line.rfind('{')?;
+    line[start + 1..].strip_suffix('}')?.trim_end_matches('+').parse().ok()
+}
+
+/// UIDs from a `* SEARCH 1 2 3` response.
+pub fn parse_search(responses: &[Untagged]) -> Vec<u32> {
+    responses
+        .iter()
+        .filter_map(|r| r.text.strip_prefix("* SEARCH"))
+        .flat_map(|rest| rest.split_whitespace().filter_map(|n| n.parse().ok()))
+        .collect()
+}
+
+impl ImapSession {
+    /// Connect and read the server greeting.
+    pub async fn connect(host: &str, port: u16, tls: bool) -> Result<Self> {
+        let tcp = TcpStream::connect((host, port))
+            .await
+            .with_context(|| format!("Failed to connect to IMAP server {}:{}", host, port))?;
+        let stream: Box<dyn Stream> = if tls {
+            let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+            let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            let config = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            let name = ServerName::try_from(host.to_string()).context("invalid IMAP host name")?;
+            Box::new(TlsConnector::from(Arc::new(config)).connect(name, tcp).await?)
+        } else {
+            Box::new(tcp)
+        };
+
+        let mut session = Self { stream: BufReader::new(stream), next_tag: 1 };
+        let greeting = session.read_line().await?;
+        if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+            bail!("Unexpected IMAP greeting: {}", greeting.trim_end());
+        }
+        Ok(session)
+    }
+
+    async fn read_line(&mut self) -> Result<String> {
+        let mut buf = Vec::new();
+        if self.stream.read_until(b'\n', &mut buf).await? == 0 {
+            bail!("IMAP connection closed");
+        }
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    /// Send a command and collect untagged responses until its tagged reply.
+    async fn command(&mut self, command: &str) -> Result<Vec<Untagged>> {
+        let tag = format!("A{:04}", self.next_tag);
+        self.next_tag += 1;
+        self.stream
+            .get_mut()
+            .write_all(format!("{} {}\r\n", tag, command).as_bytes())
+            .await?;
+        self.stream.get_mut().flush().await?;
+
+        let mut responses = Vec::new();
+        loop {
+            let mut line = self.read_line().await?;
+            if let Some(status) = line.strip_prefix(&format!("{} ", tag)) {
+                if status.starts_with("OK") {
+                    return Ok(responses);
+                }
+                // Don't echo the password back into the logs
+                let verb = command.split_whitespace().next().unwrap_or_default();
+                bail!("IMAP {} failed: {}", verb, status.trim_end());
+            }
+
+            let mut response = Untagged::default();
+            while let Some(len) = literal_len(&line) {
+                let mut literal = vec![0; len];
+                self.stream.read_exact(&mut literal).await?;
+                response.literals.push(literal);
+                response.text.push_str(&line);
+                line = self.read_line().await?;
+            }
+            response.text.push_str(line.trim_end_matches(['\r', '\n']));
+            responses.push(response);
+        }
+    }
+
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        self.command(&format!("LOGIN {} {}", quote(username), quote(password))).await?;
+        Ok(())
+    }
+
+    pub async fn select(&mut self, mailbox: &str) -> Result<()> {
+        self.command(&format!("SELECT {}", quote(mailbox))).await?;
+        Ok(())
+    }
+
+    pub async fn search_unseen(&mut self) -> Result<Vec<u32>> {
+        Ok(parse_search(&self.command("UID SEARCH UNSEEN").await?))
+    }
+
+    /// Fetch the raw RFC 822 message without setting `\Seen`.
+    pub async fn fetch(&mut self, uid: u32) -> Result<Vec<u8>> {
+        let responses = self.command(&format!("UID FETCH {} (BODY.PEEK[])", uid)).await?;
+        responses
+            .into_iter()
+            .find_map(|r| r.literals.into_iter().next())
+            .with_context(|| format!("No body returned for UID {}", uid))
+    }
+
+    pub async fn mark_seen(&mut self, uid: u32) -> Result<()> {
+        self.command(&format!("UID STORE {} +FLAGS (\\Seen)", uid)).await?;
+        Ok(())
+    }
+
+    pub async fn logout(mut self) -> Result<()> {
+        let _ = self.command("LOGOUT").await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_len() {
+        assert_eq!(literal_len("* 1 FETCH (UID 5 BODY[] {342}\r\n"), Some(342));
+        assert_eq!(literal_len("* 1 FETCH (UID 5 BODY[] {12+}\r\n"), Some(12));
+        assert_eq!(literal_len("* OK ready\r\n"), None);
+    }
+
+    #[test]
+    fn test_parse_search() {
+        let responses = vec![
+            Untagged { text: "* SEARCH 3 7 12".to_string(), literals: Vec::new() },
+            Untagged { text: "* OK still here".to_string(), literals: Vec::new() },
+        ];
+        assert_eq!(parse_search(&responses), vec![3, 7, 12]);
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(r#"pa"ss\word"#), r#""pa\"ss\\word""#);
+    }
+}
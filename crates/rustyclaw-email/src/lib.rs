@@ -0,0 +1,698 @@
+//! Email channel client. Polls an IMAP mailbox for new mail and replies
+//! over SMTP, using the same file queue as the other channel clients.
+
+mod imap;
+
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment as MailAttachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use mail_parser::{MessageParser, MimeHeaders};
+use serde::{Deserialize, Serialize};
+
+use rustyclaw_channel::{
+    now_millis, poll_outgoing, prepare_dirs, random_id, try_receive, Attachment, AttachmentSource,
+    ChannelAdapter, Incoming, Outgoing,
+};
+use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::logging::{self, log};
+use rustyclaw_core::types::{EmailChannelConfig, ReplyTarget};
+
+use crate::imap::ImapSession;
+
+/// Thread metadata kept for replies; older entries are pruned.
+const MAX_THREAD_ENTRIES: usize = 1000;
+
+/// Connection settings with defaults and env overrides applied.
+#[derive(Debug, Clone)]
+struct EmailSettings {
+    address: String,
+    username: String,
+    password: String,
+    imap_host: String,
+    imap_port: u16,
+    imap_tls: bool,
+    mailbox: String,
+    smtp_host: String,
+    smtp_port: u16,
+    smtp_tls: bool,
+    smtp_username: String,
+    smtp_password: String,
+    poll_interval_secs: u64,
+    trusted_authserv_id: String,
+}
+
+impl EmailSettings {
+    fn resolve(config: &EmailChannelConfig) -> Self {
+        let username = config.username.clone().unwrap_or_default();
+        let password = env::var("EMAIL_PASSWORD").ok().or(config.password.clone()).unwrap_or_default();
+        let imap_tls = config.imap_tls.unwrap_or(true);
+        Self {
+            address: config.address.clone().unwrap_or_else(|| username.clone()),
+            imap_host: config.imap_host.clone().unwrap_or_default(),
+            imap_port: config.imap_port.unwrap_or(if imap_tls { 993 } else { 143 }),
+            imap_tls,
+            mailbox: config.mailbox.clone().unwrap_or_else(|| "INBOX".to_string()),
+            smtp_host: config.smtp_host.clone().or(config.imap_host.clone()).unwrap_or_default(),
+            smtp_port: config.smtp_port.unwrap_or(587),
+            smtp_tls: config.smtp_tls.unwrap_or(true),
+            smtp_username: config.smtp_username.clone().unwrap_or_else(|| username.clone()),
+            smtp_password: config.smtp_password.clone().unwrap_or_else(|| password.clone()),
+            poll_interval_secs: config.poll_interval_secs.unwrap_or(30).max(1),
+            trusted_authserv_id: config.trusted_authserv_id.clone().unwrap_or_default(),
+            username,
+            password,
+        }
+    }
+}
+
+/// Subject and `References` chain of a received email, for threading replies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ThreadInfo {
+    subject: String,
+    references: Vec<String>,
+    saved_at: u64,
+}
+
+fn threads_file(paths: &Paths) -> PathBuf {
+    paths.rustyclaw_home.join("email_threads.json")
+}
+
+fn load_threads(file: &Path) -> HashMap<String, ThreadInfo> {
+    std::fs::read_to_string(file)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_thread(file: &Path, message_id: &str, info: ThreadInfo) {
+    let mut threads = load_threads(file);
+    threads.insert(message_id.to_string(), info);
+    if threads.len() > MAX_THREAD_ENTRIES {
+        let mut by_age: Vec<(String, u64)> = threads.iter().map(|(k, v)| (k.clone(), v.saved_at)).collect();
+        by_age.sort_by_key(|(_, saved_at)| *saved_at);
+        for (id, _) in by_age.iter().take(threads.len() - MAX_THREAD_ENTRIES) {
+            threads.remove(id);
+        }
+    }
+    if let Ok(json) = serde_json::to_string(&threads) {
+        let _ = std::fs::write(file, json);
+    }
+}
+
+/// The `+tag` of a plus-address of the bot (`bot+coder@example.org` → `coder`).
+fn plus_tag(address: &str, bot_address: &str) -> Option<String> {
+    let (local, domain) = address.rsplit_once('@')?;
+    let (bot_local, bot_domain) = bot_address.rsplit_once('@')?;
+    let (base, tag) = local.split_once('+')?;
+    (base.eq_ignore_ascii_case(bot_local) && domain.eq_ignore_ascii_case(bot_domain) && !tag.is_empty())
+        .then(|| tag.to_lowercase())
+}
+
+/// Strip `Re:`/`Fwd:` prefixes and split off a leading `@agent` route.
+fn parse_subject(subject: &str) -> (Option<String>, String) {
+    let mut rest = subject.trim();
+    loop {
+        let lower = rest.to_lowercase();
+        match ["re:", "fwd:", "fw:", "aw:"].iter().find(|p| lower.starts_with(*p)) {
+            Some(prefix) => rest = rest[prefix.len()..].trim_start(),
+            None => break,
+        }
+    }
+    match rest.strip_prefix('@') {
+        Some(tail) => {
+            let (id, text) = tail.split_once(char::is_whitespace).unwrap_or((tail, ""));
+            (Some(id.to_lowercase()), text.trim().to_string())
+        }
+        None => (None, rest.to_string()),
+    }
+}
+
+/// Cut the quoted history and signature off a reply.
+fn strip_quoted_reply(body: &str) -> String {
+    let mut kept = Vec::new();
+    for line in body.lines() {
+        let trimmed = line.trim();
+        let attribution = trimmed.starts_with("On ") && trimmed.ends_with("wrote:");
+        if trimmed.starts_with('>')
+            || attribution
+            || trimmed == "-----Original Message-----"
+            || line.trim_end() == "--"
+        {
+            break;
+        }
+        kept.push(line);
+    }
+    kept.join("\n").trim().to_string()
+}
+
+/// Whether an `Authentication-Results` header vouches for `from_domain`:
+/// it must come from `authserv_id` and report DMARC pass or a DKIM pass for
+/// the From domain. The receiving MTA strips forged headers carrying its own
+/// authserv-id, so a matching header can be trusted.
+fn authenticates(results: &str, authserv_id: &str, from_domain: &str) -> bool {
+    let mut parts = results.split(';').map(str::trim);
+    let id = parts.next().and_then(|p| p.split_whitespace().next()).unwrap_or_default();
+    if authserv_id.is_empty() || !id.eq_ignore_ascii_case(authserv_id) {
+        return false;
+    }
+    // DKIM may sign for a parent domain (relaxed alignment)
+    let aligned = |domain: &str| {
+        let domain = domain.to_lowercase();
+        from_domain == domain || from_domain.ends_with(&format!(".{}", domain))
+    };
+    parts.any(|result| {
+        let mut props = result.split_whitespace();
+        let Some((method, outcome)) = props.next().and_then(|m| m.split_once('=')) else {
+            return false;
+        };
+        let prop = |key: &str| {
+            props
+                .clone()
+                .filter_map(|p| p.split_once('='))
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.trim_matches('"'))
+        };
+        if !outcome.eq_ignore_ascii_case("pass") {
+            return false;
+        }
+        match method.to_lowercase().as_str() {
+            "dmarc" => prop("header.from").is_none_or(|d| d.eq_ignore_ascii_case(from_domain)),
+            "dkim" => prop("header.d").is_some_and(aligned),
+            _ => false,
+        }
+    })
+}
+
+fn angle(id: &str) -> String {
+    format!("<{}>", id.trim_matches(['<', '>']))
+}
+
+fn reply_subject(subject: &str) -> String {
+    if subject.to_lowercase().starts_with("re:") {
+        subject.to_string()
+    } else if subject.is_empty() {
+        "Re: your message".to_string()
+    } else {
+        format!("Re: {}", subject)
+    }
+}
+
+struct EmailBot {
+    settings: EmailSettings,
+    paths: Arc<Paths>,
+    smtp: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl EmailBot {
+    fn new(settings: EmailSettings, paths: Arc<Paths>) -> Result<Self> {
+        let host = settings.smtp_host.as_str();
+        let builder = if !settings.smtp_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        } else if settings.smtp_port == 465 {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        };
+        let mut builder = builder.port(settings.smtp_port);
+        if !settings.smtp_username.is_empty() {
+            builder = builder.credentials(Credentials::new(
+                settings.smtp_username.clone(),
+                settings.smtp_password.clone(),
+            ));
+        }
+        Ok(Self { smtp: builder.build(), settings, paths })
+    }
+
+    /// Fetch unseen mail, queue each message, and mark it seen. Mail that
+    /// could not be queued stays unseen and is retried on the next poll.
+    async fn poll_mailbox(&self) -> Result<()> {
+        let s = &self.settings;
+        let mut session = ImapSession::connect(&s.imap_host, s.imap_port, s.imap_tls).await?;
+        session.login(&s.username, &s.password).await?;
+        session.select(&s.mailbox).await?;
+        for uid in session.search_unseen().await? {
+            let raw = session.fetch(uid).await?;
+            match self.handle_email(&raw).await {
+                Ok(()) => session.mark_seen(uid).await?,
+                Err(e) => log(
+                    "ERROR",
+                    &format!("Failed to handle email UID {}, will retry: {}", uid, e),
+                    &self.paths.log_file,
+                ),
+            }
+        }
+        session.logout().await
+    }
+
+    /// Queue one email. Errors are transient; mail that can never be handled
+    /// (unparseable, unauthenticated) is logged and counts as done.
+    async fn handle_email(&self, raw: &[u8]) -> Result<()> {
+        let paths = &self.paths;
+        let parsed = MessageParser::default().parse(raw);
+        let Some((email, from, address)) = parsed.as_ref().and_then(|email| {
+            let from = email.from()?.first()?;
+            Some((email, from, from.address()?.to_lowercase()))
+        }) else {
+            log("WARN", "Skipping email without a parseable From address", &paths.log_file);
+            return Ok(());
+        };
+        if address.eq_ignore_ascii_case(&self.settings.address) {
+            return Ok(());
+        }
+        let from_domain = address.rsplit_once('@').map(|(_, d)| d).unwrap_or_default();
+        let authenticated = email
+            .header_values("Authentication-Results")
+            .filter_map(|v| v.as_text())
+            .any(|results| authenticates(results, &self.settings.trusted_authserv_id, from_domain));
+        if !authenticated {
+            log(
+                "WARN",
+                &format!(
+                    "Ignoring email from {}: no passing DMARC/DKIM result from {}",
+                    address, self.settings.trusted_authserv_id
+                ),
+                &paths.log_file,
+            );
+            return Ok(());
+        }
+        // Never answer auto-replies and bounces; that is how mail loops start
+        let auto_submitted = email.header_raw("Auto-Submitted").map(|v| v.trim().to_lowercase());
+        if auto_submitted.is_some_and(|v| v != "no") {
+            log("INFO", &format!("Skipping auto-submitted email from {}", address), &paths.log_file);
+            return Ok(());
+        }
+        let sender = from.name().filter(|n| !n.is_empty()).unwrap_or(&address).to_string();
+
+        let subject = email.subject().unwrap_or_default().to_string();
+        let message_id = email
+            .message_id()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}@rustyclaw", random_id()));
+        let mut references: Vec<String> = email
+            .references()
+            .as_text_list()
+            .map(|ids| ids.iter().map(|id| id.to_string()).collect())
+            .unwrap_or_default();
+        let in_reply_to = email.in_reply_to().as_text().map(str::to_string);
+        if references.is_empty() {
+            references.extend(in_reply_to.clone());
+        }
+        // The first message of a thread identifies the conversation
+        let thread_root = references.first().cloned().unwrap_or_else(|| message_id.clone());
+        references.push(message_id.clone());
+        save_thread(
+            &threads_file(paths),
+            &message_id,
+            ThreadInfo { subject: subject.clone(), references, saved_at: now_millis() },
+        );
+        let target = ReplyTarget {
+            chat_id: address.clone(),
+            reply_to_message_id: Some(message_id.clone()),
+            thread_id: Some(thread_root),
+        };
+
+        let (subject_route, subject_text) = parse_subject(&subject);
+        let recipients = [email.to(), email.cc()];
+        let plus_route = recipients
+            .iter()
+            .flatten()
+            .flat_map(|list| list.iter())
+            .filter_map(|addr| addr.address())
+            .chain(email.header_raw("Delivered-To").map(str::trim))
+            .find_map(|addr| plus_tag(addr, &self.settings.address));
+
+        let body = strip_quoted_reply(&email.body_text(0).unwrap_or_default());
+        // A new thread's subject is part of the request; replies only add the body
+        let text = if in_reply_to.is_none() && !subject_text.is_empty() {
+            format!("{}\n\n{}", subject_text, body).trim().to_string()
+        } else {
+            body
+        };
+        let attachments = email
+            .attachments()
+            .map(|part| Attachment {
+                name: part.attachment_name().unwrap_or("attachment.bin").to_string(),
+                source: AttachmentSource::Bytes(part.contents().to_vec()),
+            })
+            .collect();
+
+        let incoming = Incoming {
+            sender,
+            sender_id: address,
+            text,
+            attachments,
+            reply_to: target,
+            default_route: subject_route.or(plus_route),
+        };
+        try_receive(self, paths, incoming).await?;
+        Ok(())
+    }
+
+    /// Reply in the thread of the email `target` points at.
+    async fn send_reply(&self, target: &ReplyTarget, body: &str, files: &[String]) -> Result<()> {
+        let thread = target
+            .reply_to_message_id
+            .as_ref()
+            .and_then(|id| load_threads(&threads_file(&self.paths)).remove(id))
+            .unwrap_or_default();
+
+        let mut builder = lettre::Message::builder()
+            .from(self.settings.address.parse::<Mailbox>()?)
+            .to(target.chat_id.parse::<Mailbox>()?)
+            .subject(reply_subject(&thread.subject));
+        if let Some(id) = &target.reply_to_message_id {
+            builder = builder.in_reply_to(angle(id));
+        }
+        if !thread.references.is_empty() {
+            let refs: Vec<String> = thread.references.iter().map(|r| angle(r)).collect();
+            builder = builder.references(refs.join(" "));
+        }
+
+        let mut parts = MultiPart::mixed().singlepart(SinglePart::plain(body.to_string()));
+        for file in files {
+            let path = Path::new(file);
+            let Ok(bytes) = std::fs::read(path) else {
+                continue;
+            };
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            parts = parts.singlepart(
+                MailAttachment::new(name).body(bytes, ContentType::parse("application/octet-stream")?),
+            );
+        }
+
+        self.smtp.send(builder.multipart(parts)?).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChannelAdapter for EmailBot {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    /// One email per response, however long.
+    fn max_message_len(&self) -> usize {
+        usize::MAX
+    }
+
+    fn format(&self, markdown: &str) -> String {
+        markdown.to_string()
+    }
+
+    async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()> {
+        let files: Vec<String> = outgoing.files.iter().map(|f| f.to_string_lossy().to_string()).collect();
+        self.send_reply(outgoing.target, &outgoing.chunks.concat(), &files).await
+    }
+
+    async fn reply(&self, target: &ReplyTarget, text: &str) -> Result<()> {
+        self.send_reply(target, text, &[]).await
+    }
+}
+
+pub async fn run(paths: Arc<Paths>) -> Result<()> {
+    prepare_dirs(&paths);
+    logging::init("email", &paths);
+
+    log("INFO", "Starting email client...", &paths.log_file);
+
+    let config = get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.channels)
+        .and_then(|c| c.email)
+        .unwrap_or_default();
+    let settings = EmailSettings::resolve(&config);
+    if settings.imap_host.is_empty() || settings.address.is_empty() || settings.trusted_authserv_id.is_empty() {
+        eprintln!("ERROR: email address, imap_host and trusted_authserv_id must be configured");
+        std::process::exit(1);
+    }
+
+    let poll_interval = settings.poll_interval_secs;
+    let bot = Arc::new(EmailBot::new(settings, Arc::clone(&paths))?);
+    log(
+        "INFO",
+        &format!("Email client polling {} as {}", bot.settings.imap_host, bot.settings.address),
+        &paths.log_file,
+    );
+
+    let bot_poll = Arc::clone(&bot);
+    tokio::spawn(async move { poll_outgoing(&*bot_poll, &bot_poll.paths, Duration::from_secs(1)).await });
+
+    let mut interval = tokio::time::interval(Duration::from_secs(poll_interval));
+    loop {
+        interval.tick().await;
+        if let Err(e) = bot.poll_mailbox().await {
+            log("WARN", &format!("Mailbox poll failed: {}", e), &paths.log_file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyclaw_channel::check_outgoing_queue;
+    use rustyclaw_core::pairing::{approve_pairing_code, ensure_sender_paired};
+    use rustyclaw_core::types::{MessageData, ResponseData};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::Mutex;
+
+    const RAW_EMAIL: &str = "From: Alice <Alice@example.com>\r\n\
+        To: bot+coder@example.com\r\n\
+        Subject: Fix the build\r\n\
+        Message-ID: <abc@example.com>\r\n\
+        Authentication-Results: mx.example.com; spf=pass smtp.mailfrom=example.com;\r\n \
+        dkim=pass header.d=example.com header.s=sel; dmarc=pass (p=reject) header.from=example.com\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"XX\"\r\n\
+        \r\n\
+        --XX\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        Please take a look.\r\n\
+        \r\n\
+        On Mon, Bob wrote:\r\n\
+        > old news\r\n\
+        --XX\r\n\
+        Content-Type: text/plain; name=\"build.log\"\r\n\
+        Content-Disposition: attachment; filename=\"build.log\"\r\n\
+        \r\n\
+        error: boom\r\n\
+        --XX--\r\n";
+
+    fn test_paths(home: &Path) -> Paths {
+        Paths {
+            script_dir: home.to_path_buf(),
+            rustyclaw_home: home.to_path_buf(),
+            queue_incoming: home.join("queue/incoming"),
+            queue_outgoing: home.join("queue/outgoing"),
+            queue_processing: home.join("queue/processing"),
+            log_file: home.join("logs/queue.log"),
+            settings_file: home.join("settings.json"),
+            events_dir: home.join("events"),
+            chats_dir: home.join("chats"),
+            files_dir: home.join("files"),
+            pairing_file: home.join("pairing.json"),
+            skills_dir: home.join("skills"),
+            skills_lock_file: home.join("skills.lock"),
+        }
+    }
+
+    /// Scripted IMAP server with one unseen message (UID 7). Records the
+    /// UIDs marked seen.
+    async fn start_imap(seen: Arc<Mutex<Vec<String>>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            socket.get_mut().write_all(b"* OK mock IMAP ready\r\n").await.unwrap();
+            let mut line = String::new();
+            while socket.read_line(&mut line).await.unwrap() > 0 {
+                let (tag, command) = line.trim_end().split_once(' ').unwrap();
+                let reply = if command.starts_with("UID SEARCH") {
+                    format!("* SEARCH 7\r\n{} OK done\r\n", tag)
+                } else if command.starts_with("UID FETCH 7") {
+                    format!(
+                        "* 1 FETCH (UID 7 BODY[] {{{}}}\r\n{})\r\n{} OK done\r\n",
+                        RAW_EMAIL.len(),
+                        RAW_EMAIL,
+                        tag
+                    )
+                } else if command.starts_with("UID STORE") {
+                    seen.lock().await.push(command.to_string());
+                    format!("{} OK done\r\n", tag)
+                } else if command.starts_with("LOGOUT") {
+                    format!("* BYE\r\n{} OK done\r\n", tag)
+                } else {
+                    format!("{} OK done\r\n", tag)
+                };
+                socket.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                line.clear();
+            }
+        });
+        port
+    }
+
+    /// Scripted SMTP server that stores each message's DATA.
+    async fn start_smtp(mails: Arc<Mutex<Vec<String>>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mails = Arc::clone(&mails);
+                tokio::spawn(async move {
+                    let mut socket = BufReader::new(socket);
+                    socket.get_mut().write_all(b"220 mock ESMTP\r\n").await.unwrap();
+                    let mut line = String::new();
+                    while socket.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        let upper = line.to_uppercase();
+                        let reply: &[u8] = if upper.starts_with("EHLO") {
+                            b"250-mock\r\n250 AUTH PLAIN LOGIN\r\n"
+                        } else if upper.starts_with("AUTH") {
+                            b"235 ok\r\n"
+                        } else if upper.starts_with("DATA") {
+                            socket.get_mut().write_all(b"354 go ahead\r\n").await.unwrap();
+                            let mut data = Vec::new();
+                            while !data.ends_with(b"\r\n.\r\n") {
+                                let mut byte = [0u8; 1];
+                                socket.read_exact(&mut byte).await.unwrap();
+                                data.push(byte[0]);
+                            }
+                            mails.lock().await.push(String::from_utf8_lossy(&data).into_owned());
+                            b"250 queued\r\n"
+                        } else if upper.starts_with("QUIT") {
+                            socket.get_mut().write_all(b"221 bye\r\n").await.unwrap();
+                            break;
+                        } else {
+                            b"250 ok\r\n"
+                        };
+                        socket.get_mut().write_all(reply).await.unwrap();
+                        line.clear();
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = Arc::new(test_paths(tmp.path()));
+        let code = ensure_sender_paired(&paths.pairing_file, "email", "alice@example.com", "Alice").code.unwrap();
+        assert!(approve_pairing_code(&paths.pairing_file, &code).ok);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mails = Arc::new(Mutex::new(Vec::new()));
+        let config = EmailChannelConfig {
+            address: Some("bot@example.com".to_string()),
+            username: Some("bot@example.com".to_string()),
+            password: Some("secret".to_string()),
+            imap_host: Some("127.0.0.1".to_string()),
+            imap_port: Some(start_imap(Arc::clone(&seen)).await),
+            imap_tls: Some(false),
+            smtp_port: Some(start_smtp(Arc::clone(&mails)).await),
+            smtp_tls: Some(false),
+            trusted_authserv_id: Some("mx.example.com".to_string()),
+            ..Default::default()
+        };
+        let bot = EmailBot::new(EmailSettings::resolve(&config), Arc::clone(&paths)).unwrap();
+
+        bot.poll_mailbox().await.unwrap();
+        assert_eq!(seen.lock().await.as_slice(), ["UID STORE 7 +FLAGS (\\Seen)"]);
+
+        let queued: Vec<_> = std::fs::read_dir(&paths.queue_incoming).unwrap().flatten().collect();
+        assert_eq!(queued.len(), 1);
+        let data: MessageData =
+            serde_json::from_str(&std::fs::read_to_string(queued[0].path()).unwrap()).unwrap();
+        assert_eq!(data.channel, "email");
+        assert_eq!(data.sender, "Alice");
+        assert_eq!(data.sender_id.as_deref(), Some("alice@example.com"));
+        let attachment = &data.files.as_ref().unwrap()[0];
+        assert_eq!(std::fs::read_to_string(attachment).unwrap().trim(), "error: boom");
+        assert_eq!(
+            data.message,
+            format!("@coder Fix the build\n\nPlease take a look.\n\n[file: {}]", attachment)
+        );
+        let target = data.reply_to.clone().unwrap();
+        assert_eq!(target.reply_to_message_id.as_deref(), Some("abc@example.com"));
+        assert_eq!(target.thread_id.as_deref(), Some("abc@example.com"));
+
+        // Reply with an attachment through the outgoing queue
+        let report = tmp.path().join("report.txt");
+        std::fs::write(&report, "all green").unwrap();
+        let response = ResponseData {
+            channel: "email".to_string(),
+            sender: data.sender.clone(),
+            message: "Fixed it.".to_string(),
+            original_message: data.message.clone(),
+            timestamp: now_millis(),
+            message_id: data.message_id.clone(),
+            agent: Some("coder".to_string()),
+            files: Some(vec![report.to_string_lossy().to_string()]),
+            reply_to: data.reply_to.clone(),
+        };
+        std::fs::create_dir_all(&paths.queue_outgoing).unwrap();
+        let out_file = paths.queue_outgoing.join(format!("email_{}.json", data.message_id));
+        std::fs::write(&out_file, serde_json::to_string(&response).unwrap()).unwrap();
+        check_outgoing_queue(&bot, &paths).await;
+
+        assert!(!out_file.exists());
+        let mails = mails.lock().await;
+        assert_eq!(mails.len(), 1);
+        let mail = &mails[0];
+        assert!(mail.contains("Subject: Re: Fix the build"), "{}", mail);
+        assert!(mail.contains("In-Reply-To: <abc@example.com>"), "{}", mail);
+        assert!(mail.contains("References: <abc@example.com>"), "{}", mail);
+        assert!(mail.contains("To: alice@example.com"), "{}", mail);
+        assert!(mail.contains("Fixed it."), "{}", mail);
+        assert!(mail.contains("filename=\"report.txt\""), "{}", mail);
+    }
+
+    #[test]
+    fn test_authenticates() {
+        let dmarc = "mx.example.com; spf=fail smtp.mailfrom=example.com; dmarc=pass (p=reject) header.from=example.com";
+        assert!(authenticates(dmarc, "mx.example.com", "example.com"));
+        // Only the trusted MTA counts
+        assert!(!authenticates(dmarc, "mx.other.org", "example.com"));
+        assert!(!authenticates(dmarc, "", "example.com"));
+        // DKIM must be aligned with the From domain
+        let dkim = "MX.example.com 1; dkim=pass header.d=example.com header.s=sel; dmarc=none";
+        assert!(authenticates(dkim, "mx.example.com", "mail.example.com"));
+        assert!(!authenticates(dkim, "mx.example.com", "evil.org"));
+        assert!(!authenticates("mx.example.com; dkim=pass header.d=evil.org", "mx.example.com", "example.com"));
+        assert!(!authenticates("mx.example.com; dkim=fail header.d=example.com; spf=pass", "mx.example.com", "example.com"));
+    }
+
+    #[test]
+    fn test_plus_tag() {
+        assert_eq!(plus_tag("bot+Coder@Example.com", "bot@example.com"), Some("coder".to_string()));
+        assert_eq!(plus_tag("bot@example.com", "bot@example.com"), None);
+        assert_eq!(plus_tag("other+coder@example.com", "bot@example.com"), None);
+    }
+
+    #[test]
+    fn test_parse_subject() {
+        assert_eq!(parse_subject("@coder fix the build"), (Some("coder".to_string()), "fix the build".to_string()));
+        assert_eq!(parse_subject("Re: Fwd: @dev plan"), (Some("dev".to_string()), "plan".to_string()));
+        assert_eq!(parse_subject("Re: Weekly sync"), (None, "Weekly sync".to_string()));
+    }
+
+    #[test]
+    fn test_strip_quoted_reply() {
+        let body = "Sounds good.\n\nOn Tue, 1 Oct 2024, Bot wrote:\n> earlier answer";
+        assert_eq!(strip_quoted_reply(body), "Sounds good.");
+        assert_eq!(strip_quoted_reply("Thanks\n--\nAlice"), "Thanks");
+    }
+}
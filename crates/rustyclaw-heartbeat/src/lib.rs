@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use anyhow::Result;
 
 use rustyclaw_core::config::{get_agents, get_settings, get_workspace_path, Paths};
-use rustyclaw_core::logging::log;
+use rustyclaw_core::logging::{self, log};
 use rustyclaw_core::types::{MessageData, ResponseData};
 
 fn now_millis() -> u64 {
@@ -14,6 +14,7 @@ fn now_millis() -> u64 {
 }
 
 pub async fn run(paths: Paths) -> Result<()> {
+    logging::init("heartbeat", &paths);
     let log_file = paths.rustyclaw_home.join("logs/heartbeat.log");
     if let Some(dir) = log_file.parent() {
         let _ = std::fs::create_dir_all(dir);
@@ -114,6 +115,9 @@ pub async fn run(paths: Paths) -> Result<()> {
                 files: None,
                 conversation_id: None,
                 from_agent: None,
+                reply_to: None,
+                priority: None,
+                traceparent: None,
             };
 
             let queue_file = paths.queue_incoming.join(format!("{}.json", message_id));
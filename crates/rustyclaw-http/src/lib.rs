@@ -0,0 +1,727 @@
+//! HTTP API channel. Accepts messages on `POST /v1/messages` with API-key
+//! auth and hands back responses by long-poll, server-sent events, or a
+//! callback URL.
+
+use std::convert::Infallible;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use axum::extract::{Path as AxumPath, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use async_trait::async_trait;
+use axum::{Json, Router};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::{Mutex, Notify};
+
+use rustyclaw_channel::{
+    enqueue, now_millis, poll_outgoing, prepare_dirs, random_id, ChannelAdapter, Outgoing, Undeliverable,
+};
+use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::logging::{self, log};
+use rustyclaw_core::types::{HttpApiKey, HttpChannelConfig, MessageData, ReplyTarget, ResponseData};
+
+const DEFAULT_BIND: &str = "127.0.0.1:8787";
+
+/// Upper bound for `?wait=` long-polls.
+const MAX_WAIT_SECS: u64 = 60;
+
+/// Message records (and so idempotency keys) are kept this long.
+const RECORD_RETENTION_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 100;
+
+/// Compare without short-circuiting, so response timing doesn't leak key prefixes.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Idempotency keys become message IDs and file names, so keep them tame.
+fn valid_idempotency_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN && valid_message_id(key)
+}
+
+fn valid_message_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 200
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+/// Message ID for an idempotency key. Namespaced by the API key, so clients
+/// neither collide with nor learn about each other's keys.
+fn idempotent_message_id(key_name: &str, key: &str) -> String {
+    let name: String = key_name
+        .chars()
+        .take(64)
+        .map(|c| if c.is_ascii_alphanumeric() || "-.".contains(c) { c } else { '-' })
+        .collect();
+    format!("http_{}_{}", name, key)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Queued,
+    Completed,
+}
+
+/// Everything known about one API message; persisted under `http/`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageRecord {
+    message_id: String,
+    key_name: String,
+    sender: String,
+    channel: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    callback_url: Option<String>,
+    status: Status,
+    created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<ResponseData>,
+}
+
+impl MessageRecord {
+    /// What API callers see; the owning key is not exposed.
+    fn view(&self) -> Value {
+        let mut view = json!({
+            "messageId": self.message_id,
+            "status": self.status,
+            "sender": self.sender,
+            "channel": self.channel,
+            "createdAt": self.created_at,
+        });
+        if let Some(response) = &self.response {
+            view["response"] = json!({
+                "message": response.message,
+                "agent": response.agent,
+                "files": response.files,
+                "timestamp": response.timestamp,
+            });
+        }
+        view
+    }
+}
+
+fn records_dir(paths: &Paths) -> PathBuf {
+    paths.rustyclaw_home.join("http")
+}
+
+fn load_record(dir: &Path, message_id: &str) -> Option<MessageRecord> {
+    let raw = std::fs::read_to_string(dir.join(format!("{}.json", message_id))).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+fn save_record(dir: &Path, record: &MessageRecord) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.json", record.message_id));
+    std::fs::write(path, serde_json::to_string_pretty(record)?)?;
+    Ok(())
+}
+
+fn prune_records(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let cutoff = now_millis().saturating_sub(RECORD_RETENTION_MS);
+    for entry in entries.flatten() {
+        let expired = std::fs::read_to_string(entry.path())
+            .ok()
+            .and_then(|raw| serde_json::from_str::<MessageRecord>(&raw).ok())
+            .is_some_and(|r| r.created_at < cutoff);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+struct HttpState {
+    paths: Arc<Paths>,
+    api_keys: Vec<HttpApiKey>,
+    http: reqwest::Client,
+    /// Woken whenever any response arrives; waiters re-check their record.
+    responses: Notify,
+    /// Serializes idempotency checks so duplicate requests can't both queue.
+    create_lock: Mutex<()>,
+}
+
+impl HttpState {
+    fn records_dir(&self) -> PathBuf {
+        records_dir(&self.paths)
+    }
+
+    /// Name of the API key presented in the request headers.
+    fn authenticate(&self, headers: &HeaderMap) -> Option<String> {
+        let presented = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))?
+            .trim();
+        self.api_keys
+            .iter()
+            .find(|k| !k.key.is_empty() && constant_time_eq(k.key.as_bytes(), presented.as_bytes()))
+            .map(|k| k.name.clone())
+    }
+
+    /// The caller's own record, or `None` if it doesn't exist or belongs to another key.
+    fn owned_record(&self, key_name: &str, message_id: &str) -> Option<MessageRecord> {
+        if !valid_message_id(message_id) {
+            return None;
+        }
+        load_record(&self.records_dir(), message_id).filter(|r| r.key_name == key_name)
+    }
+
+    /// Wait until the record is completed or the timeout passes.
+    async fn wait_for_response(&self, key_name: &str, message_id: &str, timeout: Duration) -> Option<MessageRecord> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register before checking so a response landing in between isn't missed
+            let notified = self.responses.notified();
+            let record = self.owned_record(key_name, message_id)?;
+            if record.status == Status::Completed {
+                return Some(record);
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Some(record);
+            }
+        }
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostMessageRequest {
+    message: String,
+    /// Who the message is from, e.g. `github` or a user name (default: the key name)
+    sender: Option<String>,
+    /// Caller-defined source label, e.g. `ci` or `webhook` (default: `api`)
+    channel: Option<String>,
+    /// Agent or team ID; same as starting the message with `@id`
+    agent: Option<String>,
+    callback_url: Option<String>,
+    /// Alternative to the `Idempotency-Key` header
+    idempotency_key: Option<String>,
+}
+
+async fn post_message(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+    Json(body): Json<PostMessageRequest>,
+) -> Response {
+    let Some(key_name) = state.authenticate(&headers) else {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid API key");
+    };
+    if body.message.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "Message cannot be empty");
+    }
+    if let Some(url) = &body.callback_url {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return error(StatusCode::BAD_REQUEST, "callbackUrl must be an http(s) URL");
+        }
+    }
+
+    let idempotency_key = headers
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or(body.idempotency_key.clone());
+    let message_id = match idempotency_key {
+        Some(key) if valid_idempotency_key(&key) => idempotent_message_id(&key_name, &key),
+        Some(_) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "Idempotency key must be 1-100 characters of letters, digits, '-', '_' or '.'",
+            )
+        }
+        None => format!("http_{}", random_id()),
+    };
+
+    let _guard = state.create_lock.lock().await;
+    if let Some(existing) = load_record(&state.records_dir(), &message_id) {
+        // Only reachable when two key names differ in characters IDs can't hold
+        if existing.key_name != key_name {
+            return error(StatusCode::CONFLICT, "Idempotency key already used by another client");
+        }
+        return (StatusCode::OK, Json(existing.view())).into_response();
+    }
+
+    let sender = body.sender.filter(|s| !s.trim().is_empty()).unwrap_or_else(|| key_name.clone());
+    let source = body.channel.filter(|c| !c.trim().is_empty()).unwrap_or_else(|| "api".to_string());
+    let record = MessageRecord {
+        message_id: message_id.clone(),
+        key_name: key_name.clone(),
+        sender: sender.clone(),
+        channel: source.clone(),
+        callback_url: body.callback_url,
+        status: Status::Queued,
+        created_at: now_millis(),
+        response: None,
+    };
+
+    let paths = &state.paths;
+    let queue_data = MessageData {
+        channel: "http".to_string(),
+        sender: sender.clone(),
+        // Scope sessions to the key as well, so clients can't share history by name
+        sender_id: Some(format!("{}:{}", key_name, sender)),
+        message: body.message,
+        timestamp: record.created_at,
+        message_id: message_id.clone(),
+        agent: body.agent.filter(|a| !a.is_empty()),
+        files: None,
+        conversation_id: None,
+        from_agent: None,
+        reply_to: Some(ReplyTarget {
+            chat_id: source,
+            reply_to_message_id: None,
+            thread_id: None,
+        }),
+        priority: None,
+        traceparent: None,
+    };
+
+    let queued = save_record(&state.records_dir(), &record).and_then(|()| enqueue(paths, &queue_data));
+    if let Err(e) = queued {
+        log("ERROR", &format!("Failed to queue API message: {}", e), &paths.log_file);
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to queue message");
+    }
+
+    log(
+        "INFO",
+        &format!("Queued API message {} from {} (key {})", message_id, sender, key_name),
+        &paths.log_file,
+    );
+    (StatusCode::ACCEPTED, Json(record.view())).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct WaitQuery {
+    /// Seconds to wait for the response before returning the current status
+    wait: Option<u64>,
+}
+
+async fn get_message(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+    AxumPath(message_id): AxumPath<String>,
+    Query(query): Query<WaitQuery>,
+) -> Response {
+    let Some(key_name) = state.authenticate(&headers) else {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid API key");
+    };
+    let wait = Duration::from_secs(query.wait.unwrap_or(0).min(MAX_WAIT_SECS));
+    match state.wait_for_response(&key_name, &message_id, wait).await {
+        Some(record) => Json(record.view()).into_response(),
+        None => error(StatusCode::NOT_FOUND, "Message not found"),
+    }
+}
+
+/// Server-sent events: the current status right away, then the response.
+async fn message_events(
+    State(state): State<Arc<HttpState>>,
+    headers: HeaderMap,
+    AxumPath(message_id): AxumPath<String>,
+) -> Response {
+    let Some(key_name) = state.authenticate(&headers) else {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid API key");
+    };
+    let Some(record) = state.owned_record(&key_name, &message_id) else {
+        return error(StatusCode::NOT_FOUND, "Message not found");
+    };
+    Sse::new(record_events(state, key_name, record))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn record_events(
+    state: Arc<HttpState>,
+    key_name: String,
+    record: MessageRecord,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    // (first event sent, done)
+    stream::unfold((false, false), move |(started, done)| {
+        let state = Arc::clone(&state);
+        let key_name = key_name.clone();
+        let record = record.clone();
+        async move {
+            if done {
+                return None;
+            }
+            if !started {
+                let finished = record.status == Status::Completed;
+                let kind = if finished { "response" } else { "status" };
+                let event = Event::default().event(kind).data(record.view().to_string());
+                return Some((Ok(event), (true, finished)));
+            }
+            loop {
+                let current = state
+                    .wait_for_response(&key_name, &record.message_id, Duration::from_secs(MAX_WAIT_SECS))
+                    .await?;
+                if current.status == Status::Completed {
+                    let event = Event::default().event("response").data(current.view().to_string());
+                    return Some((Ok(event), (true, true)));
+                }
+            }
+        }
+    })
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "ok": true }))
+}
+
+fn router(state: Arc<HttpState>) -> Router {
+    Router::new()
+        .route("/v1/health", get(health))
+        .route("/v1/messages", post(post_message))
+        .route("/v1/messages/{id}", get(get_message))
+        .route("/v1/messages/{id}/events", get(message_events))
+        .with_state(state)
+}
+
+/// POST the finished record to the caller's callback URL, with a few retries.
+async fn deliver_callback(state: &HttpState, url: &str, record: &MessageRecord) {
+    for attempt in 1..=3u32 {
+        let result = state
+            .http
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("X-RustyClaw-Message-Id", &record.message_id)
+            .body(record.view().to_string())
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        match result {
+            Ok(_) => return,
+            Err(e) => {
+                log(
+                    "WARN",
+                    &format!("Callback for {} failed (attempt {}): {}", record.message_id, attempt, e),
+                    &state.paths.log_file,
+                );
+                tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            }
+        }
+    }
+}
+
+/// Moves `http_*` responses from the outgoing queue into their records.
+struct Responder(Arc<HttpState>);
+
+#[async_trait]
+impl ChannelAdapter for Responder {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    /// Callers get the whole response at once.
+    fn max_message_len(&self) -> usize {
+        usize::MAX
+    }
+
+    fn format(&self, markdown: &str) -> String {
+        markdown.to_string()
+    }
+
+    async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()> {
+        let state = &self.0;
+        let response = outgoing.response;
+        let dir = state.records_dir();
+        // A pruned record has no caller left; a failed save is retried
+        let Some(mut record) = load_record(&dir, &response.message_id) else {
+            bail!(Undeliverable(format!("no API record for {}", response.message_id)));
+        };
+        record.status = Status::Completed;
+        record.response = Some(response.clone());
+        save_record(&dir, &record)?;
+        state.responses.notify_waiters();
+
+        if let Some(url) = record.callback_url.clone() {
+            let state = Arc::clone(state);
+            tokio::spawn(async move { deliver_callback(&state, &url, &record).await });
+        }
+        Ok(())
+    }
+
+    /// API messages skip pairing and built-in commands, so there is nobody to notify.
+    async fn reply(&self, _target: &ReplyTarget, _text: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Configured keys plus `RUSTYCLAW_HTTP_API_KEY`, if set.
+fn api_keys(config: &HttpChannelConfig) -> Vec<HttpApiKey> {
+    let mut keys = config.api_keys.clone();
+    if let Ok(key) = env::var("RUSTYCLAW_HTTP_API_KEY") {
+        if !key.is_empty() {
+            keys.push(HttpApiKey { name: "env".to_string(), key });
+        }
+    }
+    keys
+}
+
+pub async fn run(paths: Arc<Paths>) -> Result<()> {
+    prepare_dirs(&paths);
+    logging::init("http", &paths);
+
+    log("INFO", "Starting HTTP API channel...", &paths.log_file);
+
+    let config = get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.channels)
+        .and_then(|c| c.http)
+        .unwrap_or_default();
+    let api_keys = api_keys(&config);
+    if api_keys.is_empty() {
+        eprintln!("ERROR: no API keys configured (channels.http.api_keys or RUSTYCLAW_HTTP_API_KEY)");
+        std::process::exit(1);
+    }
+    prune_records(&records_dir(&paths));
+
+    let state = Arc::new(HttpState {
+        paths: Arc::clone(&paths),
+        api_keys,
+        http: reqwest::Client::new(),
+        responses: Notify::new(),
+        create_lock: Mutex::new(()),
+    });
+
+    let responder = Responder(Arc::clone(&state));
+    tokio::spawn(async move { poll_outgoing(&responder, &responder.0.paths, Duration::from_millis(500)).await });
+
+    let bind = config.bind.unwrap_or_else(|| DEFAULT_BIND.to_string());
+    let listener = tokio::net::TcpListener::bind(&bind)
+        .await
+        .with_context(|| format!("Failed to bind {}", bind))?;
+    log("INFO", &format!("HTTP API listening on {}", bind), &paths.log_file);
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyclaw_channel::check_outgoing_queue;
+
+    fn test_paths(home: &Path) -> Paths {
+        Paths {
+            script_dir: home.to_path_buf(),
+            rustyclaw_home: home.to_path_buf(),
+            queue_incoming: home.join("queue/incoming"),
+            queue_outgoing: home.join("queue/outgoing"),
+            queue_processing: home.join("queue/processing"),
+            log_file: home.join("logs/queue.log"),
+            settings_file: home.join("settings.json"),
+            events_dir: home.join("events"),
+            chats_dir: home.join("chats"),
+            files_dir: home.join("files"),
+            pairing_file: home.join("pairing.json"),
+            skills_dir: home.join("skills"),
+            skills_lock_file: home.join("skills.lock"),
+        }
+    }
+
+    async fn start(home: &Path) -> (String, Arc<HttpState>) {
+        let state = Arc::new(HttpState {
+            paths: Arc::new(test_paths(home)),
+            api_keys: vec![
+                HttpApiKey { name: "ci".to_string(), key: "ci-secret".to_string() },
+                HttpApiKey { name: "bot".to_string(), key: "bot-secret".to_string() },
+            ],
+            http: reqwest::Client::new(),
+            responses: Notify::new(),
+            create_lock: Mutex::new(()),
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = router(Arc::clone(&state));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (base, state)
+    }
+
+    /// Stand in for the queue processor: answer the one queued message.
+    fn respond(paths: &Paths, text: &str) {
+        let entry = std::fs::read_dir(&paths.queue_incoming).unwrap().flatten().next().unwrap();
+        let msg: MessageData = serde_json::from_str(&std::fs::read_to_string(entry.path()).unwrap()).unwrap();
+        let response = ResponseData {
+            channel: msg.channel.clone(),
+            sender: msg.sender.clone(),
+            message: text.to_string(),
+            original_message: msg.message.clone(),
+            timestamp: now_millis(),
+            message_id: msg.message_id.clone(),
+            agent: Some("coder".to_string()),
+            files: None,
+            reply_to: msg.reply_to.clone(),
+        };
+        std::fs::create_dir_all(&paths.queue_outgoing).unwrap();
+        let out = paths.queue_outgoing.join(format!("http_{}_{}.json", msg.message_id, now_millis()));
+        std::fs::write(out, serde_json::to_string(&response).unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_post_and_long_poll() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (base, state) = start(tmp.path()).await;
+        let client = reqwest::Client::new();
+
+        let unauthorized = client
+            .post(format!("{}/v1/messages", base))
+            .header("Content-Type", "application/json").body(json!({ "message": "hi" }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status(), 401);
+
+        let post = || {
+            client
+                .post(format!("{}/v1/messages", base))
+                .bearer_auth("ci-secret")
+                .header("Idempotency-Key", "build-42")
+                .header("Content-Type", "application/json").body(json!({ "message": "@coder why did build 42 fail?", "sender": "jenkins", "channel": "ci" }).to_string())
+                .send()
+        };
+        let first = post().await.unwrap();
+        assert_eq!(first.status(), 202);
+        let body: Value = serde_json::from_str(&first.text().await.unwrap()).unwrap();
+        assert_eq!(body["messageId"], "http_ci_build-42");
+        assert_eq!(body["status"], "queued");
+
+        // A retry with the same key is not queued twice
+        assert_eq!(post().await.unwrap().status(), 200);
+        let queued: Vec<_> = std::fs::read_dir(&state.paths.queue_incoming).unwrap().flatten().collect();
+        assert_eq!(queued.len(), 1);
+        let msg: MessageData =
+            serde_json::from_str(&std::fs::read_to_string(queued[0].path()).unwrap()).unwrap();
+        assert_eq!(msg.channel, "http");
+        assert_eq!(msg.message_id, "http_ci_build-42");
+        assert_eq!(msg.sender, "jenkins");
+        assert_eq!(msg.sender_id.as_deref(), Some("ci:jenkins"));
+        assert_eq!(msg.reply_to.as_ref().unwrap().chat_id, "ci");
+
+        // Another key can't read it, and its own build-42 is a separate message
+        let other = client
+            .get(format!("{}/v1/messages/http_ci_build-42", base))
+            .header("X-API-Key", "bot-secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(other.status(), 404);
+        let other = client
+            .post(format!("{}/v1/messages", base))
+            .header("X-API-Key", "bot-secret")
+            .header("Idempotency-Key", "build-42")
+            .header("Content-Type", "application/json")
+            .body(json!({ "message": "deploy build 42" }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(other.status(), 202);
+        let body: Value = serde_json::from_str(&other.text().await.unwrap()).unwrap();
+        assert_eq!(body["messageId"], "http_bot_build-42");
+        for entry in std::fs::read_dir(&state.paths.queue_incoming).unwrap().flatten() {
+            if entry.path() != queued[0].path() {
+                std::fs::remove_file(entry.path()).unwrap();
+            }
+        }
+
+        let poll = tokio::spawn({
+            let client = client.clone();
+            let url = format!("{}/v1/messages/http_ci_build-42?wait=10", base);
+            async move { client.get(url).bearer_auth("ci-secret").send().await.unwrap().text().await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        respond(&state.paths, "The linker ran out of memory.");
+        check_outgoing_queue(&Responder(Arc::clone(&state)), &state.paths).await;
+
+        let done: Value = serde_json::from_str(&poll.await.unwrap()).unwrap();
+        assert_eq!(done["status"], "completed");
+        assert_eq!(done["response"]["message"], "The linker ran out of memory.");
+        assert_eq!(done["response"]["agent"], "coder");
+    }
+
+    #[tokio::test]
+    async fn test_sse_and_callback() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (base, state) = start(tmp.path()).await;
+        let client = reqwest::Client::new();
+
+        // Callback receiver
+        let received: Arc<Mutex<Vec<Value>>> = Arc::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let callback_url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let hook = Router::new()
+            .route(
+                "/hook",
+                post(|State(received): State<Arc<Mutex<Vec<Value>>>>, Json(body): Json<Value>| async move {
+                    received.lock().await.push(body);
+                    StatusCode::NO_CONTENT
+                }),
+            )
+            .with_state(Arc::clone(&received));
+        tokio::spawn(async move { axum::serve(listener, hook).await.unwrap() });
+
+        let body = client
+            .post(format!("{}/v1/messages", base))
+            .bearer_auth("bot-secret")
+            .header("Content-Type", "application/json").body(json!({ "message": "summarize PR 7", "callbackUrl": callback_url }).to_string())
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_str(&body).unwrap();
+        let id = body["messageId"].as_str().unwrap().to_string();
+        assert!(id.starts_with("http_"));
+
+        let events = tokio::spawn({
+            let client = client.clone();
+            let url = format!("{}/v1/messages/{}/events", base, id);
+            async move { client.get(url).bearer_auth("bot-secret").send().await.unwrap().text().await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        respond(&state.paths, "PR 7 renames the config loader.");
+        check_outgoing_queue(&Responder(Arc::clone(&state)), &state.paths).await;
+
+        let stream = events.await.unwrap();
+        assert!(stream.contains("event: status"), "{}", stream);
+        assert!(stream.contains("event: response"), "{}", stream);
+        assert!(stream.contains("PR 7 renames the config loader."), "{}", stream);
+
+        for _ in 0..50 {
+            if !received.lock().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let received = received.lock().await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["messageId"], id.as_str());
+        assert_eq!(received[0]["response"]["message"], "PR 7 renames the config loader.");
+    }
+
+    #[test]
+    fn test_idempotency_key_validation() {
+        assert!(valid_idempotency_key("gh-delivery.1234_a"));
+        assert!(!valid_idempotency_key(""));
+        assert!(!valid_idempotency_key("../etc/passwd"));
+        assert!(!valid_idempotency_key(&"a".repeat(101)));
+        assert_eq!(idempotent_message_id("ci", "build-42"), "http_ci_build-42");
+        assert_eq!(idempotent_message_id("ci_bot/2", "x"), "http_ci-bot-2_x");
+        assert!(valid_message_id(&idempotent_message_id(&"n".repeat(300), &"a".repeat(100))));
+    }
+}
@@ -0,0 +1,771 @@
+//! Matrix channel client. Syncs through matrix-sdk and delivers responses as
+//! room messages, using the same file queue as the other channel clients.
+//!
+//! Sync state and encryption keys live in a sqlite store under
+//! `~/.rustyclaw/matrix`, so encrypted rooms work and restarts pick up from
+//! the last sync instead of replaying history.
+
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use matrix_sdk::authentication::matrix::MatrixSession;
+use matrix_sdk::attachment::AttachmentConfig;
+use matrix_sdk::config::SyncSettings;
+use matrix_sdk::event_handler::Ctx;
+use matrix_sdk::media::{MediaFormat, MediaRequestParameters};
+use matrix_sdk::room::reply::{EnforceThread, Reply};
+use matrix_sdk::ruma::api::client::filter::{Filter, FilterDefinition};
+use matrix_sdk::ruma::events::room::encrypted::OriginalSyncRoomEncryptedEvent;
+use matrix_sdk::ruma::events::room::member::StrippedRoomMemberEvent;
+use matrix_sdk::ruma::events::room::message::{AddMentions, MessageType, OriginalSyncRoomMessageEvent};
+use matrix_sdk::ruma::events::room::MediaSource;
+use matrix_sdk::ruma::{EventId, OwnedUserId, RoomId};
+use matrix_sdk::store::StateStoreDataKey;
+use matrix_sdk::{Client, Room, RoomState, SessionMeta, SessionTokens};
+use reqwest::Url;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use rustyclaw_channel::{
+    poll_outgoing, prepare_dirs, receive, Attachment, AttachmentSource, ChannelAdapter, Incoming, Outgoing,
+    Undeliverable,
+};
+use rustyclaw_core::channels::{matrix_default_route, matrix_room_allowed};
+use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::format::to_matrix_html;
+use rustyclaw_core::logging::{self, log};
+use rustyclaw_core::types::{MatrixChannelConfig, ReplyTarget};
+
+/// Matrix events are capped at 64 KiB; the HTML body counts too.
+const MAX_MESSAGE_LEN: usize = 16000;
+
+/// Long-poll timeout for `/sync`.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeline events per room per sync.
+const SYNC_TIMELINE_LIMIT: u32 = 50;
+
+/// MIME type for an outgoing file.
+fn media_type(path: &Path) -> mime::Mime {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => mime::IMAGE_PNG,
+        "jpg" | "jpeg" => mime::IMAGE_JPEG,
+        "gif" => mime::IMAGE_GIF,
+        "webp" => "image/webp".parse().unwrap_or(mime::APPLICATION_OCTET_STREAM),
+        "md" | "txt" => mime::TEXT_PLAIN,
+        "pdf" => mime::APPLICATION_PDF,
+        _ => mime::APPLICATION_OCTET_STREAM,
+    }
+}
+
+/// Validate the configured homeserver URL. The trailing slash makes
+/// `Url::join` append API paths instead of replacing the last segment.
+fn parse_homeserver(homeserver_url: &str) -> Result<Url> {
+    let mut url = Url::parse(homeserver_url).context("invalid homeserver_url")?;
+    if !matches!(url.scheme(), "http" | "https") || url.cannot_be_a_base() {
+        bail!("homeserver_url must be an http(s) URL, got {}", homeserver_url);
+    }
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+/// Look up the user and device an access token belongs to. The device ID
+/// ties the token to its encryption keys, so tokens without one are rejected.
+async fn whoami(homeserver: &Url, access_token: &str) -> Result<MatrixSession> {
+    let text = reqwest::Client::new()
+        .get(homeserver.join("_matrix/client/v3/account/whoami")?)
+        .bearer_auth(access_token)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let resp: Value = serde_json::from_str(&text)?;
+    let user_id = resp["user_id"].as_str().context("whoami returned no user_id")?;
+    let Some(device_id) = resp["device_id"].as_str() else {
+        bail!("the access token has no device; create it with a normal login so encryption can work");
+    };
+    Ok(MatrixSession {
+        meta: SessionMeta {
+            user_id: user_id.try_into()?,
+            device_id: device_id.into(),
+        },
+        tokens: SessionTokens {
+            access_token: access_token.to_string(),
+            refresh_token: None,
+        },
+    })
+}
+
+/// Build a client on the sqlite store in `store_dir` and log it in.
+async fn build_client(homeserver: &Url, store_dir: &Path, session: MatrixSession) -> Result<Client> {
+    let client = Client::builder()
+        .homeserver_url(homeserver.as_str())
+        .sqlite_store(store_dir, None)
+        .build()
+        .await
+        .with_context(|| format!("failed to open the Matrix store at {}", store_dir.display()))?;
+    client.restore_session(session).await.with_context(|| {
+        format!(
+            "failed to restore the Matrix session; if the bot account or device changed, remove {}",
+            store_dir.display()
+        )
+    })?;
+    Ok(client)
+}
+
+/// Sync settings with lazy-loaded members and no presence. Account data
+/// stays in: `m.direct` is how direct rooms are recognised.
+fn sync_settings(timeout: Duration) -> SyncSettings {
+    let mut filter = FilterDefinition::with_lazy_loading();
+    filter.room.timeline.limit = Some(SYNC_TIMELINE_LIMIT.into());
+    filter.presence = Filter::ignore_all();
+    SyncSettings::default().filter(filter.into()).timeout(timeout)
+}
+
+/// Where matrix-sdk keeps sync state and encryption keys.
+fn store_dir(paths: &Paths) -> PathBuf {
+    paths.rustyclaw_home.join("matrix")
+}
+
+/// Drop the quoted `> ` fallback that older clients prepend to replies.
+fn strip_reply_fallback(body: &str) -> String {
+    let mut lines = body.lines().peekable();
+    if !lines.peek().is_some_and(|l| l.starts_with("> ")) {
+        return body.to_string();
+    }
+    while lines.peek().is_some_and(|l| l.starts_with('>')) {
+        lines.next();
+    }
+    lines.collect::<Vec<_>>().join("\n").trim_start().to_string()
+}
+
+/// Remove mentions of the bot (its user ID, or a `Name:` prefix as
+/// clients insert for pills). Returns the cleaned text and whether the bot
+/// was mentioned.
+fn strip_mention(body: &str, user_id: &str, display_name: &str) -> (String, bool) {
+    let mut text = body.to_string();
+    let mut mentioned = false;
+    if text.contains(user_id) {
+        text = text.replace(user_id, "");
+        mentioned = true;
+    }
+    let localpart = user_id.trim_start_matches('@').split(':').next().unwrap_or_default();
+    for name in [display_name, localpart] {
+        if name.is_empty() {
+            continue;
+        }
+        let prefix = format!("{}:", name);
+        if text.len() >= prefix.len()
+            && text.is_char_boundary(prefix.len())
+            && text[..prefix.len()].eq_ignore_ascii_case(&prefix)
+        {
+            text = text[prefix.len()..].to_string();
+            mentioned = true;
+            break;
+        }
+    }
+    (text.split_whitespace().collect::<Vec<_>>().join(" "), mentioned)
+}
+
+/// Whether intentional-mention metadata (`m.mentions`) names the bot.
+fn mentions_user(content: &Value, user_id: &str) -> bool {
+    content["m.mentions"]["user_ids"]
+        .as_array()
+        .is_some_and(|ids| ids.iter().any(|id| id.as_str() == Some(user_id)))
+}
+
+/// Thread root when the event is part of a Matrix thread.
+fn thread_root(content: &Value) -> Option<String> {
+    let relates = &content["m.relates_to"];
+    if relates["rel_type"].as_str() == Some("m.thread") {
+        relates["event_id"].as_str().map(str::to_string)
+    } else {
+        None
+    }
+}
+
+/// Build the `m.room.message` content for one chunk of a response.
+fn message_content(markdown: &str, target: &ReplyTarget, first: bool) -> Value {
+    let mut content = json!({
+        "msgtype": "m.text",
+        "body": markdown,
+        "format": "org.matrix.custom.html",
+        "formatted_body": to_matrix_html(markdown),
+    });
+    if let Some(relates) = relation(target, first) {
+        content["m.relates_to"] = relates;
+    }
+    content
+}
+
+/// Thread replies stay in the thread; room replies quote the original message once.
+fn relation(target: &ReplyTarget, first: bool) -> Option<Value> {
+    let reply_to = target.reply_to_message_id.as_deref();
+    match target.thread_id.as_deref() {
+        Some(root) => Some(json!({
+            "rel_type": "m.thread",
+            "event_id": root,
+            "is_falling_back": true,
+            "m.in_reply_to": { "event_id": reply_to.unwrap_or(root) },
+        })),
+        None if first => reply_to.map(|id| json!({ "m.in_reply_to": { "event_id": id } })),
+        None => None,
+    }
+}
+
+struct MatrixBot {
+    client: Client,
+    paths: Arc<Paths>,
+    user_id: OwnedUserId,
+    display_name: String,
+    /// Threads the bot has answered in, so follow-ups need no mention.
+    active_threads: Mutex<HashSet<String>>,
+}
+
+impl MatrixBot {
+    fn config(&self) -> MatrixChannelConfig {
+        get_settings(&self.paths.settings_file)
+            .ok()
+            .and_then(|s| s.channels)
+            .and_then(|c| c.matrix)
+            .unwrap_or_default()
+    }
+
+    /// Rooms the bot has left are [`Undeliverable`].
+    fn room(&self, room_id: &str) -> Result<Room> {
+        let room_id =
+            RoomId::parse(room_id).map_err(|e| Undeliverable(format!("bad room ID {}: {}", room_id, e)))?;
+        match self.client.get_room(&room_id) {
+            Some(room) if room.state() != RoomState::Left => Ok(room),
+            _ => bail!(Undeliverable(format!("not in room {}", room_id))),
+        }
+    }
+
+    async fn handle_invite(&self, room: Room, event: StrippedRoomMemberEvent) {
+        if event.state_key != self.user_id || room.state() != RoomState::Invited {
+            return;
+        }
+        if !self.config().auto_join.unwrap_or(true) {
+            return;
+        }
+        // Joining a room the inviter flagged as direct also records it in m.direct
+        match room.join().await {
+            Ok(()) => log("INFO", &format!("Joined room {}", room.room_id()), &self.paths.log_file),
+            Err(e) => log(
+                "WARN",
+                &format!("Failed to join {}: {}", room.room_id(), e),
+                &self.paths.log_file,
+            ),
+        }
+    }
+
+    async fn handle_message(&self, room: Room, event: OriginalSyncRoomMessageEvent) {
+        let paths = &self.paths;
+        if event.sender == self.user_id || room.state() != RoomState::Joined {
+            return;
+        }
+        let config = self.config();
+        let room_id = room.room_id().to_string();
+        let content = serde_json::to_value(&event.content).unwrap_or_default();
+        // Edits arrive as new events; only the original is handled
+        if content["m.relates_to"]["rel_type"].as_str() == Some("m.replace") {
+            return;
+        }
+        let media = match &event.content.msgtype {
+            MessageType::Image(c) => Some(c.source.clone()),
+            MessageType::File(c) => Some(c.source.clone()),
+            MessageType::Audio(c) => Some(c.source.clone()),
+            MessageType::Video(c) => Some(c.source.clone()),
+            // m.notice is how bots talk; never answer those
+            MessageType::Text(_) | MessageType::Emote(_) => None,
+            _ => return,
+        };
+
+        let body = content["body"].as_str().unwrap_or_default();
+        // For media, `body` is a caption only when a separate filename is set
+        let caption = if media.is_some() {
+            match content["filename"].as_str() {
+                Some(name) if name != body => body.to_string(),
+                _ => String::new(),
+            }
+        } else {
+            strip_reply_fallback(body)
+        };
+
+        let is_direct = room.is_direct().await.unwrap_or(false);
+        let thread_id = thread_root(&content);
+        let (text, mut mentioned) = strip_mention(&caption, self.user_id.as_str(), &self.display_name);
+        mentioned |= mentions_user(&content, self.user_id.as_str());
+        if !is_direct {
+            let active_thread = match &thread_id {
+                Some(root) => self.active_threads.lock().await.contains(root),
+                None => false,
+            };
+            if !mentioned && !active_thread && !matrix_room_allowed(&config, &room_id) {
+                return;
+            }
+        }
+
+        let sender = match room.get_member_no_sync(&event.sender).await {
+            Ok(Some(member)) => member.name().to_string(),
+            _ => event.sender.to_string(),
+        };
+        let mut attachments = Vec::new();
+        if let Some(source) = media {
+            // The source (plain or encrypted) is handed back to `download`
+            attachments.push(Attachment {
+                name: content["filename"].as_str().unwrap_or(body).to_string(),
+                source: AttachmentSource::Remote(serde_json::to_string(&source).unwrap_or_default()),
+            });
+        }
+        let incoming = Incoming {
+            sender,
+            sender_id: event.sender.to_string(),
+            text,
+            attachments,
+            reply_to: ReplyTarget {
+                chat_id: room_id.clone(),
+                reply_to_message_id: Some(event.event_id.to_string()),
+                thread_id: thread_id.clone(),
+            },
+            default_route: matrix_default_route(&config, &room_id).map(str::to_string),
+        };
+        if receive(self, paths, incoming).await.is_none() {
+            return;
+        }
+        if let Some(root) = thread_id {
+            self.active_threads.lock().await.insert(root);
+        }
+        let _ = room.typing_notice(true).await;
+    }
+}
+
+/// Register the event handlers. On a fresh store the first sync runs before
+/// the message handler exists, so invites are joined but old history is not
+/// answered; later starts resume from the sync token in the store.
+async fn start(bot: &Arc<MatrixBot>) -> Result<()> {
+    let client = &bot.client;
+    client.add_event_handler_context(Arc::clone(bot));
+    client.add_event_handler(
+        |event: StrippedRoomMemberEvent, room: Room, Ctx(bot): Ctx<Arc<MatrixBot>>| async move {
+            bot.handle_invite(room, event).await
+        },
+    );
+
+    let fresh = client.state_store().get_kv_data(StateStoreDataKey::SyncToken).await?.is_none();
+    if fresh {
+        client.sync_once(sync_settings(Duration::ZERO)).await?;
+    }
+
+    client.add_event_handler(
+        |event: OriginalSyncRoomMessageEvent, room: Room, Ctx(bot): Ctx<Arc<MatrixBot>>| async move {
+            bot.handle_message(room, event).await
+        },
+    );
+    // Events that still carry ciphertext here could not be decrypted
+    client.add_event_handler(
+        |event: OriginalSyncRoomEncryptedEvent, room: Room, Ctx(bot): Ctx<Arc<MatrixBot>>| async move {
+            log(
+                "WARN",
+                &format!(
+                    "Cannot decrypt {} in {}; the sender's device has not shared its keys with the bot",
+                    event.event_id,
+                    room.room_id()
+                ),
+                &bot.paths.log_file,
+            );
+        },
+    );
+    Ok(())
+}
+
+#[async_trait]
+impl ChannelAdapter for MatrixBot {
+    fn name(&self) -> &'static str {
+        "matrix"
+    }
+
+    fn max_message_len(&self) -> usize {
+        MAX_MESSAGE_LEN
+    }
+
+    fn format(&self, markdown: &str) -> String {
+        to_matrix_html(markdown)
+    }
+
+    async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()> {
+        let target = outgoing.target;
+        let room = self.room(&target.chat_id)?;
+        let _ = room.typing_notice(false).await;
+
+        for fp in &outgoing.files {
+            let filename = fp.file_name().unwrap_or_default().to_string_lossy().to_string();
+            // Files stay in the thread; in the room they stand alone
+            let reply = match (&target.thread_id, &target.reply_to_message_id) {
+                (Some(root), reply_to) => Some(Reply {
+                    event_id: EventId::parse(reply_to.as_deref().unwrap_or(root))?,
+                    enforce_thread: EnforceThread::MaybeThreaded,
+                    add_mentions: AddMentions::No,
+                }),
+                (None, _) => None,
+            };
+            let result = match std::fs::read(fp) {
+                Ok(data) => room
+                    .send_attachment(&filename, &media_type(fp), data, AttachmentConfig::new().reply(reply))
+                    .await
+                    .map(|_| ())
+                    .map_err(anyhow::Error::from),
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Ok(()) => log("INFO", &format!("Sent file to Matrix: {}", filename), &self.paths.log_file),
+                Err(e) => log("ERROR", &format!("Failed to send file: {}", e), &self.paths.log_file),
+            }
+        }
+
+        for (i, chunk) in outgoing.chunks.iter().enumerate() {
+            room.send_raw("m.room.message", message_content(chunk, target, i == 0)).await?;
+        }
+        Ok(())
+    }
+
+    /// Notices, so other bots in the room don't answer them.
+    async fn reply(&self, target: &ReplyTarget, text: &str) -> Result<()> {
+        let mut content = message_content(text, target, true);
+        content["msgtype"] = json!("m.notice");
+        self.room(&target.chat_id)?.send_raw("m.room.message", content).await?;
+        Ok(())
+    }
+
+    /// `reference` is the serialized media source, so encrypted files are
+    /// decrypted on the way.
+    async fn download(&self, reference: &str, dest: &Path) -> Result<()> {
+        let source: MediaSource = serde_json::from_str(reference).context("invalid media reference")?;
+        let request = MediaRequestParameters {
+            source,
+            format: MediaFormat::File,
+        };
+        let bytes = self.client.media().get_media_content(&request, false).await?;
+        if let Some(dir) = dest.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(dest, bytes)?;
+        Ok(())
+    }
+}
+
+pub async fn run(paths: Arc<Paths>) -> Result<()> {
+    prepare_dirs(&paths);
+    logging::init("matrix", &paths);
+
+    log("INFO", "Starting Matrix client...", &paths.log_file);
+
+    let config = get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.channels)
+        .and_then(|c| c.matrix)
+        .unwrap_or_default();
+    let homeserver = env::var("MATRIX_HOMESERVER_URL").ok().or(config.homeserver_url).unwrap_or_default();
+    let access_token = env::var("MATRIX_ACCESS_TOKEN").ok().or(config.access_token).unwrap_or_default();
+    if homeserver.is_empty() || access_token.is_empty() {
+        eprintln!("ERROR: Matrix homeserver_url and access_token must both be configured");
+        std::process::exit(1);
+    }
+    let homeserver = parse_homeserver(&homeserver)?;
+
+    let session = match whoami(&homeserver, &access_token).await {
+        Ok(session) => session,
+        Err(e) => {
+            log("ERROR", &format!("Failed to connect: {}", e), &paths.log_file);
+            std::process::exit(1);
+        }
+    };
+    let client = build_client(&homeserver, &store_dir(&paths), session).await?;
+    let user_id = client.user_id().context("no user after login")?.to_owned();
+    let display_name = client.account().get_display_name().await.ok().flatten().unwrap_or_default();
+    log("INFO", &format!("Matrix bot logged in as {}", user_id), &paths.log_file);
+
+    let bot = Arc::new(MatrixBot {
+        client,
+        paths: Arc::clone(&paths),
+        user_id,
+        display_name,
+        active_threads: Mutex::new(HashSet::new()),
+    });
+    start(&bot).await?;
+
+    let bot_poll = Arc::clone(&bot);
+    tokio::spawn(async move { poll_outgoing(&*bot_poll, &bot_poll.paths, Duration::from_secs(1)).await });
+
+    loop {
+        if let Err(e) = bot.client.sync_once(sync_settings(SYNC_TIMEOUT)).await {
+            log("WARN", &format!("Sync failed: {}", e), &paths.log_file);
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path as AxumPath, Query, State};
+    use axum::http::StatusCode;
+    use axum::routing::{get, post, put};
+    use axum::{Json, Router};
+    use rustyclaw_channel::{check_outgoing_queue, now_millis};
+    use rustyclaw_core::pairing::{approve_pairing_code, ensure_sender_paired};
+    use rustyclaw_core::types::{MessageData, ResponseData};
+    use std::collections::HashMap;
+
+    /// Requests recorded by the mock homeserver.
+    #[derive(Default)]
+    struct MockLog {
+        joined: Vec<String>,
+        sent: Vec<(String, Value)>,
+    }
+
+    type Shared = Arc<Mutex<MockLog>>;
+
+    fn test_paths(home: &Path) -> Paths {
+        Paths {
+            script_dir: home.to_path_buf(),
+            rustyclaw_home: home.to_path_buf(),
+            queue_incoming: home.join("queue/incoming"),
+            queue_outgoing: home.join("queue/outgoing"),
+            queue_processing: home.join("queue/processing"),
+            log_file: home.join("logs/queue.log"),
+            settings_file: home.join("settings.json"),
+            events_dir: home.join("events"),
+            chats_dir: home.join("chats"),
+            files_dir: home.join("files"),
+            pairing_file: home.join("pairing.json"),
+            skills_dir: home.join("skills"),
+            skills_lock_file: home.join("skills.lock"),
+        }
+    }
+
+    fn message(event_id: &str, sender: &str, content: Value) -> Value {
+        json!({
+            "type": "m.room.message",
+            "event_id": event_id,
+            "sender": sender,
+            "origin_server_ts": 1,
+            "content": content,
+        })
+    }
+
+    fn member(user_id: &str, name: &str) -> Value {
+        json!({
+            "type": "m.room.member",
+            "event_id": format!("$member-{}", name),
+            "sender": user_id,
+            "state_key": user_id,
+            "origin_server_ts": 1,
+            "content": { "membership": "join", "displayname": name },
+        })
+    }
+
+    /// Mock homeserver. The initial sync carries an invite and old history;
+    /// the incremental sync carries new messages in a group room. Anything
+    /// else (key uploads, room state lookups) gets M_NOT_FOUND.
+    async fn start_mock() -> (String, Shared) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared: Shared = Arc::default();
+
+        let sync = |Query(query): Query<HashMap<String, String>>| async move {
+            Json(match query.get("since").map(String::as_str) {
+                None => json!({
+                    "next_batch": "s1",
+                    "rooms": {
+                        "invite": { "!new:localhost": { "invite_state": { "events": [{
+                            "type": "m.room.member",
+                            "sender": "@alice:localhost",
+                            "state_key": "@rustyclaw:localhost",
+                            "content": { "membership": "invite" },
+                        }]}}},
+                        "join": { "!room:localhost": {
+                            "state": { "events": [member("@alice:localhost", "Alice")] },
+                            "timeline": { "events": [
+                                message("$old", "@alice:localhost", json!({ "msgtype": "m.text", "body": "rustyclaw: old" }))
+                            ]},
+                        }},
+                    }
+                }),
+                _ => json!({
+                    "next_batch": "s2",
+                    "rooms": { "join": { "!room:localhost": { "timeline": { "events": [
+                        message("$chatter", "@alice:localhost", json!({ "msgtype": "m.text", "body": "just chatting" })),
+                        message("$notice", "@other-bot:localhost", json!({ "msgtype": "m.notice", "body": "rustyclaw: hi" })),
+                        message("$ask", "@alice:localhost", json!({
+                            "msgtype": "m.text",
+                            "body": "rustyclaw: review the <patch>",
+                            "m.mentions": { "user_ids": ["@rustyclaw:localhost"] }
+                        })),
+                    ]}}}}
+                }),
+            })
+        };
+        let join = |State(shared): State<Shared>, AxumPath(room): AxumPath<String>| async move {
+            shared.lock().await.joined.push(room.clone());
+            Json(json!({ "room_id": room }))
+        };
+        let send = |State(shared): State<Shared>,
+                    AxumPath((room, _kind, _txn)): AxumPath<(String, String, String)>,
+                    Json(body): Json<Value>| async move {
+            shared.lock().await.sent.push((room, body));
+            Json(json!({ "event_id": "$sent" }))
+        };
+
+        let app = Router::new()
+            .route(
+                "/_matrix/client/versions",
+                get(|| async { Json(json!({ "versions": ["v1.11"] })) }),
+            )
+            .route("/_matrix/client/v3/sync", get(sync))
+            .route("/_matrix/client/v3/rooms/{room}/join", post(join))
+            .route("/_matrix/client/v3/rooms/{room}/send/{kind}/{txn}", put(send))
+            .route("/_matrix/client/v3/rooms/{room}/typing/{user}", put(|| async { Json(json!({})) }))
+            .fallback(|| async {
+                (StatusCode::NOT_FOUND, Json(json!({ "errcode": "M_NOT_FOUND", "error": "Not found" })))
+            })
+            .with_state(Arc::clone(&shared));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), shared)
+    }
+
+    #[test]
+    fn test_parse_homeserver() {
+        assert_eq!(parse_homeserver("https://matrix.example.org").unwrap().as_str(), "https://matrix.example.org/");
+        assert_eq!(
+            parse_homeserver("https://example.org/matrix").unwrap().as_str(),
+            "https://example.org/matrix/"
+        );
+        assert!(parse_homeserver("mailto:bot@example.org").is_err());
+        assert!(parse_homeserver("matrix.example.org").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = Arc::new(test_paths(tmp.path()));
+        std::fs::write(
+            &paths.settings_file,
+            json!({ "channels": { "matrix": { "rooms": { "!room:localhost": "dev" } } } }).to_string(),
+        )
+        .unwrap();
+        let code = ensure_sender_paired(&paths.pairing_file, "matrix", "@alice:localhost", "Alice").code.unwrap();
+        assert!(approve_pairing_code(&paths.pairing_file, &code).ok);
+
+        let (homeserver, shared) = start_mock().await;
+        let session = MatrixSession {
+            meta: SessionMeta {
+                user_id: "@rustyclaw:localhost".try_into().unwrap(),
+                device_id: "BOTDEVICE".into(),
+            },
+            tokens: SessionTokens {
+                access_token: "token".to_string(),
+                refresh_token: None,
+            },
+        };
+        let homeserver = parse_homeserver(&homeserver).unwrap();
+        let client = build_client(&homeserver, &store_dir(&paths), session).await.unwrap();
+        let bot = Arc::new(MatrixBot {
+            client,
+            paths: Arc::clone(&paths),
+            user_id: "@rustyclaw:localhost".try_into().unwrap(),
+            display_name: "rustyclaw".to_string(),
+            active_threads: Mutex::new(HashSet::new()),
+        });
+
+        // Initial sync joins invites but skips history
+        start(&bot).await.unwrap();
+        assert_eq!(shared.lock().await.joined, vec!["!new:localhost".to_string()]);
+        assert!(std::fs::read_dir(&paths.queue_incoming).map(|d| d.count() == 0).unwrap_or(true));
+
+        // Only the mention is queued, routed to the room's team
+        bot.client.sync_once(sync_settings(Duration::ZERO)).await.unwrap();
+        let token = bot.client.state_store().get_kv_data(StateStoreDataKey::SyncToken).await.unwrap();
+        assert_eq!(token.and_then(|t| t.into_sync_token()).as_deref(), Some("s2"));
+        let queued: Vec<_> = std::fs::read_dir(&paths.queue_incoming).unwrap().flatten().collect();
+        assert_eq!(queued.len(), 1);
+        let data: MessageData =
+            serde_json::from_str(&std::fs::read_to_string(queued[0].path()).unwrap()).unwrap();
+        assert_eq!(data.channel, "matrix");
+        assert_eq!(data.sender, "Alice");
+        assert_eq!(data.message, "@dev review the <patch>");
+        let target = data.reply_to.clone().unwrap();
+        assert_eq!(target.chat_id, "!room:localhost");
+        assert_eq!(target.reply_to_message_id.as_deref(), Some("$ask"));
+
+        // Deliver a response through the outgoing queue
+        let response = ResponseData {
+            channel: "matrix".to_string(),
+            sender: data.sender.clone(),
+            message: "**Looks good**".to_string(),
+            original_message: data.message.clone(),
+            timestamp: now_millis(),
+            message_id: data.message_id.clone(),
+            agent: None,
+            files: None,
+            reply_to: data.reply_to.clone(),
+        };
+        std::fs::create_dir_all(&paths.queue_outgoing).unwrap();
+        let out_file = paths.queue_outgoing.join(format!("matrix_{}.json", data.message_id));
+        std::fs::write(&out_file, serde_json::to_string(&response).unwrap()).unwrap();
+        check_outgoing_queue(&*bot, &paths).await;
+
+        assert!(!out_file.exists());
+        let log = shared.lock().await;
+        assert_eq!(log.sent.len(), 1);
+        let (room, content) = &log.sent[0];
+        assert_eq!(room, "!room:localhost");
+        assert_eq!(content["body"], "**Looks good**");
+        assert_eq!(content["formatted_body"], "<b>Looks good</b>");
+        assert_eq!(content["m.relates_to"]["m.in_reply_to"]["event_id"], "$ask");
+    }
+
+    #[test]
+    fn test_strip_mention() {
+        let id = "@rustyclaw:example.org";
+        assert_eq!(strip_mention("RustyClaw: hi", id, "RustyClaw"), ("hi".to_string(), true));
+        assert_eq!(strip_mention("ping @rustyclaw:example.org now", id, ""), ("ping now".to_string(), true));
+        assert_eq!(strip_mention("rustyclaw: hi", id, ""), ("hi".to_string(), true));
+        assert_eq!(strip_mention("hello all", id, "RustyClaw"), ("hello all".to_string(), false));
+    }
+
+    #[test]
+    fn test_strip_reply_fallback() {
+        assert_eq!(strip_reply_fallback("> <@a:x> earlier\n> more\n\nmy answer"), "my answer");
+        assert_eq!(strip_reply_fallback("plain"), "plain");
+    }
+
+    #[test]
+    fn test_thread_relation() {
+        let target = ReplyTarget {
+            chat_id: "!r:x".to_string(),
+            reply_to_message_id: Some("$msg".to_string()),
+            thread_id: Some("$root".to_string()),
+        };
+        let content = message_content("hi", &target, false);
+        assert_eq!(content["m.relates_to"]["rel_type"], "m.thread");
+        assert_eq!(content["m.relates_to"]["event_id"], "$root");
+        assert_eq!(content["m.relates_to"]["m.in_reply_to"]["event_id"], "$msg");
+
+        let room = ReplyTarget { thread_id: None, ..target };
+        assert!(message_content("hi", &room, false).get("m.relates_to").is_none());
+        assert_eq!(message_content("hi", &room, true)["m.relates_to"]["m.in_reply_to"]["event_id"], "$msg");
+    }
+}
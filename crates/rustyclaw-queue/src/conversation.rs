@@ -3,24 +3,31 @@ use std::path::Path;
 
 use regex::Regex;
 
-use rustyclaw_core::config::Paths;
+use rustyclaw_core::channels::long_response_mode;
+use rustyclaw_core::chat_history::ChatRecord;
+use rustyclaw_core::config::{get_settings, Paths};
 use rustyclaw_core::logging::{emit_event, log};
 use rustyclaw_core::types::{
-    AgentConfig, Conversation, MessageData, ResponseData, TeamContext,
+    AgentConfig, Conversation, LongResponseMode, MessageData, ReplyTarget, ResponseData,
+    TeamContext,
 };
 
+use crate::trace;
+
 pub const MAX_CONVERSATION_MESSAGES: u32 = 50;
 pub const LONG_RESPONSE_THRESHOLD: usize = 4000;
 
 /// If a response exceeds the threshold, save the full text as a .md file
-/// and return a truncated preview with the file attached.
+/// and return a truncated preview with the file attached. Channels that
+/// split or paginate long responses get the full text unchanged.
 pub fn handle_long_response(
     response: &str,
     existing_files: &[String],
+    mode: LongResponseMode,
     files_dir: &Path,
     log_file: &Path,
 ) -> (String, Vec<String>) {
-    if response.len() <= LONG_RESPONSE_THRESHOLD {
+    if mode != LongResponseMode::Attach || response.len() <= LONG_RESPONSE_THRESHOLD {
         return (response.to_string(), existing_files.to_vec());
     }
 
@@ -44,9 +51,13 @@ pub fn handle_long_response(
         log_file,
     );
 
+    let mut cut = LONG_RESPONSE_THRESHOLD;
+    while !response.is_char_boundary(cut) {
+        cut -= 1;
+    }
     let preview = format!(
         "{}\n\n_(Full response attached as file)_",
-        &response[..LONG_RESPONSE_THRESHOLD]
+        &response[..cut]
     );
     let mut files = existing_files.to_vec();
     files.push(file_path.to_string_lossy().to_string());
@@ -65,6 +76,8 @@ pub fn collect_files(response: &str, file_set: &mut HashSet<String>) {
 }
 
 /// Enqueue an internal (agent-to-agent) message into the incoming queue.
+/// The message carries the trace context of a `handoff` span, so the target
+/// agent's work joins the same trace.
 pub fn enqueue_internal_message(
     conversation_id: &str,
     from_agent: &str,
@@ -91,6 +104,13 @@ pub fn enqueue_internal_message(
         })
         .collect();
 
+    let span = tracing::info_span!(
+        "handoff",
+        conversation_id,
+        from_agent,
+        to_agent = target_agent,
+    );
+
     let internal_message = MessageData {
         channel: original_data.channel.clone(),
         sender: original_data.sender.clone(),
@@ -102,6 +122,9 @@ pub fn enqueue_internal_message(
         files: None,
         conversation_id: Some(conversation_id.to_string()),
         from_agent: Some(from_agent.to_string()),
+        reply_to: original_data.reply_to.clone(),
+        priority: original_data.priority,
+        traceparent: trace::traceparent(&span),
     };
 
     let filename = format!(
@@ -122,6 +145,7 @@ pub fn enqueue_internal_message(
                 &format!("Failed to serialize internal message: {}", e),
                 log_file,
             );
+            trace::fail(&span, e);
             return;
         }
     }
@@ -193,9 +217,11 @@ pub fn complete_conversation(
     final_response = tag_re.replace_all(&final_response, "").trim().to_string();
 
     // Handle long responses
+    let settings = get_settings(&paths.settings_file).unwrap_or_default();
     let (response_message, all_files) = handle_long_response(
         &final_response,
         &outbound_files_vec,
+        long_response_mode(&settings, &conv.channel),
         &paths.files_dir,
         &paths.log_file,
     );
@@ -214,6 +240,7 @@ pub fn complete_conversation(
         } else {
             Some(all_files)
         },
+        reply_to: conv.reply_to.clone(),
     };
 
     let response_file = if conv.channel == "heartbeat" {
@@ -275,16 +302,14 @@ fn save_chat_history(
         let tc = conv.team_context.as_ref().ok_or("No team context for chat history")?;
         let team_chats_dir = chats_dir.join(&tc.team_id);
         std::fs::create_dir_all(&team_chats_dir)?;
+        let now = chrono::Utc::now();
 
         let mut lines = Vec::new();
         lines.push(format!(
             "# Team Conversation: {} (@{})",
             tc.team.name, tc.team_id
         ));
-        lines.push(format!(
-            "**Date:** {}",
-            chrono::Utc::now().to_rfc3339()
-        ));
+        lines.push(format!("**Date:** {}", now.to_rfc3339()));
         lines.push(format!(
             "**Channel:** {} | **Sender:** {}",
             conv.channel, conv.sender
@@ -312,12 +337,30 @@ fn save_chat_history(
             lines.push(String::new());
         }
 
-        let now = chrono::Utc::now();
         let date_time = now.format("%Y-%m-%dT%H-%M-%S").to_string();
         std::fs::write(
             team_chats_dir.join(format!("{}.md", date_time)),
             lines.join("\n"),
         )?;
+
+        // Structured sidecar for the dashboard's conversation explorer
+        let mut files: Vec<String> = conv.files.iter().cloned().collect();
+        files.sort();
+        let record = ChatRecord {
+            team_id: tc.team_id.clone(),
+            team_name: tc.team.name.clone(),
+            date: now.to_rfc3339(),
+            channel: conv.channel.clone(),
+            sender: conv.sender.clone(),
+            message: conv.original_message.clone(),
+            started_at: conv.start_time,
+            steps: conv.responses.clone(),
+            files,
+        };
+        std::fs::write(
+            team_chats_dir.join(format!("{}.json", date_time)),
+            serde_json::to_string_pretty(&record)?,
+        )?;
         log("INFO", "Chat history saved", log_file);
         Ok(())
     })();
@@ -338,6 +381,7 @@ pub fn create_conversation(
     sender: &str,
     original_message: &str,
     team_context: Option<TeamContext>,
+    reply_to: Option<ReplyTarget>,
 ) -> Conversation {
     let conv_id = format!(
         "{}_{}",
@@ -362,6 +406,7 @@ pub fn create_conversation(
         team_context,
         start_time: now_millis(),
         outgoing_mentions: HashMap::new(),
+        reply_to,
     }
 }
 
@@ -383,7 +428,8 @@ mod tests {
         let log_file = tmp.path().join("test.log");
         let files_dir = tmp.path().join("files");
 
-        let (msg, files) = handle_long_response("short text", &[], &files_dir, &log_file);
+        let (msg, files) =
+            handle_long_response("short text", &[], LongResponseMode::Attach, &files_dir, &log_file);
         assert_eq!(msg, "short text");
         assert!(files.is_empty());
     }
@@ -395,12 +441,30 @@ mod tests {
         let files_dir = tmp.path().join("files");
 
         let long_text = "x".repeat(5000);
-        let (msg, files) = handle_long_response(&long_text, &[], &files_dir, &log_file);
+        let (msg, files) =
+            handle_long_response(&long_text, &[], LongResponseMode::Attach, &files_dir, &log_file);
         assert!(msg.contains("Full response attached as file"));
         assert_eq!(files.len(), 1);
         assert!(Path::new(&files[0]).exists());
     }
 
+    #[test]
+    fn test_handle_long_response_paginated_channel_keeps_text() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let log_file = tmp.path().join("test.log");
+        let files_dir = tmp.path().join("files");
+
+        let long_text = "é".repeat(3000);
+        let (msg, files) =
+            handle_long_response(&long_text, &[], LongResponseMode::Paginate, &files_dir, &log_file);
+        assert_eq!(msg, long_text);
+        assert!(files.is_empty());
+
+        let (msg, _) =
+            handle_long_response(&long_text, &[], LongResponseMode::Attach, &files_dir, &log_file);
+        assert!(msg.contains("Full response attached as file"));
+    }
+
     #[test]
     fn test_collect_files_none() {
         let mut set = HashSet::new();
@@ -419,11 +483,49 @@ mod tests {
                 description: None,
             },
         };
-        let conv = create_conversation("msg1", "discord", "Alice", "hello", Some(tc));
+        let conv = create_conversation("msg1", "discord", "Alice", "hello", Some(tc), None);
         assert!(conv.id.starts_with("msg1_"));
         assert_eq!(conv.pending, 1);
         assert_eq!(conv.total_messages, 0);
         assert_eq!(conv.max_messages, MAX_CONVERSATION_MESSAGES);
         assert!(conv.team_context.is_some());
     }
+
+    #[test]
+    fn test_internal_message_keeps_reply_target() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let reply_to = ReplyTarget {
+            chat_id: "12345".to_string(),
+            reply_to_message_id: Some("678".to_string()),
+            thread_id: None,
+        };
+        let original = MessageData {
+            channel: "telegram".to_string(),
+            sender: "Alice".to_string(),
+            sender_id: Some("12345".to_string()),
+            message: "hello".to_string(),
+            timestamp: 0,
+            message_id: "msg1".to_string(),
+            agent: None,
+            files: None,
+            conversation_id: None,
+            from_agent: None,
+            reply_to: Some(reply_to.clone()),
+            priority: None,
+            traceparent: None,
+        };
+
+        let log_file = tmp.path().join("queue.log");
+        enqueue_internal_message("conv1", "coder", "reviewer", "check this", &original, tmp.path(), &log_file);
+
+        let entry = std::fs::read_dir(tmp.path())
+            .unwrap()
+            .flatten()
+            .find(|e| e.file_name().to_string_lossy().starts_with("internal_"))
+            .unwrap();
+        let raw = std::fs::read_to_string(entry.path()).unwrap();
+        assert!(raw.contains("\"replyTo\""));
+        let queued: MessageData = serde_json::from_str(&raw).unwrap();
+        assert_eq!(queued.reply_to, Some(reply_to));
+    }
 }
@@ -1,19 +1,26 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use anyhow::{Context, Result};
 use tokio::process::Command;
+use tracing::Instrument;
 
 use rustyclaw_core::agent_setup::{ensure_agent_directory, update_agent_teammates};
+use rustyclaw_core::config::{resolve_working_dir, Paths};
 use rustyclaw_core::context;
 use rustyclaw_core::failover::{
     classify_error, clear_cooldown, cooldown_key, is_in_cooldown, load_cooldowns, record_failure,
-    save_cooldowns,
+    save_cooldowns, FailoverReason,
 };
-use rustyclaw_core::logging::log;
+use rustyclaw_core::logging::{emit_event, log};
+use rustyclaw_core::skills::{self, EligibilityContext, Skill};
+use rustyclaw_core::timezone::resolve_timezone;
 use rustyclaw_core::models::{resolve_claude_model, resolve_codex_model, resolve_opencode_model};
 use rustyclaw_core::types::{AgentConfig, Settings, SkillOverride, TeamConfig};
 
+use crate::{metrics, trace};
+
 /// Run a command and capture stdout. Returns an error if the process exits non-zero.
 pub async fn run_command(command: &str, args: &[&str], cwd: &Path) -> Result<String> {
     let output = Command::new(command)
@@ -39,6 +46,62 @@ pub async fn run_command(command: &str, args: &[&str], cwd: &Path) -> Result<Str
     }
 }
 
+/// A provider response along with the CLI's own session/thread ID, when reported.
+#[derive(Debug, Clone)]
+pub struct AgentResponse {
+    pub text: String,
+    /// Claude session ID, Codex thread ID, or OpenCode session ID.
+    pub session_id: Option<String>,
+}
+
+/// Parse Claude `--output-format json` output — extract `result` and `session_id`.
+/// Falls back to the raw output if it isn't a JSON result object.
+pub fn parse_claude_output(raw: &str) -> AgentResponse {
+    match serde_json::from_str::<serde_json::Value>(raw.trim()) {
+        Ok(json) if json.get("result").is_some() || json.get("session_id").is_some() => {
+            AgentResponse {
+                text: json
+                    .get("result")
+                    .and_then(|r| r.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                session_id: json
+                    .get("session_id")
+                    .and_then(|s| s.as_str())
+                    .map(|s| s.to_string()),
+            }
+        }
+        _ => AgentResponse {
+            text: raw.to_string(),
+            session_id: None,
+        },
+    }
+}
+
+/// Parse Codex JSONL output — extract the `thread_id` from the `thread.started` event.
+pub fn parse_codex_thread_id(raw: &str) -> Option<String> {
+    raw.trim().lines().find_map(|line| {
+        let json = serde_json::from_str::<serde_json::Value>(line).ok()?;
+        if json.get("type").and_then(|t| t.as_str()) != Some("thread.started") {
+            return None;
+        }
+        json.get("thread_id")
+            .and_then(|t| t.as_str())
+            .map(|t| t.to_string())
+    })
+}
+
+/// Parse OpenCode JSONL output — extract the `sessionID` carried on its events.
+pub fn parse_opencode_session_id(raw: &str) -> Option<String> {
+    raw.trim().lines().find_map(|line| {
+        let json = serde_json::from_str::<serde_json::Value>(line).ok()?;
+        json.get("sessionID")
+            .or_else(|| json.get("part").and_then(|p| p.get("sessionID")))
+            .and_then(|s| s.as_str())
+            .map(|s| s.to_string())
+    })
+}
+
 /// Parse Codex JSONL output — extract the final `agent_message` text.
 pub fn parse_codex_output(raw: &str) -> String {
     let mut response = String::new();
@@ -83,23 +146,83 @@ pub fn parse_opencode_output(raw: &str) -> String {
     }
 }
 
+/// Discover the skills available to an agent and filter them for eligibility.
+///
+/// Searches project, installed, and per-agent skill directories (later ones take precedence).
+pub fn eligible_skills_for_agent(
+    agent: &AgentConfig,
+    agent_id: &str,
+    workspace_path: &Path,
+    teams: &HashMap<String, TeamConfig>,
+    paths: &Paths,
+    settings: &Settings,
+) -> Vec<Skill> {
+    let agent_dir = workspace_path.join(agent_id);
+    let skill_dirs_owned: Vec<PathBuf> = vec![
+        paths.script_dir.join("skills"),
+        paths.skills_dir.clone(),
+        agent_dir.join(".rustyclaw/skills"),
+    ];
+    let skill_dirs: Vec<&Path> = skill_dirs_owned.iter().map(|p| p.as_path()).collect();
+    let empty_overrides = HashMap::new();
+    let skill_overrides: &HashMap<String, SkillOverride> = settings
+        .skills
+        .as_ref()
+        .unwrap_or(&empty_overrides);
+
+    let provider = if agent.provider.is_empty() {
+        "anthropic"
+    } else {
+        &agent.provider
+    };
+    let team_ids: Vec<String> = teams
+        .iter()
+        .filter(|(_, team)| team.agents.iter().any(|a| a == agent_id))
+        .map(|(id, _)| id.clone())
+        .collect();
+    let working_dir = resolve_working_dir(agent, agent_id, workspace_path);
+    let ctx = EligibilityContext {
+        agent_id,
+        provider,
+        team_ids: &team_ids,
+        working_dir: &working_dir,
+    };
+
+    skills::discover_skills(&skill_dirs)
+        .into_iter()
+        .filter(|s| skills::is_skill_eligible(s, skill_overrides, &ctx))
+        .collect()
+}
+
+/// What to send to the provider.
+#[derive(Debug, Clone, Copy)]
+pub enum Prompt<'a> {
+    /// A message, sent after the context preamble
+    Message(&'a str),
+    /// A follow-up within the resumed provider session, which already has
+    /// the context, so it is sent as is
+    FollowUp(&'a str),
+}
+
 /// Invoke an agent with a message. Dispatches to Claude, Codex, or OpenCode CLI
-/// depending on the agent's provider. Returns the raw response text.
+/// depending on the agent's provider. Resumes `resume_session_id` when given,
+/// otherwise starts a new provider session. Returns the response text and the
+/// provider's session ID so the caller can store it per sender.
 pub async fn invoke_agent(
     agent: &AgentConfig,
     agent_id: &str,
-    message: &str,
+    prompt: Prompt<'_>,
     workspace_path: &Path,
-    should_reset: bool,
+    resume_session_id: Option<&str>,
     agents: &HashMap<String, AgentConfig>,
     teams: &HashMap<String, TeamConfig>,
-    script_dir: &Path,
-    log_file: &Path,
+    paths: &Paths,
     settings: &Settings,
-) -> Result<String> {
+) -> Result<AgentResponse> {
+    let log_file = &paths.log_file;
     let agent_dir = workspace_path.join(agent_id);
     let is_new = !agent_dir.exists();
-    ensure_agent_directory(&agent_dir, script_dir)?;
+    ensure_agent_directory(&agent_dir, &paths.script_dir)?;
     if is_new {
         log(
             "INFO",
@@ -111,42 +234,8 @@ pub async fn invoke_agent(
     // Update AGENTS.md with current teammate info
     let _ = update_agent_teammates(&agent_dir, agent_id, agents, teams);
 
-    // Build skill discovery directories
-    let skills_dir_project = script_dir.join("skills");
-    let skills_dir_home = agent_dir.join(".rustyclaw/skills");
-    let skill_dirs_owned: Vec<PathBuf> = vec![skills_dir_project, skills_dir_home];
-    let skill_dirs: Vec<&Path> = skill_dirs_owned.iter().map(|p| p.as_path()).collect();
-    let empty_overrides = HashMap::new();
-    let skill_overrides: &HashMap<String, SkillOverride> = settings
-        .skills
-        .as_ref()
-        .unwrap_or(&empty_overrides);
-
-    // Build context preamble from bootstrap files, memory, transcripts, and skills
-    let context_preamble = context::build_context_preamble(
-        &agent_dir,
-        agent_id,
-        context::MAX_TRANSCRIPT_CONTEXT_CHARS,
-        &skill_dirs,
-        skill_overrides,
-    );
-    let enriched_message = if context_preamble.is_empty() {
-        message.to_string()
-    } else {
-        format!("{}{}", context_preamble, message)
-    };
-
     // Resolve working directory
-    let working_dir = if agent.working_directory.is_empty() {
-        agent_dir.clone()
-    } else {
-        let wd = PathBuf::from(&agent.working_directory);
-        if wd.is_absolute() {
-            wd
-        } else {
-            workspace_path.join(&agent.working_directory)
-        }
-    };
+    let working_dir = resolve_working_dir(agent, agent_id, workspace_path);
 
     // Ensure working directory exists
     if !working_dir.exists() {
@@ -159,6 +248,37 @@ pub async fn invoke_agent(
         &agent.provider
     };
 
+    let enriched_message = match prompt {
+        Prompt::FollowUp(text) if resume_session_id.is_some() => text.to_string(),
+        Prompt::Message(message) | Prompt::FollowUp(message) => {
+            let context_span = tracing::info_span!(
+                "build_context",
+                skills = tracing::field::Empty,
+                preamble_chars = tracing::field::Empty,
+            );
+            let skills =
+                eligible_skills_for_agent(agent, agent_id, workspace_path, teams, paths, settings);
+
+            // Build context preamble from bootstrap files, memory, transcripts, and skills
+            let context_preamble = context::build_context_preamble(
+                &agent_dir,
+                agent_id,
+                context::MAX_TRANSCRIPT_CONTEXT_CHARS,
+                resolve_timezone(agent),
+                &skills,
+                Some(message),
+            );
+            context_span.record("skills", skills.len());
+            context_span.record("preamble_chars", context_preamble.len());
+            drop(context_span);
+            if context_preamble.is_empty() {
+                message.to_string()
+            } else {
+                format!("{}{}", context_preamble, message)
+            }
+        }
+    };
+
     match provider {
         "openai" => {
             log(
@@ -167,20 +287,19 @@ pub async fn invoke_agent(
                 log_file,
             );
 
-            let should_resume = !should_reset;
-            if should_reset {
+            if resume_session_id.is_none() {
                 log(
                     "INFO",
-                    &format!("Resetting Codex conversation for agent: {}", agent_id),
+                    &format!("Starting new Codex thread for agent: {}", agent_id),
                     log_file,
                 );
             }
 
             let model_id = resolve_codex_model(&agent.model);
             let mut args: Vec<String> = vec!["exec".to_string()];
-            if should_resume {
+            if let Some(thread_id) = resume_session_id {
                 args.push("resume".to_string());
-                args.push("--last".to_string());
+                args.push(thread_id.to_string());
             }
             if !model_id.is_empty() {
                 args.push("--model".to_string());
@@ -195,7 +314,11 @@ pub async fn invoke_agent(
 
             let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
             let output = run_command("codex", &args_ref, &working_dir).await?;
-            Ok(parse_codex_output(&output))
+            Ok(AgentResponse {
+                text: parse_codex_output(&output),
+                session_id: parse_codex_thread_id(&output)
+                    .or_else(|| resume_session_id.map(|s| s.to_string())),
+            })
         }
         "opencode" => {
             let model_id = resolve_opencode_model(&agent.model);
@@ -208,11 +331,10 @@ pub async fn invoke_agent(
                 log_file,
             );
 
-            let continue_conversation = !should_reset;
-            if should_reset {
+            if resume_session_id.is_none() {
                 log(
                     "INFO",
-                    &format!("Resetting OpenCode conversation for agent: {}", agent_id),
+                    &format!("Starting new OpenCode session for agent: {}", agent_id),
                     log_file,
                 );
             }
@@ -226,14 +348,19 @@ pub async fn invoke_agent(
                 args.push("--model".to_string());
                 args.push(model_id);
             }
-            if continue_conversation {
-                args.push("-c".to_string());
+            if let Some(session_id) = resume_session_id {
+                args.push("--session".to_string());
+                args.push(session_id.to_string());
             }
             args.push(enriched_message.clone());
 
             let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
             let output = run_command("opencode", &args_ref, &working_dir).await?;
-            Ok(parse_opencode_output(&output))
+            Ok(AgentResponse {
+                text: parse_opencode_output(&output),
+                session_id: parse_opencode_session_id(&output)
+                    .or_else(|| resume_session_id.map(|s| s.to_string())),
+            })
         }
         _ => {
             // Default to Claude (Anthropic)
@@ -243,11 +370,10 @@ pub async fn invoke_agent(
                 log_file,
             );
 
-            let continue_conversation = !should_reset;
-            if should_reset {
+            if resume_session_id.is_none() {
                 log(
                     "INFO",
-                    &format!("Resetting conversation for agent: {}", agent_id),
+                    &format!("Starting new conversation for agent: {}", agent_id),
                     log_file,
                 );
             }
@@ -258,54 +384,160 @@ pub async fn invoke_agent(
                 args.push("--model".to_string());
                 args.push(model_id);
             }
-            if continue_conversation {
-                args.push("-c".to_string());
+            if let Some(session_id) = resume_session_id {
+                args.push("--resume".to_string());
+                args.push(session_id.to_string());
             }
+            args.push("--output-format".to_string());
+            args.push("json".to_string());
             args.push("-p".to_string());
             args.push(enriched_message);
 
             let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-            run_command("claude", &args_ref, &working_dir).await
+            let output = run_command("claude", &args_ref, &working_dir).await?;
+            let mut response = parse_claude_output(&output);
+            if response.session_id.is_none() {
+                response.session_id = resume_session_id.map(|s| s.to_string());
+            }
+            Ok(response)
+        }
+    }
+}
+
+/// One model attempt of an invocation, timed and traced from its start.
+struct Attempt<'a> {
+    model: &'a str,
+    fallback: bool,
+    span: tracing::Span,
+    started: Instant,
+}
+
+impl<'a> Attempt<'a> {
+    fn start(agent_id: &str, model: &'a str, fallback: bool) -> Self {
+        let span = tracing::info_span!(
+            "invoke_agent",
+            agent_id,
+            provider = tracing::field::Empty,
+            model = tracing::field::Empty,
+            fallback = tracing::field::Empty,
+            failover_reason = tracing::field::Empty,
+        );
+        Self {
+            model,
+            fallback,
+            span,
+            started: Instant::now(),
         }
     }
 }
 
+/// Emit an `agent_invocation` event for one model attempt, add it to the
+/// Prometheus metrics and end its span. `failure` holds the reason and the
+/// cooldown end time when the attempt failed.
+fn emit_invocation(
+    paths: &Paths,
+    agent: &AgentConfig,
+    agent_id: &str,
+    attempt: Attempt<'_>,
+    failure: Option<(FailoverReason, u64)>,
+) {
+    let Attempt {
+        model,
+        fallback,
+        span,
+        started,
+    } = attempt;
+    let elapsed = started.elapsed();
+    metrics::record_invocation(
+        &agent.provider,
+        model,
+        elapsed,
+        failure.as_ref().map(|(reason, _)| reason),
+    );
+    span.record("provider", agent.provider.as_str());
+    span.record("model", model);
+    span.record("fallback", fallback);
+    if let Some((reason, _)) = &failure {
+        span.record("failover_reason", reason.as_str());
+        trace::fail(
+            &span,
+            format!("{}:{} failed ({})", agent.provider, model, reason.as_str()),
+        );
+    }
+    let (reason, cooldown_until) = match failure {
+        Some((reason, until)) => (Some(reason), Some(until)),
+        None => (None, None),
+    };
+    emit_event(
+        "agent_invocation",
+        serde_json::json!({
+            "agentId": agent_id,
+            "provider": agent.provider,
+            "model": model,
+            "durationMs": elapsed.as_millis() as u64,
+            "ok": reason.is_none(),
+            "fallback": fallback,
+            "reason": reason,
+            "cooldownUntil": cooldown_until,
+        }),
+        &paths.events_dir,
+    );
+}
+
 /// Invoke an agent with failover support. Tries the primary model first,
 /// then falls back to each model in the agent's `fallbacks` list.
 /// Respects cooldown periods to avoid hammering failing providers.
 pub async fn invoke_agent_with_failover(
     agent: &AgentConfig,
     agent_id: &str,
-    message: &str,
+    prompt: Prompt<'_>,
     workspace_path: &Path,
-    should_reset: bool,
+    resume_session_id: Option<&str>,
     agents: &HashMap<String, AgentConfig>,
     teams: &HashMap<String, TeamConfig>,
-    script_dir: &Path,
-    log_file: &Path,
+    paths: &Paths,
     cooldowns_file: &Path,
     settings: &Settings,
-) -> Result<String> {
+) -> Result<AgentResponse> {
+    let log_file = &paths.log_file;
     let mut cooldowns = load_cooldowns(cooldowns_file);
     let primary_key = cooldown_key(&agent.provider, &agent.model);
+    // Why the last model was given up, until the next one is tried
+    let mut failover_reason;
 
     // Try primary model (unless in cooldown)
     if !is_in_cooldown(&cooldowns, &primary_key) {
-        match invoke_agent(
-            agent, agent_id, message, workspace_path, should_reset, agents, teams, script_dir,
-            log_file, settings,
+        let attempt = Attempt::start(agent_id, &agent.model, false);
+        let result = invoke_agent(
+            agent,
+            agent_id,
+            prompt,
+            workspace_path,
+            resume_session_id,
+            agents,
+            teams,
+            paths,
+            settings,
         )
-        .await
-        {
+        .instrument(attempt.span.clone())
+        .await;
+        match result {
             Ok(response) => {
+                emit_invocation(paths, agent, agent_id, attempt, None);
                 clear_cooldown(&mut cooldowns, &primary_key);
                 let _ = save_cooldowns(cooldowns_file, &cooldowns);
                 return Ok(response);
             }
             Err(e) => {
                 let reason = classify_error(&e.to_string());
-                record_failure(&mut cooldowns, &primary_key, reason);
+                record_failure(&mut cooldowns, &primary_key, reason.clone());
                 let _ = save_cooldowns(cooldowns_file, &cooldowns);
+                let until = cooldowns
+                    .get(&primary_key)
+                    .map(|c| c.until)
+                    .unwrap_or_default();
+                failover_reason = Some(reason.as_str());
+                emit_invocation(paths, agent, agent_id, attempt, Some((reason, until)));
                 log(
                     "WARN",
                     &format!(
@@ -317,6 +549,7 @@ pub async fn invoke_agent_with_failover(
             }
         }
     } else {
+        failover_reason = Some("cooldown");
         log(
             "INFO",
             &format!(
@@ -349,6 +582,9 @@ pub async fn invoke_agent_with_failover(
 
         let mut fallback_agent = agent.clone();
         fallback_agent.model = fallback_model.clone();
+        if let Some(reason) = failover_reason.take() {
+            metrics::record_failover(reason);
+        }
 
         log(
             "INFO",
@@ -359,21 +595,34 @@ pub async fn invoke_agent_with_failover(
             log_file,
         );
 
-        match invoke_agent(
-            &fallback_agent, agent_id, message, workspace_path, should_reset, agents, teams,
-            script_dir, log_file, settings,
+        let attempt = Attempt::start(agent_id, fallback_model, true);
+        let result = invoke_agent(
+            &fallback_agent,
+            agent_id,
+            prompt,
+            workspace_path,
+            resume_session_id,
+            agents,
+            teams,
+            paths,
+            settings,
         )
-        .await
-        {
+        .instrument(attempt.span.clone())
+        .await;
+        match result {
             Ok(response) => {
+                emit_invocation(paths, agent, agent_id, attempt, None);
                 clear_cooldown(&mut cooldowns, &fb_key);
                 let _ = save_cooldowns(cooldowns_file, &cooldowns);
                 return Ok(response);
             }
             Err(e) => {
                 let reason = classify_error(&e.to_string());
-                record_failure(&mut cooldowns, &fb_key, reason);
+                record_failure(&mut cooldowns, &fb_key, reason.clone());
                 let _ = save_cooldowns(cooldowns_file, &cooldowns);
+                let until = cooldowns.get(&fb_key).map(|c| c.until).unwrap_or_default();
+                failover_reason = Some(reason.as_str());
+                emit_invocation(paths, agent, agent_id, attempt, Some((reason, until)));
                 log(
                     "WARN",
                     &format!("Fallback model {} failed: {}", fallback_model, e),
@@ -441,4 +690,42 @@ mod tests {
         let raw = "not json at all\n{\"type\":\"item.completed\",\"item\":{\"type\":\"agent_message\",\"text\":\"Works\"}}\nmore junk";
         assert_eq!(parse_codex_output(raw), "Works");
     }
+
+    #[test]
+    fn test_parse_claude_output_json() {
+        let raw = r#"{"type":"result","subtype":"success","is_error":false,"result":"Hello from Claude!","session_id":"3f2a9c1e-0000-4000-8000-000000000001"}"#;
+        let response = parse_claude_output(raw);
+        assert_eq!(response.text, "Hello from Claude!");
+        assert_eq!(
+            response.session_id.as_deref(),
+            Some("3f2a9c1e-0000-4000-8000-000000000001")
+        );
+    }
+
+    #[test]
+    fn test_parse_claude_output_plain_text() {
+        let response = parse_claude_output("Plain text reply\n");
+        assert_eq!(response.text, "Plain text reply\n");
+        assert!(response.session_id.is_none());
+    }
+
+    #[test]
+    fn test_parse_codex_thread_id() {
+        let raw = r#"{"type":"thread.started","thread_id":"0199a213-81c0-7800-8aa1-bbab2a035a53"}
+{"type":"turn.started"}
+{"type":"item.completed","item":{"type":"agent_message","text":"Done"}}"#;
+        assert_eq!(
+            parse_codex_thread_id(raw).as_deref(),
+            Some("0199a213-81c0-7800-8aa1-bbab2a035a53")
+        );
+        assert!(parse_codex_thread_id(r#"{"type":"turn.started"}"#).is_none());
+    }
+
+    #[test]
+    fn test_parse_opencode_session_id() {
+        let raw = r#"{"type":"step_start","sessionID":"ses_abc123","part":{}}
+{"type":"text","sessionID":"ses_abc123","part":{"text":"Hi"}}"#;
+        assert_eq!(parse_opencode_session_id(raw).as_deref(), Some("ses_abc123"));
+        assert!(parse_opencode_session_id(r#"{"type":"text","part":{"text":"Hi"}}"#).is_none());
+    }
 }
@@ -1,13 +1,22 @@
 pub mod conversation;
 pub mod invoke;
+pub mod metrics;
 pub mod processor;
+pub mod queue_admin;
+pub mod trace;
 
 use std::sync::Arc;
 
 use anyhow::Result;
-use rustyclaw_core::config::Paths;
+use rustyclaw_core::config::{get_settings, Paths};
 
 pub async fn run(paths: Arc<Paths>) -> Result<()> {
+    let tracing = get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.monitoring)
+        .and_then(|m| m.tracing);
+    let extra = trace::init(tracing.as_ref(), &paths).into_iter().collect();
+    rustyclaw_core::logging::init_with("queue", &paths, extra);
     println!("Rusty Claw Queue Processor");
     println!("  Home: {}", paths.rustyclaw_home.display());
     println!("  Incoming: {}", paths.queue_incoming.display());
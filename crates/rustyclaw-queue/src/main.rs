@@ -1,6 +1,9 @@
 mod conversation;
 mod invoke;
+mod metrics;
 mod processor;
+mod queue_admin;
+mod trace;
 
 use std::env;
 use std::path::PathBuf;
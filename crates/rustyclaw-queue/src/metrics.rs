@@ -0,0 +1,355 @@
+//! Prometheus exporter for the queue processor.
+//!
+//! Counters and histograms live in memory and start from zero when the
+//! processor starts. Queue depth, cooldowns and open conversations are read
+//! when `/metrics` is scraped. The endpoint is only served when
+//! `monitoring.metrics_bind` is set in settings.json.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use tokio::sync::Mutex;
+
+use rustyclaw_core::config::Paths;
+use rustyclaw_core::failover::{load_cooldowns, FailoverReason};
+use rustyclaw_core::logging::log;
+use rustyclaw_core::types::Conversation;
+
+/// Upper bounds (seconds) of the invocation duration histogram buckets.
+const DURATION_BUCKETS: [f64; 10] = [1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default, Clone, Copy)]
+struct Histogram {
+    /// Non-cumulative counts per bucket; the last slot is `+Inf`
+    buckets: [u64; DURATION_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let slot = DURATION_BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.buckets[slot] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+struct Registry {
+    /// `(channel, agent, status)` -> messages processed
+    messages: BTreeMap<(String, String, &'static str), u64>,
+    /// `(provider, model)` -> invocation durations
+    invocations: BTreeMap<(String, String), Histogram>,
+    /// `(provider, model, reason)` -> failed invocations
+    invocation_failures: BTreeMap<(String, String, &'static str), u64>,
+    /// Failed attempts that sent the agent to its next model, by reason
+    failovers: BTreeMap<&'static str, u64>,
+    /// Agent ID -> context compactions
+    compactions: BTreeMap<String, u64>,
+}
+
+static REGISTRY: StdMutex<Registry> = StdMutex::new(Registry {
+    messages: BTreeMap::new(),
+    invocations: BTreeMap::new(),
+    invocation_failures: BTreeMap::new(),
+    failovers: BTreeMap::new(),
+    compactions: BTreeMap::new(),
+});
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// ─── Recording ─────────────────────────────────────────────────────────────
+
+/// Count one message handled by an agent.
+pub fn record_message(channel: &str, agent_id: &str, failed: bool) {
+    let status = if failed { "failed" } else { "ok" };
+    *registry()
+        .messages
+        .entry((channel.to_string(), agent_id.to_string(), status))
+        .or_default() += 1;
+}
+
+/// Record one model invocation. `failure` is set when the attempt failed.
+pub fn record_invocation(
+    provider: &str,
+    model: &str,
+    elapsed: Duration,
+    failure: Option<&FailoverReason>,
+) {
+    let mut reg = registry();
+    let key = (provider.to_string(), model.to_string());
+    reg.invocations
+        .entry(key.clone())
+        .or_default()
+        .observe(elapsed.as_secs_f64());
+    if let Some(reason) = failure {
+        *reg.invocation_failures
+            .entry((key.0, key.1, reason.as_str()))
+            .or_default() += 1;
+    }
+}
+
+/// Count an agent moving on to its next model. `reason` is why the previous
+/// model was given up: a failure reason, or `cooldown`.
+pub fn record_failover(reason: &'static str) {
+    *registry().failovers.entry(reason).or_default() += 1;
+}
+
+/// Count one context compaction.
+pub fn record_compaction(agent_id: &str) {
+    *registry().compactions.entry(agent_id.to_string()).or_default() += 1;
+}
+
+// ─── Text format ───────────────────────────────────────────────────────────
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    if pairs.is_empty() {
+        return String::new();
+    }
+    let inner: Vec<String> = pairs
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+        .collect();
+    format!("{{{}}}", inner.join(","))
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn count_json_files(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+/// Render all metrics in the Prometheus text format.
+pub fn render(paths: &Paths, open_conversations: usize, now_ms: u64) -> String {
+    let mut out = String::new();
+    {
+        let reg = registry();
+
+        header(&mut out, "rustyclaw_messages_processed_total", "counter",
+            "Messages handled by an agent, by channel, agent and outcome.");
+        for ((channel, agent, status), n) in &reg.messages {
+            let l = labels(&[("channel", channel), ("agent", agent), ("status", status)]);
+            let _ = writeln!(out, "rustyclaw_messages_processed_total{} {}", l, n);
+        }
+
+        header(&mut out, "rustyclaw_invocation_duration_seconds", "histogram",
+            "Duration of model invocations, by provider and model.");
+        for ((provider, model), h) in &reg.invocations {
+            let mut cumulative = 0;
+            for (i, count) in h.buckets.iter().enumerate() {
+                cumulative += count;
+                let le = DURATION_BUCKETS
+                    .get(i)
+                    .map(|b| b.to_string())
+                    .unwrap_or_else(|| "+Inf".to_string());
+                let l = labels(&[("provider", provider), ("model", model), ("le", &le)]);
+                let _ = writeln!(out, "rustyclaw_invocation_duration_seconds_bucket{} {}", l, cumulative);
+            }
+            let l = labels(&[("provider", provider), ("model", model)]);
+            let _ = writeln!(out, "rustyclaw_invocation_duration_seconds_sum{} {}", l, h.sum);
+            let _ = writeln!(out, "rustyclaw_invocation_duration_seconds_count{} {}", l, h.count);
+        }
+
+        header(&mut out, "rustyclaw_invocation_failures_total", "counter",
+            "Failed model invocations, by provider, model and reason.");
+        for ((provider, model, reason), n) in &reg.invocation_failures {
+            let l = labels(&[("provider", provider), ("model", model), ("reason", reason)]);
+            let _ = writeln!(out, "rustyclaw_invocation_failures_total{} {}", l, n);
+        }
+
+        header(&mut out, "rustyclaw_failovers_total", "counter",
+            "Times an agent moved on to its next model, by why the previous one was given up.");
+        for (reason, n) in &reg.failovers {
+            let _ = writeln!(out, "rustyclaw_failovers_total{} {}", labels(&[("reason", reason)]), n);
+        }
+
+        header(&mut out, "rustyclaw_compactions_total", "counter",
+            "Context compactions, by agent.");
+        for (agent, n) in &reg.compactions {
+            let _ = writeln!(out, "rustyclaw_compactions_total{} {}", labels(&[("agent", agent)]), n);
+        }
+    }
+
+    header(&mut out, "rustyclaw_cooldown_remaining_seconds", "gauge",
+        "Seconds left on each active model cooldown in cooldowns.json.");
+    let mut cooldowns: Vec<_> = load_cooldowns(&paths.rustyclaw_home.join("cooldowns.json"))
+        .into_iter()
+        .filter(|(_, c)| c.until > now_ms)
+        .collect();
+    cooldowns.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, c) in &cooldowns {
+        let (provider, model) = key.split_once(':').unwrap_or(("", key));
+        let l = labels(&[("provider", provider), ("model", model)]);
+        let remaining = (c.until - now_ms) as f64 / 1000.0;
+        let _ = writeln!(out, "rustyclaw_cooldown_remaining_seconds{} {}", l, remaining);
+    }
+    header(&mut out, "rustyclaw_cooldowns_active", "gauge", "Models currently in cooldown.");
+    let _ = writeln!(out, "rustyclaw_cooldowns_active {}", cooldowns.len());
+
+    header(&mut out, "rustyclaw_queue_depth", "gauge", "Messages waiting in each queue directory.");
+    for (name, dir) in [
+        ("incoming", &paths.queue_incoming),
+        ("processing", &paths.queue_processing),
+        ("outgoing", &paths.queue_outgoing),
+    ] {
+        let l = labels(&[("queue", name)]);
+        let _ = writeln!(out, "rustyclaw_queue_depth{} {}", l, count_json_files(dir));
+    }
+
+    header(&mut out, "rustyclaw_open_conversations", "gauge",
+        "Team and multi-agent conversations still waiting on responses.");
+    let _ = writeln!(out, "rustyclaw_open_conversations {}", open_conversations);
+
+    out
+}
+
+// ─── Server ────────────────────────────────────────────────────────────────
+
+#[derive(Clone)]
+struct ExporterState {
+    paths: Arc<Paths>,
+    conversations: Arc<Mutex<HashMap<String, Conversation>>>,
+}
+
+async fn metrics_handler(State(state): State<ExporterState>) -> impl IntoResponse {
+    let open = state.conversations.lock().await.len();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], render(&state.paths, open, now))
+}
+
+/// Serve `GET /metrics` on `bind` until the processor stops.
+pub async fn serve(
+    bind: &str,
+    paths: Arc<Paths>,
+    conversations: Arc<Mutex<HashMap<String, Conversation>>>,
+) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("Failed to bind metrics endpoint to {}", bind))?;
+    log(
+        "INFO",
+        &format!("Metrics endpoint: http://{}/metrics", bind),
+        &paths.log_file,
+    );
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(ExporterState { paths, conversations });
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative_in_output() {
+        let mut h = Histogram::default();
+        h.observe(0.5);
+        h.observe(3.0);
+        h.observe(1000.0);
+        assert_eq!(h.count, 3);
+        assert_eq!(h.buckets[0], 1);
+        assert_eq!(h.buckets[2], 1);
+        assert_eq!(h.buckets[DURATION_BUCKETS.len()], 1);
+    }
+
+    #[test]
+    fn test_render_includes_recorded_and_scraped_metrics() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let home = tmp.path();
+        let paths = Paths {
+            script_dir: home.to_path_buf(),
+            rustyclaw_home: home.to_path_buf(),
+            queue_incoming: home.join("queue/incoming"),
+            queue_outgoing: home.join("queue/outgoing"),
+            queue_processing: home.join("queue/processing"),
+            log_file: home.join("logs/queue.log"),
+            settings_file: home.join("settings.json"),
+            events_dir: home.join("events"),
+            chats_dir: home.join("chats"),
+            files_dir: home.join("files"),
+            pairing_file: home.join("pairing.json"),
+            skills_dir: home.join("skills"),
+            skills_lock_file: home.join("skills.lock"),
+        };
+        paths.ensure_queue_dirs().unwrap();
+        std::fs::write(paths.queue_incoming.join("a.json"), "{}").unwrap();
+        std::fs::write(paths.queue_incoming.join("b.json"), "{}").unwrap();
+        std::fs::write(
+            paths.rustyclaw_home.join("cooldowns.json"),
+            r#"{"anthropic:opus": {"until": 70000, "error_count": 1}, "openai:gpt": {"until": 10, "error_count": 1}}"#,
+        )
+        .unwrap();
+
+        record_message("test-render", "coder", false);
+        record_message("test-render", "coder", false);
+        record_invocation("test-render", "m\"1", Duration::from_millis(1500), Some(&FailoverReason::Timeout));
+        record_failover("cooldown");
+        record_compaction("test-render-agent");
+
+        let text = render(&paths, 2, 10_000);
+        assert!(text.contains(
+            "rustyclaw_messages_processed_total{channel=\"test-render\",agent=\"coder\",status=\"ok\"} 2"
+        ));
+        assert!(text.contains(
+            "rustyclaw_invocation_duration_seconds_bucket{provider=\"test-render\",model=\"m\\\"1\",le=\"1\"} 0"
+        ));
+        assert!(text.contains(
+            "rustyclaw_invocation_duration_seconds_bucket{provider=\"test-render\",model=\"m\\\"1\",le=\"2.5\"} 1"
+        ));
+        assert!(text.contains(
+            "rustyclaw_invocation_duration_seconds_bucket{provider=\"test-render\",model=\"m\\\"1\",le=\"+Inf\"} 1"
+        ));
+        assert!(text.contains(
+            "rustyclaw_invocation_failures_total{provider=\"test-render\",model=\"m\\\"1\",reason=\"timeout\"} 1"
+        ));
+        assert!(!text.contains("rustyclaw_failovers_total{reason=\"timeout\"}"));
+        assert!(text.contains("rustyclaw_failovers_total{reason=\"cooldown\"} 1"));
+        assert!(text.contains("rustyclaw_compactions_total{agent=\"test-render-agent\"} 1"));
+        assert!(text.contains(
+            "rustyclaw_cooldown_remaining_seconds{provider=\"anthropic\",model=\"opus\"} 60"
+        ));
+        assert!(!text.contains("model=\"gpt\""));
+        assert!(text.contains("rustyclaw_cooldowns_active 1"));
+        assert!(text.contains("rustyclaw_queue_depth{queue=\"incoming\"} 2"));
+        assert!(text.contains("rustyclaw_queue_depth{queue=\"outgoing\"} 0"));
+        assert!(text.contains("rustyclaw_open_conversations 2"));
+    }
+}
@@ -5,15 +5,21 @@ use std::sync::Arc;
 use anyhow::Result;
 use regex::Regex;
 use tokio::sync::{mpsc, Mutex};
+use tracing::Instrument;
 
+use rustyclaw_core::channels::long_response_mode;
 use rustyclaw_core::compaction;
-use rustyclaw_core::config::{get_agents, get_settings, get_teams, get_workspace_path, Paths};
+use rustyclaw_core::config::{
+    get_agents, get_settings, get_teams, get_workspace_path, validate_settings, Paths,
+};
 use rustyclaw_core::logging::{emit_event, log};
 use rustyclaw_core::routing::{
     extract_all_agent_mentions, extract_cross_team_mentions, extract_natural_handoffs,
     extract_teammate_mentions, find_team_for_agent, parse_agent_routing,
 };
 use rustyclaw_core::session;
+use rustyclaw_core::skills::{self, Skill};
+use rustyclaw_core::timezone::resolve_timezone;
 use rustyclaw_core::transcript::{self, TranscriptEntry};
 use rustyclaw_core::types::{
     ChainStep, Conversation, MessageData, QueueFile, ResponseData, TeamContext,
@@ -23,11 +29,17 @@ use crate::conversation::{
     collect_files, complete_conversation, create_conversation, enqueue_internal_message,
     handle_long_response,
 };
-use crate::invoke::{invoke_agent, invoke_agent_with_failover};
+use crate::invoke::{
+    eligible_skills_for_agent, invoke_agent, invoke_agent_with_failover, AgentResponse, Prompt,
+};
+use crate::{metrics, queue_admin, trace};
 
 /// Maximum age for a conversation before it's considered timed out (30 minutes).
 const CONVERSATION_TIMEOUT_MS: u64 = 30 * 60 * 1000;
 
+/// Maximum follow-up turns spent loading skills requested with `[load_skill: name]`.
+const MAX_SKILL_LOAD_ROUNDS: usize = 2;
+
 /// Move orphaned files from processing/ back to incoming/ on startup.
 pub fn recover_orphaned_files(paths: &Paths) {
     let processing_dir = &paths.queue_processing;
@@ -126,6 +138,25 @@ pub fn peek_agent_id(file_path: &Path, paths: &Paths) -> String {
     result.unwrap_or_else(|_| "default".to_string())
 }
 
+/// Remove and return the waiting message with the highest priority; ties
+/// go to the one queued first. Files that can't be read sort last.
+fn take_next_message(waiting: &mut Vec<PathBuf>) -> PathBuf {
+    let priority = |path: &Path| -> i32 {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<MessageData>(&raw).ok())
+            .map(|msg| msg.priority.unwrap_or(0))
+            .unwrap_or(i32::MIN)
+    };
+    let next = waiting
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, path)| (priority(path), std::cmp::Reverse(*i)))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    waiting.remove(next)
+}
+
 /// Process a single message file. This is the heart of the queue processor.
 async fn process_message_inner(
     message_file: &Path,
@@ -140,8 +171,19 @@ async fn process_message_inner(
             .as_ref(),
     );
 
-    // Move to processing
-    std::fs::rename(message_file, &processing_file)?;
+    // Move to processing. The message may have been deleted, re-queued or
+    // claimed for editing from the dashboard since it was queued.
+    if let Err(e) = std::fs::rename(message_file, &processing_file) {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            log(
+                "INFO",
+                &format!("Skipping {}: no longer in the queue", message_file.display()),
+                &paths.log_file,
+            );
+            return Ok(());
+        }
+        return Err(e.into());
+    }
 
     // Read message
     let raw = std::fs::read_to_string(&processing_file)?;
@@ -191,6 +233,12 @@ async fn process_message_inner(
     let workspace_path = get_workspace_path(&settings);
 
     // Route message to agent (or team)
+    let route_span = tracing::info_span!(
+        "route",
+        agent_id = tracing::field::Empty,
+        team_routed = tracing::field::Empty,
+        multi_agents = tracing::field::Empty,
+    );
     let (mut agent_id, mut message, is_team_routed, multi_agents);
 
     if let Some(ref pre_routed) = message_data.agent {
@@ -216,6 +264,8 @@ async fn process_message_inner(
 
     // Multi-agent dispatch: create ad-hoc conversation and fan out to all agents
     if !is_internal && multi_agents.len() > 1 {
+        route_span.record("multi_agents", multi_agents.join(","));
+        drop(route_span);
         log(
             "INFO",
             &format!(
@@ -238,6 +288,7 @@ async fn process_message_inner(
         // Create an ad-hoc conversation (no team context) to aggregate responses
         let mut conv = create_conversation(
             message_id, channel, sender, raw_message, None,
+            message_data.reply_to.clone(),
         );
         conv.pending = multi_agents.len() as i32;
         let conv_id = conv.id.clone();
@@ -280,6 +331,10 @@ async fn process_message_inner(
     }
 
     let agent = agents[&agent_id].clone();
+    route_span.record("agent_id", agent_id.as_str());
+    route_span.record("team_routed", is_team_routed);
+    tracing::Span::current().record("agent_id", agent_id.as_str());
+    drop(route_span);
     log(
         "INFO",
         &format!(
@@ -331,8 +386,10 @@ async fn process_message_inner(
 
     // Resolve session state and determine if reset is needed
     let agent_dir = workspace_path.join(&agent_id);
-    let (should_reset, _session_id) = session::resolve_should_reset(
-        &agent_dir, &agent_id, &agent, channel, sender, &workspace_path,
+    let tz = resolve_timezone(&agent);
+    let session_sender = session::session_scope(sender, message_data.reply_to.as_ref());
+    let (should_reset, resume_session_id) = session::resolve_should_reset(
+        &agent_dir, &agent_id, &agent, channel, &session_sender, &workspace_path,
     );
 
     // For internal messages: append pending response indicator
@@ -366,10 +423,11 @@ async fn process_message_inner(
             entry_type: None,
             chars_before: None,
         };
-        let _ = transcript::append_transcript_entry(&transcripts_dir, &user_entry);
+        let _ = transcript::append_transcript_entry(&transcripts_dir, &user_entry, tz);
     }
 
     // Invoke agent (with failover support)
+    let step_started = now_millis();
     emit_event(
         "chain_step_start",
         serde_json::json!({
@@ -381,16 +439,16 @@ async fn process_message_inner(
     );
 
     let cooldowns_file = paths.rustyclaw_home.join("cooldowns.json");
+    let mut step_failed = false;
     let response = match invoke_agent_with_failover(
         &agent,
         &agent_id,
-        &message,
+        Prompt::Message(&message),
         &workspace_path,
-        should_reset,
+        resume_session_id.as_deref(),
         &agents,
         &teams,
-        &paths.script_dir,
-        &paths.log_file,
+        paths,
         &cooldowns_file,
         &settings,
     )
@@ -408,16 +466,115 @@ async fn process_message_inner(
                 &format!("{} error (agent: {}): {}", provider_label, agent_id, e),
                 &paths.log_file,
             );
-            "Sorry, I encountered an error processing your request. Please check the queue logs."
-                .to_string()
+            step_failed = true;
+            AgentResponse {
+                text: "Sorry, I encountered an error processing your request. Please check the queue logs."
+                    .to_string(),
+                session_id: resume_session_id.clone(),
+            }
         }
     };
+    let mut provider_session_id = response.session_id;
+    let mut response = response.text;
+
+    // Satisfy [load_skill: name] requests with a follow-up turn
+    let mut loaded_skills: HashSet<String> = HashSet::new();
+    for _ in 0..MAX_SKILL_LOAD_ROUNDS {
+        let requested: Vec<String> = skills::extract_skill_requests(&response)
+            .into_iter()
+            .filter(|name| !loaded_skills.contains(name))
+            .collect();
+        if requested.is_empty() {
+            break;
+        }
+
+        let available =
+            eligible_skills_for_agent(&agent, &agent_id, &workspace_path, &teams, paths, &settings);
+        let found: Vec<&Skill> = available
+            .iter()
+            .filter(|s| requested.contains(&s.meta.name))
+            .collect();
+        let missing: Vec<&String> = requested
+            .iter()
+            .filter(|name| !found.iter().any(|s| &&s.meta.name == name))
+            .collect();
+        loaded_skills.extend(requested.iter().cloned());
+
+        log(
+            "INFO",
+            &format!("Agent {} requested skill(s): {}", agent_id, requested.join(", ")),
+            &paths.log_file,
+        );
+
+        let mut follow_up = String::new();
+        if !found.is_empty() {
+            follow_up.push_str(&format!(
+                "<loaded_skills>\n{}\n</loaded_skills>\n\n",
+                skills::format_skill_instructions(&found)
+            ));
+        }
+        if !missing.is_empty() {
+            let names: Vec<&str> = missing.iter().map(|n| n.as_str()).collect();
+            follow_up.push_str(&format!(
+                "[Skill(s) not available: {}]\n\n",
+                names.join(", ")
+            ));
+        }
+        // A resumed provider session already holds the context and the
+        // request, so only the skill instructions go in
+        let prompt = if provider_session_id.is_some() {
+            follow_up.push_str("Continue with the original request using these instructions.");
+            Prompt::FollowUp(&follow_up)
+        } else {
+            follow_up.push_str(&format!(
+                "Continue with the original request using these instructions:\n{}",
+                message
+            ));
+            Prompt::Message(&follow_up)
+        };
 
+        match invoke_agent_with_failover(
+            &agent,
+            &agent_id,
+            prompt,
+            &workspace_path,
+            provider_session_id.as_deref(),
+            &agents,
+            &teams,
+            paths,
+            &cooldowns_file,
+            &settings,
+        )
+        .await
+        {
+            Ok(r) => {
+                provider_session_id = r.session_id.or(provider_session_id);
+                response = r.text;
+            }
+            Err(e) => {
+                log(
+                    "WARN",
+                    &format!("Skill follow-up failed for agent {}: {}", agent_id, e),
+                    &paths.log_file,
+                );
+                break;
+            }
+        }
+    }
+    if !loaded_skills.is_empty() {
+        response = skills::strip_skill_requests(&response);
+    }
+
+    metrics::record_message(channel, &agent_id, step_failed);
     emit_event(
         "chain_step_done",
         serde_json::json!({
             "agentId": agent_id,
             "agentName": agent.name,
+            "provider": agent.provider,
+            "model": agent.model,
+            "durationMs": now_millis().saturating_sub(step_started),
+            "failed": step_failed,
             "responseLength": response.len(),
             "responseText": response,
         }),
@@ -439,15 +596,15 @@ async fn process_message_inner(
             entry_type: None,
             chars_before: None,
         };
-        let _ = transcript::append_transcript_entry(&transcripts_dir, &assistant_entry);
+        let _ = transcript::append_transcript_entry(&transcripts_dir, &assistant_entry, tz);
     }
 
     // Update session state and check for compaction
     {
         let agent_dir = workspace_path.join(&agent_id);
         if let Ok(session_entry) = session::update_session(
-            &agent_dir, &agent_id, channel, sender,
-            message.len(), response.len(), should_reset,
+            &agent_dir, &agent_id, channel, &session_sender,
+            message.len(), response.len(), should_reset, provider_session_id.as_deref(),
         ) {
             let context_window = compaction::resolve_context_window(agent.context_window);
             if compaction::should_compact(
@@ -466,15 +623,16 @@ async fn process_message_inner(
                     &paths.log_file,
                 );
 
-                // Ask the agent to summarize
+                // Ask the agent to summarize this sender's session
                 let compaction_prompt = compaction::build_compaction_prompt();
                 let summary = match invoke_agent(
-                    &agent, &agent_id, &compaction_prompt, &workspace_path,
-                    false, &agents, &teams, &paths.script_dir, &paths.log_file, &settings,
+                    &agent, &agent_id, Prompt::Message(&compaction_prompt), &workspace_path,
+                    session_entry.provider_session_id.as_deref(), &agents, &teams,
+                    paths, &settings,
                 )
                 .await
                 {
-                    Ok(s) => s,
+                    Ok(s) => s.text,
                     Err(e) => {
                         log(
                             "WARN",
@@ -502,10 +660,10 @@ async fn process_message_inner(
                     entry_type: Some("compaction".to_string()),
                     chars_before: Some(session_entry.total_chars),
                 };
-                let _ = transcript::append_transcript_entry(&transcripts_dir, &compaction_entry);
+                let _ = transcript::append_transcript_entry(&transcripts_dir, &compaction_entry, tz);
 
                 // Reset session chars and increment compaction count
-                let session_key = session::resolve_session_key(&agent_id, channel, sender);
+                let session_key = session::resolve_session_key(&agent_id, channel, &session_sender);
                 let mut sessions = session::load_sessions(&agent_dir);
                 if let Some(entry) = sessions.get_mut(&session_key) {
                     entry.total_chars = summary.len() as u64;
@@ -522,6 +680,7 @@ async fn process_message_inner(
                     &paths.log_file,
                 );
 
+                metrics::record_compaction(&agent_id);
                 emit_event(
                     "compaction",
                     serde_json::json!({
@@ -572,6 +731,7 @@ async fn process_message_inner(
             } else {
                 let conv = create_conversation(
                     message_id, channel, sender, raw_message, None,
+                    message_data.reply_to.clone(),
                 );
                 active_conv_id = conv.id.clone();
                 log(
@@ -588,6 +748,9 @@ async fn process_message_inner(
                 conv.responses.push(ChainStep {
                     agent_id: agent_id.clone(),
                     response: response.clone(),
+                    from_agent: message_data.from_agent.clone(),
+                    started_at: step_started,
+                    finished_at: now_millis(),
                 });
                 conv.total_messages += 1;
                 collect_files(&response, &mut conv.files);
@@ -691,6 +854,7 @@ async fn process_message_inner(
         let (response_message, all_files) = handle_long_response(
             &final_response,
             &outbound_files,
+            long_response_mode(&settings, channel),
             &paths.files_dir,
             &paths.log_file,
         );
@@ -708,6 +872,7 @@ async fn process_message_inner(
             } else {
                 Some(all_files)
             },
+            reply_to: message_data.reply_to.clone(),
         };
 
         let response_file = if channel == "heartbeat" {
@@ -777,6 +942,7 @@ async fn process_message_inner(
             sender,
             raw_message,
             Some(team_context.clone()),
+            message_data.reply_to.clone(),
         );
         active_conv_id = conv.id.clone();
         log(
@@ -806,6 +972,9 @@ async fn process_message_inner(
         conv.responses.push(ChainStep {
             agent_id: agent_id.clone(),
             response: response.clone(),
+            from_agent: message_data.from_agent.clone(),
+            started_at: step_started,
+            finished_at: now_millis(),
         });
         conv.total_messages += 1;
         collect_files(&response, &mut conv.files);
@@ -1007,7 +1176,32 @@ pub async fn process_message(
             .as_ref(),
     );
 
-    if let Err(e) = process_message_inner(&message_file, &paths, &conversations).await {
+    // Context attached to every log line and trace span of the message
+    let data: Option<MessageData> = std::fs::read_to_string(&message_file)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok());
+    let span = tracing::info_span!(
+        "process_message",
+        message_id = data.as_ref().map(|d| d.message_id.as_str()),
+        channel = data.as_ref().map(|d| d.channel.as_str()),
+        conversation_id = data.as_ref().and_then(|d| d.conversation_id.as_deref()),
+        agent_id = tracing::field::Empty,
+    );
+    // Continue the trace of the handoff that queued this message, if any
+    if let Some(tp) = data.as_ref().and_then(|d| d.traceparent.as_deref()) {
+        trace::set_parent(&span, tp);
+    }
+
+    // Held until the message is done or back in incoming, so the dashboard
+    // can't requeue or delete it meanwhile. A missing file is skipped below.
+    let _in_flight = queue_admin::lock_in_flight(&message_file).ok();
+
+    let result = process_message_inner(&message_file, &paths, &conversations)
+        .instrument(span.clone())
+        .await;
+    let _entered = span.enter();
+    if let Err(e) = result {
+        trace::fail(&span, &e);
         log(
             "ERROR",
             &format!("Processing error: {}", e),
@@ -1036,6 +1230,9 @@ pub fn log_agent_config(paths: &Paths) {
     let agents = get_agents(&settings);
     let teams = get_teams(&settings);
 
+    for error in validate_settings(&settings) {
+        log("WARN", &format!("Invalid setting: {}", error), &paths.log_file);
+    }
     log(
         "INFO",
         &format!("Loaded {} agent(s):", agents.len()),
@@ -1088,6 +1285,7 @@ pub async fn run_queue_processor(paths: Arc<Paths>) -> Result<()> {
 
     log("INFO", "Queue processor started", &paths.log_file);
     recover_orphaned_files(&paths);
+    queue_admin::release_stale_claims(&paths);
     log(
         "INFO",
         &format!("Watching: {}", paths.queue_incoming.display()),
@@ -1116,6 +1314,21 @@ pub async fn run_queue_processor(paths: Arc<Paths>) -> Result<()> {
     let agent_senders: Arc<Mutex<HashMap<String, mpsc::Sender<PathBuf>>>> =
         Arc::new(Mutex::new(HashMap::new()));
 
+    // Prometheus endpoint, if configured
+    let monitoring = get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.monitoring)
+        .unwrap_or_default();
+    if let Some(bind) = monitoring.metrics_bind {
+        let paths_clone = Arc::clone(&paths);
+        let conversations_clone = Arc::clone(&conversations);
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(&bind, Arc::clone(&paths_clone), conversations_clone).await {
+                log("ERROR", &format!("Metrics endpoint stopped: {:#}", e), &paths_clone.log_file);
+            }
+        });
+    }
+
     // Queued files set to prevent duplicate processing
     let queued_files: Arc<Mutex<std::collections::HashSet<String>>> =
         Arc::new(Mutex::new(std::collections::HashSet::new()));
@@ -1207,14 +1420,33 @@ pub async fn run_queue_processor(paths: Arc<Paths>) -> Result<()> {
                             let queued_clone = Arc::clone(&queued_files);
 
                             // Spawn agent processing task
+                            let worker_agent_id = target_agent_id.clone();
                             tokio::spawn(async move {
-                                while let Some(msg_path) = rx.recv().await {
+                                let mut waiting: Vec<PathBuf> = Vec::new();
+                                loop {
+                                    if waiting.is_empty() {
+                                        match rx.recv().await {
+                                            Some(path) => waiting.push(path),
+                                            None => break,
+                                        }
+                                    }
+                                    while let Ok(path) = rx.try_recv() {
+                                        waiting.push(path);
+                                    }
+                                    let msg_path = take_next_message(&mut waiting);
                                     let file_name = msg_path
                                         .file_name()
                                         .unwrap_or_default()
                                         .to_string_lossy()
                                         .to_string();
 
+                                    // Re-routed since it was queued: let the
+                                    // poll loop hand it to the new agent
+                                    if peek_agent_id(&msg_path, &paths_clone) != worker_agent_id {
+                                        queued_clone.lock().await.remove(&file_name);
+                                        continue;
+                                    }
+
                                     process_message(
                                         msg_path,
                                         Arc::clone(&paths_clone),
@@ -1248,6 +1480,7 @@ pub async fn run_queue_processor(paths: Arc<Paths>) -> Result<()> {
             }
             _ = &mut shutdown => {
                 log("INFO", "Shutting down queue processor...", &paths.log_file);
+                trace::flush().await;
                 break;
             }
         }
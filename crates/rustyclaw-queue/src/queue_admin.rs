@@ -0,0 +1,320 @@
+//! Operator actions on queued message files (delete, re-route, requeue,
+//! bump priority). The processor claims a message by renaming it from
+//! `incoming/` to `processing/`, so every action here either is a single
+//! rename or unlink itself, or first claims the file the same way (renaming
+//! it to a `.claimed` name the processor ignores) before editing it.
+//!
+//! While the processor works on a message it holds a lock on the file, so
+//! actions on `processing/` only touch files left behind by a processor that
+//! stopped.
+
+use std::fs::{File, TryLockError};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use rustyclaw_core::config::Paths;
+use rustyclaw_core::types::MessageData;
+
+const CLAIM_EXTENSION: &str = "json.claimed";
+
+/// Which queue directory a message file is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueDir {
+    Incoming,
+    Processing,
+}
+
+impl std::str::FromStr for QueueDir {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "incoming" => Ok(Self::Incoming),
+            "processing" => Ok(Self::Processing),
+            other => Err(format!("Unknown queue '{}'", other)),
+        }
+    }
+}
+
+impl QueueDir {
+    fn path(self, paths: &Paths) -> &Path {
+        match self {
+            Self::Incoming => &paths.queue_incoming,
+            Self::Processing => &paths.queue_processing,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum QueueActionError {
+    /// The file is no longer there: processed, moved or deleted meanwhile
+    Gone,
+    /// The processor is working on the message
+    InFlight,
+    Invalid(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for QueueActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gone => write!(f, "Message is no longer in that queue"),
+            Self::InFlight => write!(f, "Message is being processed"),
+            Self::Invalid(msg) => write!(f, "{}", msg),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for QueueActionError {}
+
+impl From<std::io::Error> for QueueActionError {
+    fn from(e: std::io::Error) -> Self {
+        if e.kind() == ErrorKind::NotFound {
+            Self::Gone
+        } else {
+            Self::Io(e)
+        }
+    }
+}
+
+type ActionResult = Result<MessageData, QueueActionError>;
+
+/// Resolve a queue file name, rejecting anything but a plain `*.json` name.
+fn queue_file(paths: &Paths, dir: QueueDir, file: &str) -> Result<PathBuf, QueueActionError> {
+    if file.contains(['/', '\\']) || file.starts_with('.') || !file.ends_with(".json") {
+        return Err(QueueActionError::Invalid(format!("Invalid queue file '{}'", file)));
+    }
+    Ok(dir.path(paths).join(file))
+}
+
+fn read_message(path: &Path) -> ActionResult {
+    let raw = std::fs::read_to_string(path)?;
+    serde_json::from_str(&raw)
+        .map_err(|e| QueueActionError::Invalid(format!("Unreadable message file: {}", e)))
+}
+
+/// Lock a message file for as long as the processor works on it. Taken
+/// before the file moves to `processing/`, so a file there is in flight
+/// exactly while its lock is held.
+pub fn lock_in_flight(path: &Path) -> std::io::Result<File> {
+    let file = File::open(path)?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Lock a message file for an operator action; `InFlight` while the
+/// processor holds it.
+fn lock_idle(path: &Path) -> Result<File, QueueActionError> {
+    let file = File::open(path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(QueueActionError::InFlight),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Take an incoming file away from the processor, apply `edit`, and put it
+/// back under its original name. If the processor renamed it first, the
+/// claim fails with `Gone` and nothing is changed.
+fn edit_incoming(
+    paths: &Paths,
+    file: &str,
+    edit: impl FnOnce(&mut MessageData) -> Result<(), QueueActionError>,
+) -> ActionResult {
+    let path = queue_file(paths, QueueDir::Incoming, file)?;
+    let claimed = path.with_extension(CLAIM_EXTENSION);
+    std::fs::rename(&path, &claimed)?;
+
+    let result = read_message(&claimed).and_then(|mut msg| {
+        edit(&mut msg)?;
+        let json = serde_json::to_string_pretty(&msg)
+            .map_err(|e| QueueActionError::Invalid(e.to_string()))?;
+        std::fs::write(&claimed, json)?;
+        Ok(msg)
+    });
+    // Release the claim whether or not the edit worked
+    std::fs::rename(&claimed, &path)?;
+    result
+}
+
+/// Delete a message from incoming or processing, unless it is in flight.
+pub fn delete_message(paths: &Paths, dir: QueueDir, file: &str) -> ActionResult {
+    let path = queue_file(paths, dir, file)?;
+    let _lock = lock_idle(&path)?;
+    let msg = read_message(&path)?;
+    std::fs::remove_file(&path)?;
+    Ok(msg)
+}
+
+/// Route an incoming message to `agent_id`, replacing any pre-routing.
+pub fn reroute_message(paths: &Paths, file: &str, agent_id: &str) -> ActionResult {
+    edit_incoming(paths, file, |msg| {
+        msg.agent = Some(agent_id.to_string());
+        Ok(())
+    })
+}
+
+/// Raise an incoming message's priority by one.
+pub fn bump_priority(paths: &Paths, file: &str) -> ActionResult {
+    edit_incoming(paths, file, |msg| {
+        msg.priority = Some(msg.priority.unwrap_or(0).saturating_add(1));
+        Ok(())
+    })
+}
+
+/// Move a message stuck in processing back to incoming so it runs again.
+/// Messages the processor is still working on are refused.
+pub fn requeue_message(paths: &Paths, file: &str) -> ActionResult {
+    let from = queue_file(paths, QueueDir::Processing, file)?;
+    let to = queue_file(paths, QueueDir::Incoming, file)?;
+    let _lock = lock_idle(&from)?;
+    let msg = read_message(&from)?;
+    std::fs::create_dir_all(&paths.queue_incoming)?;
+    std::fs::rename(&from, &to)?;
+    Ok(msg)
+}
+
+/// Restore incoming files left claimed by an interrupted edit.
+pub fn release_stale_claims(paths: &Paths) {
+    let Ok(entries) = std::fs::read_dir(&paths.queue_incoming) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(stem) = name.strip_suffix(".claimed") {
+            let _ = std::fs::rename(&path, paths.queue_incoming.join(stem));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, Paths) {
+        let tmp = TempDir::new().unwrap();
+        let home = tmp.path();
+        let paths = Paths {
+            script_dir: home.to_path_buf(),
+            rustyclaw_home: home.to_path_buf(),
+            queue_incoming: home.join("queue/incoming"),
+            queue_outgoing: home.join("queue/outgoing"),
+            queue_processing: home.join("queue/processing"),
+            log_file: home.join("logs/queue.log"),
+            settings_file: home.join("settings.json"),
+            events_dir: home.join("events"),
+            chats_dir: home.join("chats"),
+            files_dir: home.join("files"),
+            pairing_file: home.join("pairing.json"),
+            skills_dir: home.join("skills"),
+            skills_lock_file: home.join("skills.lock"),
+        };
+        std::fs::create_dir_all(&paths.queue_incoming).unwrap();
+        std::fs::create_dir_all(&paths.queue_processing).unwrap();
+        (tmp, paths)
+    }
+
+    fn write_message(dir: &Path, file: &str) {
+        let msg = MessageData {
+            channel: "discord".to_string(),
+            sender: "alice".to_string(),
+            sender_id: None,
+            message: "hello".to_string(),
+            timestamp: 1,
+            message_id: "m1".to_string(),
+            agent: Some("coder".to_string()),
+            files: None,
+            conversation_id: None,
+            from_agent: None,
+            reply_to: None,
+            priority: None,
+            traceparent: None,
+        };
+        std::fs::write(dir.join(file), serde_json::to_string(&msg).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_reroute_and_bump() {
+        let (_tmp, paths) = setup();
+        write_message(&paths.queue_incoming, "a.json");
+
+        let msg = reroute_message(&paths, "a.json", "reviewer").unwrap();
+        assert_eq!(msg.agent.as_deref(), Some("reviewer"));
+        bump_priority(&paths, "a.json").unwrap();
+        let msg = bump_priority(&paths, "a.json").unwrap();
+        assert_eq!(msg.priority, Some(2));
+
+        let stored = read_message(&paths.queue_incoming.join("a.json")).unwrap();
+        assert_eq!(stored.agent.as_deref(), Some("reviewer"));
+        assert_eq!(stored.priority, Some(2));
+        assert!(!paths.queue_incoming.join("a.json.claimed").exists());
+    }
+
+    #[test]
+    fn test_actions_on_claimed_file_are_gone() {
+        let (_tmp, paths) = setup();
+        write_message(&paths.queue_incoming, "a.json");
+        // The processor got there first
+        std::fs::rename(
+            paths.queue_incoming.join("a.json"),
+            paths.queue_processing.join("a.json"),
+        )
+        .unwrap();
+
+        assert!(matches!(reroute_message(&paths, "a.json", "x"), Err(QueueActionError::Gone)));
+        assert!(matches!(bump_priority(&paths, "a.json"), Err(QueueActionError::Gone)));
+        assert!(matches!(
+            delete_message(&paths, QueueDir::Incoming, "a.json"),
+            Err(QueueActionError::Gone)
+        ));
+        assert!(!paths.queue_incoming.join("a.json").exists());
+
+        requeue_message(&paths, "a.json").unwrap();
+        assert!(paths.queue_incoming.join("a.json").exists());
+        delete_message(&paths, QueueDir::Incoming, "a.json").unwrap();
+        assert!(!paths.queue_incoming.join("a.json").exists());
+    }
+
+    #[test]
+    fn test_in_flight_file_is_refused() {
+        let (_tmp, paths) = setup();
+        write_message(&paths.queue_processing, "a.json");
+        let lock = lock_in_flight(&paths.queue_processing.join("a.json")).unwrap();
+
+        assert!(matches!(requeue_message(&paths, "a.json"), Err(QueueActionError::InFlight)));
+        assert!(matches!(
+            delete_message(&paths, QueueDir::Processing, "a.json"),
+            Err(QueueActionError::InFlight)
+        ));
+        assert!(paths.queue_processing.join("a.json").exists());
+
+        // Left behind by a processor that stopped
+        drop(lock);
+        requeue_message(&paths, "a.json").unwrap();
+        assert!(paths.queue_incoming.join("a.json").exists());
+    }
+
+    #[test]
+    fn test_rejects_path_traversal() {
+        let (_tmp, paths) = setup();
+        assert!(matches!(
+            delete_message(&paths, QueueDir::Incoming, "../settings.json"),
+            Err(QueueActionError::Invalid(_))
+        ));
+        assert!(matches!(
+            requeue_message(&paths, "x.txt"),
+            Err(QueueActionError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_release_stale_claims() {
+        let (_tmp, paths) = setup();
+        write_message(&paths.queue_incoming, "a.json.claimed");
+        release_stale_claims(&paths);
+        assert!(paths.queue_incoming.join("a.json").exists());
+    }
+}
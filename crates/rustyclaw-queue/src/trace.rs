@@ -0,0 +1,295 @@
+//! Distributed tracing for the queue processor.
+//!
+//! Each queue message is processed inside a `tracing` span, with child spans
+//! for routing, context assembly, model invocations and handoffs. Their
+//! fields also appear on the log lines written inside them. The W3C
+//! `traceparent` of a handoff span is stored in the internal message, so
+//! every hop of a team chain ends up in the trace of the user message that
+//! started it. When `monitoring.tracing` is set in settings.json,
+//! `tracing-opentelemetry` exports the spans over OTLP/HTTP (Jaeger, the
+//! OpenTelemetry Collector) and/or appends them to a local JSONL file.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{Status, TraceContextExt, TracerProvider as _};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::transform::trace::tonic::group_spans_by_resource_and_scope;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{
+    BatchConfigBuilder, BatchSpanProcessor, SdkTracerProvider, SpanData, SpanExporter,
+};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::Registry;
+
+use rustyclaw_core::config::Paths;
+use rustyclaw_core::logging::{log, ExtraLayer};
+use rustyclaw_core::types::TracingConfig;
+
+/// Spans are sent at least this often.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// The span's W3C `traceparent`, or `None` when spans are not exported.
+pub fn traceparent(span: &tracing::Span) -> Option<String> {
+    let cx = span.context();
+    if !cx.span().span_context().is_valid() {
+        return None;
+    }
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&cx, &mut carrier);
+    carrier.remove("traceparent")
+}
+
+/// Continue the trace of a `traceparent` in `span`. Call before entering it.
+pub fn set_parent(span: &tracing::Span, traceparent: &str) {
+    let carrier = HashMap::from([("traceparent".to_string(), traceparent.to_string())]);
+    let cx = TraceContextPropagator::new().extract(&carrier);
+    if cx.span().span_context().is_valid() {
+        let _ = span.set_parent(cx);
+    }
+}
+
+/// Mark a span as failed.
+pub fn fail(span: &tracing::Span, message: impl std::fmt::Display) {
+    span.set_status(Status::error(message.to_string()));
+}
+
+// ─── Export ────────────────────────────────────────────────────────────────
+
+/// `http://host:4318` -> `http://host:4318/v1/traces`
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+/// Appends each batch to a file as one OTLP JSON `ExportTraceServiceRequest`.
+#[derive(Debug)]
+struct FileExporter {
+    path: PathBuf,
+    resource: Resource,
+}
+
+impl SpanExporter for FileExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let request = ExportTraceServiceRequest {
+            resource_spans: group_spans_by_resource_and_scope(batch, &(&self.resource).into()),
+        };
+        let line = serde_json::to_string(&request).map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| writeln!(f, "{}", line))
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.clone();
+    }
+}
+
+/// Logs failed exports, which the SDK would otherwise drop silently.
+#[derive(Debug)]
+struct LoggedExporter<E> {
+    inner: E,
+    target: String,
+    log_file: PathBuf,
+}
+
+impl<E: SpanExporter> SpanExporter for LoggedExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let result = self.inner.export(batch).await;
+        if let Err(e) = &result {
+            log("WARN", &format!("Failed to export traces to {}: {}", self.target, e), &self.log_file);
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+fn batch<E: SpanExporter + 'static>(inner: E, target: String, paths: &Paths) -> BatchSpanProcessor {
+    let exporter = LoggedExporter {
+        inner,
+        target,
+        log_file: paths.log_file.clone(),
+    };
+    BatchSpanProcessor::builder(exporter)
+        .with_batch_config(BatchConfigBuilder::default().with_scheduled_delay(FLUSH_INTERVAL).build())
+        .build()
+}
+
+/// The layer that exports spans, if tracing is configured. Pass it to
+/// `logging::init_with`.
+pub fn init(config: Option<&TracingConfig>, paths: &Paths) -> Option<ExtraLayer> {
+    let config = config?;
+    let url = config
+        .otlp_endpoint
+        .as_deref()
+        .filter(|e| !e.is_empty())
+        .map(traces_url);
+    let file = config.file.as_deref().filter(|f| !f.is_empty()).map(|f| {
+        let path = PathBuf::from(f);
+        if path.is_absolute() {
+            path
+        } else {
+            paths.rustyclaw_home.join(path)
+        }
+    });
+    if url.is_none() && file.is_none() {
+        return None;
+    }
+
+    let mut builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name("rustyclaw").build());
+    let mut targets = Vec::new();
+    if let Some(url) = url {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(&url)
+            .with_timeout(Duration::from_secs(10))
+            .build();
+        match exporter {
+            Ok(exporter) => {
+                builder = builder.with_span_processor(batch(exporter, url.clone(), paths));
+                targets.push(url);
+            }
+            Err(e) => log("WARN", &format!("Cannot export traces to {}: {}", url, e), &paths.log_file),
+        }
+    }
+    if let Some(path) = file {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let exporter = FileExporter {
+            path: path.clone(),
+            resource: Resource::builder_empty().build(),
+        };
+        let target = path.display().to_string();
+        builder = builder.with_span_processor(batch(exporter, target.clone(), paths));
+        targets.push(target);
+    }
+    if targets.is_empty() {
+        return None;
+    }
+    log("INFO", &format!("Exporting traces to {}", targets.join(", ")), &paths.log_file);
+
+    let provider = builder.build();
+    let tracer = provider.tracer("rustyclaw-queue");
+    let _ = PROVIDER.set(provider);
+    Some(Box::new(tracing_opentelemetry::layer::<Registry>().with_tracer(tracer)))
+}
+
+/// Export the spans that are still waiting. Called on shutdown.
+pub async fn flush() {
+    let Some(provider) = PROVIDER.get() else { return };
+    let provider = provider.clone();
+    let _ = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanId, TraceId};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_traceparent_joins_the_trace() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let handoff = tracing::info_span!("handoff");
+            let header = traceparent(&handoff).unwrap();
+            drop(handoff);
+
+            let next = tracing::info_span!("process_message");
+            set_parent(&next, &header);
+            fail(&next, "boom");
+            drop(next);
+
+            let orphan = tracing::info_span!("orphan");
+            set_parent(&orphan, "00-00000000000000000000000000000000-00f067aa0ba902b7-01");
+            drop(orphan);
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let [handoff, next, orphan] = &spans[..] else {
+            panic!("expected 3 spans, got {}", spans.len());
+        };
+        assert_eq!(next.span_context.trace_id(), handoff.span_context.trace_id());
+        assert_eq!(next.parent_span_id, handoff.span_context.span_id());
+        assert_eq!(next.status, Status::error("boom"));
+        assert_ne!(orphan.span_context.trace_id(), handoff.span_context.trace_id());
+        assert_eq!(orphan.parent_span_id, SpanId::INVALID);
+        assert_ne!(orphan.span_context.trace_id(), TraceId::INVALID);
+    }
+
+    #[test]
+    fn test_file_exporter_writes_otlp_json() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("spans.jsonl");
+        let exporter = FileExporter {
+            path: path.clone(),
+            resource: Resource::builder_empty().build(),
+        };
+        let provider = SdkTracerProvider::builder()
+            .with_resource(Resource::builder_empty().with_service_name("rustyclaw").build())
+            .with_simple_exporter(exporter)
+            .build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("route", agent_id = "coder").in_scope(|| {});
+        });
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        let request: serde_json::Value = serde_json::from_str(raw.trim()).unwrap();
+        let resource = &request["resourceSpans"][0];
+        assert_eq!(resource["resource"]["attributes"][0]["value"]["stringValue"], "rustyclaw");
+        let span = &resource["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "route");
+        assert!(span["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|a| a["key"] == "agent_id" && a["value"]["stringValue"] == "coder"));
+    }
+
+    #[test]
+    fn test_traceparent_without_export() {
+        let span = tracing::info_span!("handoff");
+        assert!(traceparent(&span).is_none());
+    }
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(traces_url("http://localhost:4318"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("http://localhost:4318/"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("http://c:4318/v1/traces"), "http://c:4318/v1/traces");
+    }
+}
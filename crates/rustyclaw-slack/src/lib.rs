@@ -0,0 +1,627 @@
+//! Slack channel client. Receives events over Socket Mode and delivers
+//! responses through the Web API, using the same file queue as the other
+//! channel clients.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+use rustyclaw_channel::{
+    poll_outgoing, prepare_dirs, receive, Attachment, AttachmentSource, ChannelAdapter, Incoming,
+    Outgoing,
+};
+use rustyclaw_core::config::{get_settings, Paths};
+use rustyclaw_core::format::to_slack_mrkdwn;
+use rustyclaw_core::logging::{self, log};
+use rustyclaw_core::types::{ReplyTarget, SlackChannelConfig};
+
+const DEFAULT_API_BASE: &str = "https://slack.com/api";
+
+/// Slack truncates messages well above this, but recommends staying under it.
+const MAX_MESSAGE_LEN: usize = 4000;
+
+/// Thin client for the Slack Web API methods the bot uses.
+pub struct SlackApi {
+    http: reqwest::Client,
+    base_url: String,
+    bot_token: String,
+    app_token: String,
+}
+
+impl SlackApi {
+    pub fn new(bot_token: &str, app_token: &str, base_url: Option<&str>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.unwrap_or(DEFAULT_API_BASE).trim_end_matches('/').to_string(),
+            bot_token: bot_token.to_string(),
+            app_token: app_token.to_string(),
+        }
+    }
+
+    async fn call(&self, method: &str, token: &str, params: &[(&str, &str)]) -> Result<Value> {
+        let body = self
+            .http
+            .post(format!("{}/{}", self.base_url, method))
+            .bearer_auth(token)
+            .form(params)
+            .send()
+            .await
+            .with_context(|| format!("{} request failed", method))?
+            .text()
+            .await?;
+        let resp: Value = serde_json::from_str(&body)?;
+        if resp["ok"].as_bool() != Some(true) {
+            bail!("{} failed: {}", method, resp["error"].as_str().unwrap_or("unknown error"));
+        }
+        Ok(resp)
+    }
+
+    /// Get a fresh Socket Mode WebSocket URL.
+    async fn open_connection(&self) -> Result<String> {
+        let resp = self.call("apps.connections.open", &self.app_token, &[]).await?;
+        resp["url"]
+            .as_str()
+            .map(str::to_string)
+            .context("apps.connections.open returned no url")
+    }
+
+    async fn bot_user_id(&self) -> Result<String> {
+        let resp = self.call("auth.test", &self.bot_token, &[]).await?;
+        resp["user_id"]
+            .as_str()
+            .map(str::to_string)
+            .context("auth.test returned no user_id")
+    }
+
+    async fn user_name(&self, user_id: &str) -> Option<String> {
+        let resp = self.call("users.info", &self.bot_token, &[("user", user_id)]).await.ok()?;
+        let user = &resp["user"];
+        [
+            &user["profile"]["display_name"],
+            &user["profile"]["real_name"],
+            &user["real_name"],
+            &user["name"],
+        ]
+        .iter()
+        .filter_map(|v| v.as_str())
+        .find(|name| !name.is_empty())
+        .map(str::to_string)
+    }
+
+    async fn post_message(&self, channel: &str, text: &str, thread_ts: Option<&str>) -> Result<()> {
+        let mut params = vec![("channel", channel), ("text", text)];
+        if let Some(ts) = thread_ts {
+            params.push(("thread_ts", ts));
+        }
+        self.call("chat.postMessage", &self.bot_token, &params).await?;
+        Ok(())
+    }
+
+    /// Upload a file with the external upload flow (get URL, POST bytes, complete).
+    async fn upload_file(&self, channel: &str, thread_ts: Option<&str>, path: &Path) -> Result<()> {
+        let bytes = std::fs::read(path)?;
+        let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let length = bytes.len().to_string();
+        let resp = self
+            .call(
+                "files.getUploadURLExternal",
+                &self.bot_token,
+                &[("filename", filename.as_str()), ("length", length.as_str())],
+            )
+            .await?;
+        let upload_url = resp["upload_url"].as_str().context("no upload_url")?;
+        let file_id = resp["file_id"].as_str().context("no file_id")?;
+
+        self.http
+            .post(upload_url)
+            .body(bytes)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let files = json!([{ "id": file_id, "title": filename }]).to_string();
+        let mut params = vec![("channel_id", channel), ("files", files.as_str())];
+        if let Some(ts) = thread_ts {
+            params.push(("thread_ts", ts));
+        }
+        self.call("files.completeUploadExternal", &self.bot_token, &params).await?;
+        Ok(())
+    }
+
+    /// Download a private Slack file (needs the bot token).
+    async fn download(&self, url: &str, dest: &Path) -> Result<()> {
+        let bytes = self
+            .http
+            .get(url)
+            .bearer_auth(&self.bot_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        if let Some(dir) = dest.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(dest, &bytes)?;
+        Ok(())
+    }
+}
+
+/// A Socket Mode envelope.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    #[serde(rename = "type")]
+    kind: String,
+    envelope_id: Option<String>,
+    payload: Option<Value>,
+}
+
+/// The fields of a `message` event the bot uses.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SlackMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    subtype: Option<String>,
+    channel: String,
+    channel_type: Option<String>,
+    user: Option<String>,
+    bot_id: Option<String>,
+    text: String,
+    ts: String,
+    thread_ts: Option<String>,
+    files: Vec<SlackFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SlackFile {
+    name: Option<String>,
+    url_private_download: Option<String>,
+    url_private: Option<String>,
+}
+
+/// Slack escapes `&`, `<` and `>` in message text.
+fn unescape_slack(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+/// Remove `<@BOT>` mentions. Returns the cleaned text and whether the bot was mentioned.
+fn strip_bot_mention(text: &str, bot_user_id: &str) -> (String, bool) {
+    let mention = format!("<@{}>", bot_user_id);
+    if !text.contains(&mention) {
+        return (text.to_string(), false);
+    }
+    let cleaned = text.replace(&mention, "");
+    (cleaned.split_whitespace().collect::<Vec<_>>().join(" "), true)
+}
+
+/// DMs are always handled. In channels the bot answers when mentioned, in
+/// allowlisted channels, or in threads it already takes part in.
+fn should_respond(config: &SlackChannelConfig, msg: &SlackMessage, mentioned: bool, active_thread: bool) -> bool {
+    if msg.channel_type.as_deref() == Some("im") {
+        return true;
+    }
+    mentioned
+        || active_thread
+        || config
+            .allowed_channels
+            .as_ref()
+            .is_some_and(|channels| channels.contains(&msg.channel))
+}
+
+struct SlackBot {
+    api: SlackApi,
+    paths: Arc<Paths>,
+    bot_user_id: String,
+    /// Threads the bot has answered in, so follow-ups need no mention.
+    active_threads: Mutex<HashSet<String>>,
+    user_names: Mutex<HashMap<String, String>>,
+}
+
+impl SlackBot {
+    fn config(&self) -> SlackChannelConfig {
+        get_settings(&self.paths.settings_file)
+            .ok()
+            .and_then(|s| s.channels)
+            .and_then(|c| c.slack)
+            .unwrap_or_default()
+    }
+
+    async fn display_name(&self, user_id: &str) -> String {
+        if let Some(name) = self.user_names.lock().await.get(user_id) {
+            return name.clone();
+        }
+        let name = self.api.user_name(user_id).await.unwrap_or_else(|| user_id.to_string());
+        self.user_names.lock().await.insert(user_id.to_string(), name.clone());
+        name
+    }
+
+    /// Run one Socket Mode connection until Slack asks us to reconnect.
+    async fn run_socket_session(self: &Arc<Self>) -> Result<()> {
+        let url = self.api.open_connection().await?;
+        let (ws, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .context("Socket Mode connection failed")?;
+        let (mut write, mut read) = ws.split();
+
+        while let Some(frame) = read.next().await {
+            match frame? {
+                WsMessage::Text(text) => {
+                    let envelope: Envelope = match serde_json::from_str(&text) {
+                        Ok(e) => e,
+                        Err(e) => {
+                            log("WARN", &format!("Bad Socket Mode frame: {}", e), &self.paths.log_file);
+                            continue;
+                        }
+                    };
+                    // Acknowledge first: Slack retries envelopes not acked within 3s
+                    if let Some(id) = &envelope.envelope_id {
+                        write
+                            .send(WsMessage::Text(json!({ "envelope_id": id }).to_string()))
+                            .await?;
+                    }
+                    match envelope.kind.as_str() {
+                        "hello" => log("INFO", "Slack Socket Mode connected", &self.paths.log_file),
+                        "disconnect" => return Ok(()),
+                        "events_api" => {
+                            let event = envelope.payload.as_ref().and_then(|p| p.get("event")).cloned();
+                            if let Some(event) = event {
+                                let bot = Arc::clone(self);
+                                tokio::spawn(async move { bot.handle_event(event).await });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                WsMessage::Ping(payload) => write.send(WsMessage::Pong(payload)).await?,
+                WsMessage::Close(_) => return Ok(()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_event(&self, event: Value) {
+        let msg: SlackMessage = match serde_json::from_value(event) {
+            Ok(m) => m,
+            Err(_) => return,
+        };
+        // Only plain user messages; edits, joins and bot posts are ignored
+        if msg.kind != "message"
+            || msg.bot_id.is_some()
+            || !matches!(msg.subtype.as_deref(), None | Some("file_share"))
+        {
+            return;
+        }
+        let Some(user_id) = msg.user.clone() else {
+            return;
+        };
+        let is_dm = msg.channel_type.as_deref() == Some("im");
+        // Channel conversations live in a thread; DMs reply inline unless already threaded
+        let thread_ts = if is_dm {
+            msg.thread_ts.clone()
+        } else {
+            Some(msg.thread_ts.clone().unwrap_or_else(|| msg.ts.clone()))
+        };
+
+        let (text, mentioned) = strip_bot_mention(&unescape_slack(&msg.text), &self.bot_user_id);
+        let active_thread = match &msg.thread_ts {
+            Some(ts) => self.active_threads.lock().await.contains(ts),
+            None => false,
+        };
+        if !should_respond(&self.config(), &msg, mentioned, active_thread) {
+            return;
+        }
+
+        let sender = self.display_name(&user_id).await;
+        let attachments = msg
+            .files
+            .iter()
+            .filter_map(|file| {
+                let url = file.url_private_download.as_ref().or(file.url_private.as_ref())?;
+                Some(Attachment {
+                    name: file.name.clone().unwrap_or_else(|| "file.bin".to_string()),
+                    source: AttachmentSource::Remote(url.clone()),
+                })
+            })
+            .collect();
+        let incoming = Incoming {
+            sender,
+            sender_id: user_id,
+            text,
+            attachments,
+            reply_to: ReplyTarget {
+                chat_id: msg.channel.clone(),
+                reply_to_message_id: Some(msg.ts.clone()),
+                thread_id: thread_ts.clone(),
+            },
+            default_route: None,
+        };
+        if receive(self, &self.paths, incoming).await.is_some() {
+            if let Some(ts) = thread_ts {
+                self.active_threads.lock().await.insert(ts);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ChannelAdapter for SlackBot {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn max_message_len(&self) -> usize {
+        MAX_MESSAGE_LEN
+    }
+
+    fn format(&self, markdown: &str) -> String {
+        to_slack_mrkdwn(markdown)
+    }
+
+    async fn send(&self, outgoing: &Outgoing<'_>) -> Result<()> {
+        let channel = outgoing.target.chat_id.as_str();
+        let thread_ts = outgoing.target.thread_id.as_deref();
+        for fp in &outgoing.files {
+            match self.api.upload_file(channel, thread_ts, fp).await {
+                Ok(()) => log(
+                    "INFO",
+                    &format!("Sent file to Slack: {}", fp.file_name().unwrap_or_default().to_string_lossy()),
+                    &self.paths.log_file,
+                ),
+                Err(e) => log("ERROR", &format!("Failed to upload file: {}", e), &self.paths.log_file),
+            }
+        }
+        for chunk in &outgoing.chunks {
+            self.api.post_message(channel, &self.format(chunk), thread_ts).await?;
+        }
+        Ok(())
+    }
+
+    async fn reply(&self, target: &ReplyTarget, text: &str) -> Result<()> {
+        self.api
+            .post_message(&target.chat_id, &self.format(text), target.thread_id.as_deref())
+            .await
+    }
+
+    async fn download(&self, url: &str, dest: &Path) -> Result<()> {
+        self.api.download(url, dest).await
+    }
+}
+
+pub async fn run(paths: Arc<Paths>) -> Result<()> {
+    prepare_dirs(&paths);
+    logging::init("slack", &paths);
+
+    log("INFO", "Starting Slack client...", &paths.log_file);
+
+    let config = get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.channels)
+        .and_then(|c| c.slack)
+        .unwrap_or_default();
+    let bot_token = env::var("SLACK_BOT_TOKEN").ok().or(config.bot_token).unwrap_or_default();
+    let app_token = env::var("SLACK_APP_TOKEN").ok().or(config.app_token).unwrap_or_default();
+    if bot_token.is_empty() || app_token.is_empty() {
+        eprintln!("ERROR: SLACK_BOT_TOKEN and SLACK_APP_TOKEN must both be configured");
+        std::process::exit(1);
+    }
+
+    let api = SlackApi::new(&bot_token, &app_token, config.api_base_url.as_deref());
+    let bot_user_id = match api.bot_user_id().await {
+        Ok(id) => id,
+        Err(e) => {
+            log("ERROR", &format!("Failed to connect: {}", e), &paths.log_file);
+            std::process::exit(1);
+        }
+    };
+    log("INFO", &format!("Slack bot connected as {}", bot_user_id), &paths.log_file);
+
+    let bot = Arc::new(SlackBot {
+        api,
+        paths: Arc::clone(&paths),
+        bot_user_id,
+        active_threads: Mutex::new(HashSet::new()),
+        user_names: Mutex::new(HashMap::new()),
+    });
+
+    let bot_poll = Arc::clone(&bot);
+    tokio::spawn(async move { poll_outgoing(&*bot_poll, &bot_poll.paths, Duration::from_secs(1)).await });
+
+    // Slack rotates Socket Mode connections; reconnect whenever one ends
+    loop {
+        if let Err(e) = bot.run_socket_session().await {
+            log("WARN", &format!("Socket Mode session ended: {}", e), &paths.log_file);
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        } else {
+            log("INFO", "Socket Mode reconnecting...", &paths.log_file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::ws::{Message as AxumWsMessage, WebSocketUpgrade};
+    use axum::extract::{Path as AxumPath, State};
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use rustyclaw_channel::{check_outgoing_queue, now_millis};
+    use rustyclaw_core::pairing::{approve_pairing_code, ensure_sender_paired};
+    use rustyclaw_core::types::{MessageData, ResponseData};
+
+    /// Web API calls and Socket Mode acks recorded by the mock server.
+    #[derive(Default)]
+    struct MockLog {
+        calls: Vec<(String, HashMap<String, String>)>,
+        acks: Vec<String>,
+    }
+
+    type Shared = Arc<Mutex<MockLog>>;
+
+    fn test_paths(home: &Path) -> Paths {
+        Paths {
+            script_dir: home.to_path_buf(),
+            rustyclaw_home: home.to_path_buf(),
+            queue_incoming: home.join("queue/incoming"),
+            queue_outgoing: home.join("queue/outgoing"),
+            queue_processing: home.join("queue/processing"),
+            log_file: home.join("logs/queue.log"),
+            settings_file: home.join("settings.json"),
+            events_dir: home.join("events"),
+            chats_dir: home.join("chats"),
+            files_dir: home.join("files"),
+            pairing_file: home.join("pairing.json"),
+            skills_dir: home.join("skills"),
+            skills_lock_file: home.join("skills.lock"),
+        }
+    }
+
+    /// Start a mock Slack: Web API under /api and a Socket Mode endpoint
+    /// that sends `hello`, one mention in a channel, then `disconnect`.
+    async fn start_mock() -> (String, Shared) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared: Shared = Arc::default();
+
+        let api = move |State(shared): State<Shared>,
+                        AxumPath(method): AxumPath<String>,
+                        Form(params): Form<HashMap<String, String>>| async move {
+            shared.lock().await.calls.push((method.clone(), params));
+            Json(match method.as_str() {
+                "auth.test" => json!({ "ok": true, "user_id": "UBOT" }),
+                "apps.connections.open" => json!({ "ok": true, "url": format!("ws://{}/socket", addr) }),
+                "users.info" => json!({ "ok": true, "user": { "name": "alice", "profile": { "display_name": "Alice" } } }),
+                _ => json!({ "ok": true }),
+            })
+        };
+
+        let socket = |ws: WebSocketUpgrade, State(shared): State<Shared>| async move {
+            ws.on_upgrade(move |mut socket| async move {
+                let event = json!({
+                    "type": "events_api",
+                    "envelope_id": "env-1",
+                    "payload": { "event": {
+                        "type": "message",
+                        "channel": "C1",
+                        "channel_type": "channel",
+                        "user": "U1",
+                        "text": "<@UBOT> fix the &lt;build&gt;",
+                        "ts": "1700000000.000100"
+                    }}
+                });
+                let _ = socket.send(AxumWsMessage::Text(r#"{"type":"hello"}"#.into())).await;
+                let _ = socket.send(AxumWsMessage::Text(event.to_string().into())).await;
+                if let Some(Ok(AxumWsMessage::Text(ack))) = socket.recv().await {
+                    let ack: Value = serde_json::from_str(ack.as_str()).unwrap();
+                    shared.lock().await.acks.push(ack["envelope_id"].as_str().unwrap().to_string());
+                }
+                let _ = socket.send(AxumWsMessage::Text(r#"{"type":"disconnect"}"#.into())).await;
+            })
+        };
+
+        let app = Router::new()
+            .route("/api/{method}", post(api))
+            .route("/socket", get(socket))
+            .with_state(Arc::clone(&shared));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/api", addr), shared)
+    }
+
+    #[tokio::test]
+    async fn test_socket_mode_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = Arc::new(test_paths(tmp.path()));
+        let code = ensure_sender_paired(&paths.pairing_file, "slack", "U1", "Alice").code.unwrap();
+        assert!(approve_pairing_code(&paths.pairing_file, &code).ok);
+
+        let (base_url, shared) = start_mock().await;
+        let bot = Arc::new(SlackBot {
+            api: SlackApi::new("xoxb-test", "xapp-test", Some(&base_url)),
+            paths: Arc::clone(&paths),
+            bot_user_id: "UBOT".to_string(),
+            active_threads: Mutex::new(HashSet::new()),
+            user_names: Mutex::new(HashMap::new()),
+        });
+
+        bot.run_socket_session().await.unwrap();
+        assert_eq!(shared.lock().await.acks, vec!["env-1".to_string()]);
+
+        // The event handler runs in the background
+        let mut queued = None;
+        for _ in 0..50 {
+            if let Some(entry) = std::fs::read_dir(&paths.queue_incoming).ok().and_then(|mut d| d.next()) {
+                queued = Some(entry.unwrap().path());
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        }
+        let raw = std::fs::read_to_string(queued.expect("message was not queued")).unwrap();
+        let data: MessageData = serde_json::from_str(&raw).unwrap();
+        assert_eq!(data.channel, "slack");
+        assert_eq!(data.sender, "Alice");
+        assert_eq!(data.message, "fix the <build>");
+        let target = data.reply_to.clone().unwrap();
+        assert_eq!(target.chat_id, "C1");
+        assert_eq!(target.thread_id.as_deref(), Some("1700000000.000100"));
+
+        // Deliver a response through the outgoing queue
+        let response = ResponseData {
+            channel: "slack".to_string(),
+            sender: data.sender.clone(),
+            message: "**Done**".to_string(),
+            original_message: data.message.clone(),
+            timestamp: now_millis(),
+            message_id: data.message_id.clone(),
+            agent: None,
+            files: None,
+            reply_to: data.reply_to.clone(),
+        };
+        std::fs::create_dir_all(&paths.queue_outgoing).unwrap();
+        let out_file = paths.queue_outgoing.join(format!("slack_{}.json", data.message_id));
+        std::fs::write(&out_file, serde_json::to_string(&response).unwrap()).unwrap();
+        check_outgoing_queue(&*bot, &paths).await;
+
+        assert!(!out_file.exists());
+        let log = shared.lock().await;
+        let (_, params) = log
+            .calls
+            .iter()
+            .find(|(method, _)| method == "chat.postMessage")
+            .expect("no chat.postMessage call");
+        assert_eq!(params["channel"], "C1");
+        assert_eq!(params["thread_ts"], "1700000000.000100");
+        assert_eq!(params["text"], "*Done*");
+    }
+
+    #[test]
+    fn test_should_respond() {
+        let mut config = SlackChannelConfig::default();
+        let dm = SlackMessage { channel: "D1".into(), channel_type: Some("im".into()), ..Default::default() };
+        let channel = SlackMessage { channel: "C1".into(), channel_type: Some("channel".into()), ..Default::default() };
+
+        assert!(should_respond(&config, &dm, false, false));
+        assert!(!should_respond(&config, &channel, false, false));
+        assert!(should_respond(&config, &channel, true, false));
+        assert!(should_respond(&config, &channel, false, true));
+        config.allowed_channels = Some(vec!["C1".into()]);
+        assert!(should_respond(&config, &channel, false, false));
+    }
+
+    #[test]
+    fn test_strip_bot_mention() {
+        assert_eq!(strip_bot_mention("<@UBOT> hi  there", "UBOT"), ("hi there".to_string(), true));
+        assert_eq!(strip_bot_mention("hi <@U2>", "UBOT"), ("hi <@U2>".to_string(), false));
+    }
+}
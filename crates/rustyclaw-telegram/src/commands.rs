@@ -0,0 +1,337 @@
+use std::sync::Arc;
+
+use teloxide::prelude::*;
+use teloxide::types::{
+    BotCommand, CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup,
+    MaybeInaccessibleMessage, ParseMode,
+};
+
+use rustyclaw_core::channels::{
+    apply_default_route, choices_dir, load_choice, load_pages, pages_dir, parse_choice_payload,
+    parse_page_payload, save_choices, telegram_commands,
+};
+use rustyclaw_core::format::to_telegram_html;
+use rustyclaw_core::config::{get_agents, get_settings, get_teams, Paths};
+use rustyclaw_core::logging::log;
+use rustyclaw_core::pairing::{approve_pairing_code, deny_pairing_code, ensure_sender_paired};
+use rustyclaw_core::types::{MessageData, ReplyTarget};
+
+use rustyclaw_channel::commands::{agent_list_text, parse_agent_args, reset_agents, team_list_text};
+use rustyclaw_channel::{now_millis, random_id};
+
+use crate::{enqueue, route_key, send_formatted, topic_thread, user_display_name, BotState};
+
+/// Telegram caps callback data at 64 bytes.
+const MAX_CALLBACK_DATA_LEN: usize = 64;
+
+/// Built-in commands handled by the bot itself rather than routed to an agent.
+pub(crate) fn is_builtin(command: &str) -> bool {
+    matches!(command, "agent" | "agents" | "team" | "teams" | "reset")
+}
+
+/// Register the command menu if the configured agents or teams changed since
+/// the last call.
+pub(crate) async fn sync_commands(bot: &Bot, paths: &Paths, registered: &mut Vec<(String, String)>) {
+    let Ok(settings) = get_settings(&paths.settings_file) else {
+        return;
+    };
+    let commands = telegram_commands(&get_agents(&settings), &get_teams(&settings));
+    if commands == *registered {
+        return;
+    }
+
+    let bot_commands: Vec<BotCommand> = commands
+        .iter()
+        .map(|(name, description)| BotCommand::new(name, description))
+        .collect();
+    match bot.set_my_commands(bot_commands).await {
+        Ok(_) => {
+            log(
+                "INFO",
+                &format!("Registered {} Telegram commands", commands.len()),
+                &paths.log_file,
+            );
+            *registered = commands;
+        }
+        Err(e) => log("WARN", &format!("Failed to register commands: {}", e), &paths.log_file),
+    }
+}
+
+/// Handle /agent, /team and /reset.
+pub(crate) async fn handle_command(bot: &Bot, msg: &Message, paths: &Paths, command: &str, args: &str) {
+    log("INFO", &format!("Command received: /{}", command), &paths.log_file);
+
+    let (text, keyboard) = match command {
+        "agent" | "agents" => (agent_list_text(paths), route_keyboard(paths)),
+        "team" | "teams" => (team_list_text(paths), None),
+        _ if args.is_empty() => (
+            "Which agent should be reset?".to_string(),
+            reset_picker_keyboard(paths),
+        ),
+        _ => {
+            let ids = parse_agent_args(args);
+            let confirm = format!("reset:{}", ids.join(","));
+            // Too many agents to fit in one button: reset without asking
+            if confirm.len() > MAX_CALLBACK_DATA_LEN {
+                (reset_agents(paths, &ids), None)
+            } else {
+                let labels: Vec<String> = ids.iter().map(|id| format!("@{}", id)).collect();
+                let keyboard = InlineKeyboardMarkup::new(vec![vec![
+                    InlineKeyboardButton::callback("Reset", confirm),
+                    InlineKeyboardButton::callback("Cancel", "reset_cancel"),
+                ]]);
+                (
+                    format!("Reset {}? Their conversation history will be cleared.", labels.join(", ")),
+                    Some(keyboard),
+                )
+            }
+        }
+    };
+
+    send_formatted(bot, msg.chat.id, topic_thread(msg), Some(msg.id), &text, keyboard).await;
+}
+
+/// Handle inline keyboard presses.
+pub(crate) async fn handle_callback(bot: Bot, q: CallbackQuery, state: Arc<BotState>) -> ResponseResult<()> {
+    let paths = &state.paths;
+    let data = q.data.clone().unwrap_or_default();
+    let user_id = q.from.id.0.to_string();
+    let user_name = user_display_name(&q.from);
+
+    let Some(MaybeInaccessibleMessage::Regular(message)) = &q.message else {
+        answer(&bot, &q, "This message is too old to use.").await;
+        return Ok(());
+    };
+    let chat_id = message.chat.id;
+    let thread_id = topic_thread(message);
+
+    if let Some(code) = data.strip_prefix("pair_approve:").or_else(|| data.strip_prefix("pair_deny:")) {
+        if !is_admin(paths, &q.from.id) {
+            answer(&bot, &q, "Only admins can approve pairings.").await;
+            return Ok(());
+        }
+        let text = if data.starts_with("pair_approve:") {
+            let result = approve_pairing_code(&paths.pairing_file, code);
+            match result.entry {
+                Some(entry) if result.ok => {
+                    format!("Approved {} ({}) — by {}.", entry.sender, entry.sender_id, user_name)
+                }
+                _ => result.reason.unwrap_or_else(|| "Approval failed.".to_string()),
+            }
+        } else {
+            match deny_pairing_code(&paths.pairing_file, code) {
+                Some(entry) => format!("Denied {} ({}) — by {}.", entry.sender, entry.sender_id, user_name),
+                None => format!("Pairing code not found: {}", code),
+            }
+        };
+        log("INFO", &format!("Pairing callback: {}", text), &paths.log_file);
+        let _ = bot.edit_message_text(chat_id, message.id, text).await;
+        answer(&bot, &q, "").await;
+        return Ok(());
+    }
+
+    if let Some((page_id, index)) = parse_page_payload(&data) {
+        let Some(pages) = load_pages(&pages_dir(paths), page_id) else {
+            answer(&bot, &q, "This response is no longer available.").await;
+            return Ok(());
+        };
+        if let Some(page) = pages.get(index) {
+            let keyboard = page_keyboard(page_id, index, pages.len());
+            let edited = bot
+                .edit_message_text(chat_id, message.id, to_telegram_html(page))
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard.clone())
+                .await;
+            if edited.is_err() {
+                let _ = bot
+                    .edit_message_text(chat_id, message.id, page)
+                    .reply_markup(keyboard)
+                    .await;
+            }
+        }
+        answer(&bot, &q, "").await;
+        return Ok(());
+    }
+    if data == "page_count" {
+        answer(&bot, &q, "").await;
+        return Ok(());
+    }
+
+    let pairing = ensure_sender_paired(&paths.pairing_file, "telegram", &user_id, &user_name);
+    if !pairing.approved {
+        answer(&bot, &q, "You need to be paired first.").await;
+        return Ok(());
+    }
+
+    if let Some(route) = data.strip_prefix("select:") {
+        state
+            .selected_routes
+            .lock()
+            .await
+            .insert(route_key(chat_id, thread_id), route.to_string());
+        let text = format!(
+            "Messages here now go to @{}. Start a message with @agent_id to reach someone else.",
+            route
+        );
+        let _ = bot.edit_message_text(chat_id, message.id, text).await;
+    } else if let Some(ids) = data.strip_prefix("reset:") {
+        let ids: Vec<String> = ids.split(',').map(str::to_string).collect();
+        let text = reset_agents(paths, &ids);
+        let _ = bot.edit_message_text(chat_id, message.id, text).await;
+    } else if data == "reset_cancel" {
+        let _ = bot.edit_message_text(chat_id, message.id, "Reset cancelled.").await;
+    } else if let Some((choice_id, index)) = parse_choice_payload(&data) {
+        let Some((agent, choice)) = load_choice(&choices_dir(paths), choice_id, index) else {
+            answer(&bot, &q, "This choice is no longer available.").await;
+            return Ok(());
+        };
+        let queue_data = MessageData {
+            channel: "telegram".to_string(),
+            sender: user_name.clone(),
+            sender_id: Some(user_id),
+            message: apply_default_route(&choice, agent.as_deref()),
+            timestamp: now_millis(),
+            message_id: random_id(),
+            agent: None,
+            files: None,
+            conversation_id: None,
+            from_agent: None,
+            reply_to: Some(ReplyTarget {
+                chat_id: chat_id.0.to_string(),
+                reply_to_message_id: Some(message.id.0.to_string()),
+                thread_id: thread_id.map(|t| t.0 .0.to_string()),
+            }),
+            priority: None,
+            traceparent: None,
+        };
+        if enqueue(&state, &queue_data, chat_id, thread_id).await {
+            let original = message.text().unwrap_or_default();
+            let text = format!("{}\n\n→ {} chose: {}", original, user_name, choice);
+            let _ = bot.edit_message_text(chat_id, message.id, text).await;
+        }
+    }
+
+    answer(&bot, &q, "").await;
+    Ok(())
+}
+
+/// Inline keyboard of `[choices: ...]` options; a press sends the choice back.
+/// The choices are saved on disk and each button carries only its index.
+pub(crate) fn choice_keyboard(
+    paths: &Paths,
+    agent: Option<&str>,
+    choices: &[String],
+) -> anyhow::Result<InlineKeyboardMarkup> {
+    let id = save_choices(&choices_dir(paths), agent, choices)?;
+    let buttons: Vec<InlineKeyboardButton> = choices
+        .iter()
+        .enumerate()
+        .map(|(i, choice)| InlineKeyboardButton::callback(choice.clone(), format!("choice:{}:{}", id, i)))
+        .collect();
+    Ok(InlineKeyboardMarkup::new(buttons.chunks(2).map(|row| row.to_vec())))
+}
+
+/// Previous/next buttons for page `index` of a paginated response.
+pub(crate) fn page_keyboard(page_id: &str, index: usize, total: usize) -> InlineKeyboardMarkup {
+    let mut row = Vec::new();
+    if index > 0 {
+        row.push(InlineKeyboardButton::callback(
+            "◀ Prev",
+            format!("page:{}:{}", page_id, index - 1),
+        ));
+    }
+    row.push(InlineKeyboardButton::callback(
+        format!("{}/{}", index + 1, total),
+        "page_count",
+    ));
+    if index + 1 < total {
+        row.push(InlineKeyboardButton::callback(
+            "Next ▶",
+            format!("page:{}:{}", page_id, index + 1),
+        ));
+    }
+    InlineKeyboardMarkup::new(vec![row])
+}
+
+/// Message every configured admin an approve/deny prompt for a new pairing request.
+pub(crate) async fn notify_admins_of_pairing(
+    bot: &Bot,
+    paths: &Paths,
+    sender: &str,
+    sender_id: &str,
+    code: &str,
+) {
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Approve", format!("pair_approve:{}", code)),
+        InlineKeyboardButton::callback("Deny", format!("pair_deny:{}", code)),
+    ]]);
+    let text = format!("Pairing request from {} ({}) with code {}.", sender, sender_id, code);
+
+    // A user's private chat ID equals their user ID
+    for admin in admin_ids(paths) {
+        let chat_id = ChatId(admin.0 as i64);
+        if let Err(e) = bot.send_message(chat_id, &text).reply_markup(keyboard.clone()).await {
+            log(
+                "WARN",
+                &format!("Failed to notify admin {} of pairing: {}", admin.0, e),
+                &paths.log_file,
+            );
+        }
+    }
+}
+
+/// One button per agent and team; pressing one routes this chat to it.
+fn route_keyboard(paths: &Paths) -> Option<InlineKeyboardMarkup> {
+    let settings = get_settings(&paths.settings_file).ok()?;
+    let mut ids: Vec<String> = get_agents(&settings).into_keys().collect();
+    ids.sort();
+    let mut team_ids: Vec<String> = get_teams(&settings).into_keys().collect();
+    team_ids.sort();
+    ids.extend(team_ids);
+    id_keyboard(&ids, "select:")
+}
+
+/// One button per agent; pressing one resets it.
+fn reset_picker_keyboard(paths: &Paths) -> Option<InlineKeyboardMarkup> {
+    let settings = get_settings(&paths.settings_file).ok()?;
+    let mut ids: Vec<String> = get_agents(&settings).into_keys().collect();
+    ids.sort();
+    id_keyboard(&ids, "reset:")
+}
+
+fn id_keyboard(ids: &[String], prefix: &str) -> Option<InlineKeyboardMarkup> {
+    let buttons: Vec<InlineKeyboardButton> = ids
+        .iter()
+        .filter(|id| prefix.len() + id.len() <= MAX_CALLBACK_DATA_LEN)
+        .map(|id| InlineKeyboardButton::callback(format!("@{}", id), format!("{}{}", prefix, id)))
+        .collect();
+    if buttons.is_empty() {
+        return None;
+    }
+    Some(InlineKeyboardMarkup::new(buttons.chunks(2).map(|row| row.to_vec())))
+}
+
+async fn answer(bot: &Bot, q: &CallbackQuery, text: &str) {
+    let mut req = bot.answer_callback_query(q.id.clone());
+    if !text.is_empty() {
+        req = req.text(text);
+    }
+    let _ = req.await;
+}
+
+fn admin_ids(paths: &Paths) -> Vec<UserId> {
+    get_settings(&paths.settings_file)
+        .ok()
+        .and_then(|s| s.channels)
+        .and_then(|c| c.telegram)
+        .and_then(|t| t.admin_user_ids)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|id| id.parse::<u64>().ok())
+        .map(UserId)
+        .collect()
+}
+
+fn is_admin(paths: &Paths, user_id: &UserId) -> bool {
+    admin_ids(paths).contains(user_id)
+}
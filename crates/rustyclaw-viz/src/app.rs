@@ -1,3 +1,4 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -5,7 +6,7 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use yew::prelude::*;
 use gloo_net::http::Request;
-use gloo_timers::callback::Interval;
+use gloo_timers::callback::{Interval, Timeout};
 use wasm_bindgen_futures::spawn_local;
 
 use crate::types::*;
@@ -15,9 +16,13 @@ use crate::components::chain_flow::ChainFlow;
 use crate::components::activity_log::ActivityLog;
 use crate::components::status_bar::StatusBar;
 use crate::components::queue_panel::QueuePanel;
+use crate::components::chat_page::ChatPage;
+use crate::components::metrics_page::MetricsPage;
+use crate::components::history_page::HistoryPage;
 use crate::components::send_form::SendForm;
 use crate::components::settings_page::SettingsPage;
 use crate::components::kanban_board::KanbanBoard;
+use crate::components::login::{login, LoginForm};
 
 // ─── App State ──────────────────────────────────────────────────────────────
 
@@ -34,6 +39,7 @@ pub struct AppState {
     pub team_filter: Option<String>,
     pub queued_messages: Vec<QueuedMessage>,
     pub kanban_cards: Vec<KanbanCard>,
+    pub chat: Vec<ChatEntry>,
 }
 
 impl Default for AppState {
@@ -50,6 +56,7 @@ impl Default for AppState {
             team_filter: get_team_filter(),
             queued_messages: Vec::new(),
             kanban_cards: Vec::new(),
+            chat: Vec::new(),
         }
     }
 }
@@ -61,6 +68,9 @@ pub enum AppAction {
     HandleEvent(VizEvent),
     SetConnected(bool),
     SetQueueMessages(Vec<QueuedMessage>),
+    AddChat(ChatEntry),
+    /// Drop the state built from events; the replay that follows rebuilds it
+    ResetEvents,
 }
 
 impl Reducible for AppState {
@@ -97,29 +107,32 @@ impl Reducible for AppState {
                     }
                 }
 
-                let mut states = HashMap::new();
-                for id in &agent_ids {
-                    if let Some(agent) = settings.agents.get(id) {
-                        states.insert(
-                            id.clone(),
-                            AgentState {
-                                id: id.clone(),
-                                name: agent.name.clone(),
-                                provider: agent.provider.clone(),
-                                model: agent.model.clone(),
-                                status: AgentStatus::Idle,
-                                last_activity: String::new(),
-                                response_length: None,
-                            },
-                        );
-                    }
-                }
-                s.agent_states = states;
+                s.agent_states = idle_agents(&settings, &agent_ids);
                 s.settings = settings;
             }
             AppAction::HandleEvent(event) => {
                 handle_event(&mut s, &event);
             }
+            AppAction::ResetEvents => {
+                let agent_ids: Vec<String> = s.agent_states.keys().cloned().collect();
+                s = AppState {
+                    agent_states: idle_agents(&s.settings, &agent_ids),
+                    settings: s.settings,
+                    connected: s.connected,
+                    team_filter: s.team_filter,
+                    queue_depth: s.queue_depth,
+                    queued_messages: s.queued_messages,
+                    chat: s.chat,
+                    ..AppState::default()
+                };
+                add_log(
+                    &mut s,
+                    js_sys::Date::now(),
+                    "\u{26A0}",
+                    "Missed older events; showing recent history only",
+                    "log-yellow",
+                );
+            }
             AppAction::SetConnected(c) => {
                 s.connected = c;
             }
@@ -127,14 +140,41 @@ impl Reducible for AppState {
                 s.queue_depth = msgs.len() as u32;
                 s.queued_messages = msgs;
             }
+            AppAction::AddChat(entry) => {
+                s.chat.push(entry);
+            }
         }
         Rc::new(s)
     }
 }
 
+/// A fresh, idle state for each of `agent_ids`.
+fn idle_agents(settings: &VizSettings, agent_ids: &[String]) -> HashMap<String, AgentState> {
+    let mut states = HashMap::new();
+    for id in agent_ids {
+        if let Some(agent) = settings.agents.get(id) {
+            states.insert(
+                id.clone(),
+                AgentState {
+                    id: id.clone(),
+                    name: agent.name.clone(),
+                    provider: agent.provider.clone(),
+                    model: agent.model.clone(),
+                    status: AgentStatus::Idle,
+                    last_activity: String::new(),
+                    response_length: None,
+                },
+            );
+        }
+    }
+    states
+}
+
 // ─── Event handler ──────────────────────────────────────────────────────────
 
 fn handle_event(s: &mut AppState, event: &VizEvent) {
+    // Replayed events carry their original time
+    let at = event.timestamp.map(|t| t as f64).unwrap_or_else(js_sys::Date::now);
     let get_str = |key: &str| -> String {
         event
             .data
@@ -147,7 +187,7 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
     match event.event_type.as_str() {
         "processor_start" => {
             s.processor_alive = true;
-            add_log(s, "\u{26A1}", "Queue processor started", "log-green");
+            add_log(s, at, "\u{26A1}", "Queue processor started", "log-green");
             // Settings may have changed — the WASM app re-fetches on connect
         }
 
@@ -157,6 +197,7 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             let message = get_str("message");
             add_log(
                 s,
+                at,
                 "\u{2709}",
                 &format!("[{}] {}: {}", channel, sender, truncate(&message, 50)),
                 "log-white",
@@ -165,7 +206,6 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             // Kanban: create card
             let message_id = get_str("messageId");
             if !message_id.is_empty() {
-                let now = js_sys::Date::now();
                 s.kanban_cards.push(KanbanCard {
                     id: message_id,
                     current_agent: String::new(),
@@ -173,8 +213,8 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
                     channel,
                     sender,
                     status: KanbanCardStatus::Queued,
-                    entered_column_at: now,
-                    created_at: now,
+                    entered_column_at: at,
+                    created_at: at,
                     handoff_trail: Vec::new(),
                     done_at: None,
                     response_length: None,
@@ -196,24 +236,24 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             if is_team {
                 add_log(
                     s,
+                    at,
                     "\u{2691}",
                     &format!("Routed to @{} (via team)", aid),
                     "log-cyan",
                 );
             } else {
-                add_log(s, "\u{2192}", &format!("Routed to @{}", aid), "log-cyan");
+                add_log(s, at, "\u{2192}", &format!("Routed to @{}", aid), "log-cyan");
             }
 
             // Kanban: assign unassigned card to this agent
-            let now = js_sys::Date::now();
             if let Some(card) = s.kanban_cards.iter_mut().rev()
                 .find(|c| c.current_agent.is_empty() && c.status == KanbanCardStatus::Queued)
             {
                 card.current_agent = aid.clone();
-                card.entered_column_at = now;
+                card.entered_column_at = at;
                 card.handoff_trail.push(HandoffStep {
                     agent_id: aid,
-                    entered_at: now,
+                    entered_at: at,
                 });
             }
         }
@@ -234,6 +274,7 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
                 .unwrap_or_default();
             add_log(
                 s,
+                at,
                 "\u{26D3}",
                 &format!("Conversation started: {} [{}]", team_name, agents_str),
                 "log-magenta",
@@ -274,6 +315,7 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             }
             add_log(
                 s,
+                at,
                 "\u{1F4AC}",
                 &format!("@{}: ({} chars)", aid, resp_len),
                 "log-white",
@@ -301,24 +343,24 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             }
             add_log(
                 s,
+                at,
                 "\u{2192}",
                 &format!("@{} \u{2192} @{}", from, to),
                 "log-yellow",
             );
 
             // Kanban: move card from one agent to another
-            let now = js_sys::Date::now();
             if let Some(card) = s.kanban_cards.iter_mut().rev()
                 .find(|c| c.current_agent == from &&
                     (c.status == KanbanCardStatus::StepDone || c.status == KanbanCardStatus::Active))
             {
                 card.current_agent = to.clone();
                 card.status = KanbanCardStatus::Queued;
-                card.entered_column_at = now;
+                card.entered_column_at = at;
                 card.response_length = None;
                 card.handoff_trail.push(HandoffStep {
                     agent_id: to,
-                    entered_at: now,
+                    entered_at: at,
                 });
             }
         }
@@ -336,7 +378,7 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
                     }
                 }
             }
-            add_log(s, "\u{2714}", "Conversation complete", "log-green");
+            add_log(s, at, "\u{2714}", "Conversation complete", "log-green");
         }
 
         "cross_team_handoff" => {
@@ -352,24 +394,24 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             }
             add_log(
                 s,
+                at,
                 "\u{2192}",
                 &format!("@{} \u{21C0} @{} (cross-team)", from, to),
                 "log-magenta",
             );
 
             // Kanban: move card cross-team
-            let now = js_sys::Date::now();
             if let Some(card) = s.kanban_cards.iter_mut().rev()
                 .find(|c| c.current_agent == from &&
                     (c.status == KanbanCardStatus::StepDone || c.status == KanbanCardStatus::Active))
             {
                 card.current_agent = to.clone();
                 card.status = KanbanCardStatus::Queued;
-                card.entered_column_at = now;
+                card.entered_column_at = at;
                 card.response_length = None;
                 card.handoff_trail.push(HandoffStep {
                     agent_id: to,
-                    entered_at: now,
+                    entered_at: at,
                 });
             }
         }
@@ -389,6 +431,7 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
                 .unwrap_or_default();
             add_log(
                 s,
+                at,
                 "\u{26A1}",
                 &format!("Multi-dispatch: [{}]", agents_str),
                 "log-cyan",
@@ -409,7 +452,6 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             // Kanban: move card to Done
             let message_id = get_str("messageId");
             let aid = get_str("agentId");
-            let now = js_sys::Date::now();
 
             // Find the card index first to avoid overlapping borrows
             let card_idx = if !message_id.is_empty() {
@@ -429,9 +471,72 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
             if let Some(card) = card {
                 card.current_agent = String::new();
                 card.status = KanbanCardStatus::Done;
-                card.done_at = Some(now);
-                card.entered_column_at = now;
+                card.done_at = Some(at);
+                card.entered_column_at = at;
+            }
+        }
+
+        "web_response" => {
+            let message_id = event.data.get("messageId").and_then(|v| v.as_str()).map(str::to_string);
+            if let Some(id) = &message_id {
+                for entry in s.chat.iter_mut().filter(|e| e.message_id.as_ref() == Some(id)) {
+                    entry.pending = false;
+                }
             }
+            let list = |key: &str| event.data.get(key).cloned().unwrap_or_default();
+            s.chat.push(ChatEntry {
+                from_user: false,
+                message_id,
+                agent: event.data.get("agent").and_then(|v| v.as_str()).map(str::to_string),
+                text: get_str("message"),
+                files: serde_json::from_value(list("files")).unwrap_or_default(),
+                choices: serde_json::from_value(list("choices")).unwrap_or_default(),
+                at,
+                pending: false,
+            });
+        }
+
+        "queue_message_deleted" => {
+            let message_id = get_str("messageId");
+            s.kanban_cards.retain(|c| c.id != message_id);
+            add_log(
+                s,
+                at,
+                "\u{2716}",
+                &format!("{} deleted a queued message", get_str("user")),
+                "log-yellow",
+            );
+        }
+
+        "queue_message_rerouted" => {
+            add_log(
+                s,
+                at,
+                "\u{21AA}",
+                &format!("{} re-routed a queued message to @{}", get_str("user"), get_str("agent")),
+                "log-yellow",
+            );
+        }
+
+        "queue_message_requeued" => {
+            add_log(
+                s,
+                at,
+                "\u{21BB}",
+                &format!("{} re-queued a stuck message", get_str("user")),
+                "log-yellow",
+            );
+        }
+
+        "queue_message_bumped" => {
+            let priority = event.data.get("priority").and_then(|v| v.as_i64()).unwrap_or(0);
+            add_log(
+                s,
+                at,
+                "\u{2191}",
+                &format!("{} bumped a queued message to priority {}", get_str("user"), priority),
+                "log-yellow",
+            );
         }
 
         _ => {}
@@ -448,8 +553,8 @@ fn handle_event(s: &mut AppState, event: &VizEvent) {
     });
 }
 
-fn add_log(s: &mut AppState, icon: &str, text: &str, css_class: &str) {
-    let now = js_sys::Date::new_0();
+fn add_log(s: &mut AppState, at: f64, icon: &str, text: &str, css_class: &str) {
+    let now = js_sys::Date::new(&JsValue::from_f64(at));
     let time = format!(
         "{:02}:{:02}:{:02}",
         now.get_hours(),
@@ -478,6 +583,80 @@ fn truncate(s: &str, max: usize) -> String {
     }
 }
 
+// ─── Event stream ───────────────────────────────────────────────────────────
+
+const RECONNECT_MS: u32 = 2_000;
+
+#[derive(Default)]
+struct EventConnection {
+    socket: RefCell<Option<web_sys::WebSocket>>,
+    /// Sequence number of the last event applied
+    last_seq: Cell<u64>,
+    /// Set on unmount so the socket isn't reopened
+    closed: Cell<bool>,
+}
+
+fn connect_events(state: UseReducerHandle<AppState>, conn: Rc<EventConnection>) {
+    let url = format!(
+        "{}?since={}&client={}",
+        get_ws_url(),
+        conn.last_seq.get(),
+        web_client_id()
+    );
+    let Ok(ws) = web_sys::WebSocket::new(&url) else {
+        return;
+    };
+
+    // onmessage — handle incoming events, skipping any already applied
+    let s = state.clone();
+    let c = Rc::clone(&conn);
+    let onmessage = Closure::wrap(Box::new(move |e: web_sys::MessageEvent| {
+        if let Some(text) = e.data().as_string() {
+            if let Ok(event) = serde_json::from_str::<VizEvent>(&text) {
+                // Events between `since` and the replay were skipped
+                if event.event_type == "replay_truncated" {
+                    s.dispatch(AppAction::ResetEvents);
+                    return;
+                }
+                if let Some(seq) = event.seq {
+                    if seq <= c.last_seq.get() {
+                        return;
+                    }
+                    c.last_seq.set(seq);
+                }
+                s.dispatch(AppAction::HandleEvent(event));
+            }
+        }
+    })
+        as Box<dyn FnMut(web_sys::MessageEvent)>);
+    ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    // onopen
+    let s = state.clone();
+    let onopen = Closure::wrap(Box::new(move |_: web_sys::Event| {
+        s.dispatch(AppAction::SetConnected(true));
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    onopen.forget();
+
+    // onclose — reconnect after a pause unless unmounted
+    let c = Rc::clone(&conn);
+    let onclose = Closure::wrap(Box::new(move |_: web_sys::CloseEvent| {
+        state.dispatch(AppAction::SetConnected(false));
+        if c.closed.get() {
+            return;
+        }
+        let state = state.clone();
+        let c = Rc::clone(&c);
+        Timeout::new(RECONNECT_MS, move || connect_events(state, c)).forget();
+    }) as Box<dyn FnMut(web_sys::CloseEvent)>);
+    ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+    onclose.forget();
+
+    *conn.socket.borrow_mut() = Some(ws);
+}
+
 fn get_ws_url() -> String {
     let window = web_sys::window().expect("no window");
     let location = window.location();
@@ -491,6 +670,26 @@ fn get_ws_url() -> String {
     format!("{}//{}/ws", ws_protocol, host)
 }
 
+/// Per-browser ID for web chat, kept in local storage so replies reach this
+/// browser across reloads.
+pub fn web_client_id() -> String {
+    const KEY: &str = "rustyclaw-web-client";
+    let storage = web_sys::window().and_then(|w| w.local_storage().ok().flatten());
+    if let Some(id) = storage.as_ref().and_then(|s| s.get_item(KEY).ok().flatten()) {
+        return id;
+    }
+    let id: String = (0..16)
+        .map(|_| {
+            let idx = (js_sys::Math::random() * 36.0) as u8;
+            if idx < 10 { (b'0' + idx) as char } else { (b'a' + idx - 10) as char }
+        })
+        .collect();
+    if let Some(storage) = storage {
+        let _ = storage.set_item(KEY, &id);
+    }
+    id
+}
+
 fn get_team_filter() -> Option<String> {
     let window = web_sys::window()?;
     let search = window.location().search().ok()?;
@@ -508,10 +707,78 @@ fn format_uptime(secs: u64) -> String {
     }
 }
 
+// ─── Login gate ─────────────────────────────────────────────────────────────
+
+/// Token from a `?token=` login link, removed from the address bar once read.
+fn take_url_token() -> Option<String> {
+    let window = web_sys::window()?;
+    let search = window.location().search().ok()?;
+    let params = web_sys::UrlSearchParams::new_with_str(&search).ok()?;
+    let token = params.get("token")?;
+    if let Ok(history) = window.history() {
+        let path = window.location().pathname().unwrap_or_else(|_| "/".to_string());
+        let _ = history.replace_state_with_url(&JsValue::NULL, "", Some(&path));
+    }
+    Some(token)
+}
+
+#[derive(Clone, PartialEq)]
+enum AuthState {
+    Checking,
+    LoggedOut(Option<String>),
+    LoggedIn(SessionInfo),
+}
+
+/// Shows the login form until there is a session, then the dashboard.
+#[function_component(Root)]
+pub fn root() -> Html {
+    let auth = use_state(|| AuthState::Checking);
+
+    {
+        let auth = auth.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                if let Some(token) = take_url_token() {
+                    match login(serde_json::json!({ "token": token })).await {
+                        Ok(session) => auth.set(AuthState::LoggedIn(session)),
+                        Err(e) => auth.set(AuthState::LoggedOut(Some(e))),
+                    }
+                    return;
+                }
+                let session = match Request::get("/api/me").send().await {
+                    Ok(resp) if resp.ok() => resp.json::<SessionInfo>().await.ok(),
+                    _ => None,
+                };
+                auth.set(match session {
+                    Some(session) => AuthState::LoggedIn(session),
+                    None => AuthState::LoggedOut(None),
+                });
+            });
+            || {}
+        });
+    }
+
+    let on_login = {
+        let auth = auth.clone();
+        Callback::from(move |session: SessionInfo| auth.set(AuthState::LoggedIn(session)))
+    };
+
+    match &*auth {
+        AuthState::Checking => html! { <div class="settings-loading">{"Loading..."}</div> },
+        AuthState::LoggedOut(error) => html! { <LoginForm {on_login} error={error.clone()} /> },
+        AuthState::LoggedIn(session) => html! { <App session={session.clone()} /> },
+    }
+}
+
 // ─── Main App Component ────────────────────────────────────────────────────
 
+#[derive(Properties, PartialEq)]
+pub struct AppProps {
+    pub session: SessionInfo,
+}
+
 #[function_component(App)]
-pub fn app() -> Html {
+pub fn app(props: &AppProps) -> Html {
     let state = use_reducer(AppState::default);
     let start_time = use_state(|| js_sys::Date::now());
     let active_tab = use_state(|| Tab::Dashboard);
@@ -542,47 +809,18 @@ pub fn app() -> Html {
         });
     }
 
-    // WebSocket connection
+    // WebSocket connection: replays history on first connect, then resumes
+    // from the last seen event after a disconnect
     {
         let state = state.clone();
         use_effect_with((), move |_| {
-            let ws_url = get_ws_url();
-            let ws = web_sys::WebSocket::new(&ws_url).ok();
-
-            if let Some(ref ws) = ws {
-                // onmessage — handle incoming events
-                let s = state.clone();
-                let onmessage = Closure::wrap(Box::new(move |e: web_sys::MessageEvent| {
-                    if let Some(text) = e.data().as_string() {
-                        if let Ok(event) = serde_json::from_str::<VizEvent>(&text) {
-                            s.dispatch(AppAction::HandleEvent(event));
-                        }
-                    }
-                })
-                    as Box<dyn FnMut(web_sys::MessageEvent)>);
-                ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
-                onmessage.forget();
-
-                // onopen
-                let s = state.clone();
-                let onopen = Closure::wrap(Box::new(move |_: web_sys::Event| {
-                    s.dispatch(AppAction::SetConnected(true));
-                }) as Box<dyn FnMut(web_sys::Event)>);
-                ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
-                onopen.forget();
-
-                // onclose
-                let s = state.clone();
-                let onclose = Closure::wrap(Box::new(move |_: web_sys::CloseEvent| {
-                    s.dispatch(AppAction::SetConnected(false));
-                }) as Box<dyn FnMut(web_sys::CloseEvent)>);
-                ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
-                onclose.forget();
-            }
+            let conn = Rc::new(EventConnection::default());
+            connect_events(state, Rc::clone(&conn));
 
-            // Cleanup: close WebSocket on unmount
+            // Cleanup: stop reconnecting and close WebSocket on unmount
             move || {
-                if let Some(ws) = ws {
+                conn.closed.set(true);
+                if let Some(ws) = conn.socket.borrow_mut().take() {
                     let _ = ws.close();
                 }
             }
@@ -644,6 +882,13 @@ pub fn app() -> Html {
         .cloned()
         .collect();
 
+    let mut history_teams: Vec<(String, String)> = state.settings.teams.iter()
+        .map(|(id, team)| (id.clone(), team.name.clone()))
+        .collect();
+    history_teams.sort();
+    let mut history_agents: Vec<String> = state.settings.agents.keys().cloned().collect();
+    history_agents.sort();
+
     // ─── Tab callbacks ─────────────────────────────────────────────────
 
     let on_dashboard = {
@@ -658,6 +903,22 @@ pub fn app() -> Html {
         let active_tab = active_tab.clone();
         Callback::from(move |_: MouseEvent| active_tab.set(Tab::Queue))
     };
+    let on_chat = {
+        let active_tab = active_tab.clone();
+        Callback::from(move |_: MouseEvent| active_tab.set(Tab::Chat))
+    };
+    let on_sent = {
+        let state = state.clone();
+        Callback::from(move |entry: ChatEntry| state.dispatch(AppAction::AddChat(entry)))
+    };
+    let on_history = {
+        let active_tab = active_tab.clone();
+        Callback::from(move |_: MouseEvent| active_tab.set(Tab::History))
+    };
+    let on_metrics = {
+        let active_tab = active_tab.clone();
+        Callback::from(move |_: MouseEvent| active_tab.set(Tab::Metrics))
+    };
     let on_settings = {
         let active_tab = active_tab.clone();
         Callback::from(move |_: MouseEvent| active_tab.set(Tab::Settings))
@@ -672,6 +933,7 @@ pub fn app() -> Html {
                 team_name={team_name}
                 uptime={uptime}
                 connected={state.connected}
+                session={props.session.clone()}
             />
 
             <nav class="tab-bar">
@@ -708,6 +970,26 @@ pub fn app() -> Html {
                         <span class="tab-badge">{state.queue_depth}</span>
                     }
                 </button>
+                if props.session.can_edit() {
+                    <button
+                        class={classes!("tab-button", (*active_tab == Tab::Chat).then_some("tab-active"))}
+                        onclick={on_chat}
+                    >
+                        {"Chat"}
+                    </button>
+                }
+                <button
+                    class={classes!("tab-button", (*active_tab == Tab::History).then_some("tab-active"))}
+                    onclick={on_history}
+                >
+                    {"History"}
+                </button>
+                <button
+                    class={classes!("tab-button", (*active_tab == Tab::Metrics).then_some("tab-active"))}
+                    onclick={on_metrics}
+                >
+                    {"Metrics"}
+                </button>
                 <button
                     class={classes!("tab-button", (*active_tab == Tab::Settings).then_some("tab-active"))}
                     onclick={on_settings}
@@ -768,8 +1050,14 @@ pub fn app() -> Html {
                 }
 
                 if *active_tab == Tab::Queue {
-                    <SendForm agents={state.settings.agents.clone()} />
-                    <QueuePanel messages={state.queued_messages.clone()} />
+                    if props.session.can_edit() {
+                        <SendForm agents={state.settings.agents.clone()} />
+                    }
+                    <QueuePanel
+                        messages={state.queued_messages.clone()}
+                        agents={state.settings.agents.keys().cloned().collect::<Vec<_>>()}
+                        can_edit={props.session.can_edit()}
+                    />
                     if state.queued_messages.is_empty() {
                         <div class="queue-empty-hint">
                             {"No messages in queue. Send one above!"}
@@ -777,8 +1065,24 @@ pub fn app() -> Html {
                     }
                 }
 
+                if *active_tab == Tab::Chat {
+                    <ChatPage
+                        entries={state.chat.clone()}
+                        agents={state.settings.agents.clone()}
+                        {on_sent}
+                    />
+                }
+
+                if *active_tab == Tab::History {
+                    <HistoryPage teams={history_teams} agents={history_agents} />
+                }
+
+                if *active_tab == Tab::Metrics {
+                    <MetricsPage />
+                }
+
                 if *active_tab == Tab::Settings {
-                    <SettingsPage />
+                    <SettingsPage can_edit={props.session.can_edit()} />
                 }
             </div>
 
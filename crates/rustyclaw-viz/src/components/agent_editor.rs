@@ -0,0 +1,304 @@
+use yew::prelude::*;
+
+use crate::types::FullAgentConfig;
+
+const PROVIDERS: &[&str] = &["anthropic", "openai", "opencode"];
+const RESET_KINDS: &[(&str, &str)] = &[
+    ("both", "Daily or idle (default)"),
+    ("daily", "Daily"),
+    ("idle", "Idle"),
+    ("manual", "Manual"),
+    ("cron", "Cron schedule"),
+    ("messages", "After N messages"),
+    ("compactions", "After N compactions"),
+];
+
+// ─── Form state ─────────────────────────────────────────────────────────────
+
+/// Every field as typed, so half-finished input survives re-renders.
+#[derive(Clone, PartialEq, Default)]
+struct AgentForm {
+    id: String,
+    name: String,
+    provider: String,
+    model: String,
+    working_directory: String,
+    reset_kind: String,
+    reset_value: String,
+    reset_hour: String,
+    idle_timeout_minutes: String,
+    timezone: String,
+    context_window: String,
+    fallbacks: String,
+    route_patterns: String,
+    route_priority: String,
+    cross_team_handoffs: bool,
+}
+
+fn opt_to_string<T: ToString>(v: &Option<T>) -> String {
+    v.as_ref().map(|v| v.to_string()).unwrap_or_default()
+}
+
+impl AgentForm {
+    fn new(id: &str, agent: Option<&FullAgentConfig>) -> Self {
+        let Some(a) = agent else {
+            return Self {
+                provider: "anthropic".to_string(),
+                model: "sonnet".to_string(),
+                reset_kind: "both".to_string(),
+                cross_team_handoffs: true,
+                ..Default::default()
+            };
+        };
+        let policy = a.reset_policy.clone().unwrap_or_else(|| "both".to_string());
+        let (reset_kind, reset_value) = match policy.split_once(':') {
+            Some((kind, value)) => (kind.to_string(), value.to_string()),
+            None => (policy, String::new()),
+        };
+        Self {
+            id: id.to_string(),
+            name: a.name.clone(),
+            provider: a.provider.clone(),
+            model: a.model.clone(),
+            working_directory: a.working_directory.clone(),
+            reset_kind,
+            reset_value,
+            reset_hour: opt_to_string(&a.reset_hour),
+            idle_timeout_minutes: opt_to_string(&a.idle_timeout_minutes),
+            timezone: a.timezone.clone().unwrap_or_default(),
+            context_window: opt_to_string(&a.context_window),
+            fallbacks: a.fallbacks.clone().unwrap_or_default().join(", "),
+            route_patterns: a.route_patterns.clone().unwrap_or_default().join("\n"),
+            route_priority: opt_to_string(&a.route_priority),
+            cross_team_handoffs: a.cross_team_handoffs.unwrap_or(true),
+        }
+    }
+
+    /// Build the agent config. Only catches what the form can check on its
+    /// own (empty ID, non-numeric numbers); the server validates the rest.
+    fn to_config(&self, workspace: Option<&str>) -> Result<(String, FullAgentConfig), String> {
+        let id = self.id.trim().to_lowercase();
+        if id.is_empty() {
+            return Err("Agent ID is required".to_string());
+        }
+        fn number<T: std::str::FromStr>(label: &str, s: &str) -> Result<Option<T>, String> {
+            let s = s.trim();
+            if s.is_empty() {
+                return Ok(None);
+            }
+            s.parse().map(Some).map_err(|_| format!("{} must be a number", label))
+        }
+        fn list(s: &str, sep: char) -> Option<Vec<String>> {
+            let items: Vec<String> = s
+                .split(sep)
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect();
+            (!items.is_empty()).then_some(items)
+        }
+        let reset_policy = if self.reset_value.trim().is_empty() {
+            self.reset_kind.clone()
+        } else {
+            format!("{}:{}", self.reset_kind, self.reset_value.trim())
+        };
+        let working_directory = match (self.working_directory.trim(), workspace) {
+            ("", Some(ws)) => format!("{}/{}", ws.trim_end_matches('/'), id),
+            (dir, _) => dir.to_string(),
+        };
+        let config = FullAgentConfig {
+            name: self.name.trim().to_string(),
+            provider: self.provider.clone(),
+            model: self.model.trim().to_string(),
+            working_directory,
+            reset_policy: Some(reset_policy),
+            reset_hour: number("Reset hour", &self.reset_hour)?,
+            idle_timeout_minutes: number("Idle timeout", &self.idle_timeout_minutes)?,
+            timezone: Some(self.timezone.trim().to_string()).filter(|t| !t.is_empty()),
+            context_window: number("Context window", &self.context_window)?,
+            fallbacks: list(&self.fallbacks, ','),
+            cross_team_handoffs: Some(self.cross_team_handoffs),
+            route_patterns: list(&self.route_patterns, '\n'),
+            route_priority: number("Route priority", &self.route_priority)?,
+        };
+        Ok((id, config))
+    }
+}
+
+// ─── Component ──────────────────────────────────────────────────────────────
+
+#[derive(Properties, PartialEq)]
+pub struct AgentEditorProps {
+    /// Agent being edited; `None` adds a new one
+    pub agent_id: Option<String>,
+    pub agent: Option<FullAgentConfig>,
+    /// Workspace path, for the default working directory of new agents
+    pub workspace: Option<String>,
+    pub saving: bool,
+    pub on_save: Callback<(String, FullAgentConfig)>,
+    pub on_cancel: Callback<()>,
+}
+
+#[function_component(AgentEditor)]
+pub fn agent_editor(props: &AgentEditorProps) -> Html {
+    let form = {
+        let id = props.agent_id.clone().unwrap_or_default();
+        let agent = props.agent.clone();
+        use_state(move || AgentForm::new(&id, agent.as_ref()))
+    };
+    let error = use_state(|| None::<String>);
+    let is_new = props.agent_id.is_none();
+
+    let text = |set: fn(&mut AgentForm, String)| {
+        let form = form.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+            let mut next = (*form).clone();
+            set(&mut next, input.value());
+            form.set(next);
+        })
+    };
+    let select = |set: fn(&mut AgentForm, String)| {
+        let form = form.clone();
+        Callback::from(move |e: Event| {
+            let input: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            let mut next = (*form).clone();
+            set(&mut next, input.value());
+            form.set(next);
+        })
+    };
+    let on_patterns = {
+        let form = form.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlTextAreaElement = e.target_unchecked_into();
+            let mut next = (*form).clone();
+            next.route_patterns = input.value();
+            form.set(next);
+        })
+    };
+    let on_handoffs = {
+        let form = form.clone();
+        Callback::from(move |e: Event| {
+            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+            let mut next = (*form).clone();
+            next.cross_team_handoffs = input.checked();
+            form.set(next);
+        })
+    };
+
+    let on_submit = {
+        let form = form.clone();
+        let error = error.clone();
+        let workspace = props.workspace.clone();
+        let on_save = props.on_save.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            match form.to_config(workspace.as_deref()) {
+                Ok(result) => {
+                    error.set(None);
+                    on_save.emit(result);
+                }
+                Err(msg) => error.set(Some(msg)),
+            }
+        })
+    };
+    let on_cancel = {
+        let on_cancel = props.on_cancel.clone();
+        Callback::from(move |_: MouseEvent| on_cancel.emit(()))
+    };
+
+    let f = &*form;
+    let needs_value = matches!(f.reset_kind.as_str(), "cron" | "messages" | "compactions");
+    let workspace_hint = props
+        .workspace
+        .as_deref()
+        .map(|ws| format!("{}/<id>", ws.trim_end_matches('/')))
+        .unwrap_or_default();
+
+    html! {
+        <form class="settings-form" onsubmit={on_submit}>
+            <h4>{ if is_new { "New agent".to_string() } else { format!("Edit @{}", f.id) } }</h4>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"ID"}</span>
+                <input class="send-input" value={f.id.clone()} disabled={!is_new}
+                    placeholder="coder" oninput={text(|f, v| f.id = v)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Name"}</span>
+                <input class="send-input" value={f.name.clone()} oninput={text(|f, v| f.name = v)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Provider"}</span>
+                <select class="send-agent-select" onchange={select(|f, v| f.provider = v)}>
+                    { for PROVIDERS.iter().map(|p| html! {
+                        <option value={*p} selected={f.provider == *p}>{*p}</option>
+                    })}
+                </select>
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Model"}</span>
+                <input class="send-input" value={f.model.clone()} placeholder="sonnet"
+                    oninput={text(|f, v| f.model = v)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Fallbacks"}</span>
+                <input class="send-input" value={f.fallbacks.clone()} placeholder="opus, gpt-5.3-codex"
+                    oninput={text(|f, v| f.fallbacks = v)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Working dir"}</span>
+                <input class="send-input" value={f.working_directory.clone()} placeholder={workspace_hint}
+                    oninput={text(|f, v| f.working_directory = v)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Reset policy"}</span>
+                <select class="send-agent-select" onchange={select(|f, v| f.reset_kind = v)}>
+                    { for RESET_KINDS.iter().map(|(kind, label)| html! {
+                        <option value={*kind} selected={f.reset_kind == *kind}>{*label}</option>
+                    })}
+                </select>
+                if needs_value {
+                    <input class="send-input" value={f.reset_value.clone()}
+                        placeholder={if f.reset_kind == "cron" { "0 4 * * *" } else { "50" }}
+                        oninput={text(|f, v| f.reset_value = v)} />
+                }
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Reset hour"}</span>
+                <input class="send-input" value={f.reset_hour.clone()} placeholder="4"
+                    oninput={text(|f, v| f.reset_hour = v)} />
+                <span class="settings-field-label">{"Idle minutes"}</span>
+                <input class="send-input" value={f.idle_timeout_minutes.clone()} placeholder="120"
+                    oninput={text(|f, v| f.idle_timeout_minutes = v)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Timezone"}</span>
+                <input class="send-input" value={f.timezone.clone()} placeholder="UTC"
+                    oninput={text(|f, v| f.timezone = v)} />
+                <span class="settings-field-label">{"Context window"}</span>
+                <input class="send-input" value={f.context_window.clone()} placeholder="200000"
+                    oninput={text(|f, v| f.context_window = v)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Route patterns"}</span>
+                <textarea class="send-input" rows="3" value={f.route_patterns.clone()}
+                    placeholder="one keyword pattern per line" oninput={on_patterns} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Route priority"}</span>
+                <input class="send-input" value={f.route_priority.clone()} placeholder="0"
+                    oninput={text(|f, v| f.route_priority = v)} />
+                <span class="settings-field-label">{"Cross-team handoffs"}</span>
+                <input type="checkbox" checked={f.cross_team_handoffs} onchange={on_handoffs} />
+            </label>
+            if let Some(err) = &*error {
+                <div class="settings-form-error">{err}</div>
+            }
+            <div class="settings-form-actions">
+                <button type="submit" class="send-button" disabled={props.saving}>
+                    { if props.saving { "Saving..." } else { "Save agent" } }
+                </button>
+                <button type="button" class="settings-button" onclick={on_cancel}>{"Cancel"}</button>
+            </div>
+        </form>
+    }
+}
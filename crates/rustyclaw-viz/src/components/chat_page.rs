@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use gloo_net::http::Request;
+
+use crate::app::web_client_id;
+use crate::types::{ChatEntry, ChatFile, VizAgentConfig};
+
+async fn error_message(resp: gloo_net::http::Response) -> String {
+    let status = resp.status();
+    resp.json::<serde_json::Value>()
+        .await
+        .ok()
+        .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(str::to_string))
+        .unwrap_or_else(|| format!("HTTP {}", status))
+}
+
+/// Upload a file for the next message; returns its upload ID.
+async fn upload(file: &web_sys::File) -> Result<String, String> {
+    let url = format!(
+        "/api/web/upload?name={}",
+        String::from(js_sys::encode_uri_component(&file.name()))
+    );
+    let resp = Request::post(&url)
+        .body(wasm_bindgen::JsValue::from(file.clone()))
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if !resp.ok() {
+        return Err(error_message(resp).await);
+    }
+    let body: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    body.get("id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| "Upload failed".to_string())
+}
+
+/// Upload the files and queue the message. Returns the queue message ID,
+/// or `None` for built-in commands, which are answered directly.
+async fn send_chat(text: String, agent: String, files: Vec<web_sys::File>) -> Result<Option<String>, String> {
+    let mut ids = Vec::new();
+    for file in &files {
+        ids.push(upload(file).await?);
+    }
+    let body = serde_json::json!({
+        "message": text,
+        "agent": if agent.is_empty() { None } else { Some(agent) },
+        "client": web_client_id(),
+        "files": ids,
+    });
+    let resp = Request::post("/api/send")
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if !resp.ok() {
+        return Err(error_message(resp).await);
+    }
+    let body: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    Ok(body.get("messageId").and_then(|v| v.as_str()).map(str::to_string))
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ChatPageProps {
+    pub entries: Vec<ChatEntry>,
+    pub agents: HashMap<String, VizAgentConfig>,
+    pub on_sent: Callback<ChatEntry>,
+}
+
+#[function_component(ChatPage)]
+pub fn chat_page(props: &ChatPageProps) -> Html {
+    let message = use_state(String::new);
+    let agent = use_state(String::new);
+    let files = use_state(Vec::<web_sys::File>::new);
+    let sending = use_state(|| false);
+    let error = use_state(|| None::<String>);
+    let file_input = use_node_ref();
+
+    // Shared by the form and the choice buttons
+    let send = {
+        let agent = agent.clone();
+        let files = files.clone();
+        let sending = sending.clone();
+        let error = error.clone();
+        let message = message.clone();
+        let file_input = file_input.clone();
+        let on_sent = props.on_sent.clone();
+        Callback::from(move |text: String| {
+            if *sending || (text.trim().is_empty() && files.is_empty()) {
+                return;
+            }
+            let attached = (*files).clone();
+            let agent_val = (*agent).clone();
+            sending.set(true);
+            error.set(None);
+            let sending = sending.clone();
+            let error = error.clone();
+            let message = message.clone();
+            let files = files.clone();
+            let file_input = file_input.clone();
+            let on_sent = on_sent.clone();
+            spawn_local(async move {
+                let names: Vec<ChatFile> = attached
+                    .iter()
+                    .map(|f| ChatFile { name: f.name(), url: String::new() })
+                    .collect();
+                match send_chat(text.clone(), agent_val, attached).await {
+                    Ok(message_id) => {
+                        on_sent.emit(ChatEntry {
+                            from_user: true,
+                            pending: message_id.is_some(),
+                            message_id,
+                            agent: None,
+                            text,
+                            files: names,
+                            choices: Vec::new(),
+                            at: js_sys::Date::now(),
+                        });
+                        message.set(String::new());
+                        files.set(Vec::new());
+                        if let Some(input) = file_input.cast::<web_sys::HtmlInputElement>() {
+                            input.set_value("");
+                        }
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+                sending.set(false);
+            });
+        })
+    };
+
+    let on_input = {
+        let message = message.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlTextAreaElement = e.target_unchecked_into();
+            message.set(input.value());
+        })
+    };
+    let on_agent_change = {
+        let agent = agent.clone();
+        Callback::from(move |e: Event| {
+            let select: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            agent.set(select.value());
+        })
+    };
+    let on_files = {
+        let files = files.clone();
+        Callback::from(move |e: Event| {
+            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+            let list = input.files();
+            let picked = (0..list.as_ref().map(|l| l.length()).unwrap_or(0))
+                .filter_map(|i| list.as_ref()?.get(i))
+                .collect();
+            files.set(picked);
+        })
+    };
+    let on_submit = {
+        let send = send.clone();
+        let message = message.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            send.emit((*message).clone());
+        })
+    };
+    // Enter sends; Shift+Enter starts a new line
+    let on_keydown = {
+        let send = send.clone();
+        let message = message.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if e.key() == "Enter" && !e.shift_key() {
+                e.prevent_default();
+                send.emit((*message).clone());
+            }
+        })
+    };
+
+    let mut agent_ids: Vec<&String> = props.agents.keys().collect();
+    agent_ids.sort();
+
+    html! {
+        <div class="chat-page">
+            <div class="chat-messages">
+                if props.entries.is_empty() {
+                    <div class="queue-empty-hint">
+                        {"Send a message to start a conversation. Replies show up here."}
+                    </div>
+                }
+                { for props.entries.iter().map(|entry| {
+                    let who = if entry.from_user {
+                        "You".to_string()
+                    } else {
+                        entry.agent.as_ref().map(|a| format!("@{}", a)).unwrap_or_else(|| "Rusty Claw".to_string())
+                    };
+                    html! {
+                        <div class={classes!("chat-bubble", if entry.from_user { "chat-user" } else { "chat-agent" })}>
+                            <div class="chat-meta">
+                                <span class="chat-who">{who}</span>
+                                if entry.pending {
+                                    <span class="chat-pending">{"waiting for reply..."}</span>
+                                }
+                            </div>
+                            if !entry.text.is_empty() {
+                                <div class="chat-text">{&entry.text}</div>
+                            }
+                            if !entry.files.is_empty() {
+                                <div class="chat-files">
+                                    { for entry.files.iter().map(|f| if f.url.is_empty() {
+                                        html! { <span class="chat-file">{format!("\u{1F4CE} {}", f.name)}</span> }
+                                    } else {
+                                        html! { <a class="chat-file" href={f.url.clone()} download={f.name.clone()}>{format!("\u{1F4CE} {}", f.name)}</a> }
+                                    })}
+                                </div>
+                            }
+                            if !entry.choices.is_empty() {
+                                <div class="chat-choices">
+                                    { for entry.choices.iter().map(|choice| {
+                                        let send = send.clone();
+                                        let label = choice.clone();
+                                        let onclick = Callback::from(move |_: MouseEvent| send.emit(label.clone()));
+                                        html! { <button class="settings-button" {onclick} disabled={*sending}>{choice}</button> }
+                                    })}
+                                </div>
+                            }
+                        </div>
+                    }
+                })}
+            </div>
+
+            <form class="chat-form" onsubmit={on_submit}>
+                <textarea
+                    class="send-input chat-input"
+                    placeholder="Message your agents... (Shift+Enter for a new line)"
+                    value={(*message).clone()}
+                    oninput={on_input}
+                    onkeydown={on_keydown}
+                    disabled={*sending}
+                />
+                <div class="chat-form-row">
+                    <select class="send-agent-select" onchange={on_agent_change}>
+                        <option value="" selected=true>{"Any agent"}</option>
+                        { for agent_ids.iter().map(|id| {
+                            let name = props.agents.get(*id).map(|a| a.name.as_str()).unwrap_or("");
+                            html! { <option value={(*id).clone()}>{format!("@{} ({})", id, name)}</option> }
+                        })}
+                    </select>
+                    <input type="file" multiple=true class="chat-file-input" ref={file_input} onchange={on_files} />
+                    <button type="submit" class="send-button" disabled={*sending}>
+                        { if *sending { "Sending..." } else { "Send" } }
+                    </button>
+                </div>
+                if let Some(err) = &*error {
+                    <div class="settings-form-error">{err}</div>
+                }
+            </form>
+        </div>
+    }
+}
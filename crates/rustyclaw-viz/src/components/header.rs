@@ -1,11 +1,16 @@
+use gloo_net::http::Request;
+use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
+use crate::types::SessionInfo;
+
 #[derive(Properties, PartialEq)]
 pub struct HeaderProps {
     pub team_id: Option<String>,
     pub team_name: Option<String>,
     pub uptime: String,
     pub connected: bool,
+    pub session: SessionInfo,
 }
 
 #[function_component(Header)]
@@ -28,6 +33,15 @@ pub fn header(props: &HeaderProps) -> Html {
         "disconnected"
     };
 
+    let on_logout = Callback::from(|_: MouseEvent| {
+        spawn_local(async {
+            let _ = Request::post("/api/logout").send().await;
+            if let Some(window) = web_sys::window() {
+                let _ = window.location().reload();
+            }
+        });
+    });
+
     html! {
         <div class="header">
             <div class="header-content">
@@ -48,6 +62,10 @@ pub fn header(props: &HeaderProps) -> Html {
                 <span class="header-uptime">{format!("up {}", props.uptime)}</span>
                 <span class="sep">{"\u{2502}"}</span>
                 <span class={conn_class}>{format!("{} {}", conn_icon, conn_text)}</span>
+                <span class="header-user">
+                    {format!("{} ({})", props.session.user, props.session.role)}
+                    <button class="settings-button" onclick={on_logout}>{"Log out"}</button>
+                </span>
             </div>
             <hr class="divider" />
         </div>
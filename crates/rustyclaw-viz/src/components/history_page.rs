@@ -0,0 +1,390 @@
+use gloo_net::http::Request;
+use serde::de::DeserializeOwned;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::types::{ChatRecord, ChatStep, ConversationSummary, Page, TranscriptEntry};
+
+const PER_PAGE: usize = 20;
+
+// ─── Loading ────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Conversations,
+    Transcripts,
+}
+
+async fn fetch_json<T: DeserializeOwned>(url: &str, params: Vec<(&str, String)>) -> Result<T, String> {
+    let resp = Request::get(url)
+        .query(params)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if !resp.ok() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    resp.json::<T>().await.map_err(|e| format!("Parse error: {}", e))
+}
+
+// ─── Formatting ─────────────────────────────────────────────────────────────
+
+/// Local date and time for an epoch-millis timestamp.
+fn format_time(ms: u64) -> String {
+    let d = js_sys::Date::new(&JsValue::from_f64(ms as f64));
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+        d.get_full_year(),
+        d.get_month() + 1,
+        d.get_date(),
+        d.get_hours(),
+        d.get_minutes(),
+        d.get_seconds()
+    )
+}
+
+/// RFC 3339 date from the server, trimmed to `YYYY-MM-DD HH:MM:SS`.
+fn format_date(date: &str) -> String {
+    date.chars().take(19).collect::<String>().replace('T', " ")
+}
+
+fn duration_str(ms: u64) -> String {
+    let secs = ms / 1000;
+    if secs < 60 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else if secs < 3600 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Paths referenced by `[send_file: path]` tags in a response.
+fn file_refs(text: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[send_file:") {
+        rest = &rest[start + "[send_file:".len()..];
+        let Some(end) = rest.find(']') else { break };
+        files.push(rest[..end].trim().to_string());
+        rest = &rest[end..];
+    }
+    files
+}
+
+fn file_chip(path: &str) -> Html {
+    let name = path.rsplit('/').next().unwrap_or(path).to_string();
+    html! { <span class="history-file" title={path.to_string()}>{format!("\u{1F4CE} {}", name)}</span> }
+}
+
+// ─── Timeline ───────────────────────────────────────────────────────────────
+
+fn render_step(record: &ChatRecord, index: usize, step: &ChatStep) -> Html {
+    // Older chat files have no timings or handoff sources
+    let timed = step.started_at > 0;
+    let from = match &step.from_agent {
+        Some(agent) => Some(format!("@{}", agent)),
+        None if index == 0 || timed => Some(record.sender.clone()),
+        None => None,
+    };
+    let duration = (timed && step.finished_at >= step.started_at)
+        .then(|| duration_str(step.finished_at - step.started_at));
+    let offset = (timed && record.started_at > 0 && step.started_at >= record.started_at)
+        .then(|| format!("+{}", duration_str(step.started_at - record.started_at)));
+    let files = file_refs(&step.response);
+
+    html! {
+        <div class="history-step">
+            <div class="history-step-header">
+                if let Some(from) = from {
+                    <span class="history-from">{from}</span>
+                    <span class="history-arrow">{"\u{2192}"}</span>
+                }
+                <span class="history-agent">{format!("@{}", step.agent_id)}</span>
+                if let Some(offset) = offset {
+                    <span class="history-meta">{offset}</span>
+                }
+                if let Some(duration) = duration {
+                    <span class="history-duration">{duration}</span>
+                }
+            </div>
+            <div class="history-text">{&step.response}</div>
+            if !files.is_empty() {
+                <div class="history-files">{ for files.iter().map(|f| file_chip(f)) }</div>
+            }
+        </div>
+    }
+}
+
+fn render_timeline(record: &ChatRecord) -> Html {
+    let total = record
+        .steps
+        .iter()
+        .map(|s| s.finished_at)
+        .max()
+        .filter(|end| record.started_at > 0 && *end >= record.started_at)
+        .map(|end| duration_str(end - record.started_at));
+
+    html! {
+        <div class="history-timeline">
+            <div class="history-timeline-header">
+                <span class="team-id">{format!("@{}", record.team_id)}</span>
+                <span class="team-name">{" "}{&record.team_name}{" "}</span>
+                <span class="history-meta">{format_date(&record.date)}</span>
+                <span class="history-meta">{format!("{}/{}", record.channel, record.sender)}</span>
+                if let Some(total) = total {
+                    <span class="history-duration">{format!("total {}", total)}</span>
+                }
+            </div>
+            <div class="history-step history-step-user">
+                <div class="history-step-header">
+                    <span class="history-from">{&record.sender}</span>
+                </div>
+                <div class="history-text">{&record.message}</div>
+            </div>
+            { for record.steps.iter().enumerate().map(|(i, step)| render_step(record, i, step)) }
+            if !record.files.is_empty() {
+                <div class="history-files">
+                    <span class="history-meta">{"Files sent:"}</span>
+                    { for record.files.iter().map(|f| file_chip(f)) }
+                </div>
+            }
+        </div>
+    }
+}
+
+fn render_transcript_entry(entry: &TranscriptEntry) -> Html {
+    let role = entry.entry_type.clone().unwrap_or_else(|| entry.role.clone());
+    let origin = match (&entry.channel, &entry.sender) {
+        (Some(c), Some(s)) => format!("{}/{}", c, s),
+        (Some(c), None) => c.clone(),
+        (None, Some(s)) => s.clone(),
+        (None, None) => String::new(),
+    };
+    html! {
+        <div class={classes!("queue-entry", format!("history-role-{}", role))}>
+            <div class="queue-entry-header">
+                <span class="history-meta">{format_time(entry.timestamp)}</span>
+                <span class="queue-agent">{format!("@{}", entry.agent_id)}</span>
+                <span class="queue-badge">{role}</span>
+                <span class="queue-sender">{origin}</span>
+            </div>
+            <div class="history-text">{&entry.content}</div>
+        </div>
+    }
+}
+
+// ─── Component ──────────────────────────────────────────────────────────────
+
+#[derive(Properties, PartialEq)]
+pub struct HistoryPageProps {
+    /// `(team_id, team_name)` pairs for the team filter, sorted
+    pub teams: Vec<(String, String)>,
+    /// Agent IDs for the transcript filter, sorted
+    pub agents: Vec<String>,
+}
+
+#[function_component(HistoryPage)]
+pub fn history_page(props: &HistoryPageProps) -> Html {
+    let mode = use_state(|| Mode::Conversations);
+    let input = use_state(String::new);
+    let search = use_state(String::new);
+    let filter = use_state(String::new);
+    let page = use_state(|| 1usize);
+    let conversations = use_state(|| None::<Page<ConversationSummary>>);
+    let transcripts = use_state(|| None::<Page<TranscriptEntry>>);
+    let selected = use_state(|| None::<(String, String)>);
+    let detail = use_state(|| None::<ChatRecord>);
+    let error = use_state(|| None::<String>);
+
+    {
+        let conversations = conversations.clone();
+        let transcripts = transcripts.clone();
+        let error = error.clone();
+        let deps = (*mode, (*search).clone(), (*filter).clone(), *page);
+        use_effect_with(deps, move |(mode, search, filter, page)| {
+            let (mode, search, filter, page) = (*mode, search.clone(), filter.clone(), *page);
+            spawn_local(async move {
+                let key = if mode == Mode::Conversations { "team" } else { "agent" };
+                let params = vec![
+                    (key, filter),
+                    ("q", search),
+                    ("page", page.to_string()),
+                    ("per_page", PER_PAGE.to_string()),
+                ];
+                let result = match mode {
+                    Mode::Conversations => fetch_json("/api/conversations", params)
+                        .await
+                        .map(|p| conversations.set(Some(p))),
+                    Mode::Transcripts => fetch_json("/api/transcripts", params)
+                        .await
+                        .map(|p| transcripts.set(Some(p))),
+                };
+                error.set(result.err());
+            });
+            || {}
+        });
+    }
+
+    {
+        let detail = detail.clone();
+        let error = error.clone();
+        use_effect_with((*selected).clone(), move |selected| {
+            detail.set(None);
+            if let Some((team_id, id)) = selected.clone() {
+                spawn_local(async move {
+                    let url = format!("/api/conversations/{}/{}", team_id, id);
+                    match fetch_json::<ChatRecord>(&url, Vec::new()).await {
+                        Ok(record) => detail.set(Some(record)),
+                        Err(e) => error.set(Some(e)),
+                    }
+                });
+            }
+            || {}
+        });
+    }
+
+    let set_mode = |m: Mode| {
+        let mode = mode.clone();
+        let filter = filter.clone();
+        let page = page.clone();
+        Callback::from(move |_: MouseEvent| {
+            mode.set(m);
+            filter.set(String::new());
+            page.set(1);
+        })
+    };
+    let on_input = {
+        let input = input.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: web_sys::HtmlInputElement = e.target_unchecked_into();
+            input.set(el.value());
+        })
+    };
+    let on_search = {
+        let input = input.clone();
+        let search = search.clone();
+        let page = page.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            search.set(input.trim().to_string());
+            page.set(1);
+        })
+    };
+    let on_filter = {
+        let filter = filter.clone();
+        let page = page.clone();
+        Callback::from(move |e: Event| {
+            let el: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            filter.set(el.value());
+            page.set(1);
+        })
+    };
+
+    let (total, page_count) = match *mode {
+        Mode::Conversations => conversations.as_ref().map(|p| (p.total, p.page_count())),
+        Mode::Transcripts => transcripts.as_ref().map(|p| (p.total, p.page_count())),
+    }
+    .unwrap_or((0, 1));
+    let go_to = |target: usize| {
+        let page = page.clone();
+        Callback::from(move |_: MouseEvent| page.set(target))
+    };
+
+    let filter_options = match *mode {
+        Mode::Conversations => html! {
+            <>
+                <option value="" selected={filter.is_empty()}>{"All teams"}</option>
+                { for props.teams.iter().map(|(id, name)| html! {
+                    <option value={id.clone()} selected={*filter == *id}>{format!("@{} {}", id, name)}</option>
+                })}
+            </>
+        },
+        Mode::Transcripts => html! {
+            <>
+                <option value="" selected={filter.is_empty()}>{"All agents"}</option>
+                { for props.agents.iter().map(|id| html! {
+                    <option value={id.clone()} selected={*filter == *id}>{format!("@{}", id)}</option>
+                })}
+            </>
+        },
+    };
+
+    html! {
+        <div class="history-page">
+            <div class="history-toolbar">
+                <button class={classes!("settings-button", (*mode == Mode::Conversations).then_some("history-mode-active"))}
+                    onclick={set_mode(Mode::Conversations)}>{"Conversations"}</button>
+                <button class={classes!("settings-button", (*mode == Mode::Transcripts).then_some("history-mode-active"))}
+                    onclick={set_mode(Mode::Transcripts)}>{"Transcripts"}</button>
+                <select class="send-agent-select" onchange={on_filter}>{filter_options}</select>
+                <form class="history-search" onsubmit={on_search}>
+                    <input class="send-input" placeholder="Search messages..."
+                        value={(*input).clone()} oninput={on_input} />
+                    <button type="submit" class="send-button">{"Search"}</button>
+                </form>
+            </div>
+            <div class="history-pager">
+                <button class="settings-button" disabled={*page <= 1} onclick={go_to(page.saturating_sub(1).max(1))}>
+                    {"\u{2039} Prev"}
+                </button>
+                <span class="history-meta">{format!("Page {} of {} \u{00B7} {} results", *page, page_count, total)}</span>
+                <button class="settings-button" disabled={*page >= page_count} onclick={go_to(*page + 1)}>
+                    {"Next \u{203A}"}
+                </button>
+            </div>
+            if let Some(err) = &*error {
+                <div class="settings-form-error">{err}</div>
+            }
+
+            if *mode == Mode::Conversations {
+                <div class="history-columns">
+                    <div class="queue-panel history-list">
+                        <h3>{"Conversations"}</h3>
+                        <hr class="divider" />
+                        <div class="queue-entries">
+                            { for conversations.iter().flat_map(|p| p.items.iter()).map(|c| {
+                                let key = (c.team_id.clone(), c.id.clone());
+                                let active = selected.as_ref() == Some(&key);
+                                let onclick = {
+                                    let selected = selected.clone();
+                                    Callback::from(move |_: MouseEvent| selected.set(Some(key.clone())))
+                                };
+                                html! {
+                                    <div class={classes!("queue-entry", "history-entry", active.then_some("history-entry-active"))} {onclick}>
+                                        <div class="queue-entry-header">
+                                            <span class="team-id">{format!("@{}", c.team_id)}</span>
+                                            <span class="history-meta">{format_date(&c.date)}</span>
+                                            <span class="queue-sender">{format!("{}/{}", c.channel, c.sender)}</span>
+                                        </div>
+                                        <div class="queue-preview">{&c.preview}</div>
+                                        <div class="history-meta">
+                                            {format!("{} steps \u{00B7} {}", c.steps,
+                                                c.agents.iter().map(|a| format!("@{}", a)).collect::<Vec<_>>().join(" "))}
+                                        </div>
+                                    </div>
+                                }
+                            })}
+                        </div>
+                    </div>
+                    <div class="history-detail">
+                        if let Some(record) = &*detail {
+                            { render_timeline(record) }
+                        } else {
+                            <div class="queue-empty-hint">{"Select a conversation to see its handoffs."}</div>
+                        }
+                    </div>
+                </div>
+            } else {
+                <div class="queue-panel">
+                    <h3>{"Transcripts"}</h3>
+                    <hr class="divider" />
+                    <div class="history-transcripts">
+                        { for transcripts.iter().flat_map(|p| p.items.iter()).map(render_transcript_entry) }
+                    </div>
+                </div>
+            }
+        </div>
+    }
+}
@@ -0,0 +1,98 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use gloo_net::http::Request;
+
+use crate::types::SessionInfo;
+
+/// POST credentials to `/api/login`. The server sets the session cookie.
+pub async fn login(body: serde_json::Value) -> Result<SessionInfo, String> {
+    let resp = Request::post("/api/login")
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if resp.ok() {
+        resp.json::<SessionInfo>().await.map_err(|e| format!("Parse error: {}", e))
+    } else if resp.status() == 401 {
+        Err("Invalid credentials".to_string())
+    } else {
+        Err(format!("HTTP {}", resp.status()))
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct LoginFormProps {
+    pub on_login: Callback<SessionInfo>,
+    #[prop_or_default]
+    pub error: Option<String>,
+}
+
+#[function_component(LoginForm)]
+pub fn login_form(props: &LoginFormProps) -> Html {
+    let username = use_state(String::new);
+    let password = use_state(String::new);
+    let token = use_state(String::new);
+    let busy = use_state(|| false);
+    let error = use_state(|| props.error.clone());
+
+    let text = |state: UseStateHandle<String>| {
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+            state.set(input.value());
+        })
+    };
+
+    let on_submit = {
+        let username = username.clone();
+        let password = password.clone();
+        let token = token.clone();
+        let busy = busy.clone();
+        let error = error.clone();
+        let on_login = props.on_login.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let body = if !token.trim().is_empty() {
+                serde_json::json!({ "token": token.trim() })
+            } else {
+                serde_json::json!({ "username": username.trim(), "password": *password })
+            };
+            busy.set(true);
+            let busy = busy.clone();
+            let error = error.clone();
+            let on_login = on_login.clone();
+            spawn_local(async move {
+                match login(body).await {
+                    Ok(session) => on_login.emit(session),
+                    Err(e) => error.set(Some(e)),
+                }
+                busy.set(false);
+            });
+        })
+    };
+
+    html! {
+        <div class="login-page">
+            <form class="settings-section login-form" onsubmit={on_submit}>
+                <h3>{"Rusty Claw — Log in"}</h3>
+                <hr class="divider" />
+                <div class="settings-section-body">
+                    <input class="send-input" placeholder="Username" autocomplete="username"
+                        value={(*username).clone()} oninput={text(username.clone())} />
+                    <input class="send-input" type="password" placeholder="Password" autocomplete="current-password"
+                        value={(*password).clone()} oninput={text(password.clone())} />
+                    <div class="login-or">{"or"}</div>
+                    <input class="send-input" type="password" placeholder="Admin token"
+                        value={(*token).clone()} oninput={text(token.clone())} />
+                    if let Some(err) = &*error {
+                        <div class="settings-form-error">{err}</div>
+                    }
+                    <button type="submit" class="send-button" disabled={*busy}>
+                        { if *busy { "Logging in..." } else { "Log in" } }
+                    </button>
+                </div>
+            </form>
+        </div>
+    }
+}
@@ -0,0 +1,310 @@
+use gloo_net::http::Request;
+use gloo_timers::callback::Interval;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::types::{HourBucket, HourCounts, LatencyStats, Metrics};
+
+/// Refresh interval for the metrics tab.
+const REFRESH_MS: u32 = 30_000;
+/// Selectable windows, in hours.
+const RANGES: [(u64, &str); 4] = [(6, "6h"), (24, "24h"), (72, "3d"), (168, "7d")];
+
+async fn fetch_metrics(hours: u64) -> Result<Metrics, String> {
+    let resp = Request::get("/api/metrics")
+        .query([("hours", hours.to_string())])
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if !resp.ok() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    resp.json::<Metrics>().await.map_err(|e| format!("Parse error: {}", e))
+}
+
+// ─── Formatting ─────────────────────────────────────────────────────────────
+
+fn format_ms(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m {}s", ms / 60_000, (ms % 60_000) / 1000)
+    }
+}
+
+/// Local `HH:MM`, with the day of month for hours that start a new day.
+fn format_hour(ms: u64) -> String {
+    let d = js_sys::Date::new(&JsValue::from_f64(ms as f64));
+    if d.get_hours() == 0 {
+        format!("{}/{}", d.get_month() + 1, d.get_date())
+    } else {
+        format!("{:02}:00", d.get_hours())
+    }
+}
+
+fn format_clock(ms: u64) -> String {
+    let d = js_sys::Date::new(&JsValue::from_f64(ms as f64));
+    format!(
+        "{}/{} {:02}:{:02}",
+        d.get_month() + 1,
+        d.get_date(),
+        d.get_hours(),
+        d.get_minutes()
+    )
+}
+
+// ─── Charts ─────────────────────────────────────────────────────────────────
+
+type Series = (&'static str, &'static str, fn(&HourCounts) -> u64);
+
+/// Grouped bar chart with one group per hour and one bar per series.
+fn bar_chart(title: &str, hours: &[HourBucket], series: &[Series]) -> Html {
+    const WIDTH: f64 = 720.0;
+    const HEIGHT: f64 = 160.0;
+    const AXIS: f64 = 18.0;
+
+    let max = hours
+        .iter()
+        .flat_map(|h| series.iter().map(|(_, _, value)| value(&h.counts)))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let group = WIDTH / hours.len().max(1) as f64;
+    let bar = (group * 0.8 / series.len() as f64).max(1.0);
+    // Label about eight hours across the axis
+    let label_every = hours.len().div_ceil(8).max(1);
+
+    html! {
+        <div class="metrics-chart">
+            <div class="metrics-chart-header">
+                <span class="metrics-chart-title">{title}</span>
+                { for series.iter().map(|(label, class, _)| html! {
+                    <span class={classes!("metrics-legend", *class)}>{*label}</span>
+                })}
+                <span class="metrics-chart-max">{format!("max {}", max)}</span>
+            </div>
+            <svg viewBox={format!("0 0 {} {}", WIDTH, HEIGHT + AXIS)} preserveAspectRatio="none" class="metrics-svg">
+                { for hours.iter().enumerate().map(|(i, h)| {
+                    let x0 = i as f64 * group + group * 0.1;
+                    html! {
+                        <g>
+                            { for series.iter().enumerate().map(|(j, (label, class, value))| {
+                                let v = value(&h.counts);
+                                let height = v as f64 / max as f64 * HEIGHT;
+                                html! {
+                                    <rect class={*class} x={(x0 + j as f64 * bar).to_string()} y={(HEIGHT - height).to_string()}
+                                        width={bar.to_string()} height={height.to_string()}>
+                                        <title>{format!("{} {}: {}", format_hour(h.start), label, v)}</title>
+                                    </rect>
+                                }
+                            })}
+                            if i % label_every == 0 {
+                                <text class="metrics-axis" x={x0.to_string()} y={(HEIGHT + AXIS - 4.0).to_string()}>
+                                    {format_hour(h.start)}
+                                </text>
+                            }
+                        </g>
+                    }
+                })}
+            </svg>
+        </div>
+    }
+}
+
+/// Table of p50/p95 latency with bars scaled to the slowest p95.
+fn latency_table(title: &str, stats: &[LatencyStats]) -> Html {
+    let max = stats.iter().map(|s| s.p95_ms).max().unwrap_or(0).max(1);
+    let width = |ms: u64| format!("width: {:.1}%", ms as f64 / max as f64 * 100.0);
+    html! {
+        <div class="settings-section">
+            <h3>{title}</h3>
+            <hr class="divider" />
+            if stats.is_empty() {
+                <div class="queue-empty-hint">{"No data in this window."}</div>
+            } else {
+                <table class="settings-table metrics-table">
+                    <thead>
+                        <tr><th>{""}</th><th>{"Runs"}</th><th>{"Failed"}</th><th>{"p50"}</th><th>{"p95"}</th><th class="metrics-bar-col">{""}</th></tr>
+                    </thead>
+                    <tbody>
+                        { for stats.iter().map(|s| html! {
+                            <tr>
+                                <td class="metrics-key">{&s.key}</td>
+                                <td>{s.count}</td>
+                                <td class={classes!((s.failures > 0).then_some("metrics-bad"))}>{s.failures}</td>
+                                <td>{format_ms(s.p50_ms)}</td>
+                                <td>{format_ms(s.p95_ms)}</td>
+                                <td class="metrics-bar-col">
+                                    <div class="metrics-bar">
+                                        <div class="metrics-bar-p95" style={width(s.p95_ms)}></div>
+                                        <div class="metrics-bar-p50" style={width(s.p50_ms)}></div>
+                                    </div>
+                                </td>
+                            </tr>
+                        })}
+                    </tbody>
+                </table>
+            }
+        </div>
+    }
+}
+
+fn counts_list(title: &str, counts: &std::collections::HashMap<String, u64>) -> Html {
+    let mut rows: Vec<(&String, &u64)> = counts.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    html! {
+        <div class="settings-section">
+            <h3>{title}</h3>
+            <hr class="divider" />
+            if rows.is_empty() {
+                <div class="queue-empty-hint">{"None in this window."}</div>
+            } else {
+                <table class="settings-table metrics-table">
+                    <tbody>
+                        { for rows.iter().map(|(key, count)| html! {
+                            <tr><td class="metrics-key">{*key}</td><td>{**count}</td></tr>
+                        })}
+                    </tbody>
+                </table>
+            }
+        </div>
+    }
+}
+
+// ─── Page ───────────────────────────────────────────────────────────────────
+
+#[function_component(MetricsPage)]
+pub fn metrics_page() -> Html {
+    let hours = use_state(|| 24u64);
+    let metrics = use_state(|| None::<Metrics>);
+    let error = use_state(|| None::<String>);
+
+    {
+        let metrics = metrics.clone();
+        let error = error.clone();
+        use_effect_with(*hours, move |hours| {
+            let hours = *hours;
+            let load = move || {
+                let metrics = metrics.clone();
+                let error = error.clone();
+                spawn_local(async move {
+                    match fetch_metrics(hours).await {
+                        Ok(m) => {
+                            metrics.set(Some(m));
+                            error.set(None);
+                        }
+                        Err(e) => error.set(Some(e)),
+                    }
+                });
+            };
+            load();
+            let interval = Interval::new(REFRESH_MS, load);
+            move || drop(interval)
+        });
+    }
+
+    let range_buttons = RANGES.iter().map(|(h, label)| {
+        let hours_state = hours.clone();
+        let h = *h;
+        let onclick = Callback::from(move |_: MouseEvent| hours_state.set(h));
+        html! {
+            <button class={classes!("settings-button", (*hours == h).then_some("metrics-range-active"))} {onclick}>
+                {*label}
+            </button>
+        }
+    });
+
+    let Some(m) = &*metrics else {
+        return html! {
+            <div class="metrics-page">
+                <div class="queue-empty-hint">
+                    { error.as_deref().unwrap_or("Loading metrics...") }
+                </div>
+            </div>
+        };
+    };
+    let now = js_sys::Date::now() as u64;
+    let t = &m.totals;
+    let stat = |label: &str, value: u64, bad: bool| html! {
+        <div class="metrics-stat">
+            <span class={classes!("metrics-stat-value", (bad && value > 0).then_some("metrics-bad"))}>{value}</span>
+            <span class="metrics-stat-label">{label.to_string()}</span>
+        </div>
+    };
+
+    html! {
+        <div class="metrics-page">
+            <div class="metrics-toolbar">
+                { for range_buttons }
+                if let Some(err) = &*error {
+                    <span class="settings-form-error">{err}</span>
+                }
+            </div>
+
+            <div class="metrics-stats">
+                {stat("received", t.received, false)}
+                {stat("responded", t.responded, false)}
+                {stat("per hour", t.received / m.hours.len().max(1) as u64, false)}
+                {stat("failed steps", t.failures, true)}
+                {stat("model errors", t.model_errors, true)}
+                {stat("failovers", t.failovers, true)}
+                {stat("compactions", t.compactions, false)}
+            </div>
+
+            {bar_chart("Throughput per hour", &m.hours, &[
+                ("received", "series-cyan", |c| c.received),
+                ("responded", "series-green", |c| c.responded),
+            ])}
+            {bar_chart("Failures per hour", &m.hours, &[
+                ("failed steps", "series-red", |c| c.failures),
+                ("model errors", "series-yellow", |c| c.model_errors),
+                ("failovers", "series-magenta", |c| c.failovers),
+            ])}
+
+            {latency_table("Step latency by agent", &m.agents)}
+            {latency_table("Invocation latency by model", &m.models)}
+
+            <div class="metrics-columns">
+                {counts_list("Model errors by reason", &m.failures_by_reason)}
+                {counts_list("Compactions by agent", &m.compactions_by_agent)}
+            </div>
+
+            <div class="settings-section">
+                <h3>{"Cooldowns"}</h3>
+                <hr class="divider" />
+                if m.active_cooldowns.is_empty() && m.cooldowns.is_empty() {
+                    <div class="queue-empty-hint">{"No cooldowns in this window."}</div>
+                } else {
+                    <table class="settings-table metrics-table">
+                        <thead>
+                            <tr><th>{"Model"}</th><th>{"Agent"}</th><th>{"Reason"}</th><th>{"From"}</th><th>{"Until"}</th></tr>
+                        </thead>
+                        <tbody>
+                            { for m.active_cooldowns.iter().map(|c| html! {
+                                <tr class="metrics-active">
+                                    <td class="metrics-key">{&c.key}</td>
+                                    <td>{""}</td>
+                                    <td>{format!("{} error(s) in a row", c.error_count)}</td>
+                                    <td>{"active"}</td>
+                                    <td>{format!("{} ({} left)", format_clock(c.until), format_ms(c.until.saturating_sub(now)))}</td>
+                                </tr>
+                            })}
+                            { for m.cooldowns.iter().rev().map(|c| html! {
+                                <tr>
+                                    <td class="metrics-key">{&c.key}</td>
+                                    <td>{format!("@{}", c.agent_id)}</td>
+                                    <td>{&c.reason}</td>
+                                    <td>{format_clock(c.started)}</td>
+                                    <td>{format_clock(c.until)}</td>
+                                </tr>
+                            })}
+                        </tbody>
+                    </table>
+                }
+            </div>
+        </div>
+    }
+}
@@ -4,6 +4,12 @@ pub mod chain_flow;
 pub mod activity_log;
 pub mod status_bar;
 pub mod queue_panel;
+pub mod history_page;
+pub mod chat_page;
+pub mod metrics_page;
 pub mod send_form;
 pub mod settings_page;
+pub mod agent_editor;
+pub mod team_editor;
 pub mod kanban_board;
+pub mod login;
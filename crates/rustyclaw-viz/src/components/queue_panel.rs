@@ -1,22 +1,69 @@
+use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
+use gloo_net::http::{Request, RequestBuilder};
 
 use crate::types::QueuedMessage;
 
+/// Send a queue action; on failure returns the server's error message.
+async fn queue_action(request: RequestBuilder, body: Option<serde_json::Value>) -> Result<(), String> {
+    let request = match body {
+        Some(body) => request
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .map_err(|e| e.to_string())?,
+        None => request.build().map_err(|e| e.to_string())?,
+    };
+    let resp = request.send().await.map_err(|e| format!("Network error: {}", e))?;
+    if resp.ok() {
+        return Ok(());
+    }
+    let status = resp.status();
+    let message = resp
+        .json::<serde_json::Value>()
+        .await
+        .ok()
+        .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(str::to_string));
+    Err(message.unwrap_or_else(|| format!("HTTP {}", status)))
+}
+
 #[derive(Properties, PartialEq)]
 pub struct QueuePanelProps {
     pub messages: Vec<QueuedMessage>,
+    /// Agent IDs offered as re-route targets
+    #[prop_or_default]
+    pub agents: Vec<String>,
+    #[prop_or_default]
+    pub can_edit: bool,
 }
 
 #[function_component(QueuePanel)]
 pub fn queue_panel(props: &QueuePanelProps) -> Html {
+    let error = use_state(|| None::<String>);
+
     if props.messages.is_empty() {
         return html! {};
     }
 
+    let run = {
+        let error = error.clone();
+        move |request: RequestBuilder, body: Option<serde_json::Value>| {
+            let error = error.clone();
+            spawn_local(async move {
+                error.set(queue_action(request, body).await.err());
+            });
+        }
+    };
+
+    let mut agents = props.agents.clone();
+    agents.sort();
+
     html! {
         <div class="queue-panel">
             <h3>{"Queue"}</h3>
             <hr class="divider" />
+            if let Some(err) = &*error {
+                <div class="settings-form-error queue-action-error">{err}</div>
+            }
             <div class="queue-entries">
                 { for props.messages.iter().map(|msg| {
                     let status_class = format!("queue-status-{}", msg.status);
@@ -30,6 +77,42 @@ pub fn queue_panel(props: &QueuePanelProps) -> Html {
                     } else {
                         msg.message.clone()
                     };
+                    let incoming = msg.status != "processing";
+                    let base = format!("/api/queue/{}/{}", status_label, msg.file);
+
+                    let on_delete = {
+                        let run = run.clone();
+                        let url = base.clone();
+                        Callback::from(move |_: MouseEvent| {
+                            let confirmed = web_sys::window()
+                                .and_then(|w| w.confirm_with_message("Delete this message?").ok())
+                                .unwrap_or(false);
+                            if confirmed {
+                                run(Request::delete(&url), None);
+                            }
+                        })
+                    };
+                    let on_bump = {
+                        let run = run.clone();
+                        let url = format!("{}/bump", base);
+                        Callback::from(move |_: MouseEvent| run(Request::post(&url), None))
+                    };
+                    let on_requeue = {
+                        let run = run.clone();
+                        let url = format!("{}/requeue", base);
+                        Callback::from(move |_: MouseEvent| run(Request::post(&url), None))
+                    };
+                    let on_route = {
+                        let run = run.clone();
+                        let url = format!("{}/route", base);
+                        Callback::from(move |e: Event| {
+                            let select: web_sys::HtmlSelectElement = e.target_unchecked_into();
+                            let agent = select.value();
+                            if !agent.is_empty() {
+                                run(Request::post(&url), Some(serde_json::json!({ "agent": agent })));
+                            }
+                        })
+                    };
 
                     html! {
                         <div class={classes!("queue-entry", status_class.clone())}>
@@ -37,6 +120,9 @@ pub fn queue_panel(props: &QueuePanelProps) -> Html {
                                 <span class={classes!("queue-badge", status_class)}>
                                     {status_label}
                                 </span>
+                                if msg.priority != 0 {
+                                    <span class="queue-priority">{format!("P{}", msg.priority)}</span>
+                                }
                                 <span class="queue-sender">
                                     {format!("{}/{}", msg.channel, msg.sender)}
                                 </span>
@@ -45,6 +131,22 @@ pub fn queue_panel(props: &QueuePanelProps) -> Html {
                                 </span>
                             </div>
                             <div class="queue-preview">{preview}</div>
+                            if props.can_edit && !msg.file.is_empty() {
+                                <div class="queue-actions">
+                                    if incoming {
+                                        <select class="send-agent-select" onchange={on_route}>
+                                            <option value="" selected=true>{"Route to..."}</option>
+                                            { for agents.iter().map(|id| html! {
+                                                <option value={id.clone()}>{format!("@{}", id)}</option>
+                                            })}
+                                        </select>
+                                        <button class="settings-button" onclick={on_bump}>{"Bump"}</button>
+                                    } else {
+                                        <button class="settings-button" onclick={on_requeue}>{"Requeue"}</button>
+                                    }
+                                    <button class="settings-button danger" onclick={on_delete}>{"Delete"}</button>
+                                </div>
+                            }
                         </div>
                     }
                 })}
@@ -4,6 +4,7 @@ use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 use gloo_net::http::Request;
 
+use crate::app::web_client_id;
 use crate::types::VizAgentConfig;
 
 #[derive(Properties, PartialEq)]
@@ -62,6 +63,7 @@ pub fn send_form(props: &SendFormProps) -> Html {
                 let body = serde_json::json!({
                     "message": msg_text,
                     "agent": agent_opt,
+                    "client": web_client_id(),
                 });
                 let result = Request::post("/api/send")
                     .header("Content-Type", "application/json")
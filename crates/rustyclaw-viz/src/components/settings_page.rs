@@ -1,35 +1,162 @@
+use std::collections::HashMap;
+
+use gloo_net::http::Request;
+use serde_json::Value;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
-use gloo_net::http::Request;
 
+use crate::components::agent_editor::AgentEditor;
+use crate::components::team_editor::TeamEditor;
 use crate::types::{
-    FullSettings, FullAgentConfig, FullChannelsConfig, FullModelsConfig,
-    FullMonitoringConfig, FullTeamConfig, FullWorkspaceConfig,
+    FullAgentConfig, FullChannelsConfig, FullModelsConfig, FullMonitoringConfig,
+    FullProviderModelConfig, FullSettings, FullSkillOverride, FullTeamConfig, FullWorkspaceConfig,
 };
 
+const CHANNELS: &[&str] = &["discord", "telegram", "slack", "matrix", "email", "http"];
+
+// ─── Loading and saving ─────────────────────────────────────────────────────
+
+/// Settings as loaded: the raw JSON (so sections this page doesn't model
+/// survive a save), the typed view, and the revision for `If-Match`.
+#[derive(Clone, PartialEq)]
+struct Loaded {
+    raw: Value,
+    settings: FullSettings,
+    revision: String,
+}
+
+/// One change from the page, applied to the raw JSON before saving.
+#[derive(Clone)]
+enum SettingsEdit {
+    Agent(String, Option<FullAgentConfig>),
+    Team(String, Option<FullTeamConfig>),
+    Models(FullModelsConfig),
+    EnabledChannels(Vec<String>),
+    Skill(String, Option<FullSkillOverride>),
+}
+
+#[derive(Clone, PartialEq)]
+enum Editing {
+    Agent(Option<String>),
+    Team(Option<String>),
+}
+
+async fn load_settings() -> Result<Loaded, String> {
+    let resp = Request::get("/api/settings/full")
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if !resp.ok() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let revision = resp.headers().get("etag").unwrap_or_default();
+    let raw: Value = resp.json().await.map_err(|e| format!("Parse error: {}", e))?;
+    let settings = serde_json::from_value(raw.clone()).map_err(|e| format!("Parse error: {}", e))?;
+    Ok(Loaded { raw, settings, revision })
+}
+
+fn set_entry(raw: &mut Value, section: &str, id: &str, value: Option<Value>) {
+    if !raw[section].is_object() {
+        raw[section] = Value::Object(Default::default());
+    }
+    let map = raw[section].as_object_mut().expect("section is an object");
+    match value {
+        Some(v) => {
+            map.insert(id.to_string(), v);
+        }
+        None => {
+            map.remove(id);
+        }
+    }
+}
+
+fn apply_edit(raw: &mut Value, edit: SettingsEdit) {
+    let to_value = |v: Option<Value>| v.unwrap_or(Value::Null);
+    match edit {
+        SettingsEdit::Agent(id, agent) => {
+            set_entry(raw, "agents", &id, agent.map(|a| to_value(serde_json::to_value(a).ok())));
+        }
+        SettingsEdit::Team(id, team) => {
+            set_entry(raw, "teams", &id, team.map(|t| to_value(serde_json::to_value(t).ok())));
+        }
+        SettingsEdit::Models(models) => {
+            raw["models"] = to_value(serde_json::to_value(models).ok());
+        }
+        SettingsEdit::EnabledChannels(enabled) => {
+            raw["channels"]["enabled"] = to_value(serde_json::to_value(enabled).ok());
+        }
+        SettingsEdit::Skill(name, skill) => {
+            set_entry(raw, "skills", &name, skill.map(|s| to_value(serde_json::to_value(s).ok())));
+        }
+    }
+}
+
+enum SaveError {
+    /// Someone else saved first; reload and retry
+    Conflict,
+    Invalid(Vec<String>),
+    Other(String),
+}
+
+async fn save_settings(raw: &Value, revision: &str) -> Result<(), SaveError> {
+    let resp = Request::put("/api/settings/full")
+        .header("Content-Type", "application/json")
+        .header("If-Match", revision)
+        .body(raw.to_string())
+        .map_err(|e| SaveError::Other(e.to_string()))?
+        .send()
+        .await
+        .map_err(|e| SaveError::Other(format!("Network error: {}", e)))?;
+    if resp.ok() {
+        return Ok(());
+    }
+    let status = resp.status();
+    let body: Value = resp.json().await.unwrap_or_default();
+    match status {
+        412 => Err(SaveError::Conflict),
+        422 => Err(SaveError::Invalid(
+            body["errors"]
+                .as_array()
+                .map(|errs| errs.iter().filter_map(|e| e.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+        )),
+        _ => Err(SaveError::Other(
+            body["error"].as_str().map(String::from).unwrap_or_else(|| format!("HTTP {}", status)),
+        )),
+    }
+}
+
 // ─── Main settings page ─────────────────────────────────────────────────────
 
+#[derive(Properties, PartialEq)]
+pub struct SettingsPageProps {
+    /// Operators get the edit controls; viewers see settings read-only
+    pub can_edit: bool,
+}
+
 #[function_component(SettingsPage)]
-pub fn settings_page() -> Html {
-    let settings = use_state(|| None::<FullSettings>);
+pub fn settings_page(props: &SettingsPageProps) -> Html {
+    let loaded = use_state(|| None::<Loaded>);
     let loading = use_state(|| true);
     let error = use_state(|| None::<String>);
+    let reload = use_state(|| 0u32);
+    let saving = use_state(|| false);
+    let status = use_state(|| None::<String>);
+    let save_errors = use_state(Vec::<String>::new);
+    let editing = use_state(|| None::<Editing>);
 
     {
-        let settings = settings.clone();
+        let loaded = loaded.clone();
         let loading = loading.clone();
         let error = error.clone();
-        use_effect_with((), move |_| {
+        use_effect_with(*reload, move |_| {
             spawn_local(async move {
-                match Request::get("/api/settings/full").send().await {
-                    Ok(resp) if resp.ok() => {
-                        match resp.json::<FullSettings>().await {
-                            Ok(s) => settings.set(Some(s)),
-                            Err(e) => error.set(Some(format!("Parse error: {}", e))),
-                        }
+                match load_settings().await {
+                    Ok(l) => {
+                        loaded.set(Some(l));
+                        error.set(None);
                     }
-                    Ok(resp) => error.set(Some(format!("HTTP {}", resp.status()))),
-                    Err(e) => error.set(Some(format!("Network error: {}", e))),
+                    Err(e) => error.set(Some(e)),
                 }
                 loading.set(false);
             });
@@ -37,24 +164,87 @@ pub fn settings_page() -> Html {
         });
     }
 
+    let on_edit = {
+        let loaded = loaded.clone();
+        let reload = reload.clone();
+        let saving = saving.clone();
+        let status = status.clone();
+        let save_errors = save_errors.clone();
+        let editing = editing.clone();
+        Callback::from(move |edit: SettingsEdit| {
+            let Some(current) = (*loaded).clone() else {
+                return;
+            };
+            let mut raw = current.raw.clone();
+            apply_edit(&mut raw, edit);
+            saving.set(true);
+            status.set(None);
+            let reload = reload.clone();
+            let saving = saving.clone();
+            let status = status.clone();
+            let save_errors = save_errors.clone();
+            let editing = editing.clone();
+            spawn_local(async move {
+                match save_settings(&raw, &current.revision).await {
+                    Ok(()) => {
+                        save_errors.set(Vec::new());
+                        status.set(Some("Saved".to_string()));
+                        editing.set(None);
+                        reload.set(*reload + 1);
+                    }
+                    Err(SaveError::Conflict) => {
+                        save_errors.set(vec![
+                            "Settings were changed elsewhere; reloaded the latest version. Apply your change again."
+                                .to_string(),
+                        ]);
+                        reload.set(*reload + 1);
+                    }
+                    Err(SaveError::Invalid(errors)) => save_errors.set(errors),
+                    Err(SaveError::Other(e)) => save_errors.set(vec![e]),
+                }
+                saving.set(false);
+            });
+        })
+    };
+
     if *loading {
         return html! { <div class="settings-loading">{"Loading settings..."}</div> };
     }
     if let Some(ref err) = *error {
         return html! { <div class="settings-error">{err}</div> };
     }
-    let s = match &*settings {
-        Some(s) => s,
+    let s = match &*loaded {
+        Some(l) => &l.settings,
         None => return html! { <div class="settings-empty">{"No settings loaded"}</div> },
     };
 
+    let set_editing = {
+        let editing = editing.clone();
+        let save_errors = save_errors.clone();
+        Callback::from(move |e: Option<Editing>| {
+            save_errors.set(Vec::new());
+            editing.set(e);
+        })
+    };
+    let workspace = s.workspace.as_ref().and_then(|w| w.path.clone());
+    let can_edit = props.can_edit;
+
     html! {
         <div class="settings-page">
+            if !save_errors.is_empty() {
+                <div class="settings-section settings-save-errors">
+                    <ul>{ for save_errors.iter().map(|e| html! { <li>{e}</li> }) }</ul>
+                </div>
+            }
+            if let Some(msg) = &*status {
+                <div class="send-feedback">{msg}</div>
+            }
             {render_workspace(&s.workspace)}
-            {render_models(&s.models)}
-            {render_channels(&s.channels)}
-            {render_agents(&s.agents)}
-            {render_teams(&s.teams)}
+            <ModelsEditor models={s.models.clone().unwrap_or_default()} saving={*saving} {can_edit} on_edit={on_edit.clone()} />
+            {render_channels(&s.channels, can_edit, &on_edit)}
+            {render_agents(s, can_edit, &editing, &set_editing, &on_edit, workspace, *saving)}
+            {render_teams(s, can_edit, &editing, &set_editing, &on_edit, *saving)}
+            <SkillsEditor skills={s.skills.clone().unwrap_or_default()} {can_edit} on_edit={on_edit.clone()} />
             {render_monitoring(&s.monitoring)}
         </div>
     }
@@ -114,156 +304,448 @@ fn render_workspace(ws: &Option<FullWorkspaceConfig>) -> Html {
     render_section("Workspace", body)
 }
 
-fn render_models(models: &Option<FullModelsConfig>) -> Html {
-    let body = match models {
-        Some(m) => {
-            let (prov, pc) = opt_display(&m.provider, "auto-detect");
-            let anthropic = m.anthropic.as_ref()
-                .and_then(|a| a.model.clone())
-                .unwrap_or_default();
-            let openai = m.openai.as_ref()
-                .and_then(|a| a.model.clone())
-                .unwrap_or_default();
-            let opencode = m.opencode.as_ref()
-                .and_then(|a| a.model.clone())
-                .unwrap_or_default();
+fn render_channels(channels: &Option<FullChannelsConfig>, can_edit: bool, on_edit: &Callback<SettingsEdit>) -> Html {
+    let enabled = channels.as_ref().and_then(|c| c.enabled.clone()).unwrap_or_default();
+    let discord_token = channels.as_ref()
+        .and_then(|c| c.discord.as_ref())
+        .and_then(|d| d.bot_token.clone())
+        .unwrap_or_default();
+    let telegram_token = channels.as_ref()
+        .and_then(|c| c.telegram.as_ref())
+        .and_then(|t| t.bot_token.clone())
+        .unwrap_or_default();
+    let body = html! {
+        <>
+            <div class="settings-field">
+                <span class="settings-field-label">{"Enabled"}</span>
+                <div class="settings-checklist">
+                    { for CHANNELS.iter().map(|channel| {
+                        let checked = enabled.iter().any(|c| c == channel);
+                        let onchange = {
+                            let enabled = enabled.clone();
+                            let on_edit = on_edit.clone();
+                            Callback::from(move |_: Event| {
+                                let mut next: Vec<String> =
+                                    enabled.iter().filter(|c| *c != channel).cloned().collect();
+                                if !checked {
+                                    next.push(channel.to_string());
+                                }
+                                on_edit.emit(SettingsEdit::EnabledChannels(next));
+                            })
+                        };
+                        html! {
+                            <label><input type="checkbox" {checked} {onchange} disabled={!can_edit} />{*channel}</label>
+                        }
+                    })}
+                </div>
+            </div>
+            <div class="settings-subsection">
+                <h4>{"Discord"}</h4>
+                if discord_token.is_empty() {
+                    {render_field("Bot token", "not set", "empty")}
+                } else {
+                    {render_field("Bot token", &discord_token, "masked")}
+                }
+            </div>
+            <div class="settings-subsection">
+                <h4>{"Telegram"}</h4>
+                if telegram_token.is_empty() {
+                    {render_field("Bot token", "not set", "empty")}
+                } else {
+                    {render_field("Bot token", &telegram_token, "masked")}
+                }
+            </div>
+        </>
+    };
+    render_section("Channels", body)
+}
+
+fn render_agents(
+    s: &FullSettings,
+    can_edit: bool,
+    editing: &Option<Editing>,
+    set_editing: &Callback<Option<Editing>>,
+    on_edit: &Callback<SettingsEdit>,
+    workspace: Option<String>,
+    saving: bool,
+) -> Html {
+    let empty = HashMap::new();
+    let map = s.agents.as_ref().unwrap_or(&empty);
+    let mut ids: Vec<&String> = map.keys().collect();
+    ids.sort();
+
+    let editor = match editing {
+        Some(Editing::Agent(agent_id)) => {
+            let on_save = {
+                let on_edit = on_edit.clone();
+                Callback::from(move |(id, agent): (String, FullAgentConfig)| {
+                    on_edit.emit(SettingsEdit::Agent(id, Some(agent)))
+                })
+            };
+            let on_cancel = {
+                let set_editing = set_editing.clone();
+                Callback::from(move |_| set_editing.emit(None))
+            };
             html! {
-                <>
-                    {render_field("Provider", &prov, pc)}
-                    if !anthropic.is_empty() {
-                        {render_field("Anthropic model", &anthropic, "")}
-                    }
-                    if !openai.is_empty() {
-                        {render_field("OpenAI model", &openai, "")}
-                    }
-                    if !opencode.is_empty() {
-                        {render_field("OpenCode model", &opencode, "")}
-                    }
-                </>
+                <AgentEditor
+                    key={agent_id.clone().unwrap_or_default()}
+                    agent_id={agent_id.clone()}
+                    agent={agent_id.as_ref().and_then(|id| map.get(id).cloned())}
+                    {workspace}
+                    {saving}
+                    {on_save}
+                    {on_cancel}
+                />
             }
         }
-        None => html! { {render_field("Status", "not configured", "empty")} },
+        _ if !can_edit => html! {},
+        _ => {
+            let set_editing = set_editing.clone();
+            let onclick = Callback::from(move |_: MouseEvent| set_editing.emit(Some(Editing::Agent(None))));
+            html! { <button class="settings-button" {onclick}>{"Add agent"}</button> }
+        }
     };
-    render_section("Models", body)
-}
 
-fn render_channels(channels: &Option<FullChannelsConfig>) -> Html {
-    let body = match channels {
-        Some(ch) => {
-            let enabled = ch.enabled.as_ref()
-                .map(|v| v.join(", "))
-                .unwrap_or_default();
-            let discord_token = ch.discord.as_ref()
-                .and_then(|d| d.bot_token.clone())
-                .unwrap_or_default();
-            let telegram_token = ch.telegram.as_ref()
-                .and_then(|t| t.bot_token.clone())
-                .unwrap_or_default();
-            html! {
-                <>
-                    if !enabled.is_empty() {
-                        {render_field("Enabled", &enabled, "")}
-                    }
-                    <div class="settings-subsection">
-                        <h4>{"Discord"}</h4>
-                        if discord_token.is_empty() {
-                            {render_field("Bot token", "not set", "empty")}
-                        } else {
-                            {render_field("Bot token", &discord_token, "masked")}
-                        }
-                    </div>
-                    <div class="settings-subsection">
-                        <h4>{"Telegram"}</h4>
-                        if telegram_token.is_empty() {
-                            {render_field("Bot token", "not set", "empty")}
-                        } else {
-                            {render_field("Bot token", &telegram_token, "masked")}
+    let table = if ids.is_empty() {
+        html! { <span class="settings-field-value empty">{"No agents configured"}</span> }
+    } else {
+        html! {
+            <table class="settings-table">
+                <thead>
+                    <tr>
+                        <th>{"ID"}</th>
+                        <th>{"Name"}</th>
+                        <th>{"Provider"}</th>
+                        <th>{"Model"}</th>
+                        <th>{"Reset"}</th>
+                        <th>{"Routes"}</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for ids.iter().map(|id| {
+                        let a = &map[*id];
+                        let model = match &a.fallbacks {
+                            Some(f) if !f.is_empty() => format!("{} → {}", a.model, f.join(" → ")),
+                            _ => a.model.clone(),
+                        };
+                        let on_edit_click = {
+                            let set_editing = set_editing.clone();
+                            let id = (*id).clone();
+                            Callback::from(move |_: MouseEvent| set_editing.emit(Some(Editing::Agent(Some(id.clone())))))
+                        };
+                        let on_delete = {
+                            let on_edit = on_edit.clone();
+                            let id = (*id).clone();
+                            Callback::from(move |_: MouseEvent| {
+                                let confirmed = web_sys::window()
+                                    .and_then(|w| w.confirm_with_message(&format!("Remove agent @{}?", id)).ok())
+                                    .unwrap_or(false);
+                                if confirmed {
+                                    on_edit.emit(SettingsEdit::Agent(id.clone(), None));
+                                }
+                            })
+                        };
+                        html! {
+                            <tr>
+                                <td>{id}</td>
+                                <td>{&a.name}</td>
+                                <td>{&a.provider}</td>
+                                <td>{model}</td>
+                                <td>{a.reset_policy.clone().unwrap_or_else(|| "both".to_string())}</td>
+                                <td>{a.route_patterns.clone().unwrap_or_default().join(", ")}</td>
+                                <td class="settings-row-actions">
+                                    if can_edit {
+                                        <button class="settings-button" onclick={on_edit_click}>{"Edit"}</button>
+                                        <button class="settings-button danger" onclick={on_delete}>{"Remove"}</button>
+                                    }
+                                </td>
+                            </tr>
                         }
-                    </div>
-                </>
-            }
+                    })}
+                </tbody>
+            </table>
         }
-        None => html! { {render_field("Status", "not configured", "empty")} },
     };
-    render_section("Channels", body)
+
+    render_section("Agents", html! { <>{table}{editor}</> })
 }
 
-fn render_agents(agents: &Option<std::collections::HashMap<String, FullAgentConfig>>) -> Html {
-    let body = match agents {
-        Some(map) if !map.is_empty() => {
-            let mut ids: Vec<&String> = map.keys().collect();
-            ids.sort();
+fn render_teams(
+    s: &FullSettings,
+    can_edit: bool,
+    editing: &Option<Editing>,
+    set_editing: &Callback<Option<Editing>>,
+    on_edit: &Callback<SettingsEdit>,
+    saving: bool,
+) -> Html {
+    let empty = HashMap::new();
+    let map = s.teams.as_ref().unwrap_or(&empty);
+    let mut ids: Vec<&String> = map.keys().collect();
+    ids.sort();
+    let mut agent_ids: Vec<String> = s.agents.as_ref().map(|a| a.keys().cloned().collect()).unwrap_or_default();
+    agent_ids.sort();
+
+    let editor = match editing {
+        Some(Editing::Team(team_id)) => {
+            let on_save = {
+                let on_edit = on_edit.clone();
+                Callback::from(move |(id, team): (String, FullTeamConfig)| {
+                    on_edit.emit(SettingsEdit::Team(id, Some(team)))
+                })
+            };
+            let on_cancel = {
+                let set_editing = set_editing.clone();
+                Callback::from(move |_| set_editing.emit(None))
+            };
             html! {
-                <table class="settings-table">
-                    <thead>
-                        <tr>
-                            <th>{"ID"}</th>
-                            <th>{"Name"}</th>
-                            <th>{"Provider"}</th>
-                            <th>{"Model"}</th>
-                            <th>{"Working Dir"}</th>
-                        </tr>
-                    </thead>
-                    <tbody>
-                        { for ids.iter().map(|id| {
-                            let a = &map[*id];
-                            html! {
-                                <tr>
-                                    <td>{id}</td>
-                                    <td>{&a.name}</td>
-                                    <td>{&a.provider}</td>
-                                    <td>{&a.model}</td>
-                                    <td>{&a.working_directory}</td>
-                                </tr>
-                            }
-                        })}
-                    </tbody>
-                </table>
+                <TeamEditor
+                    key={team_id.clone().unwrap_or_default()}
+                    team_id={team_id.clone()}
+                    team={team_id.as_ref().and_then(|id| map.get(id).cloned())}
+                    {agent_ids}
+                    {saving}
+                    {on_save}
+                    {on_cancel}
+                />
             }
         }
-        _ => html! { <span class="settings-field-value empty">{"No agents configured"}</span> },
+        _ if !can_edit => html! {},
+        _ => {
+            let set_editing = set_editing.clone();
+            let onclick = Callback::from(move |_: MouseEvent| set_editing.emit(Some(Editing::Team(None))));
+            html! { <button class="settings-button" {onclick}>{"Add team"}</button> }
+        }
     };
-    render_section("Agents", body)
+
+    let table = if ids.is_empty() {
+        html! { <span class="settings-field-value empty">{"No teams configured"}</span> }
+    } else {
+        html! {
+            <table class="settings-table">
+                <thead>
+                    <tr>
+                        <th>{"ID"}</th>
+                        <th>{"Name"}</th>
+                        <th>{"Agents"}</th>
+                        <th>{"Leader"}</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for ids.iter().map(|id| {
+                        let t = &map[*id];
+                        let agents_str = t.agents.iter()
+                            .map(|a| format!("@{}", a))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let on_edit_click = {
+                            let set_editing = set_editing.clone();
+                            let id = (*id).clone();
+                            Callback::from(move |_: MouseEvent| set_editing.emit(Some(Editing::Team(Some(id.clone())))))
+                        };
+                        let on_delete = {
+                            let on_edit = on_edit.clone();
+                            let id = (*id).clone();
+                            Callback::from(move |_: MouseEvent| {
+                                let confirmed = web_sys::window()
+                                    .and_then(|w| w.confirm_with_message(&format!("Remove team @{}?", id)).ok())
+                                    .unwrap_or(false);
+                                if confirmed {
+                                    on_edit.emit(SettingsEdit::Team(id.clone(), None));
+                                }
+                            })
+                        };
+                        html! {
+                            <tr>
+                                <td>{id}</td>
+                                <td>{&t.name}</td>
+                                <td>{agents_str}</td>
+                                <td>{format!("@{}", &t.leader_agent)}</td>
+                                <td class="settings-row-actions">
+                                    if can_edit {
+                                        <button class="settings-button" onclick={on_edit_click}>{"Edit"}</button>
+                                        <button class="settings-button danger" onclick={on_delete}>{"Remove"}</button>
+                                    }
+                                </td>
+                            </tr>
+                        }
+                    })}
+                </tbody>
+            </table>
+        }
+    };
+
+    render_section("Teams", html! { <>{table}{editor}</> })
 }
 
-fn render_teams(teams: &Option<std::collections::HashMap<String, FullTeamConfig>>) -> Html {
-    let body = match teams {
-        Some(map) if !map.is_empty() => {
-            let mut ids: Vec<&String> = map.keys().collect();
-            ids.sort();
-            html! {
-                <table class="settings-table">
-                    <thead>
-                        <tr>
-                            <th>{"ID"}</th>
-                            <th>{"Name"}</th>
-                            <th>{"Agents"}</th>
-                            <th>{"Leader"}</th>
-                        </tr>
-                    </thead>
-                    <tbody>
-                        { for ids.iter().map(|id| {
-                            let t = &map[*id];
-                            let agents_str = t.agents.iter()
-                                .map(|a| format!("@{}", a))
-                                .collect::<Vec<_>>()
-                                .join(", ");
-                            html! {
-                                <tr>
-                                    <td>{id}</td>
-                                    <td>{&t.name}</td>
-                                    <td>{agents_str}</td>
-                                    <td>{format!("@{}", &t.leader_agent)}</td>
-                                </tr>
-                            }
-                        })}
-                    </tbody>
-                </table>
+// ─── Models and skills editors ──────────────────────────────────────────────
+
+#[derive(Properties, PartialEq)]
+struct ModelsEditorProps {
+    models: FullModelsConfig,
+    saving: bool,
+    can_edit: bool,
+    on_edit: Callback<SettingsEdit>,
+}
+
+/// Provider and per-provider model used by the default agent.
+#[function_component(ModelsEditor)]
+fn models_editor(props: &ModelsEditorProps) -> Html {
+    let draft = use_state(|| props.models.clone());
+    {
+        let draft = draft.clone();
+        use_effect_with(props.models.clone(), move |models| {
+            draft.set(models.clone());
+            || {}
+        });
+    }
+
+    let on_provider = {
+        let draft = draft.clone();
+        Callback::from(move |e: Event| {
+            let select: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            let mut next = (*draft).clone();
+            next.provider = Some(select.value()).filter(|v| !v.is_empty());
+            draft.set(next);
+        })
+    };
+    let model_input = |get: fn(&mut FullModelsConfig) -> &mut Option<FullProviderModelConfig>| {
+        let draft = draft.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+            let mut next = (*draft).clone();
+            let value = input.value().trim().to_string();
+            *get(&mut next) = (!value.is_empty()).then_some(FullProviderModelConfig { model: Some(value) });
+            draft.set(next);
+        })
+    };
+    let model_of = |m: &Option<FullProviderModelConfig>| {
+        m.as_ref().and_then(|m| m.model.clone()).unwrap_or_default()
+    };
+    let on_submit = {
+        let draft = draft.clone();
+        let on_edit = props.on_edit.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            on_edit.emit(SettingsEdit::Models((*draft).clone()));
+        })
+    };
+
+    let provider = draft.provider.clone().unwrap_or_default();
+    let body = html! {
+        <form class="settings-form" onsubmit={on_submit}>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Provider"}</span>
+                <select class="send-agent-select" onchange={on_provider}>
+                    <option value="" selected={provider.is_empty()}>{"auto-detect"}</option>
+                    { for ["anthropic", "openai", "opencode"].iter().map(|p| html! {
+                        <option value={*p} selected={provider == *p}>{*p}</option>
+                    })}
+                </select>
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Anthropic model"}</span>
+                <input class="send-input" value={model_of(&draft.anthropic)} placeholder="sonnet"
+                    oninput={model_input(|m| &mut m.anthropic)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"OpenAI model"}</span>
+                <input class="send-input" value={model_of(&draft.openai)} placeholder="gpt-5.3-codex"
+                    oninput={model_input(|m| &mut m.openai)} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"OpenCode model"}</span>
+                <input class="send-input" value={model_of(&draft.opencode)} placeholder="sonnet"
+                    oninput={model_input(|m| &mut m.opencode)} />
+            </label>
+            if props.can_edit {
+                <div class="settings-form-actions">
+                    <button type="submit" class="send-button" disabled={props.saving || *draft == props.models}>
+                        {"Save models"}
+                    </button>
+                </div>
             }
-        }
-        _ => html! { <span class="settings-field-value empty">{"No teams configured"}</span> },
+        </form>
     };
-    render_section("Teams", body)
+    render_section("Models", body)
+}
+
+#[derive(Properties, PartialEq)]
+struct SkillsEditorProps {
+    skills: HashMap<String, FullSkillOverride>,
+    can_edit: bool,
+    on_edit: Callback<SettingsEdit>,
+}
+
+/// Per-skill overrides. Skills without an override are enabled.
+#[function_component(SkillsEditor)]
+fn skills_editor(props: &SkillsEditorProps) -> Html {
+    let new_name = use_state(String::new);
+    let mut names: Vec<&String> = props.skills.keys().collect();
+    names.sort();
+
+    let on_name = {
+        let new_name = new_name.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+            new_name.set(input.value());
+        })
+    };
+    let on_add = {
+        let new_name = new_name.clone();
+        let on_edit = props.on_edit.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let name = new_name.trim().to_string();
+            if !name.is_empty() {
+                on_edit.emit(SettingsEdit::Skill(name, Some(FullSkillOverride { enabled: false })));
+                new_name.set(String::new());
+            }
+        })
+    };
+
+    let body = html! {
+        <>
+            if names.is_empty() {
+                <span class="settings-field-value empty">{"No overrides — all skills enabled"}</span>
+            }
+            { for names.iter().map(|name| {
+                let enabled = props.skills[*name].enabled;
+                let on_toggle = {
+                    let on_edit = props.on_edit.clone();
+                    let name = (*name).clone();
+                    Callback::from(move |_: Event| {
+                        on_edit.emit(SettingsEdit::Skill(name.clone(), Some(FullSkillOverride { enabled: !enabled })))
+                    })
+                };
+                let on_remove = {
+                    let on_edit = props.on_edit.clone();
+                    let name = (*name).clone();
+                    Callback::from(move |_: MouseEvent| on_edit.emit(SettingsEdit::Skill(name.clone(), None)))
+                };
+                html! {
+                    <div class="settings-field">
+                        <label class="settings-field-label">
+                            <input type="checkbox" checked={enabled} onchange={on_toggle} disabled={!props.can_edit} />
+                            {name}
+                        </label>
+                        if props.can_edit {
+                            <button class="settings-button" onclick={on_remove}>{"Clear override"}</button>
+                        }
+                    </div>
+                }
+            })}
+            if props.can_edit {
+                <form class="send-form" onsubmit={on_add}>
+                    <input class="send-input" placeholder="skill name to disable"
+                        value={(*new_name).clone()} oninput={on_name} />
+                    <button type="submit" class="settings-button">{"Disable skill"}</button>
+                </form>
+            }
+        </>
+    };
+    render_section("Skills", body)
 }
 
 fn render_monitoring(mon: &Option<FullMonitoringConfig>) -> Html {
@@ -272,7 +754,24 @@ fn render_monitoring(mon: &Option<FullMonitoringConfig>) -> Html {
             let interval = m.heartbeat_interval
                 .map(|i| format!("{}s", i))
                 .unwrap_or_else(|| "3600s (default)".to_string());
-            html! { {render_field("Heartbeat interval", &interval, "")} }
+            let metrics = m.metrics_bind
+                .as_ref()
+                .map(|b| format!("http://{}/metrics", b))
+                .unwrap_or_else(|| "off".to_string());
+            let tracing = m.tracing
+                .as_ref()
+                .map(|t| {
+                    t.otlp_endpoint.iter().chain(t.file.iter()).cloned().collect::<Vec<_>>().join(", ")
+                })
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| "off".to_string());
+            html! {
+                <>
+                    {render_field("Heartbeat interval", &interval, "")}
+                    {render_field("Prometheus endpoint", &metrics, "")}
+                    {render_field("Trace export", &tracing, "")}
+                </>
+            }
         }
         None => html! { {render_field("Status", "not configured (defaults apply)", "empty")} },
     };
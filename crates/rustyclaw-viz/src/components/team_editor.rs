@@ -0,0 +1,143 @@
+use yew::prelude::*;
+
+use crate::types::FullTeamConfig;
+
+#[derive(Properties, PartialEq)]
+pub struct TeamEditorProps {
+    /// Team being edited; `None` adds a new one
+    pub team_id: Option<String>,
+    pub team: Option<FullTeamConfig>,
+    /// Agent IDs that can be members, sorted
+    pub agent_ids: Vec<String>,
+    pub saving: bool,
+    pub on_save: Callback<(String, FullTeamConfig)>,
+    pub on_cancel: Callback<()>,
+}
+
+#[function_component(TeamEditor)]
+pub fn team_editor(props: &TeamEditorProps) -> Html {
+    let id = use_state(|| props.team_id.clone().unwrap_or_default());
+    let name = use_state(|| props.team.as_ref().map(|t| t.name.clone()).unwrap_or_default());
+    let description = use_state(|| {
+        props.team.as_ref().and_then(|t| t.description.clone()).unwrap_or_default()
+    });
+    let members = use_state(|| props.team.as_ref().map(|t| t.agents.clone()).unwrap_or_default());
+    let leader = use_state(|| props.team.as_ref().map(|t| t.leader_agent.clone()).unwrap_or_default());
+    let error = use_state(|| None::<String>);
+    let is_new = props.team_id.is_none();
+
+    let text = |state: UseStateHandle<String>| {
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+            state.set(input.value());
+        })
+    };
+    let on_leader = {
+        let leader = leader.clone();
+        Callback::from(move |e: Event| {
+            let select: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            leader.set(select.value());
+        })
+    };
+
+    let on_submit = {
+        let id = id.clone();
+        let name = name.clone();
+        let description = description.clone();
+        let members = members.clone();
+        let leader = leader.clone();
+        let error = error.clone();
+        let on_save = props.on_save.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let team_id = id.trim().to_lowercase();
+            if team_id.is_empty() {
+                error.set(Some("Team ID is required".to_string()));
+                return;
+            }
+            // Default the leader to the first member
+            let leader_agent = if members.contains(&*leader) {
+                (*leader).clone()
+            } else {
+                members.first().cloned().unwrap_or_default()
+            };
+            error.set(None);
+            on_save.emit((
+                team_id,
+                FullTeamConfig {
+                    name: name.trim().to_string(),
+                    agents: (*members).clone(),
+                    leader_agent,
+                    description: Some(description.trim().to_string()).filter(|d| !d.is_empty()),
+                },
+            ));
+        })
+    };
+    let on_cancel = {
+        let on_cancel = props.on_cancel.clone();
+        Callback::from(move |_: MouseEvent| on_cancel.emit(()))
+    };
+
+    html! {
+        <form class="settings-form" onsubmit={on_submit}>
+            <h4>{ if is_new { "New team".to_string() } else { format!("Edit @{}", *id) } }</h4>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"ID"}</span>
+                <input class="send-input" value={(*id).clone()} disabled={!is_new}
+                    placeholder="dev" oninput={text(id.clone())} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Name"}</span>
+                <input class="send-input" value={(*name).clone()} oninput={text(name.clone())} />
+            </label>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Description"}</span>
+                <input class="send-input" value={(*description).clone()} oninput={text(description.clone())} />
+            </label>
+            <div class="settings-form-row">
+                <span class="settings-field-label">{"Members"}</span>
+                <div class="settings-checklist">
+                    { for props.agent_ids.iter().map(|agent_id| {
+                        let checked = members.contains(agent_id);
+                        let onchange = {
+                            let members = members.clone();
+                            let agent_id = agent_id.clone();
+                            Callback::from(move |_: Event| {
+                                let mut next = (*members).clone();
+                                if let Some(pos) = next.iter().position(|m| *m == agent_id) {
+                                    next.remove(pos);
+                                } else {
+                                    next.push(agent_id.clone());
+                                }
+                                members.set(next);
+                            })
+                        };
+                        html! {
+                            <label>
+                                <input type="checkbox" {checked} {onchange} />
+                                {format!("@{}", agent_id)}
+                            </label>
+                        }
+                    })}
+                </div>
+            </div>
+            <label class="settings-form-row">
+                <span class="settings-field-label">{"Leader"}</span>
+                <select class="send-agent-select" onchange={on_leader}>
+                    { for members.iter().map(|m| html! {
+                        <option value={m.clone()} selected={*m == *leader}>{format!("@{}", m)}</option>
+                    })}
+                </select>
+            </label>
+            if let Some(err) = &*error {
+                <div class="settings-form-error">{err}</div>
+            }
+            <div class="settings-form-actions">
+                <button type="submit" class="send-button" disabled={props.saving}>
+                    { if props.saving { "Saving..." } else { "Save team" } }
+                </button>
+                <button type="button" class="settings-button" onclick={on_cancel}>{"Cancel"}</button>
+            </div>
+        </form>
+    }
+}
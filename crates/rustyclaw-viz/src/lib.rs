@@ -6,5 +6,5 @@ use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(start)]
 pub fn run() {
-    yew::Renderer::<app::App>::new().render();
+    yew::Renderer::<app::Root>::new().render();
 }
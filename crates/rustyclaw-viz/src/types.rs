@@ -103,12 +103,16 @@ pub struct VizAgentConfig {
 
 #[derive(Clone, PartialEq, Deserialize)]
 pub struct QueuedMessage {
+    #[serde(default)]
+    pub file: String,
     pub message_id: String,
     pub channel: String,
     pub sender: String,
     pub message: String,
     pub agent: Option<String>,
     pub timestamp: u64,
+    #[serde(default)]
+    pub priority: i32,
     pub status: String,
 }
 
@@ -120,6 +124,82 @@ pub struct QueueMessagesResponse {
     pub processing: Vec<QueuedMessage>,
 }
 
+// ─── History (from /api/conversations and /api/transcripts) ─────────────────
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub page: usize,
+    #[serde(rename = "perPage")]
+    pub per_page: usize,
+}
+
+impl<T> Page<T> {
+    pub fn page_count(&self) -> usize {
+        self.total.div_ceil(self.per_page.max(1)).max(1)
+    }
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    #[serde(rename = "teamId")]
+    pub team_id: String,
+    #[serde(rename = "teamName")]
+    pub team_name: String,
+    pub date: String,
+    pub channel: String,
+    pub sender: String,
+    pub preview: String,
+    pub agents: Vec<String>,
+    pub steps: usize,
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct ChatStep {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    pub response: String,
+    #[serde(rename = "fromAgent", default)]
+    pub from_agent: Option<String>,
+    #[serde(rename = "startedAt", default)]
+    pub started_at: u64,
+    #[serde(rename = "finishedAt", default)]
+    pub finished_at: u64,
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct ChatRecord {
+    #[serde(rename = "teamId")]
+    pub team_id: String,
+    #[serde(rename = "teamName")]
+    pub team_name: String,
+    pub date: String,
+    pub channel: String,
+    pub sender: String,
+    pub message: String,
+    #[serde(rename = "startedAt", default)]
+    pub started_at: u64,
+    pub steps: Vec<ChatStep>,
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct TranscriptEntry {
+    pub timestamp: u64,
+    pub agent_id: String,
+    pub role: String,
+    pub content: String,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub sender: Option<String>,
+    #[serde(default)]
+    pub entry_type: Option<String>,
+}
+
 // ─── Kanban board types ─────────────────────────────────────────────────────
 
 #[derive(Clone, PartialEq, Debug)]
@@ -169,6 +249,9 @@ pub enum Tab {
     Dashboard,
     Kanban,
     Queue,
+    Chat,
+    History,
+    Metrics,
     Settings,
 }
 
@@ -188,6 +271,8 @@ pub struct FullSettings {
     pub teams: Option<HashMap<String, FullTeamConfig>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub monitoring: Option<FullMonitoringConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skills: Option<HashMap<String, FullSkillOverride>>,
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
@@ -220,7 +305,7 @@ pub struct FullTelegramConfig {
     pub bot_token: Option<String>,
 }
 
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct FullModelsConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub provider: Option<String>,
@@ -232,7 +317,7 @@ pub struct FullModelsConfig {
     pub opencode: Option<FullProviderModelConfig>,
 }
 
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct FullProviderModelConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
@@ -251,6 +336,8 @@ pub struct FullAgentConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub idle_timeout_minutes: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub context_window: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub fallbacks: Option<Vec<String>>,
@@ -271,12 +358,31 @@ pub struct FullTeamConfig {
     pub name: String,
     pub agents: Vec<String>,
     pub leader_agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct FullSkillOverride {
+    pub enabled: bool,
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct FullMonitoringConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub heartbeat_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_bind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracing: Option<FullTracingConfig>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct FullTracingConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
 }
 
 // ─── WebSocket event from viz server ────────────────────────────────────────
@@ -286,9 +392,112 @@ pub struct VizEvent {
     #[serde(rename = "type")]
     pub event_type: String,
     #[serde(default)]
-    #[allow(dead_code)]
     pub timestamp: Option<u64>,
+    /// Position in the server's event log
+    #[serde(default)]
+    pub seq: Option<u64>,
     /// All other dynamic fields
     #[serde(flatten)]
     pub data: HashMap<String, serde_json::Value>,
 }
+
+// ─── Web chat ───────────────────────────────────────────────────────────────
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct ChatFile {
+    pub name: String,
+    pub url: String,
+}
+
+/// One bubble in the chat view: something the user sent, or a reply.
+#[derive(Clone, PartialEq)]
+pub struct ChatEntry {
+    pub from_user: bool,
+    /// Queue message ID; replies carry the ID of the message they answer
+    pub message_id: Option<String>,
+    pub agent: Option<String>,
+    pub text: String,
+    pub files: Vec<ChatFile>,
+    pub choices: Vec<String>,
+    pub at: f64,
+    /// Sent and still waiting for a reply
+    pub pending: bool,
+}
+
+// ─── Metrics (from /api/metrics) ───────────────────────────────────────────
+
+#[derive(Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HourCounts {
+    pub received: u64,
+    pub responded: u64,
+    pub failures: u64,
+    pub model_errors: u64,
+    pub failovers: u64,
+    pub compactions: u64,
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct HourBucket {
+    pub start: u64,
+    #[serde(flatten)]
+    pub counts: HourCounts,
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStats {
+    pub key: String,
+    pub count: u64,
+    pub failures: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CooldownPeriod {
+    pub key: String,
+    pub agent_id: String,
+    pub reason: String,
+    pub started: u64,
+    pub until: u64,
+}
+
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveCooldown {
+    pub key: String,
+    pub until: u64,
+    pub error_count: u32,
+}
+
+#[derive(Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Metrics {
+    pub since: u64,
+    pub until: u64,
+    pub totals: HourCounts,
+    pub hours: Vec<HourBucket>,
+    pub agents: Vec<LatencyStats>,
+    pub models: Vec<LatencyStats>,
+    pub failures_by_reason: HashMap<String, u64>,
+    pub cooldowns: Vec<CooldownPeriod>,
+    pub active_cooldowns: Vec<ActiveCooldown>,
+    pub compactions_by_agent: HashMap<String, u64>,
+}
+
+// ─── Login session (from /api/me and /api/login) ───────────────────────────
+
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct SessionInfo {
+    pub user: String,
+    pub role: String,
+}
+
+impl SessionInfo {
+    /// Operators can send messages and edit settings; viewers only read.
+    pub fn can_edit(&self) -> bool {
+        self.role == "operator"
+    }
+}